@@ -0,0 +1,174 @@
+// Copyright (C) 2021-2023  Tassilo Horn <tsdh@gnu.org>
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Benchmarks of the hot path of the switchers, i.e., building the tree,
+//! ordering its windows, and formatting them for the menu, over synthetic
+//! trees of different sizes.  Run with `cargo bench -p swayr`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::collections::HashSet;
+use std::sync::mpsc;
+use swayipc as s;
+use swayr::focus::FocusData;
+use swayr::shared::ipc::NodeMethods;
+use swayr::tree;
+use swayr::util::DisplayFormat;
+
+const WINDOW_COUNTS: [usize; 3] = [50, 500, 5000];
+const WINDOWS_PER_WORKSPACE: usize = 10;
+
+fn load_fixture() -> s::Node {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join("tree.json");
+    let json = std::fs::read_to_string(path).unwrap();
+    serde_json::from_str(&json).unwrap()
+}
+
+/// Returns a tree with a single output whose workspaces contain
+/// `window_count` windows in total.  The nodes are copies of the ones in the
+/// `get_windows_as_json` test fixture.
+fn synthetic_tree(window_count: usize) -> s::Node {
+    let mut root = load_fixture();
+    let mut output = root
+        .nodes
+        .iter()
+        .find(|n| n.node_type == s::NodeType::Output && !n.is_scratchpad())
+        .unwrap()
+        .clone();
+    let ws_template = output.nodes[0].clone();
+    let win_template = ws_template
+        .nodes
+        .iter()
+        .find(|n| n.node_type == s::NodeType::Con && n.app_id.is_some())
+        .unwrap()
+        .clone();
+
+    let mut id = 1000;
+    let mut next_id = || {
+        id += 1;
+        id
+    };
+    output.id = next_id();
+    output.nodes = (0..window_count.div_ceil(WINDOWS_PER_WORKSPACE))
+        .map(|ws_idx| {
+            let mut ws = ws_template.clone();
+            ws.id = next_id();
+            ws.name = Some(format!("{}", ws_idx + 1));
+            ws.floating_nodes = vec![];
+            ws.nodes = (0..WINDOWS_PER_WORKSPACE)
+                .map(|win_idx| ws_idx * WINDOWS_PER_WORKSPACE + win_idx)
+                .take_while(|n| *n < window_count)
+                .map(|n| {
+                    let mut win = win_template.clone();
+                    win.id = next_id();
+                    win.name = Some(format!("Window {n} — <Some & Title>"));
+                    win.app_id = Some(format!("app-{}", n % 7));
+                    win.urgent = n % 50 == 0;
+                    win
+                })
+                .collect();
+            ws
+        })
+        .collect();
+    root.nodes = vec![output];
+    root
+}
+
+/// Returns focus data where the windows have been focused in some arbitrary
+/// order, so that ordering them has some work to do.
+fn focus_data(root: &s::Node) -> FocusData {
+    let (tx, _rx) = mpsc::channel();
+    let fdata = FocusData::new(tx);
+    let tree = tree::get_tree(root);
+    for (tick, win) in tree.get_windows(&fdata).iter().enumerate() {
+        fdata.update_last_focus_tick(win.node.id, (tick as u64 * 7919) % 10007);
+    }
+    fdata
+}
+
+fn bench_get_tree(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_tree");
+    for count in WINDOW_COUNTS {
+        let root = synthetic_tree(count);
+        group.bench_function(count.to_string(), |b| {
+            b.iter(|| tree::get_tree(black_box(&root)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_get_windows(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_windows");
+    for count in WINDOW_COUNTS {
+        let root = synthetic_tree(count);
+        let fdata = focus_data(&root);
+        let tree = tree::get_tree(&root);
+        group.bench_function(count.to_string(), |b| {
+            b.iter(|| tree.get_windows(black_box(&fdata)))
+        });
+    }
+    group.finish();
+}
+
+/// Benchmarks formatting the menu entries without (`cold`) and with (`warm`)
+/// the entries cached by previous menu invocations.
+fn bench_format_menu(c: &mut Criterion) {
+    let mut group = c.benchmark_group("format_menu");
+    for count in WINDOW_COUNTS {
+        let root = synthetic_tree(count);
+        let fdata = focus_data(&root);
+        let tree = tree::get_tree(&root);
+        let wins = tree.get_windows(&fdata);
+        let format = || {
+            wins.iter()
+                .map(|w| w.format_for_display())
+                .collect::<Vec<_>>()
+        };
+        group.bench_function(format!("cold/{count}"), |b| {
+            b.iter(|| {
+                tree::retain_formatted_windows(&HashSet::new());
+                format()
+            })
+        });
+        group.bench_function(format!("warm/{count}"), |b| b.iter(format));
+    }
+    group.finish();
+}
+
+fn bench_subst_placeholders(c: &mut Criterion) {
+    let root = synthetic_tree(WINDOW_COUNTS[0]);
+    let fdata = focus_data(&root);
+    let tree = tree::get_tree(&root);
+    let wins = tree.get_windows(&fdata);
+    let win = &wins[0];
+    let fmt = "<b>{app_name:{:>12.12}}</b> — {urgency_start}\
+               <i>{title:{:.80}}</i>{urgency_end} \
+               on workspace {workspace_name} of {output_name} \
+               {marks} <span alpha=\"20000\">({id}, {pid})</span>";
+    c.bench_function("subst_node_placeholders", |b| {
+        b.iter(|| win.subst_node_placeholders(black_box(fmt), true))
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_get_tree,
+    bench_get_windows,
+    bench_format_menu,
+    bench_subst_placeholders
+);
+criterion_main!(benches);
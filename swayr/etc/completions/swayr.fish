@@ -0,0 +1,17 @@
+# Fish completions for swayr.  Copy this file to
+# ~/.config/fish/completions/swayr.fish.  The command names and the values
+# of arguments like marks or app_ids are queried from the running swayrd.
+
+function __swayr_complete -a kind
+    swayr completions-dynamic --$kind 2>/dev/null
+end
+
+complete -c swayr -f
+complete -c swayr -n __fish_use_subcommand -a '(__swayr_complete commands)'
+
+complete -c swayr -n '__fish_seen_subcommand_from switch-to-mark-or-urgent-or-lru-window' \
+    -a '(__swayr_complete marks)'
+complete -c swayr -n '__fish_seen_subcommand_from switch-to-app-or-urgent-or-lru-window get-icon' \
+    -a '(__swayr_complete app-ids)'
+complete -c swayr -n '__fish_seen_subcommand_from get-workspace-lru-window' \
+    -a '(__swayr_complete workspaces)'
@@ -16,24 +16,91 @@
 //! The `swayr` binary.
 
 use clap::Parser;
+use std::io::Write;
+use std::process::ExitCode;
 
 #[derive(clap::Parser)]
 #[clap(about, version, author)]
 struct Opts {
+    /// Send the command to the swayrd listening on the given TCP address,
+    /// e.g., `kiosk:7685`, instead of the local one.
+    #[clap(long, global = true, value_name = "ADDRESS")]
+    remote: Option<String>,
+    /// The token granting read-write access to a remote swayrd.  Defaults to
+    /// the value of the SWAYR_REMOTE_TOKEN environment variable.
+    #[clap(long, global = true, requires = "remote")]
+    token: Option<String>,
+    /// Talk to the swayrd started with the same `--socket-suffix`.  Defaults
+    /// to the value of the SWAYR_SOCKET_SUFFIX environment variable.
+    #[clap(long, global = true, value_name = "SUFFIX")]
+    socket_suffix: Option<String>,
     #[clap(subcommand)]
-    command: swayr::cmds::SwayrCommand,
+    command: Command,
 }
 
-fn main() -> Result<(), String> {
+#[derive(clap::Subcommand)]
+enum Command {
+    #[clap(flatten)]
+    Swayr(swayr::cmds::SwayrCommand),
+    /// Read swayr commands in their JSON form, one per line, from stdin,
+    /// send them to swayrd one after the other, and write one JSON result
+    /// object per line to stdout.
+    Pipe,
+}
+
+fn main() -> ExitCode {
     let opts: Opts = Opts::parse();
-    match swayr::client::send_swayr_cmd(opts.command) {
-        Ok(val) => {
-            println!("{val}");
-            Ok(())
+    if let Some(suffix) = opts
+        .socket_suffix
+        .or_else(|| std::env::var("SWAYR_SOCKET_SUFFIX").ok())
+    {
+        swayr::util::set_socket_suffix(suffix);
+    }
+    let remote = opts.remote.map(|address| swayr::client::Remote {
+        address,
+        token: opts
+            .token
+            .or_else(|| std::env::var("SWAYR_REMOTE_TOKEN").ok()),
+    });
+    match opts.command {
+        Command::Swayr(cmd) if cmd.is_streamed() => {
+            // The records are printed as they arrive, and each already ends
+            // with a newline.
+            let mut stdout = std::io::stdout().lock();
+            match swayr::client::stream_swayr_cmd_to(
+                cmd,
+                remote.as_ref(),
+                &mut |part| {
+                    let _ = stdout.write_all(part.as_bytes());
+                    let _ = stdout.flush();
+                },
+            ) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(err) => {
+                    eprintln!("{err}");
+                    ExitCode::from(err.exit_code())
+                }
+            }
         }
-        Err(err) => {
-            eprintln!("{err}");
-            Err("Command failed".to_owned())
+        Command::Swayr(cmd) => {
+            match swayr::client::send_swayr_cmd_to(cmd, remote.as_ref()) {
+                Ok(val) => {
+                    println!("{val}");
+                    ExitCode::SUCCESS
+                }
+                Err(err) => {
+                    eprintln!("{err}");
+                    ExitCode::from(err.exit_code())
+                }
+            }
         }
+        Command::Pipe => match swayr::client::pipe_swayr_cmds(
+            std::io::stdin().lock(),
+            std::io::stdout().lock(),
+            remote.as_ref(),
+        ) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => ExitCode::from(err.exit_code()),
+        },
     }
 }
@@ -15,10 +15,32 @@
 
 //! The `swayrd` binary.
 
+use clap::Parser;
 use env_logger::Env;
 
+#[derive(clap::Parser)]
+#[clap(about, version, author)]
+struct Opts {
+    /// Listen on `$XDG_RUNTIME_DIR/swayr-$WAYLAND_DISPLAY-<SUFFIX>.sock` so
+    /// that several swayrd instances can coexist, e.g., in a nested sway
+    /// session.  Defaults to the value of the SWAYR_SOCKET_SUFFIX environment
+    /// variable.
+    #[clap(long, value_name = "SUFFIX")]
+    socket_suffix: Option<String>,
+}
+
 fn main() {
+    let opts: Opts = Opts::parse();
     env_logger::Builder::from_env(Env::default().default_filter_or("warn"))
         .init();
+    if let Some(suffix) = opts
+        .socket_suffix
+        .or_else(|| std::env::var("SWAYR_SOCKET_SUFFIX").ok())
+    {
+        // Export it so that the swayr invocations of hooks and other child
+        // processes talk to this instance.  There are no other threads yet.
+        std::env::set_var("SWAYR_SOCKET_SUFFIX", &suffix);
+        swayr::util::set_socket_suffix(suffix);
+    }
     swayr::daemon::run_daemon();
 }
@@ -14,16 +14,283 @@
 // this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use crate::cmds;
+use crate::cmds::SwayrError;
+use crate::daemon::CONFIG;
 use crate::util;
+use std::io::{BufRead, Write};
+use std::net::TcpStream;
 use std::os::unix::net::UnixStream;
+use std::process::Command;
+use std::time::{Duration, Instant};
 
-pub fn send_swayr_cmd(cmd: cmds::SwayrCommand) -> Result<String, String> {
-    let stream = UnixStream::connect(util::get_swayr_socket_path())
-        .map_err(|e| e.to_string())?;
-    serde_json::to_writer(&stream, &cmd).map_err(|e| e.to_string())?;
+/// A swayrd reachable over TCP, see the `[remote]` config section.
+pub struct Remote {
+    /// The address of swayrd's TCP listener, e.g., `kiosk:7685`.
+    pub address: String,
+    /// The shared secret granting read-write access.
+    pub token: Option<String>,
+}
+
+pub fn send_swayr_cmd(cmd: cmds::SwayrCommand) -> Result<String, SwayrError> {
+    send_swayr_cmd_to(cmd, None)
+}
+
+/// Sends `cmd` to the local swayrd or to the given `remote` one.
+pub fn send_swayr_cmd_to(
+    cmd: cmds::SwayrCommand,
+    remote: Option<&Remote>,
+) -> Result<String, SwayrError> {
+    let start = Instant::now();
+    let mut output = String::new();
+    let result = stream_swayr_cmd_to(cmd.clone(), remote, &mut |part| {
+        output.push_str(part)
+    })
+    .map(|()| output);
+    match cmd {
+        cmds::SwayrCommand::Ping => result.map(|status| {
+            let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+            match serde_json::from_str::<serde_json::Value>(&status) {
+                Ok(serde_json::Value::Object(mut obj)) => {
+                    obj.insert("latency_ms".to_owned(), latency_ms.into());
+                    serde_json::Value::Object(obj).to_string()
+                }
+                // Older swayrd versions reply with just their version.
+                _ => serde_json::json!({
+                    "version": status,
+                    "latency_ms": latency_ms,
+                })
+                .to_string(),
+            }
+        }),
+        _ => result,
+    }
+}
+
+/// Reads swayr commands in their JSON form, one per line, from `input`, sends
+/// them to swayrd one after the other, and writes one JSON object per result
+/// to `output`.  Returns the first error, if any.
+pub fn pipe_swayr_cmds(
+    input: impl BufRead,
+    mut output: impl Write,
+    remote: Option<&Remote>,
+) -> Result<(), SwayrError> {
+    let io_error =
+        |e: std::io::Error| SwayrError::ConnectionError(e.to_string());
+    let mut first_err = None;
+    for line in input.lines() {
+        let line = line.map_err(io_error)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = cmds::parse_swayr_command_json(&line)
+            .map_err(|e| {
+                SwayrError::InvalidInput(format!(
+                    "Could not parse input line {line}: {e}"
+                ))
+            })
+            .and_then(|cmd| send_swayr_cmd_to(cmd, remote));
+        let json = match &result {
+            Ok(val) => serde_json::json!({ "ok": true, "result": val }),
+            Err(err) => serde_json::json!({
+                "ok": false,
+                "error": err.to_string(),
+                "exit_code": err.exit_code(),
+            }),
+        };
+        writeln!(output, "{json}").map_err(io_error)?;
+        output.flush().map_err(io_error)?;
+        if let Err(err) = result {
+            first_err.get_or_insert(err);
+        }
+    }
+    first_err.map_or(Ok(()), Err)
+}
+
+#[test]
+fn test_pipe_swayr_cmds_invalid_input() {
+    let mut output = vec![];
+    let result = pipe_swayr_cmds(
+        "\n{\"NoSuchCommand\": 1}\n".as_bytes(),
+        &mut output,
+        None,
+    );
+    assert!(matches!(result, Err(SwayrError::InvalidInput(_))));
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(output.lines().count(), 1);
+    let json: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(json["ok"], false);
+    assert_eq!(json["exit_code"], 7);
+}
+
+/// How long to wait for swayrd after starting its systemd unit.
+const UNIT_START_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Starts the systemd user unit `unit` and waits until swayrd listens on
+/// `socket_path`.
+fn start_systemd_unit(
+    unit: &str,
+    socket_path: &str,
+) -> Result<UnixStream, String> {
+    let status = Command::new("systemctl")
+        .args(["--user", "start", unit])
+        .status()
+        .map_err(|e| format!("Could not run systemctl: {e}"))?;
+    if !status.success() {
+        return Err(format!("Could not start {unit}: systemctl {status}"));
+    }
+    let start = Instant::now();
+    loop {
+        match UnixStream::connect(socket_path) {
+            Ok(stream) => return Ok(stream),
+            Err(err) if start.elapsed() >= UNIT_START_TIMEOUT => {
+                return Err(format!(
+                    "Started {unit} but swayrd isn't listening: {err}"
+                ))
+            }
+            Err(_) => std::thread::sleep(Duration::from_millis(50)),
+        }
+    }
+}
+
+fn connect(socket_path: &str) -> Result<UnixStream, SwayrError> {
+    UnixStream::connect(socket_path).or_else(|err| {
+        match CONFIG.get_misc_systemd_unit() {
+            Some(unit) => start_systemd_unit(&unit, socket_path).map_err(|e| {
+                SwayrError::ConnectionError(format!(
+                    "Could not connect to swayrd at {socket_path}: {err}\n{e}"
+                ))
+            }),
+            None => Err(SwayrError::ConnectionError(connection_error_message(
+                socket_path,
+                &err,
+            ))),
+        }
+    })
+}
+
+/// Like `send_swayr_cmd_to` but passes the output to `on_output` piece by
+/// piece as it arrives.  Only streamed commands (see
+/// `SwayrCommand::is_streamed`) have more than one piece.
+pub fn stream_swayr_cmd_to(
+    cmd: cmds::SwayrCommand,
+    remote: Option<&Remote>,
+    on_output: &mut dyn FnMut(&str),
+) -> Result<(), SwayrError> {
+    match remote {
+        Some(remote) => send_remote_swayr_cmd(cmd, remote, on_output),
+        None => send_swayr_cmd_1(&cmd, on_output),
+    }
+}
+
+/// Reads the response of swayrd from `reader`, i.e., one result or, for
+/// streamed commands, a sequence of partial results, and passes the output to
+/// `on_output`.  Returns the first error result.
+fn read_response(
+    reader: impl std::io::Read,
+    on_output: &mut dyn FnMut(&str),
+    address: &str,
+) -> Result<(), SwayrError> {
+    let read_error = |e: &dyn std::fmt::Display| {
+        SwayrError::ConnectionError(format!(
+            "Could not read response from swayrd at {address}: {e}"
+        ))
+    };
+    let mut results = serde_json::Deserializer::from_reader(reader)
+        .into_iter::<Result<String, SwayrError>>()
+        .peekable();
+    if results.peek().is_none() {
+        return Err(read_error(&"no result"));
+    }
+    for result in results {
+        on_output(&result.map_err(|e| read_error(&e))??);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_read_response() {
+    let mut parts = vec![];
+    let result = read_response(
+        r#"{"Ok":"{\"id\":1}\n"}{"Ok":"{\"id\":2}\n"}"#.as_bytes(),
+        &mut |part| parts.push(part.to_owned()),
+        "test",
+    );
+    assert!(result.is_ok());
+    assert_eq!(parts, vec!["{\"id\":1}\n", "{\"id\":2}\n"]);
+
+    let mut parts = vec![];
+    let result = read_response(
+        r#"{"Ok":"a\n"}{"Err":{"DaemonError":"oops"}}"#.as_bytes(),
+        &mut |part| parts.push(part.to_owned()),
+        "test",
+    );
+    assert!(matches!(result, Err(SwayrError::DaemonError(_))));
+    assert_eq!(parts, vec!["a\n"]);
+
+    assert!(matches!(
+        read_response("".as_bytes(), &mut |_| (), "test"),
+        Err(SwayrError::ConnectionError(_))
+    ));
+}
+
+fn send_swayr_cmd_1(
+    cmd: &cmds::SwayrCommand,
+    on_output: &mut dyn FnMut(&str),
+) -> Result<(), SwayrError> {
+    let socket_path = util::get_swayr_socket_path();
+    let stream = connect(&socket_path)?;
+    serde_json::to_writer(&stream, cmd)
+        .map_err(|e| SwayrError::ConnectionError(e.to_string()))?;
     stream
         .shutdown(std::net::Shutdown::Write)
-        .map_err(|e| e.to_string())?;
-    serde_json::from_reader::<_, Result<String, String>>(&stream)
-        .expect("Could not read response from swayrd")
+        .map_err(|e| SwayrError::ConnectionError(e.to_string()))?;
+    read_response(&stream, on_output, &socket_path)
+}
+
+fn send_remote_swayr_cmd(
+    cmd: cmds::SwayrCommand,
+    remote: &Remote,
+    on_output: &mut dyn FnMut(&str),
+) -> Result<(), SwayrError> {
+    let address = &remote.address;
+    let stream = TcpStream::connect(address).map_err(|e| {
+        SwayrError::ConnectionError(format!(
+            "Could not connect to swayrd at {address}: {e}"
+        ))
+    })?;
+    let req = match &remote.token {
+        Some(token) => cmds::RemoteRequest::Authenticated {
+            token: token.clone(),
+            command: cmd,
+        },
+        None => cmds::RemoteRequest::Anonymous(cmd),
+    };
+    serde_json::to_writer(&stream, &req)
+        .map_err(|e| SwayrError::ConnectionError(e.to_string()))?;
+    stream
+        .shutdown(std::net::Shutdown::Write)
+        .map_err(|e| SwayrError::ConnectionError(e.to_string()))?;
+    read_response(&stream, on_output, address)
+}
+
+fn connection_error_message(socket_path: &str, err: &std::io::Error) -> String {
+    let pids = util::get_pids_of_process("swayrd");
+    let hint = if pids.is_empty() {
+        "swayrd doesn't seem to be running.  Start it in your sway config, \
+         e.g., with `exec env RUST_BACKTRACE=1 swayrd`, or set \
+         misc.systemd_unit if it's run as systemd user service."
+            .to_owned()
+    } else {
+        format!(
+            "swayrd seems to be running (pid {}) but not listening on that \
+             socket.  Maybe it belongs to another sway session; the socket \
+             path is derived from $XDG_RUNTIME_DIR, $WAYLAND_DISPLAY, and \
+             the --socket-suffix.",
+            pids.iter()
+                .map(|pid| pid.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    };
+    format!("Could not connect to swayrd at {socket_path}: {err}\n{hint}")
 }
@@ -16,11 +16,18 @@
 //! Functions and data structures of the swayr client.
 
 use crate::config as cfg;
+use crate::config::CommandOrder;
 use crate::criteria;
-use crate::daemon::CONFIG;
+use crate::daemon::{self, CONFIG};
+use crate::focus::DoNotDisturb;
+use crate::focus::FocusBookmark;
 use crate::focus::FocusData;
 use crate::focus::FocusMessage;
 use crate::layout;
+use crate::metrics;
+use crate::ordering;
+use crate::shared::fmt::subst_placeholders;
+use crate::shared::icon;
 use crate::shared::ipc;
 use crate::shared::ipc::NodeMethods;
 use crate::tree as t;
@@ -30,31 +37,108 @@ use once_cell::sync::Lazy;
 use rand::prelude::SliceRandom;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::collections::HashSet;
 use std::io::Read;
 use std::sync::mpsc::channel;
 use std::sync::Mutex;
 use std::sync::MutexGuard;
 use std::thread;
+use std::time::{Duration, Instant};
 use swayipc as s;
 
-pub fn run_sway_command_1(cmd: &str) -> Result<String, String> {
+/// The error of a swayr command.  It is sent from swayrd to the client which
+/// maps it to its exit code.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SwayrError {
+    /// The client couldn't talk to swayrd.
+    ConnectionError(String),
+    /// Any other error during command execution.
+    DaemonError(String),
+    /// No window, workspace, etc. matched.
+    NoMatch(String),
+    /// The menu program has been quit without selection.
+    MenuAborted,
+    /// Sway couldn't execute a command.
+    SwayCommandFailed(String),
+    /// A remote client wanted to run a command it isn't authorized for.
+    PermissionDenied(String),
+    /// The client couldn't parse its input, e.g., a line read by `swayr pipe`.
+    InvalidInput(String),
+}
+
+impl SwayrError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            SwayrError::DaemonError(_) => 1,
+            SwayrError::NoMatch(_) => 2,
+            SwayrError::MenuAborted => 3,
+            SwayrError::SwayCommandFailed(_) => 4,
+            SwayrError::ConnectionError(_) => 5,
+            SwayrError::PermissionDenied(_) => 6,
+            SwayrError::InvalidInput(_) => 7,
+        }
+    }
+}
+
+impl std::fmt::Display for SwayrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SwayrError::ConnectionError(msg)
+            | SwayrError::DaemonError(msg)
+            | SwayrError::NoMatch(msg)
+            | SwayrError::SwayCommandFailed(msg)
+            | SwayrError::PermissionDenied(msg)
+            | SwayrError::InvalidInput(msg) => write!(f, "{msg}"),
+            SwayrError::MenuAborted => write!(f, "Nothing selected in menu."),
+        }
+    }
+}
+
+impl From<String> for SwayrError {
+    fn from(msg: String) -> Self {
+        SwayrError::DaemonError(msg)
+    }
+}
+
+impl From<criteria::CriteriaError> for SwayrError {
+    fn from(err: criteria::CriteriaError) -> Self {
+        SwayrError::DaemonError(err.to_string())
+    }
+}
+
+impl From<&str> for SwayrError {
+    fn from(msg: &str) -> Self {
+        SwayrError::DaemonError(msg.to_owned())
+    }
+}
+
+pub fn run_sway_command_1(cmd: &str) -> Result<String, SwayrError> {
     log::debug!("Running sway command: {cmd}");
     match s::Connection::new() {
         Ok(mut con) => match con.run_command(cmd) {
+            Ok(outcomes) => match outcomes.into_iter().find_map(|o| o.err()) {
+                Some(err) => {
+                    log::error!("Sway command '{cmd}' failed: {err}");
+                    Err(SwayrError::SwayCommandFailed(format!(
+                        "Sway command '{cmd}' failed: {err}"
+                    )))
+                }
+                None => Ok(format!("Executed sway command '{cmd}'")),
+            },
             Err(err) => {
                 log::error!("Could not run sway command: {err}");
-                Err(err.to_string())
+                Err(SwayrError::SwayCommandFailed(err.to_string()))
             }
-            _ => Ok(format!("Executed sway command '{cmd}'")),
         },
         Err(err) => {
             log::error!("Couldn't create sway ipc connection: {err}");
-            Err(err.to_string())
+            Err(SwayrError::SwayCommandFailed(err.to_string()))
         }
     }
 }
 
-pub fn run_sway_command(args: &[&str]) -> Result<String, String> {
+pub fn run_sway_command(args: &[&str]) -> Result<String, SwayrError> {
     let cmd = args.join(" ");
     run_sway_command_1(&cmd)
 }
@@ -67,12 +151,66 @@ pub enum ConsiderFloating {
     ExcludeFloating,
 }
 
+#[derive(clap::Parser, Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub enum NeighborDirection {
+    /// The neighbor to the left.
+    Left,
+    /// The neighbor to the right.
+    Right,
+    /// The neighbor above.
+    Up,
+    /// The neighbor below.
+    Down,
+}
+
+/// Options of get-windows-as-json restricting the returned nodes.
+#[derive(
+    clap::Parser, PartialEq, Eq, Debug, Clone, Default, Deserialize, Serialize,
+)]
+pub struct NodeFilter {
+    #[clap(
+        short = 'c',
+        long,
+        help = "Also include workspaces and containers, distinguished by their swayr_type."
+    )]
+    pub include_containers: bool,
+    #[clap(
+        long,
+        help = "Only include nodes at most this deep below their workspace, e.g., 1 for direct children."
+    )]
+    pub max_depth: Option<usize>,
+    #[clap(
+        long,
+        help = "Only include nodes on workspaces whose name matches this regex."
+    )]
+    pub workspace: Option<String>,
+}
+
 #[derive(clap::Parser, Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
 pub enum ConsiderWindows {
     /// Consider windows of all workspaces.
     AllWorkspaces,
     /// Consider windows of only the current workspaces.
     CurrentWorkspace,
+    /// Consider windows of all workspaces on the current output.
+    CurrentOutput,
+}
+
+/// The parts of swayrd's runtime state which can be reset.
+#[derive(clap::Parser, Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub enum ResettableState {
+    /// Forget the recency of use of all windows, workspaces, and outputs.
+    Lru,
+    /// Abort the current sequence of prev/next or switch-to-*-or-lru
+    /// commands.
+    Sequence,
+    /// Forget all focus bookmarks.
+    Slots,
+    /// Forget the workspace back/forward history, the focus history, and the
+    /// workspaces recently shown on each output.
+    History,
+    /// Reset all of the above.
+    All,
 }
 
 #[derive(clap::Parser, PartialEq, Eq, Debug, Clone, Deserialize, Serialize)]
@@ -95,6 +233,75 @@ pub struct SkipFlags {
     skip_lru_if_current_doesnt_match: bool,
     #[clap(short = 'o', long, help = "Don't switch back to the origin window")]
     skip_origin: bool,
+    #[clap(
+        short = 'w',
+        long,
+        help = "Switch to the workspaces of urgent windows instead of focusing them"
+    )]
+    focus_urgent_workspace: bool,
+}
+
+/// Formats overriding the ones defined in the config for a single command
+/// invocation.
+#[derive(
+    clap::Parser, PartialEq, Eq, Clone, Default, Deserialize, Serialize,
+)]
+pub struct FormatOverrides {
+    #[clap(long, help = "Override format.output_format")]
+    pub output_format: Option<String>,
+    #[clap(long, help = "Override format.workspace_format")]
+    pub workspace_format: Option<String>,
+    #[clap(long, help = "Override format.container_format")]
+    pub container_format: Option<String>,
+    #[clap(long, help = "Override format.window_format")]
+    pub window_format: Option<String>,
+    #[clap(long, help = "Override format.urgent_first")]
+    pub urgent_first: Option<bool>,
+}
+
+// Only show the overrides actually given so that the logged commands stay
+// readable.
+impl std::fmt::Debug for FormatOverrides {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut d = f.debug_struct("FormatOverrides");
+        for (name, val) in [
+            ("output_format", &self.output_format),
+            ("workspace_format", &self.workspace_format),
+            ("container_format", &self.container_format),
+            ("window_format", &self.window_format),
+        ] {
+            if let Some(val) = val {
+                d.field(name, val);
+            }
+        }
+        if let Some(urgent_first) = &self.urgent_first {
+            d.field("urgent_first", urgent_first);
+        }
+        d.finish()
+    }
+}
+
+/// How many windows the prev/next window commands move at once.
+#[derive(
+    clap::Parser, PartialEq, Eq, Debug, Clone, Copy, Deserialize, Serialize,
+)]
+#[serde(transparent)]
+pub struct WindowCount {
+    /// Move this many windows at once in the command's direction, e.g., with
+    /// 2, one window is skipped.
+    #[clap(
+        long,
+        global = true,
+        default_value_t = 1,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub count: usize,
+}
+
+impl Default for WindowCount {
+    fn default() -> Self {
+        WindowCount { count: 1 }
+    }
 }
 
 #[derive(clap::Parser, PartialEq, Eq, Debug, Clone, Deserialize, Serialize)]
@@ -102,6 +309,21 @@ pub enum SwayrCommand {
     /// No-operation. Interrupts any in-progress prev/next sequence but has
     /// no other effect
     Nop,
+    /// Executes the given swayr commands in order using a single connection
+    /// to swayrd, e.g., `swayr do 'next-window all-workspaces' 'nop'`.
+    /// Returns the outputs of all commands or the error of the first failing
+    /// one.
+    Do {
+        #[clap(
+            long,
+            help = "Don't execute the remaining commands after one \
+                    of them failed"
+        )]
+        stop_on_error: bool,
+        /// The commands given like on the command line, each as one argument.
+        #[clap(required = true)]
+        commands: Vec<String>,
+    },
     /// Switch to next urgent window (if any) or to last recently used window.
     SwitchToUrgentOrLRUWindow {
         #[clap(flatten)]
@@ -147,26 +369,101 @@ pub enum SwayrCommand {
         #[clap(flatten)]
         skip_flags: SkipFlags,
     },
+    /// Switch to the workspace containing the window which most recently got
+    /// an urgency hint without focusing the window itself.
+    SwitchToUrgentWorkspace,
+    /// Focus the n-th last used window where 1 is the LRU window, i.e., the
+    /// window which had the focus before the current one.
+    FocusNthLastWindow {
+        /// The position in the LRU order, starting at 1.
+        n: usize,
+    },
+    /// Focus the window whose title, app_id or window class, and marks match
+    /// the given query best without showing a menu.
+    ///
+    /// Each word of the query is matched fuzzily against those properties,
+    /// and more recently used windows are preferred over slightly better
+    /// matches of long unused ones.
+    QuickSwitch {
+        /// The query, e.g., "fire mail".
+        query: String,
+    },
+    /// Show the chronological focus history (most recent first) in the menu
+    /// and focus the selected window.
+    ListFocusHistory {
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
+    /// Go back to the previously focused workspace in the workspace history.
+    WorkspaceBack,
+    /// Go forward in the workspace history after `workspace-back`.
+    WorkspaceForward,
     /// Focus the selected window.
-    SwitchWindow,
+    SwitchWindow {
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
+    /// Select an application and focus its most recently used window, or
+    /// select one of its windows with `--select-window`.
+    SwitchApp {
+        #[clap(
+            short,
+            long,
+            help = "Select the window using a second menu if the application has several windows."
+        )]
+        select_window: bool,
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
     /// Steal the selected window from another workspace into the current
     /// workspace.
-    StealWindow,
+    StealWindow {
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
     /// Steal the selected window or container from another workspace into the
     /// current workspace.
-    StealWindowOrContainer,
+    StealWindowOrContainer {
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
     /// Switch to the selected workspace.
-    SwitchWorkspace,
+    SwitchWorkspace {
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
     /// Switch to the selected output.
-    SwitchOutput,
+    SwitchOutput {
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
     /// Switch to the selected workspace or focus the selected window.
-    SwitchWorkspaceOrWindow,
+    SwitchWorkspaceOrWindow {
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
     /// Switch to the selected workspace or focus the selected container, or
     /// window.
-    SwitchWorkspaceContainerOrWindow,
+    SwitchWorkspaceContainerOrWindow {
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
     /// Switch to the selected output or workspace or focus the selected
     /// container, or window.
-    SwitchTo,
+    SwitchTo {
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
     /// Quit the selected window.
     QuitWindow {
         #[clap(
@@ -175,62 +472,168 @@ pub enum SwayrCommand {
             help = "Kill the window's process rather than just quitting it"
         )]
         kill: bool,
+        #[clap(
+            short,
+            long,
+            help = "Don't ask for confirmation before quitting protected windows"
+        )]
+        force: bool,
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
     },
     /// Quit all windows of selected workspace or the selected window.
-    QuitWorkspaceOrWindow,
+    QuitWorkspaceOrWindow {
+        #[clap(
+            short,
+            long,
+            help = "Don't ask for confirmation before quitting protected windows"
+        )]
+        force: bool,
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
     /// Quit all windows of selected workspace, or container or the selected
     /// window.
-    QuitWorkspaceContainerOrWindow,
+    QuitWorkspaceContainerOrWindow {
+        #[clap(
+            short,
+            long,
+            help = "Don't ask for confirmation before quitting protected windows"
+        )]
+        force: bool,
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
+    /// Reopen the most recently closed window by re-running the command line
+    /// of its process.
+    ReopenLastClosed,
     /// Focus the next window in LRU order.
     NextWindow {
         #[clap(subcommand)]
         windows: ConsiderWindows,
+        /// Only consider windows also matching the given criteria query.
+        #[clap(long, global = true)]
+        #[serde(default)]
+        matching: Option<String>,
+        #[clap(flatten)]
+        #[serde(default)]
+        count: WindowCount,
     },
     /// Focus the previous window in LRU order.
     PrevWindow {
         #[clap(subcommand)]
         windows: ConsiderWindows,
+        /// Only consider windows also matching the given criteria query.
+        #[clap(long, global = true)]
+        #[serde(default)]
+        matching: Option<String>,
+        #[clap(flatten)]
+        #[serde(default)]
+        count: WindowCount,
     },
     /// Focus the next window of a tiled container.
     NextTiledWindow {
         #[clap(subcommand)]
         windows: ConsiderWindows,
+        /// Only consider windows also matching the given criteria query.
+        #[clap(long, global = true)]
+        #[serde(default)]
+        matching: Option<String>,
+        #[clap(flatten)]
+        #[serde(default)]
+        count: WindowCount,
     },
     /// Focus the previous window of a tiled container.
     PrevTiledWindow {
         #[clap(subcommand)]
         windows: ConsiderWindows,
+        /// Only consider windows also matching the given criteria query.
+        #[clap(long, global = true)]
+        #[serde(default)]
+        matching: Option<String>,
+        #[clap(flatten)]
+        #[serde(default)]
+        count: WindowCount,
     },
     /// Focus the next window of a tabbed or stacked container.
     NextTabbedOrStackedWindow {
         #[clap(subcommand)]
         windows: ConsiderWindows,
+        /// Only consider windows also matching the given criteria query.
+        #[clap(long, global = true)]
+        #[serde(default)]
+        matching: Option<String>,
+        #[clap(flatten)]
+        #[serde(default)]
+        count: WindowCount,
     },
     /// Focus the previous window of a tabbed or stacked container.
     PrevTabbedOrStackedWindow {
         #[clap(subcommand)]
         windows: ConsiderWindows,
+        /// Only consider windows also matching the given criteria query.
+        #[clap(long, global = true)]
+        #[serde(default)]
+        matching: Option<String>,
+        #[clap(flatten)]
+        #[serde(default)]
+        count: WindowCount,
     },
     /// Focus the next floating window.
     NextFloatingWindow {
         #[clap(subcommand)]
         windows: ConsiderWindows,
+        /// Only consider windows also matching the given criteria query.
+        #[clap(long, global = true)]
+        #[serde(default)]
+        matching: Option<String>,
+        #[clap(flatten)]
+        #[serde(default)]
+        count: WindowCount,
     },
     /// Focus the previous floating window.
     PrevFloatingWindow {
         #[clap(subcommand)]
         windows: ConsiderWindows,
+        /// Only consider windows also matching the given criteria query.
+        #[clap(long, global = true)]
+        #[serde(default)]
+        matching: Option<String>,
+        #[clap(flatten)]
+        #[serde(default)]
+        count: WindowCount,
     },
     /// Focus the next window having the same layout as the current one.
     NextWindowOfSameLayout {
         #[clap(subcommand)]
         windows: ConsiderWindows,
+        /// Only consider windows also matching the given criteria query.
+        #[clap(long, global = true)]
+        #[serde(default)]
+        matching: Option<String>,
+        #[clap(flatten)]
+        #[serde(default)]
+        count: WindowCount,
     },
     /// Focus the previous window having the same layout as the current one.
     PrevWindowOfSameLayout {
         #[clap(subcommand)]
         windows: ConsiderWindows,
+        /// Only consider windows also matching the given criteria query.
+        #[clap(long, global = true)]
+        #[serde(default)]
+        matching: Option<String>,
+        #[clap(flatten)]
+        #[serde(default)]
+        count: WindowCount,
     },
+    /// Focus the next window in the same container as the current one.
+    NextWindowInContainer,
+    /// Focus the previous window in the same container as the current one.
+    PrevWindowInContainer,
     /// Focus the next window matching the given criteria query.
     NextMatchingWindow {
         /// The criteria query defining which windows to switch to.
@@ -243,13 +646,45 @@ pub enum SwayrCommand {
     },
     /// Move the currently focused window or container to the selected
     /// workspace.
-    MoveFocusedToWorkspace,
+    MoveFocusedToWorkspace {
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
+    /// Rename the focused workspace to the name typed into the menu which
+    /// shows the current workspace names.  Selecting a workspace renames that
+    /// one instead.  The number of the workspace is kept if the new name
+    /// doesn't start with one.
+    RenameWorkspaceInteractively {
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
+    /// Rename the workspaces containing windows matching the given criteria
+    /// query according to the given template.
+    RenameWorkspace {
+        /// The criteria query defining the windows whose workspaces are
+        /// renamed, e.g., `[workspace=__focused__]`.
+        from_criteria: String,
+        /// The new name with the placeholders `{name}` (the current name),
+        /// `{number}`, and `{app_names}` (the distinct app names of the
+        /// workspace's windows separated by commas).
+        to_template: String,
+    },
     /// Move the currently focused window or container to the selected output,
     /// workspace, container or window.
-    MoveFocusedTo,
+    MoveFocusedTo {
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
     /// Swap the currently focused window or container with the selected
     /// container or window.
-    SwapFocusedWith,
+    SwapFocusedWith {
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
     /// Tab or shuffle-and-tile the windows on the current workspace, including
     /// or excluding floating windows.
     ToggleTabShuffleTileWorkspace {
@@ -274,8 +709,169 @@ pub enum SwayrCommand {
         #[clap(subcommand)]
         floating: ConsiderFloating,
     },
+    /// Move the focused window out of its parent container into its
+    /// grandparent container.
+    PromoteFocused,
+    /// Make the focused window the only visible one on its workspace by
+    /// moving all other windows to a parking workspace.  Invoked again, the
+    /// previous arrangement is restored.
+    ToggleMaximizeFocused,
+    /// Wrap the focused window and its neighbor in the given direction into a
+    /// new split container.
+    DemoteFocusedInto {
+        #[clap(
+            short,
+            long,
+            help = "The layout of the new container (splith, splitv, tabbed, \
+                    or stacking); by default the one matching the direction"
+        )]
+        layout: Option<String>,
+        #[clap(subcommand)]
+        direction: NeighborDirection,
+    },
+    /// Spread the windows of the current workspace across it and the next N
+    /// empty numbered workspaces.  The most recently used window stays put.
+    SpreadWorkspace {
+        /// The number of additional workspaces to spread the windows across.
+        n: usize,
+    },
+    /// Move all windows of the given app or matching the given criteria query
+    /// to the current or the given workspace.
+    GatherApp {
+        #[clap(
+            short,
+            long,
+            help = "The workspace to gather the windows on (default: current)"
+        )]
+        workspace: Option<String>,
+        #[clap(
+            short,
+            long,
+            help = "Tab the windows of the workspace after gathering"
+        )]
+        tab: bool,
+        /// An app name (as for switch-to-app-or-urgent-or-lru-window) or a
+        /// criteria query in brackets.
+        name_or_criteria: String,
+    },
+    /// Float the focused window and set its size and position according to
+    /// the given preset from the `float_presets` config section.  Without
+    /// preset, select it using the menu program.
+    FloatFocusedPreset {
+        /// The name of the preset, e.g., center-60 or right-third.
+        preset: Option<String>,
+    },
+    /// Set the title shown for the focused window using sway's title_format,
+    /// e.g., `%title (ssh)`.  `%title` resets it.  Without title, select one
+    /// of the `layout.title_format` formats or enter one using the menu
+    /// program.
+    RenameFocusedWindow {
+        /// The title format, may contain sway's placeholders like `%title`.
+        title: Option<String>,
+    },
+    /// Apply the given appearance preset, i.e., the sway commands setting
+    /// gaps, borders, etc. defined in the `appearance_presets` config section.
+    /// Without preset, select it using the menu program.
+    AppearancePresets {
+        /// The name of the preset, e.g., compact or presentation.
+        preset: Option<String>,
+    },
+    /// Add the focused window to the given window set.
+    AddFocusedToSet {
+        /// The name of the window set.
+        name: String,
+    },
+    /// Remove the focused window from the given window set.
+    RemoveFocusedFromSet {
+        /// The name of the window set.
+        name: String,
+    },
+    /// Hide all windows of the given window set if any of them is visible,
+    /// otherwise show them all on the current workspace.
+    ToggleSetVisibility {
+        /// The name of the window set.
+        name: String,
+    },
+    /// Select a window set and toggle its visibility or add or remove the
+    /// focused window.
+    ManageWindowSets,
+    /// Select one of the ancestors of the focused window, i.e., its
+    /// containers, workspace, or output, and focus it.
+    SelectContainerScope {
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
+    /// Select a window and then an action to perform on it, e.g., focusing,
+    /// moving it to a workspace, toggling floating or fullscreen, marking,
+    /// quitting, or copying its title to the clipboard.
+    WindowActions {
+        #[clap(flatten)]
+        #[serde(default)]
+        formats: FormatOverrides,
+    },
+    /// Copy the given format string with the placeholders substituted for the
+    /// selected window to the clipboard using wl-copy.
+    CopyWindowInfo {
+        #[clap(
+            short,
+            long,
+            help = "Use the windows matching the criteria query (one line each) instead of selecting a window using the menu program."
+        )]
+        criteria: Option<String>,
+        /// The format string, e.g., "{app_name}: {title} ({pid})".
+        format: String,
+    },
+    /// Keep the focus on the window matching the given criteria query, i.e.,
+    /// re-focus it whenever some other window gets focused.  Without
+    /// criteria, stop following.
+    FollowMatching {
+        /// The criteria query of the window to keep focused.
+        criteria: Option<String>,
+        #[clap(
+            short,
+            long,
+            help = "Stop following if already following the given criteria."
+        )]
+        toggle: bool,
+    },
+    /// Reload the sway config, check that swayrd still receives sway's
+    /// events, and re-prime swayrd's focus data from the fresh tree.
+    ReloadSway,
+    /// Toggle do-not-disturb mode, e.g., during screen sharing.  While it is
+    /// active, the switch-to-*-or-urgent-or-lru-window commands don't prefer
+    /// windows with urgency hint.
+    ToggleDoNotDisturb {
+        #[clap(
+            long,
+            help = "Also remove the urgency hints of windows while \
+                    do-not-disturb is active"
+        )]
+        clear_urgency: bool,
+    },
+    /// Reset parts of swayrd's runtime state without restarting it, e.g.,
+    /// after experiments.
+    ResetState {
+        #[clap(subcommand)]
+        what: ResettableState,
+    },
+    /// Save the focused window together with its workspace and output as
+    /// focus bookmark.
+    SaveFocusBookmark {
+        /// The name of the bookmark.
+        name: String,
+    },
+    /// Restore the given focus bookmark, i.e., focus its window (or a window
+    /// of the same app if it has been closed) or its workspace on its
+    /// output.  Without name, select the bookmark using the menu program.
+    RestoreFocusBookmark {
+        /// The name of the bookmark.
+        name: Option<String>,
+    },
     /// Select and execute a swaymsg command.
     ExecuteSwaymsgCommand,
+    /// Select a sway binding mode and switch to it.
+    SwitchBindingMode,
     /// Select and execute a swayr command.
     ExecuteSwayrCommand,
     /// Configure outputs.
@@ -302,6 +898,36 @@ pub enum SwayrCommand {
             help = "Return non-zero if no (matching) windows are found instead of returning an empty JSON array."
         )]
         error_if_no_match: bool,
+        #[clap(
+            short,
+            long,
+            value_delimiter = ',',
+            help = "Only include the given comma-separated fields, e.g., id,app_id,pid."
+        )]
+        fields: Option<Vec<String>>,
+        #[clap(flatten)]
+        filter: NodeFilter,
+        #[clap(long, help = "Return compact instead of pretty-printed JSON.")]
+        #[serde(default)]
+        compact: bool,
+        #[clap(
+            long,
+            conflicts_with = "compact",
+            help = "Return one compact JSON object per line (JSON Lines) instead of an array."
+        )]
+        #[serde(default)]
+        jsonl: bool,
+    },
+    /// Returns the focused window as JSON object like in the array returned by
+    /// `get-windows-as-json`.  Returns non-zero if no window is focused.
+    GetFocusedAsJson {
+        #[clap(
+            short,
+            long,
+            value_delimiter = ',',
+            help = "Only include the given comma-separated fields, e.g., id,app_id,pid."
+        )]
+        fields: Option<Vec<String>>,
     },
     /// Executes a shell command for each matching window.
     ForEachWindow {
@@ -317,14 +943,119 @@ pub enum SwayrCommand {
             help = "Return non-zero if no (matching) windows are found instead of just doing nothing."
         )]
         error_if_no_match: bool,
+        #[clap(
+            short,
+            long,
+            help = "Don't capture the stdout and stderr of the shell commands."
+        )]
+        discard_output: bool,
+        #[clap(
+            short,
+            long,
+            help = "Run the command of the given custom window action instead of a shell command.",
+            conflicts_with = "shell_command"
+        )]
+        #[serde(default)]
+        action: Option<String>,
         criteria: String,
         shell_command: Vec<String>,
     },
+    /// Waits until a window matching the given criteria query exists and
+    /// returns it as JSON.  Returns non-zero if no such window appears within
+    /// the timeout.
+    WaitForWindow {
+        #[clap(
+            short,
+            long,
+            default_value_t = 5000,
+            help = "The maximum time to wait in milliseconds."
+        )]
+        timeout: u64,
+        #[clap(
+            short = 'n',
+            long,
+            help = "Only consider windows which didn't exist at invocation time."
+        )]
+        only_new: bool,
+        #[clap(short, long, help = "Focus the window once it appeared.")]
+        focus: bool,
+        #[clap(
+            short,
+            long,
+            help = "Move the window to the given workspace once it appeared."
+        )]
+        move_to_workspace: Option<String>,
+        /// The criteria query defining which window to wait for.
+        criteria: String,
+    },
+    /// Returns the most recently used window of the given workspace as JSON.
+    GetWorkspaceLRUWindow {
+        /// The name of the workspace.
+        workspace: String,
+    },
+    /// Prints the icon file of the given app_id or window class as determined
+    /// by swayrd for the menus.
+    GetIcon {
+        /// The app_id or window class, e.g., firefox.
+        app_name: String,
+    },
+    /// Prints `on` if do-not-disturb mode is active, otherwise `off`.
+    GetDoNotDisturb,
+    /// Returns the state of the current sequence of
+    /// switch-to-*-or-urgent-or-lru-window commands as JSON, i.e., its origin
+    /// window, the visited windows, and the remaining candidates.
+    GetSwitchSequenceState,
+    /// Returns a JSON array of the recently closed windows (most recently
+    /// closed first) including the command lines used for reopening them.
+    ListRecentlyClosed,
+    /// Checks that swayrd is running and responsive and returns its version,
+    /// the sizes of its focus data, and the round-trip latency as JSON.
+    Ping,
+    /// Returns swayrd's metrics like the number of executed commands, their
+    /// execution times, and the number of sway events in the Prometheus text
+    /// format.  Requires `metrics.enabled = true`.
+    Metrics,
     /// Print the current effective swayr configuration (without default
     /// values).
-    PrintConfig,
+    PrintConfig {
+        #[clap(
+            long,
+            help = "Print all settings including defaults as JSON object \
+                    with the value and source (file, env, or default) of \
+                    each setting"
+        )]
+        json: bool,
+    },
+    /// Prints the current values of the given kinds, one per line, for
+    /// shell completion.
+    #[clap(hide = true)]
+    CompletionsDynamic {
+        #[clap(long, help = "Print the workspace names")]
+        workspaces: bool,
+        #[clap(long, help = "Print the marks")]
+        marks: bool,
+        #[clap(long, help = "Print the app_ids and window classes")]
+        app_ids: bool,
+        #[clap(long, help = "Print the output names")]
+        outputs: bool,
+        #[clap(long, help = "Print the swayr command names")]
+        commands: bool,
+    },
     /// Prints the default swayr configuration.
     PrintDefaultConfig,
+    /// Prints the JSON schema of swayr's config.toml for validation and
+    /// completion in editors.
+    PrintConfigSchema,
+    /// Prints a sway config snippet binding keys to the most common swayr
+    /// commands.
+    PrintSwayBindings {
+        #[clap(
+            long,
+            default_value = "$mod",
+            help = "The modifier of the key bindings"
+        )]
+        modifier: String,
+    },
 }
 
 impl SwayrCommand {
@@ -341,6 +1072,8 @@ impl SwayrCommand {
                 | SwayrCommand::PrevFloatingWindow { .. }
                 | SwayrCommand::NextWindowOfSameLayout { .. }
                 | SwayrCommand::PrevWindowOfSameLayout { .. }
+                | SwayrCommand::NextWindowInContainer
+                | SwayrCommand::PrevWindowInContainer
                 | SwayrCommand::NextMatchingWindow { .. }
                 | SwayrCommand::PrevMatchingWindow { .. }
         )
@@ -350,57 +1083,856 @@ impl SwayrCommand {
         matches!(
             self,
             SwayrCommand::GetWindowsAsJson { .. }
+                | SwayrCommand::GetFocusedAsJson { .. }
+                | SwayrCommand::GetWorkspaceLRUWindow { .. }
+                | SwayrCommand::GetIcon { .. }
+                | SwayrCommand::GetDoNotDisturb
+                | SwayrCommand::GetSwitchSequenceState
+                | SwayrCommand::CompletionsDynamic { .. }
                 | SwayrCommand::ForEachWindow { .. }
+                | SwayrCommand::ListRecentlyClosed
+                | SwayrCommand::Ping
+                | SwayrCommand::Metrics
+                | SwayrCommand::WaitForWindow { .. }
         )
     }
-}
 
-pub struct ExecSwayrCmdArgs<'a> {
-    pub cmd: &'a SwayrCommand,
-    pub focus_data: &'a FocusData,
-}
+    /// Read-only commands may also be run by unauthenticated remote clients.
+    /// They are listed explicitly so that new commands aren't reachable
+    /// remotely by accident.  Commands like `list-recently-closed` or
+    /// `metrics` are left out on purpose because they reveal command lines or
+    /// usage data.
+    pub(crate) fn is_read_only_command(&self) -> bool {
+        matches!(
+            self,
+            SwayrCommand::Ping
+                | SwayrCommand::GetWindowsAsJson { .. }
+                | SwayrCommand::GetFocusedAsJson { .. }
+                | SwayrCommand::GetWorkspaceLRUWindow { .. }
+                | SwayrCommand::GetDoNotDisturb
+        )
+    }
 
-impl DisplayFormat for SwayrCommand {
-    fn format_for_display(&self) -> std::string::String {
-        // TODO: It would be very nice if the display format was exactly like
-        // the swayr invocation in the shell.  Can that somehow be retrieved
-        // from clap?
-        format!("{self:?}")
+    /// Commands which may run while the session is locked because they
+    /// neither change the focus or layout nor run other programs.  That's
+    /// the read-only commands, a few other queries, and `wait-for-window`
+    /// without `--focus` and `--move-to-workspace`.
+    pub(crate) fn is_allowed_while_locked(&self) -> bool {
+        self.is_read_only_command()
+            || matches!(
+                self,
+                SwayrCommand::GetIcon { .. }
+                    | SwayrCommand::GetSwitchSequenceState
+                    | SwayrCommand::CompletionsDynamic { .. }
+                    | SwayrCommand::ListRecentlyClosed
+                    | SwayrCommand::Metrics
+                    | SwayrCommand::WaitForWindow {
+                        focus: false,
+                        move_to_workspace: None,
+                        ..
+                    }
+            )
     }
 
-    fn get_indent_level(&self) -> usize {
-        0
+    /// Streamed commands are answered with a sequence of partial results,
+    /// each sent as soon as it's available, instead of one result.  Currently,
+    /// that's only `get-windows-as-json --jsonl` with one JSON Lines record
+    /// per result.
+    pub fn is_streamed(&self) -> bool {
+        matches!(self, SwayrCommand::GetWindowsAsJson { jsonl: true, .. })
+    }
+
+    /// Blocking commands may take a long time and are therefore executed in
+    /// their own thread.  A batch is only blocking if one of its commands is,
+    /// otherwise it runs inline so that it's executed in order with respect to
+    /// other commands.
+    pub(crate) fn is_blocking_command(&self) -> bool {
+        match self {
+            SwayrCommand::WaitForWindow { .. } | SwayrCommand::ReloadSway => {
+                true
+            }
+            SwayrCommand::Do { commands, .. } => commands.iter().any(|c| {
+                util::shell_split(c)
+                    .and_then(|args| parse_swayr_command(&args))
+                    .is_ok_and(|cmd| cmd.is_blocking_command())
+            }),
+            _ => false,
+        }
     }
 }
 
-fn always_true(_x: &t::DisplayNode) -> bool {
-    true
+#[test]
+fn test_is_blocking_command() {
+    let batch = |commands: &[&str]| SwayrCommand::Do {
+        stop_on_error: false,
+        commands: commands.iter().map(|c| c.to_string()).collect(),
+    };
+    assert!(
+        !batch(&["next-window all-workspaces", "nop"]).is_blocking_command()
+    );
+    assert!(batch(&["nop", "wait-for-window '[app_id=\"foot\"]'"])
+        .is_blocking_command());
+    assert!(!batch(&["no-such-command"]).is_blocking_command());
 }
 
-static LAST_COMMAND: Lazy<Mutex<SwayrCommand>> =
-    Lazy::new(|| Mutex::new(SwayrCommand::Nop));
+/// A command sent to swayrd over TCP.  Either just the command like on the
+/// UNIX socket or the command together with the shared secret token which
+/// authorizes read-write access.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum RemoteRequest {
+    Authenticated {
+        token: String,
+        command: SwayrCommand,
+    },
+    Anonymous(SwayrCommand),
+}
 
-#[derive(Debug)]
-pub struct SwitchToMatchingData {
-    visited: Vec<i64>,
-    lru: Option<i64>,
-    origin: Option<i64>,
-    skip_urgent: bool,
-    skip_lru: bool,
-    skip_lru_if_current_doesnt_match: bool,
-    skip_origin: bool,
+#[derive(clap::Parser)]
+#[clap(no_binary_name = true)]
+struct CommandLine {
+    #[clap(subcommand)]
+    command: SwayrCommand,
 }
 
-impl SwitchToMatchingData {
+/// Parses a swayr command given as list of arguments like on the command
+/// line, e.g., `["next-window", "current-workspace"]`.
+pub fn parse_swayr_command(args: &[String]) -> Result<SwayrCommand, String> {
+    use clap::Parser;
+    CommandLine::try_parse_from(args)
+        .map(|cl| cl.command)
+        .map_err(|e| e.to_string())
+}
+
+/// Parses a swayr command given in its JSON form, e.g.,
+/// `{"NextWindow":{"windows":"AllWorkspaces"}}`.  Commands whose arguments
+/// all have defaults may also be given by their name only, e.g.,
+/// `"SwitchWindow"`.
+pub fn parse_swayr_command_json(
+    json: &str,
+) -> Result<SwayrCommand, serde_json::Error> {
+    serde_json::from_str(json).or_else(|err| {
+        match serde_json::from_str::<serde_json::Value>(json) {
+            Ok(serde_json::Value::String(name)) => {
+                serde_json::from_value(serde_json::json!({ name: {} }))
+                    .map_err(|_| err)
+            }
+            _ => Err(err),
+        }
+    })
+}
+
+#[test]
+fn test_parse_swayr_command_json() {
+    let switch_window = SwayrCommand::SwitchWindow {
+        formats: FormatOverrides::default(),
+    };
+    for json in [r#"{"SwitchWindow":{}}"#, r#""SwitchWindow""#] {
+        assert_eq!(switch_window, parse_swayr_command_json(json).unwrap());
+    }
+    let json = serde_json::to_string(&switch_window).unwrap();
+    assert_eq!(switch_window, parse_swayr_command_json(&json).unwrap());
+    assert_eq!(
+        SwayrCommand::Ping,
+        parse_swayr_command_json(r#""Ping""#).unwrap()
+    );
+    assert!(parse_swayr_command_json(r#""NextWindow""#).is_err());
+    assert!(parse_swayr_command_json(r#""NoSuchCommand""#).is_err());
+}
+
+#[test]
+fn test_parse_swayr_command() {
+    let args = |s: &str| -> Vec<String> {
+        s.split_whitespace().map(|a| a.to_owned()).collect()
+    };
+    assert_eq!(
+        parse_swayr_command(&args("next-window current-workspace")),
+        Ok(SwayrCommand::NextWindow {
+            windows: ConsiderWindows::CurrentWorkspace,
+            matching: None,
+            count: WindowCount::default(),
+        })
+    );
+    for cl in [
+        "next-tiled-window current-workspace --matching [app_id=foot]",
+        "next-tiled-window --matching [app_id=foot] current-workspace",
+    ] {
+        assert_eq!(
+            parse_swayr_command(&args(cl)),
+            Ok(SwayrCommand::NextTiledWindow {
+                windows: ConsiderWindows::CurrentWorkspace,
+                matching: Some("[app_id=foot]".to_owned()),
+                count: WindowCount::default(),
+            })
+        );
+    }
+    assert_eq!(
+        parse_swayr_command(&args("prev-floating-window current-output")),
+        Ok(SwayrCommand::PrevFloatingWindow {
+            windows: ConsiderWindows::CurrentOutput,
+            matching: None,
+            count: WindowCount::default(),
+        })
+    );
+    assert_eq!(
+        serde_json::from_str::<SwayrCommand>(
+            r#"{"PrevWindow": {"windows": "AllWorkspaces"}}"#
+        )
+        .unwrap(),
+        SwayrCommand::PrevWindow {
+            windows: ConsiderWindows::AllWorkspaces,
+            matching: None,
+            count: WindowCount::default(),
+        }
+    );
+    assert_eq!(
+        parse_swayr_command(&[
+            "do".to_owned(),
+            "--stop-on-error".to_owned(),
+            "next-window all-workspaces".to_owned(),
+            "nop".to_owned(),
+        ]),
+        Ok(SwayrCommand::Do {
+            stop_on_error: true,
+            commands: vec![
+                "next-window all-workspaces".to_owned(),
+                "nop".to_owned()
+            ],
+        })
+    );
+    assert_eq!(
+        parse_swayr_command(&args("switch-window --urgent-first false")),
+        Ok(SwayrCommand::SwitchWindow {
+            formats: FormatOverrides {
+                urgent_first: Some(false),
+                ..FormatOverrides::default()
+            },
+        })
+    );
+    assert_eq!(
+        parse_swayr_command(&args("reset-state lru")),
+        Ok(SwayrCommand::ResetState {
+            what: ResettableState::Lru
+        })
+    );
+    assert_eq!(
+        parse_swayr_command(&args("prev-window --count 3 current-output")),
+        Ok(SwayrCommand::PrevWindow {
+            windows: ConsiderWindows::CurrentOutput,
+            matching: None,
+            count: WindowCount { count: 3 },
+        })
+    );
+    assert!(
+        parse_swayr_command(&args("next-window --count 0 all-workspaces"))
+            .is_err()
+    );
+    assert!(parse_swayr_command(&args(
+        "for-each-window --action browse '[app_id=foot]'"
+    ))
+    .is_ok());
+    assert!(parse_swayr_command(&args(
+        "for-each-window --action browse '[app_id=foot]' echo {id}"
+    ))
+    .is_err());
+    assert!(parse_swayr_command(&args("do")).is_err());
+    assert!(parse_swayr_command(&args("no-such-command")).is_err());
+}
+
+#[test]
+fn test_remote_request() {
+    let req: RemoteRequest = serde_json::from_str(r#""Ping""#).unwrap();
+    assert!(matches!(req, RemoteRequest::Anonymous(SwayrCommand::Ping)));
+    let req: RemoteRequest =
+        serde_json::from_str(r#"{"token": "s3cr3t", "command": "Ping"}"#)
+            .unwrap();
+    assert!(matches!(
+        req,
+        RemoteRequest::Authenticated {
+            command: SwayrCommand::Ping,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_is_allowed_while_locked() {
+    let allowed = |cl: &str| {
+        let args: Vec<String> =
+            cl.split_whitespace().map(|a| a.to_owned()).collect();
+        parse_swayr_command(&args)
+            .unwrap()
+            .is_allowed_while_locked()
+    };
+    assert!(allowed("get-windows-as-json"));
+    assert!(allowed("metrics"));
+    assert!(allowed("wait-for-window [app_id=foot]"));
+    assert!(!allowed("wait-for-window --focus [app_id=foot]"));
+    assert!(!allowed(
+        "wait-for-window --move-to-workspace 2 [app_id=foot]"
+    ));
+    assert!(!allowed("for-each-window [app_id=foot] true"));
+    assert!(!allowed("next-window all-workspaces"));
+}
+
+#[test]
+fn test_is_read_only_command() {
+    use clap::Subcommand;
+    let cli = SwayrCommand::augment_subcommands(clap::Command::new("swayr"));
+    let mut read_only: Vec<String> = cli
+        .get_subcommands()
+        .filter(|c| {
+            // Give required arguments so that every command can be parsed.
+            let mut args = vec![c.get_name().to_owned()];
+            args.extend(
+                c.get_subcommands().take(1).map(|s| s.get_name().to_owned()),
+            );
+            args.extend(
+                c.get_positionals()
+                    .filter(|a| a.is_required_set())
+                    .map(|_| "1".to_owned()),
+            );
+            parse_swayr_command(&args)
+                .unwrap_or_else(|e| panic!("Can't parse {args:?}: {e}"))
+                .is_read_only_command()
+        })
+        .map(|c| c.get_name().to_owned())
+        .collect();
+    read_only.sort();
+    assert_eq!(
+        read_only,
+        vec![
+            "get-do-not-disturb",
+            "get-focused-as-json",
+            "get-windows-as-json",
+            "get-workspace-lru-window",
+            "ping",
+        ]
+    );
+}
+
+pub struct ExecSwayrCmdArgs<'a> {
+    pub cmd: &'a SwayrCommand,
+    pub focus_data: &'a FocusData,
+}
+
+impl DisplayFormat for SwayrCommand {
+    fn format_for_display(&self) -> std::string::String {
+        self.invocation()
+    }
+
+    fn get_indent_level(&self) -> usize {
+        0
+    }
+}
+
+impl SwayrCommand {
+    /// Returns the name of the command as given on the command line, e.g.,
+    /// `switch-to-urgent-or-lru-window`.
+    pub fn name(&self) -> String {
+        kebab_case(&format!("{self:?}"))
+    }
+
+    /// Returns the command like it would be given on the command line, e.g.,
+    /// `next-window all-workspaces` or `quit-window --kill`.  All arguments
+    /// differing from their defaults are included except for format
+    /// overrides.  Used as the entry in the `execute-swayr-command` menu and
+    /// as the key of its frecency data, so it must be stable.
+    pub fn invocation(&self) -> String {
+        fn flag(args: &mut Vec<String>, name: &str, set: bool) {
+            if set {
+                args.push(format!("--{name}"));
+            }
+        }
+        fn opt(args: &mut Vec<String>, name: &str, val: Option<&str>) {
+            if let Some(val) = val {
+                args.push(format!("--{name} {val}"));
+            }
+        }
+        fn skip(args: &mut Vec<String>, skip_flags: &SkipFlags) {
+            flag(args, "skip-urgent", skip_flags.skip_urgent);
+            flag(args, "skip-lru", skip_flags.skip_lru);
+            flag(
+                args,
+                "skip-lru-if-current-doesnt-match",
+                skip_flags.skip_lru_if_current_doesnt_match,
+            );
+            flag(args, "skip-origin", skip_flags.skip_origin);
+            flag(
+                args,
+                "focus-urgent-workspace",
+                skip_flags.focus_urgent_workspace,
+            );
+        }
+
+        let mut args = vec![self.name()];
+        match self {
+            SwayrCommand::Nop
+            | SwayrCommand::SwitchToUrgentWorkspace
+            | SwayrCommand::WorkspaceBack
+            | SwayrCommand::WorkspaceForward
+            | SwayrCommand::ReopenLastClosed
+            | SwayrCommand::NextWindowInContainer
+            | SwayrCommand::PrevWindowInContainer
+            | SwayrCommand::PromoteFocused
+            | SwayrCommand::ToggleMaximizeFocused
+            | SwayrCommand::ManageWindowSets
+            | SwayrCommand::ReloadSway
+            | SwayrCommand::ExecuteSwaymsgCommand
+            | SwayrCommand::SwitchBindingMode
+            | SwayrCommand::ExecuteSwayrCommand
+            | SwayrCommand::ConfigureOutputs
+            | SwayrCommand::GetDoNotDisturb
+            | SwayrCommand::GetSwitchSequenceState
+            | SwayrCommand::ListRecentlyClosed
+            | SwayrCommand::Ping
+            | SwayrCommand::Metrics
+            | SwayrCommand::PrintDefaultConfig
+            | SwayrCommand::PrintConfigSchema
+            | SwayrCommand::ListFocusHistory { .. }
+            | SwayrCommand::SwitchWindow { .. }
+            | SwayrCommand::StealWindow { .. }
+            | SwayrCommand::StealWindowOrContainer { .. }
+            | SwayrCommand::SwitchWorkspace { .. }
+            | SwayrCommand::SwitchOutput { .. }
+            | SwayrCommand::SwitchWorkspaceOrWindow { .. }
+            | SwayrCommand::SwitchWorkspaceContainerOrWindow { .. }
+            | SwayrCommand::SwitchTo { .. }
+            | SwayrCommand::MoveFocusedToWorkspace { .. }
+            | SwayrCommand::RenameWorkspaceInteractively { .. }
+            | SwayrCommand::MoveFocusedTo { .. }
+            | SwayrCommand::SwapFocusedWith { .. }
+            | SwayrCommand::SelectContainerScope { .. }
+            | SwayrCommand::WindowActions { .. } => (),
+            SwayrCommand::Do {
+                stop_on_error,
+                commands,
+            } => {
+                flag(&mut args, "stop-on-error", *stop_on_error);
+                args.extend(commands.iter().cloned());
+            }
+            SwayrCommand::SwitchToUrgentOrLRUWindow { skip_flags } => {
+                skip(&mut args, skip_flags)
+            }
+            SwayrCommand::SwitchToAppOrUrgentOrLRUWindow {
+                name: arg,
+                skip_flags,
+            }
+            | SwayrCommand::SwitchToMarkOrUrgentOrLRUWindow {
+                con_mark: arg,
+                skip_flags,
+            }
+            | SwayrCommand::SwitchToMatchingOrUrgentOrLRUWindow {
+                criteria: arg,
+                skip_flags,
+            } => {
+                skip(&mut args, skip_flags);
+                args.push(arg.clone());
+            }
+            SwayrCommand::FocusNthLastWindow { n }
+            | SwayrCommand::SpreadWorkspace { n } => args.push(n.to_string()),
+            SwayrCommand::SwitchApp { select_window, .. } => {
+                flag(&mut args, "select-window", *select_window)
+            }
+            SwayrCommand::QuitWindow { kill, force, .. } => {
+                flag(&mut args, "kill", *kill);
+                flag(&mut args, "force", *force);
+            }
+            SwayrCommand::QuitWorkspaceOrWindow { force, .. }
+            | SwayrCommand::QuitWorkspaceContainerOrWindow { force, .. } => {
+                flag(&mut args, "force", *force)
+            }
+            SwayrCommand::NextWindow {
+                windows,
+                matching,
+                count,
+            }
+            | SwayrCommand::PrevWindow {
+                windows,
+                matching,
+                count,
+            }
+            | SwayrCommand::NextTiledWindow {
+                windows,
+                matching,
+                count,
+            }
+            | SwayrCommand::PrevTiledWindow {
+                windows,
+                matching,
+                count,
+            }
+            | SwayrCommand::NextTabbedOrStackedWindow {
+                windows,
+                matching,
+                count,
+            }
+            | SwayrCommand::PrevTabbedOrStackedWindow {
+                windows,
+                matching,
+                count,
+            }
+            | SwayrCommand::NextFloatingWindow {
+                windows,
+                matching,
+                count,
+            }
+            | SwayrCommand::PrevFloatingWindow {
+                windows,
+                matching,
+                count,
+            }
+            | SwayrCommand::NextWindowOfSameLayout {
+                windows,
+                matching,
+                count,
+            }
+            | SwayrCommand::PrevWindowOfSameLayout {
+                windows,
+                matching,
+                count,
+            } => {
+                args.push(kebab_case(&format!("{windows:?}")));
+                opt(&mut args, "matching", matching.as_deref());
+                if count.count != 1 {
+                    args.push(format!("--count {}", count.count));
+                }
+            }
+            SwayrCommand::QuickSwitch { query: arg }
+            | SwayrCommand::NextMatchingWindow { criteria: arg }
+            | SwayrCommand::PrevMatchingWindow { criteria: arg }
+            | SwayrCommand::AddFocusedToSet { name: arg }
+            | SwayrCommand::RemoveFocusedFromSet { name: arg }
+            | SwayrCommand::ToggleSetVisibility { name: arg }
+            | SwayrCommand::SaveFocusBookmark { name: arg }
+            | SwayrCommand::GetWorkspaceLRUWindow { workspace: arg }
+            | SwayrCommand::GetIcon { app_name: arg } => args.push(arg.clone()),
+            SwayrCommand::RenameWorkspace {
+                from_criteria,
+                to_template,
+            } => {
+                args.push(from_criteria.clone());
+                args.push(to_template.clone());
+            }
+            SwayrCommand::ToggleTabShuffleTileWorkspace { floating }
+            | SwayrCommand::TileWorkspace { floating }
+            | SwayrCommand::TabWorkspace { floating }
+            | SwayrCommand::ShuffleTileWorkspace { floating } => {
+                args.push(kebab_case(&format!("{floating:?}")))
+            }
+            SwayrCommand::DemoteFocusedInto { layout, direction } => {
+                opt(&mut args, "layout", layout.as_deref());
+                args.push(kebab_case(&format!("{direction:?}")));
+            }
+            SwayrCommand::GatherApp {
+                workspace,
+                tab,
+                name_or_criteria,
+            } => {
+                opt(&mut args, "workspace", workspace.as_deref());
+                flag(&mut args, "tab", *tab);
+                args.push(name_or_criteria.clone());
+            }
+            SwayrCommand::FloatFocusedPreset { preset: arg }
+            | SwayrCommand::AppearancePresets { preset: arg }
+            | SwayrCommand::RenameFocusedWindow { title: arg }
+            | SwayrCommand::RestoreFocusBookmark { name: arg } => {
+                args.extend(arg.clone())
+            }
+            SwayrCommand::CopyWindowInfo { criteria, format } => {
+                opt(&mut args, "criteria", criteria.as_deref());
+                args.push(format.clone());
+            }
+            SwayrCommand::FollowMatching { criteria, toggle } => {
+                flag(&mut args, "toggle", *toggle);
+                args.extend(criteria.clone());
+            }
+            SwayrCommand::ToggleDoNotDisturb { clear_urgency } => {
+                flag(&mut args, "clear-urgency", *clear_urgency)
+            }
+            SwayrCommand::ResetState { what } => {
+                args.push(kebab_case(&format!("{what:?}")))
+            }
+            SwayrCommand::GetWindowsAsJson {
+                include_scratchpad,
+                criteria,
+                error_if_no_match,
+                fields,
+                filter,
+                compact,
+                jsonl,
+            } => {
+                flag(&mut args, "include-scratchpad", *include_scratchpad);
+                opt(&mut args, "matching", criteria.as_deref());
+                flag(&mut args, "error-if-no-match", *error_if_no_match);
+                opt(
+                    &mut args,
+                    "fields",
+                    fields.as_ref().map(|f| f.join(",")).as_deref(),
+                );
+                flag(
+                    &mut args,
+                    "include-containers",
+                    filter.include_containers,
+                );
+                opt(
+                    &mut args,
+                    "max-depth",
+                    filter.max_depth.map(|d| d.to_string()).as_deref(),
+                );
+                opt(&mut args, "workspace", filter.workspace.as_deref());
+                flag(&mut args, "compact", *compact);
+                flag(&mut args, "jsonl", *jsonl);
+            }
+            SwayrCommand::GetFocusedAsJson { fields } => opt(
+                &mut args,
+                "fields",
+                fields.as_ref().map(|f| f.join(",")).as_deref(),
+            ),
+            SwayrCommand::ForEachWindow {
+                include_scratchpad,
+                error_if_no_match,
+                discard_output,
+                action,
+                criteria,
+                shell_command,
+            } => {
+                flag(&mut args, "include-scratchpad", *include_scratchpad);
+                flag(&mut args, "error-if-no-match", *error_if_no_match);
+                flag(&mut args, "discard-output", *discard_output);
+                opt(&mut args, "action", action.as_deref());
+                args.push(criteria.clone());
+                args.extend(shell_command.iter().cloned());
+            }
+            SwayrCommand::WaitForWindow {
+                timeout,
+                only_new,
+                focus,
+                move_to_workspace,
+                criteria,
+            } => {
+                if *timeout != 5000 {
+                    args.push(format!("--timeout {timeout}"));
+                }
+                flag(&mut args, "only-new", *only_new);
+                flag(&mut args, "focus", *focus);
+                opt(
+                    &mut args,
+                    "move-to-workspace",
+                    move_to_workspace.as_deref(),
+                );
+                args.push(criteria.clone());
+            }
+            SwayrCommand::PrintConfig { json } => {
+                flag(&mut args, "json", *json)
+            }
+            SwayrCommand::CompletionsDynamic {
+                workspaces,
+                marks,
+                app_ids,
+                outputs,
+                commands,
+            } => {
+                flag(&mut args, "workspaces", *workspaces);
+                flag(&mut args, "marks", *marks);
+                flag(&mut args, "app-ids", *app_ids);
+                flag(&mut args, "outputs", *outputs);
+                flag(&mut args, "commands", *commands);
+            }
+            SwayrCommand::PrintSwayBindings { modifier } => {
+                if modifier != "$mod" {
+                    args.push(format!("--modifier {modifier}"));
+                }
+            }
+        }
+        args.join(" ")
+    }
+}
+
+/// Converts the leading identifier of `debug`, e.g., the `Debug` output of an
+/// enum variant like `GetWorkspaceLRUWindow { .. }`, to kebab-case, e.g.,
+/// `get-workspace-lru-window`.
+fn kebab_case(debug: &str) -> String {
+    let chars: Vec<char> =
+        debug.chars().take_while(|c| c.is_alphanumeric()).collect();
+    let mut name = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        // A new word starts at an upper-case letter after a lower-case
+        // one, or at the last upper-case letter of an acronym.
+        if i > 0
+            && c.is_uppercase()
+            && (chars[i - 1].is_lowercase()
+                || chars.get(i + 1).is_some_and(|n| n.is_lowercase()))
+        {
+            name.push('-');
+        }
+        name.extend(c.to_lowercase());
+    }
+    name
+}
+
+#[test]
+fn test_swayr_command_name() {
+    assert_eq!("nop", SwayrCommand::Nop.name());
+    assert_eq!("ping", SwayrCommand::Ping.name());
+    assert_eq!(
+        "get-workspace-lru-window",
+        SwayrCommand::GetWorkspaceLRUWindow {
+            workspace: "1".to_owned()
+        }
+        .name()
+    );
+    assert_eq!(
+        "execute-swaymsg-command",
+        SwayrCommand::ExecuteSwaymsgCommand.name()
+    );
+}
+
+#[test]
+fn test_swayr_command_invocation() {
+    assert_eq!(
+        "switch-window",
+        SwayrCommand::SwitchWindow {
+            formats: FormatOverrides {
+                window_format: Some("{title}".to_owned()),
+                ..FormatOverrides::default()
+            }
+        }
+        .invocation()
+    );
+    assert_eq!(
+        "next-window all-workspaces",
+        SwayrCommand::NextWindow {
+            windows: ConsiderWindows::AllWorkspaces,
+            matching: None,
+            count: WindowCount::default(),
+        }
+        .invocation()
+    );
+    assert_eq!(
+        "prev-tiled-window current-output --matching [app_id=foot] --count 2",
+        SwayrCommand::PrevTiledWindow {
+            windows: ConsiderWindows::CurrentOutput,
+            matching: Some("[app_id=foot]".to_owned()),
+            count: WindowCount { count: 2 },
+        }
+        .invocation()
+    );
+    assert_eq!(
+        "tab-workspace include-floating",
+        SwayrCommand::TabWorkspace {
+            floating: ConsiderFloating::IncludeFloating
+        }
+        .invocation()
+    );
+    assert_eq!(
+        "quit-window --kill",
+        SwayrCommand::QuitWindow {
+            kill: true,
+            force: false,
+            formats: FormatOverrides::default(),
+        }
+        .invocation()
+    );
+    assert_eq!(
+        "switch-app --select-window",
+        SwayrCommand::SwitchApp {
+            select_window: true,
+            formats: FormatOverrides::default(),
+        }
+        .invocation()
+    );
+    assert_eq!(
+        "focus-nth-last-window 3",
+        SwayrCommand::FocusNthLastWindow { n: 3 }.invocation()
+    );
+    assert_eq!(
+        "gather-app --workspace 9 --tab firefox",
+        SwayrCommand::GatherApp {
+            workspace: Some("9".to_owned()),
+            tab: true,
+            name_or_criteria: "firefox".to_owned(),
+        }
+        .invocation()
+    );
+    assert_eq!(
+        "reset-state lru",
+        SwayrCommand::ResetState {
+            what: ResettableState::Lru
+        }
+        .invocation()
+    );
+    assert_eq!(
+        "toggle-do-not-disturb --clear-urgency",
+        SwayrCommand::ToggleDoNotDisturb {
+            clear_urgency: true
+        }
+        .invocation()
+    );
+}
+
+fn always_true(_x: &t::DisplayNode) -> bool {
+    true
+}
+
+static LAST_COMMAND: Lazy<Mutex<SwayrCommand>> =
+    Lazy::new(|| Mutex::new(SwayrCommand::Nop));
+
+#[derive(Debug)]
+pub struct SwitchToMatchingData {
+    visited: Vec<i64>,
+    lru: Option<i64>,
+    origin: Option<i64>,
+    /// The windows the last command of the sequence could have switched to
+    /// in order of preference.
+    candidates: Vec<i64>,
+    skip_urgent: bool,
+    skip_lru: bool,
+    skip_lru_if_current_doesnt_match: bool,
+    skip_origin: bool,
+    focus_urgent_workspace: bool,
+}
+
+impl SwitchToMatchingData {
     pub fn reset(&mut self, reset_skip_flags: bool) {
         self.visited.clear();
         self.lru = None;
-        self.origin = None;
+        self.set_origin(None);
+        self.candidates.clear();
         if reset_skip_flags {
             self.skip_urgent = false;
             self.skip_lru = false;
             self.skip_lru_if_current_doesnt_match = false;
             self.skip_origin = false;
+            self.focus_urgent_workspace = false;
+        }
+    }
+
+    fn set_origin(&mut self, origin: Option<i64>) {
+        self.origin = origin;
+        t::set_switch_sequence_origin(origin);
+    }
+
+    fn state(&self) -> SwitchSequenceState {
+        let mut visited: Vec<i64> = vec![];
+        for id in &self.visited {
+            // -1 is recorded if no window was focused.
+            if *id >= 0 && !visited.contains(id) {
+                visited.push(*id);
+            }
+        }
+        let mut remaining: Vec<i64> = vec![];
+        for id in &self.candidates {
+            if !visited.contains(id) && !remaining.contains(id) {
+                remaining.push(*id);
+            }
+        }
+        SwitchSequenceState {
+            active: !self.visited.is_empty(),
+            origin: self.origin,
+            lru: self.lru,
+            visited,
+            remaining,
         }
     }
 
@@ -409,20 +1941,323 @@ impl SwitchToMatchingData {
             visited: vec![],
             lru: None,
             origin: None,
+            candidates: vec![],
             skip_urgent: false,
             skip_lru: false,
             skip_lru_if_current_doesnt_match: false,
             skip_origin: false,
+            focus_urgent_workspace: false,
         }
     }
 }
 
+/// The state of a sequence of switch-to-*-or-urgent-or-lru-window commands
+/// as returned by get-switch-sequence-state.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct SwitchSequenceState {
+    active: bool,
+    origin: Option<i64>,
+    lru: Option<i64>,
+    visited: Vec<i64>,
+    remaining: Vec<i64>,
+}
+
+#[test]
+fn test_switch_sequence_state() {
+    let mut data = SwitchToMatchingData::new();
+    assert!(!data.state().active);
+    data.visited = vec![1, 3, 3];
+    data.lru = Some(2);
+    data.origin = Some(1);
+    data.candidates = vec![3, 4, 2, 1];
+    assert_eq!(
+        data.state(),
+        SwitchSequenceState {
+            active: true,
+            origin: Some(1),
+            lru: Some(2),
+            visited: vec![1, 3],
+            remaining: vec![4, 2],
+        }
+    );
+}
+
 static SWITCH_TO_MATCHING_DATA: Lazy<Mutex<SwitchToMatchingData>> =
     Lazy::new(|| Mutex::new(SwitchToMatchingData::new()));
 
-pub fn exec_swayr_cmd(args: ExecSwayrCmdArgs) -> Result<String, String> {
+/// Returns the commands offered by the `execute-swayr-command` menu.
+fn execute_swayr_command_menu_cmds() -> Vec<SwayrCommand> {
+    let mut cmds = vec![
+        SwayrCommand::MoveFocusedToWorkspace {
+            formats: FormatOverrides::default(),
+        },
+        SwayrCommand::MoveFocusedTo {
+            formats: FormatOverrides::default(),
+        },
+        SwayrCommand::RenameWorkspaceInteractively {
+            formats: FormatOverrides::default(),
+        },
+        SwayrCommand::SwapFocusedWith {
+            formats: FormatOverrides::default(),
+        },
+        SwayrCommand::QuitWorkspaceOrWindow {
+            force: false,
+            formats: FormatOverrides::default(),
+        },
+        SwayrCommand::ReopenLastClosed,
+        SwayrCommand::SwitchWindow {
+            formats: FormatOverrides::default(),
+        },
+        SwayrCommand::SwitchApp {
+            select_window: false,
+            formats: FormatOverrides::default(),
+        },
+        SwayrCommand::SwitchApp {
+            select_window: true,
+            formats: FormatOverrides::default(),
+        },
+        SwayrCommand::StealWindow {
+            formats: FormatOverrides::default(),
+        },
+        SwayrCommand::StealWindowOrContainer {
+            formats: FormatOverrides::default(),
+        },
+        SwayrCommand::SwitchWorkspace {
+            formats: FormatOverrides::default(),
+        },
+        SwayrCommand::SwitchOutput {
+            formats: FormatOverrides::default(),
+        },
+        SwayrCommand::SwitchWorkspaceOrWindow {
+            formats: FormatOverrides::default(),
+        },
+        SwayrCommand::SwitchToUrgentOrLRUWindow {
+            skip_flags: SkipFlags {
+                skip_urgent: false,
+                skip_lru: false,
+                skip_lru_if_current_doesnt_match: false,
+                skip_origin: false,
+                focus_urgent_workspace: false,
+            },
+        },
+        SwayrCommand::SwitchToUrgentWorkspace,
+        SwayrCommand::ListFocusHistory {
+            formats: FormatOverrides::default(),
+        },
+        SwayrCommand::PromoteFocused,
+        SwayrCommand::ToggleMaximizeFocused,
+        SwayrCommand::WorkspaceBack,
+        SwayrCommand::WorkspaceForward,
+        SwayrCommand::ConfigureOutputs,
+        SwayrCommand::FloatFocusedPreset { preset: None },
+        SwayrCommand::RenameFocusedWindow { title: None },
+        SwayrCommand::AppearancePresets { preset: None },
+        SwayrCommand::ExecuteSwaymsgCommand,
+        SwayrCommand::SwitchBindingMode,
+        SwayrCommand::ManageWindowSets,
+        SwayrCommand::SelectContainerScope {
+            formats: FormatOverrides::default(),
+        },
+        SwayrCommand::WindowActions {
+            formats: FormatOverrides::default(),
+        },
+        SwayrCommand::RestoreFocusBookmark { name: None },
+    ];
+    for f in [
+        ConsiderFloating::ExcludeFloating,
+        ConsiderFloating::IncludeFloating,
+    ] {
+        cmds.push(SwayrCommand::ToggleTabShuffleTileWorkspace {
+            floating: f.clone(),
+        });
+        cmds.push(SwayrCommand::TileWorkspace {
+            floating: f.clone(),
+        });
+        cmds.push(SwayrCommand::TabWorkspace {
+            floating: f.clone(),
+        });
+        cmds.push(SwayrCommand::ShuffleTileWorkspace {
+            floating: f.clone(),
+        });
+    }
+
+    for kill in [false, true] {
+        cmds.push(SwayrCommand::QuitWindow {
+            kill,
+            force: false,
+            formats: FormatOverrides::default(),
+        });
+    }
+
+    for w in [
+        ConsiderWindows::AllWorkspaces,
+        ConsiderWindows::CurrentWorkspace,
+        ConsiderWindows::CurrentOutput,
+    ] {
+        cmds.push(SwayrCommand::NextWindow {
+            windows: w.clone(),
+            matching: None,
+            count: WindowCount::default(),
+        });
+        cmds.push(SwayrCommand::PrevWindow {
+            windows: w.clone(),
+            matching: None,
+            count: WindowCount::default(),
+        });
+        cmds.push(SwayrCommand::NextTiledWindow {
+            windows: w.clone(),
+            matching: None,
+            count: WindowCount::default(),
+        });
+        cmds.push(SwayrCommand::PrevTiledWindow {
+            windows: w.clone(),
+            matching: None,
+            count: WindowCount::default(),
+        });
+        cmds.push(SwayrCommand::NextTabbedOrStackedWindow {
+            windows: w.clone(),
+            matching: None,
+            count: WindowCount::default(),
+        });
+        cmds.push(SwayrCommand::PrevTabbedOrStackedWindow {
+            windows: w.clone(),
+            matching: None,
+            count: WindowCount::default(),
+        });
+        cmds.push(SwayrCommand::NextFloatingWindow {
+            windows: w.clone(),
+            matching: None,
+            count: WindowCount::default(),
+        });
+        cmds.push(SwayrCommand::PrevFloatingWindow {
+            windows: w.clone(),
+            matching: None,
+            count: WindowCount::default(),
+        })
+    }
+    cmds.push(SwayrCommand::NextWindowInContainer);
+    cmds.push(SwayrCommand::PrevWindowInContainer);
+    cmds
+}
+
+#[test]
+fn test_execute_swayr_command_menu_entries_are_distinct() {
+    let cmds = execute_swayr_command_menu_cmds();
+    let mut entries: Vec<String> =
+        cmds.iter().map(|c| c.format_for_display()).collect();
+    entries.sort();
+    entries.dedup();
+    assert_eq!(cmds.len(), entries.len());
+}
+
+/// Sorts `cmds` so that the most frequently and recently used ones come first.
+/// Commands with equal scores, e.g., never used ones, keep their order.
+fn sort_by_frecency(cmds: &mut Vec<SwayrCommand>, fdata: &FocusData) {
+    let strs: Vec<String> = cmds.iter().map(|c| c.invocation()).collect();
+    let scores = fdata.get_command_frecencies(&strs);
+    let mut scored: Vec<(f64, SwayrCommand)> =
+        scores.into_iter().zip(cmds.drain(..)).collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    cmds.extend(scored.into_iter().map(|(_, c)| c));
+}
+
+/// Executes `args.cmd` like `exec_swayr_cmd` but writes its output to `out`.
+/// The output of streamed commands (see `SwayrCommand::is_streamed`) is
+/// written piecemeal while it's produced, the output of all other commands
+/// at once.
+pub fn exec_swayr_cmd_streaming(
+    args: ExecSwayrCmdArgs,
+    out: &mut dyn std::io::Write,
+) -> Result<(), SwayrError> {
+    if let SwayrCommand::GetWindowsAsJson {
+        include_scratchpad,
+        criteria,
+        error_if_no_match,
+        fields,
+        filter,
+        jsonl: true,
+        ..
+    } = args.cmd
+    {
+        log::info!("Running SwayrCommand {:?}", args.cmd);
+        let root = ipc::get_root_node(*include_scratchpad);
+        return write_windows_as_json(
+            out,
+            &root,
+            args.focus_data,
+            criteria,
+            *error_if_no_match,
+            fields.as_deref(),
+            filter,
+            JsonStyle::Lines,
+        );
+    }
+    let output = exec_swayr_cmd(args)?;
+    out.write_all(output.as_bytes())
+        .map_err(|e| SwayrError::DaemonError(e.to_string()))
+}
+
+pub fn exec_swayr_cmd(args: ExecSwayrCmdArgs) -> Result<String, SwayrError> {
     log::info!("Running SwayrCommand {:?}", args.cmd);
 
+    // This is the one place all commands go through, no matter if they come
+    // from a client, D-Bus, a hook, or the auto-nop timer.
+    if !args.cmd.is_allowed_while_locked() && daemon::is_session_locked() {
+        log::debug!(
+            "Refusing command {:?} while the session is locked.",
+            args.cmd
+        );
+        return Err(SwayrError::DaemonError(
+            "The session is locked.".to_owned(),
+        ));
+    }
+
+    // Ping must answer even when some other command holds the locks below,
+    // e.g., while a menu is shown.
+    if let SwayrCommand::Ping = args.cmd {
+        return Ok(serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "focus_data": args.focus_data.get_stats(),
+        })
+        .to_string());
+    }
+    if let SwayrCommand::Metrics = args.cmd {
+        return metrics::render().ok_or_else(|| {
+            SwayrError::DaemonError(
+                "Metrics are disabled, set metrics.enabled = true.".to_owned(),
+            )
+        });
+    }
+
+    // Blocking commands must not hold the locks below because that would
+    // block all other commands, too.
+    if let SwayrCommand::WaitForWindow {
+        timeout,
+        only_new,
+        focus,
+        move_to_workspace,
+        criteria,
+    } = args.cmd
+    {
+        return wait_for_window(
+            args.focus_data,
+            criteria,
+            Duration::from_millis(*timeout),
+            *only_new,
+            *focus,
+            move_to_workspace.as_deref(),
+        );
+    }
+
+    // The batched commands take the locks below one after the other.
+    if let SwayrCommand::Do {
+        stop_on_error,
+        commands,
+    } = args.cmd
+    {
+        return exec_batch(args.focus_data, commands, *stop_on_error);
+    }
+
     let mut last_command = LAST_COMMAND.lock().expect("Could not lock mutex");
     let mut switch_to_matching_data = SWITCH_TO_MATCHING_DATA
         .lock()
@@ -453,9 +2288,15 @@ pub fn exec_swayr_cmd(args: ExecSwayrCmdArgs) -> Result<String, String> {
 fn exec_swayr_cmd_1(
     args: ExecSwayrCmdArgs,
     switch_to_matching_data: &mut MutexGuard<SwitchToMatchingData>,
-) -> Result<String, String> {
+) -> Result<String, SwayrError> {
     let fdata = args.focus_data;
 
+    if !args.cmd.is_scripting_command()
+        && !matches!(args.cmd, SwayrCommand::ExecuteSwayrCommand)
+    {
+        fdata.record_command_usage(&args.cmd.invocation());
+    }
+
     match args.cmd {
         SwayrCommand::Nop => Ok("done".to_owned()),
         SwayrCommand::SwitchToUrgentOrLRUWindow { skip_flags } => {
@@ -492,115 +2333,205 @@ fn exec_swayr_cmd_1(
                 fdata,
             )
         }
-        SwayrCommand::SwitchWindow => switch_window(fdata),
-        SwayrCommand::StealWindow => steal_window(fdata),
-        SwayrCommand::StealWindowOrContainer => {
-            steal_window_or_container(fdata)
+        SwayrCommand::SwitchToUrgentWorkspace => {
+            switch_to_urgent_workspace(fdata)
         }
-        SwayrCommand::SwitchWorkspace => switch_workspace(fdata),
-        SwayrCommand::SwitchOutput => switch_output(),
-        SwayrCommand::SwitchWorkspaceOrWindow => {
-            switch_workspace_or_window(fdata)
+        SwayrCommand::QuickSwitch { query } => quick_switch(fdata, query),
+        SwayrCommand::FocusNthLastWindow { n } => {
+            focus_nth_last_window(fdata, *n)
         }
-        SwayrCommand::SwitchWorkspaceContainerOrWindow => {
-            switch_workspace_container_or_window(fdata)
+        SwayrCommand::ListFocusHistory { formats } => {
+            list_focus_history(fdata, formats)
         }
-        SwayrCommand::SwitchTo => switch_to(fdata),
-        SwayrCommand::QuitWindow { kill } => quit_window(fdata, *kill),
-        SwayrCommand::QuitWorkspaceOrWindow => quit_workspace_or_window(fdata),
-        SwayrCommand::QuitWorkspaceContainerOrWindow => {
-            quit_workspace_container_or_window(fdata)
+        SwayrCommand::WorkspaceBack => step_workspace_history(fdata, true),
+        SwayrCommand::WorkspaceForward => step_workspace_history(fdata, false),
+        SwayrCommand::SwitchWindow { formats } => switch_window(fdata, formats),
+        SwayrCommand::SwitchApp {
+            select_window,
+            formats,
+        } => switch_app(fdata, *select_window, formats),
+        SwayrCommand::StealWindow { formats } => steal_window(fdata, formats),
+        SwayrCommand::StealWindowOrContainer { formats } => {
+            steal_window_or_container(fdata, formats)
         }
-        SwayrCommand::MoveFocusedToWorkspace => {
-            move_focused_to_workspace(fdata)
+        SwayrCommand::SwitchWorkspace { formats } => {
+            switch_workspace(fdata, formats)
         }
-        SwayrCommand::MoveFocusedTo => move_focused_to(fdata),
-        SwayrCommand::SwapFocusedWith => swap_focused_with(fdata),
-        SwayrCommand::NextWindow { windows } => focus_window_in_direction(
+        SwayrCommand::SwitchOutput { formats } => switch_output(fdata, formats),
+        SwayrCommand::SwitchWorkspaceOrWindow { formats } => {
+            switch_workspace_or_window(fdata, formats)
+        }
+        SwayrCommand::SwitchWorkspaceContainerOrWindow { formats } => {
+            switch_workspace_container_or_window(fdata, formats)
+        }
+        SwayrCommand::SwitchTo { formats } => switch_to(fdata, formats),
+        SwayrCommand::QuitWindow {
+            kill,
+            force,
+            formats,
+        } => quit_window(fdata, *kill, *force, formats),
+        SwayrCommand::QuitWorkspaceOrWindow { force, formats } => {
+            quit_workspace_or_window(fdata, *force, formats)
+        }
+        SwayrCommand::QuitWorkspaceContainerOrWindow { force, formats } => {
+            quit_workspace_container_or_window(fdata, *force, formats)
+        }
+        SwayrCommand::ReopenLastClosed => reopen_last_closed(fdata),
+        SwayrCommand::MoveFocusedToWorkspace { formats } => {
+            move_focused_to_workspace(fdata, formats)
+        }
+        SwayrCommand::RenameWorkspaceInteractively { formats } => {
+            rename_workspace_interactively(fdata, formats)
+        }
+        SwayrCommand::RenameWorkspace {
+            from_criteria,
+            to_template,
+        } => rename_workspace(fdata, from_criteria, to_template),
+        SwayrCommand::MoveFocusedTo { formats } => {
+            move_focused_to(fdata, formats)
+        }
+        SwayrCommand::SwapFocusedWith { formats } => {
+            swap_focused_with(fdata, formats)
+        }
+        SwayrCommand::NextWindow {
+            windows,
+            matching,
+            count,
+        } => focus_window_in_direction(
             Direction::Forward,
             windows,
+            matching.as_deref(),
+            count.count,
             fdata,
             always_true,
         ),
-        SwayrCommand::PrevWindow { windows } => focus_window_in_direction(
+        SwayrCommand::PrevWindow {
+            windows,
+            matching,
+            count,
+        } => focus_window_in_direction(
             Direction::Backward,
             windows,
+            matching.as_deref(),
+            count.count,
             fdata,
             always_true,
         ),
-        SwayrCommand::NextTiledWindow { windows } => focus_window_in_direction(
+        SwayrCommand::NextTiledWindow {
+            windows,
+            matching,
+            count,
+        } => focus_window_in_direction(
             Direction::Forward,
             windows,
+            matching.as_deref(),
+            count.count,
             fdata,
             |dn: &t::DisplayNode| {
                 !dn.node.is_floating()
                     && dn.tree.is_child_of_tiled_container(dn.node.id)
             },
         ),
-        SwayrCommand::PrevTiledWindow { windows } => focus_window_in_direction(
+        SwayrCommand::PrevTiledWindow {
+            windows,
+            matching,
+            count,
+        } => focus_window_in_direction(
             Direction::Backward,
             windows,
+            matching.as_deref(),
+            count.count,
             fdata,
             |dn: &t::DisplayNode| {
                 !dn.node.is_floating()
                     && dn.tree.is_child_of_tiled_container(dn.node.id)
             },
         ),
-        SwayrCommand::NextTabbedOrStackedWindow { windows } => {
-            focus_window_in_direction(
-                Direction::Forward,
-                windows,
-                fdata,
-                |dn: &t::DisplayNode| {
-                    !dn.node.is_floating()
-                        && dn
-                            .tree
-                            .is_child_of_tabbed_or_stacked_container(dn.node.id)
-                },
-            )
-        }
-        SwayrCommand::PrevTabbedOrStackedWindow { windows } => {
-            focus_window_in_direction(
-                Direction::Backward,
-                windows,
-                fdata,
-                |dn: &t::DisplayNode| {
-                    !dn.node.is_floating()
-                        && dn
-                            .tree
-                            .is_child_of_tabbed_or_stacked_container(dn.node.id)
-                },
-            )
-        }
-        SwayrCommand::NextFloatingWindow { windows } => {
-            focus_window_in_direction(
-                Direction::Forward,
-                windows,
-                fdata,
-                |dn: &t::DisplayNode| dn.node.is_floating(),
-            )
-        }
-        SwayrCommand::PrevFloatingWindow { windows } => {
-            focus_window_in_direction(
-                Direction::Backward,
-                windows,
-                fdata,
-                |dn: &t::DisplayNode| dn.node.is_floating(),
-            )
-        }
-        SwayrCommand::NextWindowOfSameLayout { windows } => {
-            focus_window_of_same_layout_in_direction(
-                Direction::Forward,
-                windows,
-                fdata,
-            )
+        SwayrCommand::NextTabbedOrStackedWindow {
+            windows,
+            matching,
+            count,
+        } => focus_window_in_direction(
+            Direction::Forward,
+            windows,
+            matching.as_deref(),
+            count.count,
+            fdata,
+            |dn: &t::DisplayNode| {
+                !dn.node.is_floating()
+                    && dn
+                        .tree
+                        .is_child_of_tabbed_or_stacked_container(dn.node.id)
+            },
+        ),
+        SwayrCommand::PrevTabbedOrStackedWindow {
+            windows,
+            matching,
+            count,
+        } => focus_window_in_direction(
+            Direction::Backward,
+            windows,
+            matching.as_deref(),
+            count.count,
+            fdata,
+            |dn: &t::DisplayNode| {
+                !dn.node.is_floating()
+                    && dn
+                        .tree
+                        .is_child_of_tabbed_or_stacked_container(dn.node.id)
+            },
+        ),
+        SwayrCommand::NextFloatingWindow {
+            windows,
+            matching,
+            count,
+        } => focus_window_in_direction(
+            Direction::Forward,
+            windows,
+            matching.as_deref(),
+            count.count,
+            fdata,
+            |dn: &t::DisplayNode| dn.node.is_floating(),
+        ),
+        SwayrCommand::PrevFloatingWindow {
+            windows,
+            matching,
+            count,
+        } => focus_window_in_direction(
+            Direction::Backward,
+            windows,
+            matching.as_deref(),
+            count.count,
+            fdata,
+            |dn: &t::DisplayNode| dn.node.is_floating(),
+        ),
+        SwayrCommand::NextWindowOfSameLayout {
+            windows,
+            matching,
+            count,
+        } => focus_window_of_same_layout_in_direction(
+            Direction::Forward,
+            windows,
+            matching.as_deref(),
+            count.count,
+            fdata,
+        ),
+        SwayrCommand::PrevWindowOfSameLayout {
+            windows,
+            matching,
+            count,
+        } => focus_window_of_same_layout_in_direction(
+            Direction::Backward,
+            windows,
+            matching.as_deref(),
+            count.count,
+            fdata,
+        ),
+        SwayrCommand::NextWindowInContainer => {
+            focus_window_in_container_in_direction(Direction::Forward, fdata)
         }
-        SwayrCommand::PrevWindowOfSameLayout { windows } => {
-            focus_window_of_same_layout_in_direction(
-                Direction::Backward,
-                windows,
-                fdata,
-            )
+        SwayrCommand::PrevWindowInContainer => {
+            focus_window_in_container_in_direction(Direction::Backward, fdata)
         }
         SwayrCommand::NextMatchingWindow { criteria } => {
             focus_matching_window_in_direction(
@@ -628,95 +2559,135 @@ fn exec_swayr_cmd_1(
         SwayrCommand::ToggleTabShuffleTileWorkspace { floating } => {
             toggle_tab_tile_current_workspace(floating)
         }
+        SwayrCommand::PromoteFocused => {
+            layout::promote_focused().map_err(SwayrError::from)
+        }
+        SwayrCommand::ToggleMaximizeFocused => {
+            layout::toggle_maximize_focused().map_err(SwayrError::from)
+        }
+        SwayrCommand::DemoteFocusedInto { layout, direction } => {
+            layout::demote_focused_into(direction, layout.as_deref())
+                .map_err(SwayrError::from)
+        }
+        SwayrCommand::SpreadWorkspace { n } => {
+            spread_current_workspace(fdata, *n)
+        }
+        SwayrCommand::GatherApp {
+            workspace,
+            tab,
+            name_or_criteria,
+        } => gather_app(fdata, name_or_criteria, workspace.as_deref(), *tab),
         SwayrCommand::ConfigureOutputs => configure_outputs(),
         SwayrCommand::GetWindowsAsJson {
             include_scratchpad,
             criteria,
             error_if_no_match,
+            fields,
+            filter,
+            compact,
+            jsonl,
         } => get_windows_as_json(
             fdata,
             *include_scratchpad,
             criteria,
             *error_if_no_match,
+            fields.as_deref(),
+            filter,
+            JsonStyle::new(*compact, *jsonl),
         ),
+        SwayrCommand::GetFocusedAsJson { fields } => {
+            get_focused_as_json(fdata, fields.as_deref())
+        }
         SwayrCommand::ForEachWindow {
             include_scratchpad,
             error_if_no_match,
+            discard_output,
+            action,
             criteria,
             shell_command,
         } => for_each_window(
             fdata,
             *include_scratchpad,
             *error_if_no_match,
+            *discard_output,
+            action.as_ref(),
             criteria,
             shell_command,
         ),
-        SwayrCommand::ExecuteSwaymsgCommand => exec_swaymsg_command(),
+        SwayrCommand::GetWorkspaceLRUWindow { workspace } => {
+            get_workspace_lru_window_as_json(fdata, workspace)
+        }
+        SwayrCommand::GetIcon { app_name } => get_icon(app_name),
+        SwayrCommand::GetDoNotDisturb => Ok(match fdata.get_do_not_disturb() {
+            Some(_) => "on".to_owned(),
+            None => "off".to_owned(),
+        }),
+        SwayrCommand::GetSwitchSequenceState => {
+            serde_json::to_string_pretty(&switch_to_matching_data.state())
+                .map_err(|e| SwayrError::DaemonError(e.to_string()))
+        }
+        SwayrCommand::ListRecentlyClosed => list_recently_closed(fdata),
+        SwayrCommand::Ping => unreachable!("Ping is handled by exec_swayr_cmd"),
+        SwayrCommand::Metrics => {
+            unreachable!("Metrics is handled by exec_swayr_cmd")
+        }
+        SwayrCommand::WaitForWindow { .. } => {
+            unreachable!("WaitForWindow is handled by exec_swayr_cmd")
+        }
+        SwayrCommand::Do { .. } => {
+            unreachable!("Do is handled by exec_swayr_cmd")
+        }
+        SwayrCommand::AppearancePresets { preset } => {
+            apply_appearance_preset(preset.as_deref(), fdata)
+        }
+        SwayrCommand::FloatFocusedPreset { preset } => {
+            float_focused_preset(preset.as_deref())
+        }
+        SwayrCommand::RenameFocusedWindow { title } => {
+            rename_focused_window(title.as_deref())
+        }
+        SwayrCommand::AddFocusedToSet { name } => add_focused_to_set(name),
+        SwayrCommand::RemoveFocusedFromSet { name } => {
+            remove_focused_from_set(name)
+        }
+        SwayrCommand::ToggleSetVisibility { name } => {
+            toggle_set_visibility(name)
+        }
+        SwayrCommand::ManageWindowSets => manage_window_sets(),
+        SwayrCommand::SelectContainerScope { formats } => {
+            select_container_scope(formats)
+        }
+        SwayrCommand::WindowActions { formats } => {
+            window_actions(fdata, formats)
+        }
+        SwayrCommand::CopyWindowInfo { criteria, format } => {
+            copy_window_info(fdata, criteria.as_ref(), format)
+        }
+        SwayrCommand::FollowMatching { criteria, toggle } => {
+            follow_matching(fdata, criteria.as_deref(), *toggle)
+        }
+        SwayrCommand::ReloadSway => reload_sway(fdata),
+        SwayrCommand::ToggleDoNotDisturb { clear_urgency } => {
+            toggle_do_not_disturb(fdata, *clear_urgency)
+        }
+        SwayrCommand::ResetState { what } => {
+            reset_state(fdata, what, switch_to_matching_data)
+        }
+        SwayrCommand::SaveFocusBookmark { name } => {
+            save_focus_bookmark(fdata, name)
+        }
+        SwayrCommand::RestoreFocusBookmark { name } => {
+            restore_focus_bookmark(fdata, name.as_deref())
+        }
+        SwayrCommand::ExecuteSwaymsgCommand => exec_swaymsg_command(fdata),
+        SwayrCommand::SwitchBindingMode => switch_binding_mode(),
         SwayrCommand::ExecuteSwayrCommand => {
-            let mut cmds = vec![
-                SwayrCommand::MoveFocusedToWorkspace,
-                SwayrCommand::MoveFocusedTo,
-                SwayrCommand::SwapFocusedWith,
-                SwayrCommand::QuitWorkspaceOrWindow,
-                SwayrCommand::SwitchWindow,
-                SwayrCommand::StealWindow,
-                SwayrCommand::StealWindowOrContainer,
-                SwayrCommand::SwitchWorkspace,
-                SwayrCommand::SwitchOutput,
-                SwayrCommand::SwitchWorkspaceOrWindow,
-                SwayrCommand::SwitchToUrgentOrLRUWindow {
-                    skip_flags: SkipFlags {
-                        skip_urgent: false,
-                        skip_lru: false,
-                        skip_lru_if_current_doesnt_match: false,
-                        skip_origin: false,
-                    },
-                },
-                SwayrCommand::ConfigureOutputs,
-                SwayrCommand::ExecuteSwaymsgCommand,
-            ];
-            for f in [
-                ConsiderFloating::ExcludeFloating,
-                ConsiderFloating::IncludeFloating,
-            ] {
-                cmds.push(SwayrCommand::ToggleTabShuffleTileWorkspace {
-                    floating: f.clone(),
-                });
-                cmds.push(SwayrCommand::TileWorkspace {
-                    floating: f.clone(),
-                });
-                cmds.push(SwayrCommand::TabWorkspace {
-                    floating: f.clone(),
-                });
-                cmds.push(SwayrCommand::ShuffleTileWorkspace {
-                    floating: f.clone(),
-                });
-            }
-
-            for kill in [false, true] {
-                cmds.push(SwayrCommand::QuitWindow { kill });
-            }
+            let mut cmds = execute_swayr_command_menu_cmds();
 
-            for w in [
-                ConsiderWindows::AllWorkspaces,
-                ConsiderWindows::CurrentWorkspace,
-            ] {
-                cmds.push(SwayrCommand::NextWindow { windows: w.clone() });
-                cmds.push(SwayrCommand::PrevWindow { windows: w.clone() });
-                cmds.push(SwayrCommand::NextTiledWindow { windows: w.clone() });
-                cmds.push(SwayrCommand::PrevTiledWindow { windows: w.clone() });
-                cmds.push(SwayrCommand::NextTabbedOrStackedWindow {
-                    windows: w.clone(),
-                });
-                cmds.push(SwayrCommand::PrevTabbedOrStackedWindow {
-                    windows: w.clone(),
-                });
-                cmds.push(SwayrCommand::NextFloatingWindow {
-                    windows: w.clone(),
-                });
-                cmds.push(SwayrCommand::PrevFloatingWindow {
-                    windows: w.clone(),
-                })
+            if CONFIG.get_misc_execute_swayr_command_order()
+                == CommandOrder::Frecency
+            {
+                sort_by_frecency(&mut cmds, fdata);
             }
 
             match util::select_from_menu("Select swayr command", &cmds) {
@@ -727,15 +2698,97 @@ fn exec_swayr_cmd_1(
                     },
                     switch_to_matching_data,
                 ),
-                _ => Err("No swayr command selected".to_owned()),
+                _ => Err(SwayrError::MenuAborted),
             }
         }
-        SwayrCommand::PrintConfig => print_config(false),
+        SwayrCommand::PrintConfig { json: true } => print_config_json(),
+        SwayrCommand::PrintConfig { json: false } => print_config(false),
         SwayrCommand::PrintDefaultConfig => print_config(true),
+        SwayrCommand::PrintConfigSchema => Ok(cfg::CONFIG_SCHEMA.to_owned()),
+        SwayrCommand::CompletionsDynamic {
+            workspaces,
+            marks,
+            app_ids,
+            outputs,
+            commands,
+        } => Ok(completions_dynamic(
+            *workspaces,
+            *marks,
+            *app_ids,
+            *outputs,
+            *commands,
+        )),
+        SwayrCommand::PrintSwayBindings { modifier } => {
+            Ok(print_sway_bindings(modifier))
+        }
+    }
+}
+
+/// The keys (without modifier) and swayr command lines of the bindings
+/// printed by `print-sway-bindings`.
+const DEFAULT_SWAY_BINDINGS: [(&str, &[&str]); 8] = [
+    ("Space", &["switch-window"]),
+    ("Delete", &["quit-window"]),
+    ("Tab", &["switch-to-urgent-or-lru-window"]),
+    ("Next", &["next-window", "all-workspaces"]),
+    ("Prior", &["prev-window", "all-workspaces"]),
+    ("Shift+Space", &["switch-workspace-or-window"]),
+    ("c", &["execute-swaymsg-command"]),
+    ("Shift+c", &["execute-swayr-command"]),
+];
+
+/// Generates the sway config snippet of `print-sway-bindings`.  The comments
+/// are the commands' descriptions, and bindings of commands which don't parse
+/// are omitted so that the snippet always matches the actual commands.
+fn print_sway_bindings(modifier: &str) -> String {
+    use clap::CommandFactory;
+    let cli = CommandLine::command();
+    let width = DEFAULT_SWAY_BINDINGS
+        .iter()
+        .map(|(key, _)| key.len())
+        .max()
+        .unwrap_or(0);
+    let mut lines = vec![
+        "# Generated by `swayr print-sway-bindings`.".to_owned(),
+        "# Start the swayr daemon unless you already do so elsewhere."
+            .to_owned(),
+        "exec env RUST_BACKTRACE=1 swayrd".to_owned(),
+    ];
+    for (key, args) in DEFAULT_SWAY_BINDINGS {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        if let Err(err) = parse_swayr_command(&args) {
+            log::error!("Invalid default binding {args:?}: {err}");
+            continue;
+        }
+        lines.push(String::new());
+        if let Some(about) =
+            cli.find_subcommand(&args[0]).and_then(|c| c.get_about())
+        {
+            lines.push(format!("# {about}"));
+        }
+        lines.push(format!(
+            "bindsym {modifier}+{key:<width$} exec swayr {}",
+            args.join(" ")
+        ));
     }
+    lines.join("\n")
+}
+
+#[test]
+fn test_print_sway_bindings() {
+    let snippet = print_sway_bindings("Mod4");
+    let bindings: Vec<&str> = snippet
+        .lines()
+        .filter(|l| l.starts_with("bindsym"))
+        .collect();
+    assert_eq!(DEFAULT_SWAY_BINDINGS.len(), bindings.len());
+    assert_eq!(
+        "bindsym Mod4+Tab         exec swayr switch-to-urgent-or-lru-window",
+        bindings[2]
+    );
 }
 
-fn print_config(default_config: bool) -> Result<String, String> {
+fn print_config(default_config: bool) -> Result<String, SwayrError> {
     let dc = cfg::Config::default();
     let cfg = if default_config {
         Some(&dc)
@@ -746,10 +2799,20 @@ fn print_config(default_config: bool) -> Result<String, String> {
     if let Some(cfg) = cfg {
         match toml::to_string_pretty(cfg) {
             Ok(json) => Ok(json),
-            Err(err) => Err(err.to_string()),
+            Err(err) => Err(err.to_string().into()),
         }
     } else {
-        Err("Config not yet initialized.".to_owned())
+        Err("Config not yet initialized.".into())
+    }
+}
+
+fn print_config_json() -> Result<String, SwayrError> {
+    match once_cell::sync::Lazy::get(&CONFIG) {
+        Some(cfg) => {
+            serde_json::to_string_pretty(&cfg.get_settings_with_sources())
+                .map_err(|e| e.to_string().into())
+        }
+        None => Err("Config not yet initialized.".into()),
     }
 }
 
@@ -762,6 +2825,8 @@ fn init_switch_to_matching_data(
     switch_to_matching_data.skip_lru_if_current_doesnt_match =
         skip_flags.skip_lru_if_current_doesnt_match;
     switch_to_matching_data.skip_origin = skip_flags.skip_origin;
+    switch_to_matching_data.focus_urgent_workspace =
+        skip_flags.focus_urgent_workspace;
 }
 
 fn get_matching_windows<'a>(
@@ -782,53 +2847,460 @@ fn get_windows_as_json(
     include_scratchpad: bool,
     criteria: &Option<String>,
     error_if_no_match: bool,
-) -> Result<String, String> {
+    fields: Option<&[String]>,
+    filter: &NodeFilter,
+    style: JsonStyle,
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(include_scratchpad);
+    windows_as_json(
+        &root,
+        fdata,
+        criteria,
+        error_if_no_match,
+        fields,
+        filter,
+        style,
+    )
+}
+
+fn get_focused_as_json(
+    fdata: &FocusData,
+    fields: Option<&[String]>,
+) -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(true);
     let tree = t::get_tree(&root);
-    let wins = tree.get_windows(fdata);
+    let win = tree
+        .get_windows(fdata)
+        .into_iter()
+        .find(|w| w.node.focused)
+        .ok_or_else(|| SwayrError::NoMatch("No focused window".to_owned()))?;
+    if let Some(fields) = fields {
+        check_json_fields(&tree, fields)?;
+        let obj = select_json_fields(&win, fields)?;
+        serde_json::to_string_pretty(&obj).map_err(|e| e.to_string().into())
+    } else {
+        serde_json::to_string_pretty(&win).map_err(|e| e.to_string().into())
+    }
+}
+
+/// Returns the depth of the node `id` below its workspace, e.g., 1 for direct
+/// children of the workspace.
+fn depth_below_workspace(tree: &t::Tree, id: i64) -> usize {
+    let mut depth = 0;
+    let mut id = id;
+    while let Some(parent) = tree.get_parent_node(id) {
+        depth += 1;
+        if parent.get_type() == ipc::Type::Workspace {
+            break;
+        }
+        id = parent.id;
+    }
+    depth
+}
+
+/// How a sequence of values is rendered as JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonStyle {
+    /// A pretty-printed array.
+    Pretty,
+    /// An array without any whitespace.
+    Compact,
+    /// One compact object per line (JSON Lines).
+    Lines,
+}
+
+impl JsonStyle {
+    fn new(compact: bool, jsonl: bool) -> JsonStyle {
+        if jsonl {
+            JsonStyle::Lines
+        } else if compact {
+            JsonStyle::Compact
+        } else {
+            JsonStyle::Pretty
+        }
+    }
+}
+
+/// Serializes `vals` one after the other in the given `style`.  With
+/// [`JsonStyle::Lines`], every record ends with a newline.
+fn write_json_seq<T: Serialize>(
+    mut out: impl std::io::Write,
+    vals: impl IntoIterator<Item = T>,
+    style: JsonStyle,
+) -> Result<(), String> {
+    use serde::Serializer;
+    match style {
+        JsonStyle::Pretty => serde_json::Serializer::pretty(out)
+            .collect_seq(vals)
+            .map_err(|e| e.to_string()),
+        JsonStyle::Compact => serde_json::Serializer::new(out)
+            .collect_seq(vals)
+            .map_err(|e| e.to_string()),
+        JsonStyle::Lines => {
+            for val in vals {
+                serde_json::to_writer(&mut out, &val)
+                    .map_err(|e| e.to_string())?;
+                out.write_all(b"\n").map_err(|e| e.to_string())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Serializes `val` with only the given top-level `fields`.
+struct SelectedFields<'a, T> {
+    val: &'a T,
+    fields: &'a [String],
+}
+
+impl<T: Serialize> Serialize for SelectedFields<'_, T> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        select_json_fields(self.val, self.fields)
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+#[test]
+fn test_write_json_seq() {
+    let vals = [
+        serde_json::json!({"id": 1, "name": "a"}),
+        serde_json::json!({"id": 2, "name": "b"}),
+    ];
+    let to_string = |style| {
+        let mut out = vec![];
+        write_json_seq(&mut out, &vals, style).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(
+        to_string(JsonStyle::Pretty),
+        serde_json::to_string_pretty(&vals).unwrap()
+    );
+    assert_eq!(
+        to_string(JsonStyle::Compact),
+        r#"[{"id":1,"name":"a"},{"id":2,"name":"b"}]"#
+    );
+    assert_eq!(
+        to_string(JsonStyle::Lines),
+        "{\"id\":1,\"name\":\"a\"}\n{\"id\":2,\"name\":\"b\"}\n"
+    );
+
+    let fields = ["id".to_owned()];
+    let mut out = vec![];
+    write_json_seq(
+        &mut out,
+        vals.iter().map(|val| SelectedFields {
+            val,
+            fields: &fields,
+        }),
+        JsonStyle::Compact,
+    )
+    .unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), r#"[{"id":1},{"id":2}]"#);
+}
+
+/// Returns the windows (and possibly workspaces and containers) of the tree
+/// `root` as JSON like the `get-windows-as-json` command.
+pub fn windows_as_json(
+    root: &s::Node,
+    fdata: &FocusData,
+    criteria: &Option<String>,
+    error_if_no_match: bool,
+    fields: Option<&[String]>,
+    filter: &NodeFilter,
+    style: JsonStyle,
+) -> Result<String, SwayrError> {
+    let mut out = vec![];
+    write_windows_as_json(
+        &mut out,
+        root,
+        fdata,
+        criteria,
+        error_if_no_match,
+        fields,
+        filter,
+        style,
+    )?;
+    String::from_utf8(out).map_err(|e| e.to_string().into())
+}
+
+/// Like `windows_as_json` but writes the JSON to `out` one window after the
+/// other.
+#[allow(clippy::too_many_arguments)]
+fn write_windows_as_json(
+    out: &mut dyn std::io::Write,
+    root: &s::Node,
+    fdata: &FocusData,
+    criteria: &Option<String>,
+    error_if_no_match: bool,
+    fields: Option<&[String]>,
+    filter: &NodeFilter,
+    style: JsonStyle,
+) -> Result<(), SwayrError> {
+    let tree = t::get_tree(root);
+    if let Some(fields) = fields {
+        check_json_fields(&tree, fields)?;
+    }
+    let mut wins = if filter.include_containers {
+        tree.get_workspaces_containers_and_windows(fdata)
+    } else {
+        tree.get_windows(fdata)
+    };
+    if let Some(max_depth) = filter.max_depth {
+        wins.retain(|w| {
+            w.node.get_type() == ipc::Type::Workspace
+                || depth_below_workspace(&tree, w.node.id) <= max_depth
+        });
+    }
+    if let Some(ws_rx) = &filter.workspace {
+        let rx = Regex::new(ws_rx).map_err(|e| e.to_string())?;
+        wins.retain(|w| {
+            tree.get_parent_node_of_type(w.node.id, ipc::Type::Workspace)
+                .is_some_and(|ws| rx.is_match(ws.get_name()))
+        });
+    }
+    for w in wins.iter_mut() {
+        if w.node.get_type() == ipc::Type::Workspace {
+            w.swayr_output_recency =
+                tree.get_output_workspace_recency(w.node, fdata);
+        }
+    }
     let wins = get_matching_windows(criteria.as_ref(), &wins)?;
     if error_if_no_match && wins.is_empty() {
-        Err(String::from(if criteria.is_some() {
-            "No matching windows"
-        } else {
-            "No windows"
-        }))
+        Err(SwayrError::NoMatch(
+            if criteria.is_some() {
+                "No matching windows"
+            } else {
+                "No windows"
+            }
+            .to_owned(),
+        ))
+    } else if let Some(fields) = fields {
+        write_json_seq(
+            out,
+            wins.iter().map(|val| SelectedFields { val: *val, fields }),
+            style,
+        )
+        .map_err(SwayrError::from)
     } else {
-        serde_json::to_string_pretty(&wins)
-            .map_or_else(|e| Err(e.to_string()), Ok)
+        write_json_seq(out, &wins, style).map_err(SwayrError::from)
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct ShellCommandResult {
-    exit_code: i32,
-    std_out: String,
-    std_err: String,
-    error: Option<String>,
+/// Returns an error listing the `fields` which windows serialized to JSON
+/// don't have.
+fn check_json_fields(
+    tree: &t::Tree,
+    fields: &[String],
+) -> Result<(), SwayrError> {
+    let known = tree.display_node_json_fields();
+    let unknown: Vec<&str> = fields
+        .iter()
+        .filter(|f| !known.contains(f))
+        .map(String::as_str)
+        .collect();
+    if unknown.is_empty() {
+        Ok(())
+    } else {
+        Err(SwayrError::DaemonError(format!(
+            "Unknown fields: {}",
+            unknown.join(", ")
+        )))
+    }
 }
 
-fn read_from_child(
-    child: &mut std::process::Child,
-    out: &mut String,
-    err: &mut String,
-) {
-    child.stdout.take().map(|mut co| co.read_to_string(out));
-    child.stderr.take().map(|mut ce| ce.read_to_string(err));
+/// Serializes `val` to a JSON object only containing the given top-level
+/// `fields`.
+fn select_json_fields<T: Serialize>(
+    val: &T,
+    fields: &[String],
+) -> Result<serde_json::Value, String> {
+    let mut json = serde_json::to_value(val).map_err(|e| e.to_string())?;
+    if let serde_json::Value::Object(map) = &mut json {
+        map.retain(|k, _| fields.contains(k));
+    }
+    Ok(json)
+}
+
+/// The result of a shell command run by `for-each-window` for one window.
+/// The JSON field names are part of the scripting interface, so they are
+/// given explicitly and must not change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShellCommandResult {
+    /// The con_id of the window the command has been run for.
+    #[serde(rename = "window_id")]
+    pub window_id: i64,
+    /// The exit code of the command, or 997 if it has been killed because it
+    /// didn't finish in time, or some other non-zero value if it couldn't be
+    /// run at all.
+    #[serde(rename = "exit_code")]
+    pub exit_code: i32,
+    /// The captured stdout, possibly truncated.
+    #[serde(rename = "std_out")]
+    pub stdout: String,
+    /// The captured stderr, possibly truncated.
+    #[serde(rename = "std_err")]
+    pub stderr: String,
+    /// A (system) error which occurred when running the command.
+    #[serde(rename = "error")]
+    pub error: Option<String>,
+    /// The time the command ran in milliseconds.
+    #[serde(rename = "duration_ms")]
+    pub duration_ms: u64,
+}
+
+/// The JSON result of `for-each-window`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForEachWindowResult {
+    /// The number of commands which exited with code 0.
+    #[serde(rename = "succeeded")]
+    pub succeeded: usize,
+    /// The number of commands which failed.
+    #[serde(rename = "failed")]
+    pub failed: usize,
+    /// The results of the commands in the order of the matching windows.
+    #[serde(rename = "results")]
+    pub results: Vec<ShellCommandResult>,
+}
+
+impl ForEachWindowResult {
+    pub fn new(results: Vec<ShellCommandResult>) -> ForEachWindowResult {
+        let succeeded = results.iter().filter(|r| r.exit_code == 0).count();
+        ForEachWindowResult {
+            succeeded,
+            failed: results.len() - succeeded,
+            results,
+        }
+    }
+}
+
+#[test]
+fn test_for_each_window_result_json() {
+    let result = ForEachWindowResult::new(vec![
+        ShellCommandResult {
+            window_id: 17,
+            exit_code: 0,
+            stdout: "ok".to_owned(),
+            stderr: String::new(),
+            error: None,
+            duration_ms: 3,
+        },
+        ShellCommandResult {
+            window_id: 23,
+            exit_code: 1,
+            stdout: String::new(),
+            stderr: "failed".to_owned(),
+            error: None,
+            duration_ms: 5,
+        },
+    ]);
+    let json = serde_json::to_value(&result).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "succeeded": 1,
+            "failed": 1,
+            "results": [
+                {"window_id": 17, "exit_code": 0, "std_out": "ok",
+                 "std_err": "", "error": null, "duration_ms": 3},
+                {"window_id": 23, "exit_code": 1, "std_out": "",
+                 "std_err": "failed", "error": null, "duration_ms": 5},
+            ],
+        })
+    );
+    assert_eq!(
+        serde_json::from_value::<ForEachWindowResult>(json).unwrap(),
+        result
+    );
+}
+
+/// Reads the given child `pipe` in a thread until it's closed.  Only the first
+/// `max_bytes` are kept, the remainder is consumed but discarded so that the
+/// child doesn't block on a full pipe.  Invalid UTF-8 is replaced.
+fn spawn_pipe_reader<R: Read + Send + 'static>(
+    pipe: Option<R>,
+    max_bytes: usize,
+) -> Option<thread::JoinHandle<String>> {
+    let mut pipe = pipe?;
+    Some(thread::spawn(move || {
+        let mut captured = vec![];
+        let mut total = 0;
+        let mut buf = [0; 8192];
+        loop {
+            match pipe.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let keep = n.min(max_bytes.saturating_sub(captured.len()));
+                    captured.extend_from_slice(&buf[..keep]);
+                    total += n;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+                Err(_) => break,
+            }
+        }
+        truncated_output(&captured, total)
+    }))
+}
+
+/// Converts the `captured` bytes to a string and appends a marker if the
+/// output had `total` bytes of which some were dropped.
+fn truncated_output(captured: &[u8], total: usize) -> String {
+    let mut s = String::from_utf8_lossy(captured).into_owned();
+    if total > captured.len() {
+        s.push_str(&format!(
+            "\n[truncated {} of {} bytes]",
+            total - captured.len(),
+            total
+        ));
+    }
+    s
+}
+
+#[test]
+fn test_truncated_output() {
+    assert_eq!("foo", truncated_output(b"foo", 3));
+    assert_eq!("foo\n[truncated 2 of 5 bytes]", truncated_output(b"foo", 5));
+    assert_eq!("f\u{FFFD}o", truncated_output(b"f\xffo", 3));
+}
+
+fn join_pipe_reader(reader: Option<thread::JoinHandle<String>>) -> String {
+    reader.and_then(|r| r.join().ok()).unwrap_or_default()
 }
 
 fn run_shell_command_on_window(
     win: &t::DisplayNode,
     shell_command: &[String],
+    discard_output: bool,
+    max_output: usize,
 ) -> ShellCommandResult {
     let cmd: Vec<String> = shell_command
         .iter()
         .map(|arg| win.subst_node_placeholders(arg, false))
         .collect();
     log::debug!("Running shell command on {}", win.node.id);
+    let start = Instant::now();
+    let result = |exit_code, stdout, stderr, error| ShellCommandResult {
+        window_id: win.node.id,
+        exit_code,
+        stdout,
+        stderr,
+        error,
+        duration_ms: start.elapsed().as_millis() as u64,
+    };
+    let output = || {
+        if discard_output {
+            std::process::Stdio::null()
+        } else {
+            std::process::Stdio::piped()
+        }
+    };
     match std::process::Command::new(&cmd[0])
         .args(&cmd[1..])
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped())
+        .stdout(output())
+        .stderr(output())
         .spawn()
     {
         Ok(mut child) => {
@@ -837,37 +3309,35 @@ fn run_shell_command_on_window(
                 drop(i)
             }
 
-            let mut out = String::new();
-            let mut err = String::new();
+            let out = spawn_pipe_reader(child.stdout.take(), max_output);
+            let err = spawn_pipe_reader(child.stderr.take(), max_output);
             let mut sleep_time: u16 = 4;
             let mut slept_time: u16 = 0;
             loop {
                 match child.try_wait() {
                     Ok(Some(status)) => {
-                        read_from_child(&mut child, &mut out, &mut err);
-                        return ShellCommandResult {
-                            exit_code: status.code().unwrap(),
-                            std_out: out,
-                            std_err: err,
-                            error: None,
-                        };
+                        return result(
+                            status.code().unwrap(),
+                            join_pipe_reader(out),
+                            join_pipe_reader(err),
+                            None,
+                        );
                     }
                     Ok(None) => {
                         if slept_time >= 2000 {
                             let k = child.kill();
-                            read_from_child(&mut child, &mut out, &mut err);
-                            return ShellCommandResult {
-                                exit_code: 997,
-                                std_out: out,
-                                std_err: err,
-                                error: Some(format!(
+                            return result(
+                                997,
+                                join_pipe_reader(out),
+                                join_pipe_reader(err),
+                                Some(format!(
                                     "Didn't finish, I killed it.{}",
                                     match k {
                                         Ok(_) => String::new(),
                                         Err(err) => format!(" And even killing failed with: {err}"),
                                     }
                                 )),
-                            };
+                            );
                         } else {
                             std::thread::sleep(
                                 std::time::Duration::from_millis(
@@ -881,22 +3351,22 @@ fn run_shell_command_on_window(
                         }
                     }
                     Err(err) => {
-                        return ShellCommandResult {
-                            exit_code: err.raw_os_error().unwrap_or(998),
-                            std_out: String::new(),
-                            std_err: String::new(),
-                            error: Some(err.to_string()),
-                        }
+                        return result(
+                            err.raw_os_error().unwrap_or(998),
+                            String::new(),
+                            String::new(),
+                            Some(err.to_string()),
+                        )
                     }
                 }
             }
         }
-        Err(err) => ShellCommandResult {
-            exit_code: err.raw_os_error().unwrap_or(999),
-            std_out: String::new(),
-            std_err: String::new(),
-            error: Some(err.to_string()),
-        },
+        Err(err) => result(
+            err.raw_os_error().unwrap_or(999),
+            String::new(),
+            String::new(),
+            Some(err.to_string()),
+        ),
     }
 }
 
@@ -904,29 +3374,60 @@ fn for_each_window(
     fdata: &FocusData,
     include_scratchpad: bool,
     error_if_no_match: bool,
+    discard_output: bool,
+    action: Option<&String>,
     criteria: &String,
     shell_command: &[String],
-) -> Result<String, String> {
+) -> Result<String, SwayrError> {
+    let action = match action {
+        Some(name) => Some(
+            CONFIG
+                .get_window_actions()
+                .into_iter()
+                .find(|a| &a.name == name)
+                .ok_or_else(|| format!("No window action named {name}."))?,
+        ),
+        None => None,
+    };
+    let shell_command = match &action {
+        Some(action) => &action.command,
+        None => shell_command,
+    };
     if shell_command.is_empty() {
-        return Err("No shell_command given".to_owned());
+        return Err("No shell_command given".into());
     }
     let root = ipc::get_root_node(include_scratchpad);
     let tree = t::get_tree(&root);
-    let wins = tree.get_windows(fdata);
-    let wins = get_matching_windows(Some(criteria), &wins)?;
+    let all_wins = tree.get_windows(fdata);
+    let mut wins = get_matching_windows(Some(criteria), &all_wins)?;
+    // The action is only for the windows matching its own criteria, too.
+    if let Some(action) = &action {
+        let c = criteria::parse_criteria(&action.criteria)?;
+        let pred = criteria::criterion_to_predicate(&c, &all_wins);
+        wins.retain(|w| pred(w));
+    }
 
     if error_if_no_match && wins.is_empty() {
-        return Err(String::from("No matching windows"));
+        return Err(SwayrError::NoMatch("No matching windows".to_owned()));
     }
 
-    let (sender, receiver) = channel::<ShellCommandResult>();
+    let max_output = CONFIG.get_misc_for_each_window_max_output();
+    let (sender, receiver) = channel::<(usize, ShellCommandResult)>();
 
     thread::scope(|scope| {
-        for w in wins {
+        for (idx, w) in wins.into_iter().enumerate() {
             let s = sender.clone();
             scope.spawn(move || {
-                s.send(run_shell_command_on_window(w, shell_command))
-                    .expect("Error on send!");
+                s.send((
+                    idx,
+                    run_shell_command_on_window(
+                        w,
+                        shell_command,
+                        discard_output,
+                        max_output,
+                    ),
+                ))
+                .expect("Error on send!");
             });
         }
     });
@@ -935,28 +3436,156 @@ fn for_each_window(
     // blocks indefinitely.
     drop(sender);
 
-    let results: Vec<ShellCommandResult> = receiver.iter().collect();
+    let mut results: Vec<(usize, ShellCommandResult)> =
+        receiver.iter().collect();
+    results.sort_by_key(|(idx, _)| *idx);
+    let result =
+        ForEachWindowResult::new(results.into_iter().map(|(_, r)| r).collect());
     let json =
-        serde_json::to_string_pretty(&results).expect("Error generating JSON");
-    if results.iter().all(|r| r.exit_code == 0) {
+        serde_json::to_string_pretty(&result).expect("Error generating JSON");
+    if result.failed == 0 {
         Ok(json)
     } else {
-        Err(json)
+        Err(json.into())
+    }
+}
+
+/// Executes the `commands` given like on the command line in order and
+/// returns their non-empty outputs separated by newlines or the error of the
+/// first failing command.
+fn exec_batch(
+    fdata: &FocusData,
+    commands: &[String],
+    stop_on_error: bool,
+) -> Result<String, SwayrError> {
+    let mut outputs = vec![];
+    let mut first_err = None;
+    for command in commands {
+        let result = util::shell_split(command)
+            .and_then(|args| parse_swayr_command(&args))
+            .map_err(SwayrError::DaemonError)
+            .and_then(|cmd| match cmd {
+                SwayrCommand::Do { .. } => {
+                    Err("The do command cannot be nested.".into())
+                }
+                cmd => exec_swayr_cmd(ExecSwayrCmdArgs {
+                    cmd: &cmd,
+                    focus_data: fdata,
+                }),
+            });
+        match result {
+            Ok(output) => {
+                if !output.is_empty() {
+                    outputs.push(output);
+                }
+            }
+            Err(err) => {
+                log::error!("Error running batched command {command:?}: {err}");
+                first_err.get_or_insert(err);
+                if stop_on_error {
+                    break;
+                }
+            }
+        }
+    }
+    match first_err {
+        Some(err) => Err(err),
+        None => Ok(outputs.join("\n")),
+    }
+}
+
+fn wait_for_window(
+    fdata: &FocusData,
+    criteria: &str,
+    timeout: Duration,
+    only_new: bool,
+    focus: bool,
+    move_to_workspace: Option<&str>,
+) -> Result<String, SwayrError> {
+    let crit = criteria::parse_criteria(criteria)?;
+    let start = Instant::now();
+    let mut existing_ids: Option<Vec<i64>> = None;
+    loop {
+        let ev_count = fdata.get_window_event_count();
+        let root = ipc::get_root_node(true);
+        let tree = t::get_tree(&root);
+        let wins = tree.get_windows(fdata);
+        if only_new && existing_ids.is_none() {
+            existing_ids = Some(wins.iter().map(|w| w.node.id).collect());
+        }
+        let pred = criteria::criterion_to_predicate(&crit, &wins);
+        if let Some(win) = wins.iter().find(|w| {
+            !existing_ids
+                .as_ref()
+                .is_some_and(|ids| ids.contains(&w.node.id))
+                && pred(w)
+        }) {
+            if let Some(ws_name) = move_to_workspace {
+                move_to_workspace_by_id(win.node.id, ws_name)?;
+            }
+            if focus {
+                focus_window_by_id(win.node.id)?;
+            }
+            return serde_json::to_string_pretty(win)
+                .map_err(|e| e.to_string().into());
+        }
+
+        let elapsed = start.elapsed();
+        if elapsed >= timeout
+            || !fdata.wait_for_window_event(ev_count, timeout - elapsed)
+        {
+            return Err(SwayrError::NoMatch(format!(
+                "No matching window appeared within {} ms.",
+                timeout.as_millis()
+            )));
+        }
+    }
+}
+
+fn list_recently_closed(fdata: &FocusData) -> Result<String, SwayrError> {
+    serde_json::to_string_pretty(&fdata.get_closed_windows())
+        .map_err(|e| e.to_string().into())
+}
+
+fn reopen_last_closed(fdata: &FocusData) -> Result<String, SwayrError> {
+    match fdata.take_last_reopenable_window() {
+        Some(win) => {
+            let cmd: Vec<String> =
+                win.cmdline.iter().map(|a| util::shell_quote(a)).collect();
+            run_sway_command(&["exec", &cmd.join(" ")]).map(|msg| {
+                format!("{msg} (Reopening {} “{}”.)", win.app_name, win.title)
+            })
+        }
+        None => Err(SwayrError::NoMatch(
+            "No recently closed window can be reopened.".to_owned(),
+        )),
     }
 }
 
-fn steal_window_by_id(id: i64) -> Result<String, String> {
+fn steal_window_by_id(id: i64) -> Result<String, SwayrError> {
     run_sway_command(&[
         format!("[con_id={id}]").as_str(),
         "move to workspace current",
     ])
 }
 
-fn focus_window_by_id(id: i64) -> Result<String, String> {
+fn move_to_workspace_by_id(
+    id: i64,
+    ws_name: &str,
+) -> Result<String, SwayrError> {
+    let con = format!("[con_id={id}]");
+    if is_numbered_workspace(ws_name) {
+        run_sway_command(&[&con, "move to workspace number", ws_name])
+    } else {
+        run_sway_command(&[&con, "move to workspace", ws_name])
+    }
+}
+
+fn focus_window_by_id(id: i64) -> Result<String, SwayrError> {
     run_sway_command(&[format!("[con_id={id}]").as_str(), "focus"])
 }
 
-fn quit_window_by_id(id: i64) -> Result<String, String> {
+fn quit_window_by_id(id: i64) -> Result<String, SwayrError> {
     run_sway_command(&[format!("[con_id={id}]").as_str(), "kill"])
 }
 
@@ -970,7 +3599,7 @@ pub fn get_outputs() -> Vec<s::Output> {
 pub fn switch_to_urgent_or_lru_window(
     stm_data: &mut MutexGuard<SwitchToMatchingData>,
     fdata: &FocusData,
-) -> Result<String, String> {
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(false);
     let tree = t::get_tree(&root);
     let wins = tree.get_windows(fdata);
@@ -989,7 +3618,7 @@ pub fn focus_urgent_or_matching_or_lru_window<P>(
     stm_data: &mut MutexGuard<SwitchToMatchingData>,
     pred: P,
     ignore_pred: bool,
-) -> Result<String, String>
+) -> Result<String, SwayrError>
 where
     P: Fn(&t::DisplayNode) -> bool,
 {
@@ -1001,13 +3630,13 @@ where
         // If we should not ignore the predicate is given, then we want at
         // least one matching window.
         if !ignore_pred && !wins.iter().any(&pred) {
-            return Err("No window matches.".to_owned());
+            return Err(SwayrError::NoMatch("No window matches.".to_owned()));
         }
 
         // The currently focused window is already visited, obviously.
         if let Some(f) = focused {
             // The focused window is the fallback we want to return to.
-            stm_data.origin = Some(f.node.id);
+            stm_data.set_origin(Some(f.node.id));
         }
 
         if !ignore_pred
@@ -1019,15 +3648,17 @@ where
         }
 
         if !stm_data.skip_lru {
-            stm_data.lru = wins
-                .iter()
-                .filter(|w| !w.node.focused)
-                .max_by(|a, b| {
-                    fdata
-                        .last_focus_tick(a.node.id)
-                        .cmp(&fdata.last_focus_tick(b.node.id))
-                })
-                .map(|w| w.node.id);
+            let mut lru_wins: Vec<&t::DisplayNode> = wins.iter().collect();
+            let now = Instant::now();
+            ordering::sort_by_recency(
+                &mut lru_wins,
+                |w| fdata.last_focus_tick(w.node.id),
+                |w| fdata.last_use(w.node.id).map(|t| now.duration_since(t)),
+                |w| w.tree.get_boost_weight(w.node.id),
+                CONFIG.get_focus_decay_half_life(),
+            );
+            stm_data.lru =
+                lru_wins.iter().find(|w| !w.node.focused).map(|w| w.node.id);
         }
 
         log::debug!("Initialized SwitchToMatchingData: {stm_data:?}");
@@ -1039,17 +3670,39 @@ where
     // We might have changed focus through other means (normal sway commands)
     // so just add the current window to visited unconditionally.
     stm_data.visited.push(focused_id);
+    let do_not_disturb = fdata.get_do_not_disturb().is_some();
+
+    let urgent_candidates = wins
+        .iter()
+        .filter(|w| !stm_data.skip_urgent && !do_not_disturb && w.node.urgent);
+    let matching_candidates = wins.iter().filter(|w| {
+        (stm_data.skip_origin || stm_data.origin != Some(w.node.id)) && pred(w)
+    });
+    stm_data.candidates = urgent_candidates
+        .chain(matching_candidates)
+        .map(|w| w.node.id)
+        .chain(stm_data.lru.filter(|_| !stm_data.skip_lru))
+        .chain(stm_data.origin.filter(|_| !stm_data.skip_origin))
+        .filter(|id| *id != focused_id)
+        .collect();
 
     if let Some(win) = wins.iter().find(|w| {
         w.node.id != focused_id
             && !stm_data.skip_urgent
+            && !do_not_disturb
             && w.node.urgent
             && !stm_data.visited.contains(&w.node.id)
     }) {
         log::debug!("Switching to by urgency");
         stm_data.visited.push(win.node.id);
-        focus_window_by_id(win.node.id)
-            .map(|msg| msg + " (It's a window with urgency hint.)")
+        if stm_data.focus_urgent_workspace {
+            switch_to_workspace_of_window(win).map(|msg| {
+                msg + " (It's the workspace of a window with urgency hint.)"
+            })
+        } else {
+            focus_window_by_id(win.node.id)
+                .map(|msg| msg + " (It's a window with urgency hint.)")
+        }
     } else if let Some(win) = wins.iter().find(|w| {
         w.node.id != focused_id
             && (stm_data.skip_origin || stm_data.origin != Some(w.node.id))
@@ -1095,7 +3748,9 @@ where
                 log::debug!("Origin is gone; resetting.");
                 stm_data.reset(false);
                 if initialized_now {
-                    Err("Nothing to be switched to.".to_owned())
+                    Err(SwayrError::NoMatch(
+                        "Nothing to be switched to.".to_owned(),
+                    ))
                 } else {
                     focus_urgent_or_matching_or_lru_window(
                         wins,
@@ -1118,7 +3773,9 @@ where
                     ignore_pred,
                 )
             } else {
-                Err("Nothing to be switched to.".to_owned())
+                Err(SwayrError::NoMatch(
+                    "Nothing to be switched to.".to_owned(),
+                ))
             }
         }
     } else {
@@ -1137,17 +3794,133 @@ where
                 Some(win) if pred(win) => Ok(format!(
                     "The single matching window {focused_id} is already focused."
                 )),
-                _ => Err("Nothing to be switched to.".to_owned()),
+                _ => Err(SwayrError::NoMatch("Nothing to be switched to.".to_owned())),
             }
         }
     }
 }
 
+fn switch_to_workspace_of_window(
+    win: &t::DisplayNode,
+) -> Result<String, SwayrError> {
+    match win
+        .tree
+        .get_parent_node_of_type(win.node.id, ipc::Type::Workspace)
+    {
+        Some(ws) if !ws.is_scratchpad() => {
+            run_sway_command(&["workspace", ws.get_name()])
+        }
+        _ => Err(format!("Window {} is on no workspace.", win.node.id).into()),
+    }
+}
+
+pub fn switch_to_urgent_workspace(
+    fdata: &FocusData,
+) -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(false);
+    let tree = t::get_tree(&root);
+    let wins = tree.get_windows(fdata);
+    if let Some(win) = wins
+        .iter()
+        .filter(|w| w.node.urgent)
+        .max_by_key(|w| fdata.last_urgent_tick(w.node.id))
+    {
+        switch_to_workspace_of_window(win)
+    } else if let Some(ws) = root
+        .nodes_of_type(ipc::Type::Workspace)
+        .iter()
+        .find(|ws| ws.urgent)
+    {
+        run_sway_command(&["workspace", ws.get_name()])
+            .and_then(|msg| restore_workspace_window(msg, ws.get_name(), fdata))
+    } else {
+        Err(SwayrError::NoMatch(
+            "No window or workspace has an urgency hint.".to_owned(),
+        ))
+    }
+}
+
+fn step_workspace_history(
+    fdata: &FocusData,
+    back: bool,
+) -> Result<String, SwayrError> {
+    let ws_name = fdata.step_workspace_history(back).ok_or_else(|| {
+        SwayrError::NoMatch(format!(
+            "No {} workspace in history.",
+            if back { "previous" } else { "next" }
+        ))
+    })?;
+    create_workspace(&ws_name)
+        .inspect_err(|_| {
+            fdata.undo_workspace_history_step(back);
+        })
+        .and_then(|msg| restore_workspace_window(msg, &ws_name, fdata))
+}
+
+/// Returns the window of the workspace named `ws_name` which has been focused
+/// most recently, if any.
+fn get_workspace_lru_window<'a>(
+    tree: &'a t::Tree,
+    ws_name: &str,
+    fdata: &FocusData,
+) -> Option<t::DisplayNode<'a>> {
+    tree.get_windows(fdata)
+        .into_iter()
+        .filter(|w| fdata.last_focus_tick(w.node.id) > 0)
+        .filter(|w| {
+            tree.get_parent_node_of_type(w.node.id, ipc::Type::Workspace)
+                .is_some_and(|ws| ws.get_name() == ws_name)
+        })
+        .max_by_key(|w| fdata.last_focus_tick(w.node.id))
+}
+
+fn get_workspace_lru_window_as_json(
+    fdata: &FocusData,
+    ws_name: &str,
+) -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(true);
+    let tree = t::get_tree(&root);
+    match get_workspace_lru_window(&tree, ws_name, fdata) {
+        Some(win) => {
+            serde_json::to_string_pretty(&win).map_err(|e| e.to_string().into())
+        }
+        None => Err(SwayrError::NoMatch(format!(
+            "No recently used window on workspace {ws_name}."
+        ))),
+    }
+}
+
+fn get_icon(app_name: &str) -> Result<String, SwayrError> {
+    icon::get_icon(&t::APP_ID_TO_ICON_MAP, app_name)
+        .map(|i| i.display().to_string())
+        .ok_or_else(|| SwayrError::NoMatch(format!("No icon for {app_name}.")))
+}
+
+/// Focuses the most recently used window of the workspace named `ws_name`
+/// after switching to it if `focus.restore_workspace_window` is enabled.
+/// `msg` is the result of switching to the workspace.
+fn restore_workspace_window(
+    msg: String,
+    ws_name: &str,
+    fdata: &FocusData,
+) -> Result<String, SwayrError> {
+    if !CONFIG.is_focus_restore_workspace_window() {
+        return Ok(msg);
+    }
+    let root = ipc::get_root_node(false);
+    let tree = t::get_tree(&root);
+    match get_workspace_lru_window(&tree, ws_name, fdata) {
+        Some(win) if !win.node.focused => focus_window_by_id(win.node.id)
+            .map(|focus_msg| format!("{msg} ({focus_msg})")),
+        _ => Ok(msg),
+    }
+}
+
 pub fn switch_to_app_or_urgent_or_lru_window(
     name: &str,
     stm_data: &mut MutexGuard<SwitchToMatchingData>,
     fdata: &FocusData,
-) -> Result<String, String> {
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(false);
     let tree = t::get_tree(&root);
     let wins = tree.get_windows(fdata);
@@ -1160,7 +3933,7 @@ pub fn switch_to_mark_or_urgent_or_lru_window(
     con_mark: &str,
     stm_data: &mut MutexGuard<SwitchToMatchingData>,
     fdata: &FocusData,
-) -> Result<String, String> {
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(false);
     let tree = t::get_tree(&root);
     let wins = tree.get_windows(fdata);
@@ -1174,7 +3947,7 @@ fn switch_to_matching_or_urgent_or_lru_window(
     criteria: &str,
     switch_to_matching_data: &mut MutexGuard<SwitchToMatchingData>,
     fdata: &FocusData,
-) -> Result<String, String> {
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(false);
     let tree = t::get_tree(&root);
     let wins = tree.get_windows(fdata);
@@ -1190,11 +3963,52 @@ fn switch_to_matching_or_urgent_or_lru_window(
     )
 }
 
-static DIGIT_AND_NAME: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r"^(\d):(.*)").unwrap());
+/// Matches the names of workspaces which are created and moved to using
+/// `workspace number` so that sway keeps them ordered by their number.
+static DIGIT_AND_NAME: Lazy<Option<Regex>> = Lazy::new(|| {
+    workspace_number_regex(&CONFIG.get_misc_workspace_number_pattern())
+});
+
+/// Returns the regex of `pattern` or `None` if it's empty, i.e., if workspace
+/// numbers shouldn't be treated specially.  Invalid patterns are logged and
+/// replaced by the default one.
+fn workspace_number_regex(pattern: &str) -> Option<Regex> {
+    if pattern.is_empty() {
+        return None;
+    }
+    match Regex::new(pattern) {
+        Ok(re) => Some(re),
+        Err(err) => {
+            log::error!("Invalid misc.workspace_number_pattern: {err}");
+            Some(Regex::new(cfg::DEFAULT_WORKSPACE_NUMBER_PATTERN).unwrap())
+        }
+    }
+}
+
+fn is_numbered_workspace(ws_name: &str) -> bool {
+    DIGIT_AND_NAME
+        .as_ref()
+        .is_some_and(|re| re.is_match(ws_name))
+}
+
+#[test]
+fn test_workspace_number_regex() {
+    let default =
+        workspace_number_regex(cfg::DEFAULT_WORKSPACE_NUMBER_PATTERN).unwrap();
+    assert!(default.is_match("3:mail"));
+    assert!(!default.is_match("12:mail"));
+    let two_digits = workspace_number_regex(r"^([0-9]{1,2})[:_-](.*)").unwrap();
+    assert!(two_digits.is_match("12_mail"));
+    assert!(!two_digits.is_match("mail"));
+    assert!(workspace_number_regex("").is_none());
+    assert_eq!(
+        workspace_number_regex("^(").unwrap().as_str(),
+        default.as_str()
+    );
+}
 
-fn create_workspace(ws_name: &str) -> Result<String, String> {
-    if DIGIT_AND_NAME.is_match(ws_name) {
+fn create_workspace(ws_name: &str) -> Result<String, SwayrError> {
+    if is_numbered_workspace(ws_name) {
         run_sway_command(&["workspace", "number", ws_name])
     } else {
         run_sway_command(&["workspace", ws_name])
@@ -1220,9 +4034,9 @@ fn chop_sway_shortcut(input: &str) -> &str {
     }
 }
 
-fn handle_non_matching_input(input: &str) -> Result<String, String> {
+fn handle_non_matching_input(input: &str) -> Result<String, SwayrError> {
     if input.is_empty() {
-        Err("Cannot handle empty string as non-matching input.".to_owned())
+        Err(SwayrError::MenuAborted)
     } else if let Some(c) = SPECIAL_SWAY.captures(input) {
         let cmd = c[1].split_ascii_whitespace().collect::<Vec<&str>>();
         run_sway_command(&cmd).map(|msg| msg + " (for non-matching input)")
@@ -1232,24 +4046,52 @@ fn handle_non_matching_input(input: &str) -> Result<String, String> {
     }
 }
 
+/// Returns the index of the most likely target among `choices`, i.e., the
+/// first window which isn't focused or, if there are no windows, the first
+/// choice not containing the focus.
+fn default_choice(choices: &[t::DisplayNode]) -> usize {
+    let is_window = |c: &t::DisplayNode| c.node.get_type() == ipc::Type::Window;
+    choices
+        .iter()
+        .position(|c| is_window(c) && !c.node.focused)
+        .or_else(|| {
+            choices
+                .iter()
+                .position(|c| !is_window(c) && !c.node.is_current())
+        })
+        .unwrap_or(0)
+}
+
 fn select_and_focus(
     prompt: &str,
     choices: &[t::DisplayNode],
-) -> Result<String, String> {
-    match util::select_from_menu(prompt, choices) {
+    fdata: &FocusData,
+) -> Result<String, SwayrError> {
+    match util::select_candidate_from_menu_with_default(
+        prompt,
+        choices,
+        default_choice(choices),
+    ) {
         Ok(tn) => match tn.node.get_type() {
             ipc::Type::Output => {
                 if tn.node.is_scratchpad() {
-                    Err("Cannot switch to the scratchpad output.".to_owned())
+                    Err("Cannot switch to the scratchpad output.".into())
                 } else {
                     run_sway_command(&["focus output", tn.node.get_name()])
                 }
             }
             ipc::Type::Workspace => {
                 if tn.node.is_scratchpad() {
-                    Err("Cannot switch to the scratchpad workspace.".to_owned())
+                    Err("Cannot switch to the scratchpad workspace.".into())
                 } else {
                     run_sway_command(&["workspace", tn.node.get_name()])
+                        .and_then(|msg| {
+                            restore_workspace_window(
+                                msg,
+                                tn.node.get_name(),
+                                fdata,
+                            )
+                        })
                 }
             }
             ipc::Type::Window | ipc::Type::Container => {
@@ -1257,7 +4099,7 @@ fn select_and_focus(
             }
             t => {
                 log::error!("Cannot handle {t:?} in select_and_focus");
-                Err(format!("Cannot handle node type {t:?}."))
+                Err(format!("Cannot handle node type {t:?}.").into())
             }
         },
         Err(non_matching_input) => {
@@ -1269,34 +4111,225 @@ fn select_and_focus(
 fn select_and_steal(
     prompt: &str,
     choices: &[t::DisplayNode],
-) -> Result<String, String> {
-    match util::select_from_menu(prompt, choices) {
+) -> Result<String, SwayrError> {
+    match util::select_candidate_from_menu(prompt, choices) {
         Ok(tn) => match tn.node.get_type() {
             ipc::Type::Window | ipc::Type::Container => {
                 steal_window_by_id(tn.node.id)
             }
             ipc::Type::Workspace => {
                 log::info!("Can't steal whole workspace");
-                Err("Can't steal whole workspace".to_owned())
+                Err("Can't steal whole workspace".into())
             }
             t => {
                 log::error!("Cannot handle {t:?} in select_and_steal");
-                Err(format!("Cannot handle {t:?}."))
+                Err(format!("Cannot handle {t:?}.").into())
             }
         },
+        Err(input) if input.is_empty() => Err(SwayrError::MenuAborted),
         Err(non_matching_input) => {
             log::warn!(
                 "Cannot handle non-matching input {non_matching_input:?} in select and steal"
             );
-            Err("Cannot handle non-matching input.".to_owned())
+            Err("Cannot handle non-matching input.".into())
         }
     }
 }
 
-pub fn switch_window(fdata: &FocusData) -> Result<String, String> {
-    let root = ipc::get_root_node(true);
+#[test]
+fn test_select_and_steal() {
+    let root: s::Node =
+        serde_json::from_str(include_str!("../tests/fixtures/tree.json"))
+            .unwrap();
     let tree = t::get_tree(&root);
-    select_and_focus("Select window", &tree.get_windows(fdata))
+    let (tx, _rx) = channel();
+    let fdata = FocusData::new(tx);
+    let choices = tree.get_workspaces_containers_and_windows(&fdata);
+    let ws = choices
+        .iter()
+        .find(|c| c.node.get_type() == ipc::Type::Workspace)
+        .unwrap();
+    let steal = |menu: util::MockMenu, choices: &[t::DisplayNode]| {
+        let menu = std::rc::Rc::new(menu);
+        let result = util::with_menu_backend(menu.clone(), || {
+            select_and_steal("Steal", choices)
+        });
+        (result.map_err(|e| e.to_string()), menu.shown().len())
+    };
+
+    let ws_text = ws.format_for_display();
+    assert_eq!(
+        (Err("Can't steal whole workspace".to_owned()), 1),
+        steal(util::MockMenu::new(&[&ws_text]), &choices)
+    );
+    assert_eq!(
+        (Err("Cannot handle non-matching input.".to_owned()), 1),
+        steal(util::MockMenu::new(&["no such window"]), &choices)
+    );
+    assert_eq!(
+        (Err(SwayrError::MenuAborted.to_string()), 1),
+        steal(util::MockMenu::new(&[""]), &choices)
+    );
+
+    // The candidate settings apply without showing the menu.
+    let settings = util::MenuSettings {
+        min_entries: choices.len() + 1,
+        auto_accept_single: true,
+        ..util::MenuSettings::default()
+    };
+    assert_eq!(
+        (Err(SwayrError::MenuAborted.to_string()), 0),
+        steal(
+            util::MockMenu::new(&[&ws_text]).with_settings(settings.clone()),
+            &choices
+        )
+    );
+    assert_eq!(
+        (Err("Can't steal whole workspace".to_owned()), 0),
+        steal(
+            util::MockMenu::new(&[]).with_settings(settings),
+            std::slice::from_ref(ws)
+        )
+    );
+}
+
+pub fn switch_window(
+    fdata: &FocusData,
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(true);
+    let tree = t::get_tree(&root).with_format_overrides(formats);
+    let wins = tree.get_windows(fdata);
+    if CONFIG.is_menu_auto_accept_single() {
+        // The focused window is no real candidate, so with just one other
+        // window, switch to that.
+        if let [other] =
+            wins.iter().filter(|w| !w.node.focused).collect::<Vec<_>>()[..]
+        {
+            return focus_window_by_id(other.node.id);
+        }
+    }
+    select_and_focus("Select window", &wins, fdata)
+}
+
+pub fn switch_app(
+    fdata: &FocusData,
+    select_window: bool,
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(true);
+    let tree = t::get_tree(&root).with_format_overrides(formats);
+    let apps = tree.get_apps(fdata);
+    let app =
+        match util::select_candidate_from_menu("Select application", &apps) {
+            Ok(app) => app,
+            Err(input) if input.is_empty() => {
+                return Err(SwayrError::MenuAborted)
+            }
+            Err(input) => {
+                return Err(SwayrError::NoMatch(format!(
+                    "No application {input}."
+                )))
+            }
+        };
+    if select_window && app.windows.len() > 1 {
+        select_and_focus("Select window", &app.windows, fdata)
+    } else {
+        // The windows are in LRU order, i.e., the focused one is last.
+        focus_window_by_id(app.windows[0].node.id)
+    }
+}
+
+fn focus_nth_last_window(
+    fdata: &FocusData,
+    n: usize,
+) -> Result<String, SwayrError> {
+    if n == 0 {
+        return Err("The LRU position starts at 1.".into());
+    }
+    let root = ipc::get_root_node(false);
+    let mut wins: Vec<&s::Node> = root
+        .nodes_of_type(ipc::Type::Window)
+        .into_iter()
+        .filter(|w| !w.focused)
+        .collect();
+    wins.sort_by_key(|w| std::cmp::Reverse(fdata.last_focus_tick(w.id)));
+    match wins.get(n - 1) {
+        Some(win) => focus_window_by_id(win.id),
+        None => Err(SwayrError::NoMatch(format!(
+            "There are only {} other windows.",
+            wins.len()
+        ))),
+    }
+}
+
+/// Returns the score of `query` against the window properties `texts` where
+/// each word of `query` must fuzzily match at least one of them, or `None` if
+/// some word doesn't.
+fn quick_switch_score(query: &str, texts: &[&str]) -> Option<u32> {
+    query
+        .split_whitespace()
+        .map(|word| {
+            texts
+                .iter()
+                .filter_map(|t| util::fuzzy_score(word, t))
+                .max()
+        })
+        .sum()
+}
+
+#[test]
+fn test_quick_switch_score() {
+    let texts = ["Inbox - Mozilla Thunderbird", "thunderbird", "mail"];
+    assert!(quick_switch_score("thun inb", &texts).is_some());
+    assert!(quick_switch_score("mail", &texts).is_some());
+    assert_eq!(None, quick_switch_score("thun firefox", &texts));
+    assert!(
+        quick_switch_score("thunderbird", &texts)
+            > quick_switch_score("tb", &texts)
+    );
+}
+
+/// The score a window loses per position in the LRU order in `quick-switch`.
+const QUICK_SWITCH_RECENCY_PENALTY: i64 = 5;
+
+fn quick_switch(fdata: &FocusData, query: &str) -> Result<String, SwayrError> {
+    if query.trim().is_empty() {
+        return Err("The query must not be empty.".into());
+    }
+    let root = ipc::get_root_node(false);
+    let mut wins: Vec<&s::Node> = root.nodes_of_type(ipc::Type::Window);
+    wins.sort_by_key(|w| {
+        (w.focused, std::cmp::Reverse(fdata.last_focus_tick(w.id)))
+    });
+    let best = wins
+        .iter()
+        .enumerate()
+        .filter_map(|(rank, w)| {
+            let mut texts = vec![w.get_name(), w.get_app_name()];
+            texts.extend(w.marks.iter().map(String::as_str));
+            quick_switch_score(query, &texts).map(|score| {
+                (score as i64 - rank as i64 * QUICK_SWITCH_RECENCY_PENALTY, w)
+            })
+        })
+        // On ties, the first, i.e., more recently used window wins.
+        .min_by_key(|(score, _)| std::cmp::Reverse(*score));
+    match best {
+        Some((_, win)) => focus_window_by_id(win.id),
+        None => {
+            Err(SwayrError::NoMatch(format!("No window matches {query:?}.")))
+        }
+    }
+}
+
+pub fn list_focus_history(
+    fdata: &FocusData,
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(true);
+    let tree = t::get_tree(&root).with_format_overrides(formats);
+    let wins = tree.get_windows_by_ids(&fdata.get_focus_history());
+    select_and_focus("Select window from focus history", &wins, fdata)
 }
 
 fn retain_nodes_of_non_current_workspaces(
@@ -1314,64 +4347,90 @@ fn retain_nodes_of_non_current_workspaces(
     };
 }
 
-pub fn steal_window(fdata: &FocusData) -> Result<String, String> {
+pub fn steal_window(
+    fdata: &FocusData,
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(true);
-    let tree = t::get_tree(&root);
+    let tree = t::get_tree(&root).with_format_overrides(formats);
     let wins = &mut tree.get_windows(fdata);
     retain_nodes_of_non_current_workspaces(&tree, wins);
     select_and_steal("Select window", wins)
 }
 
-pub fn steal_window_or_container(fdata: &FocusData) -> Result<String, String> {
+pub fn steal_window_or_container(
+    fdata: &FocusData,
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(true);
-    let tree = t::get_tree(&root);
+    let tree = t::get_tree(&root).with_format_overrides(formats);
     let wins_and_ws = &mut tree.get_workspaces_containers_and_windows(fdata);
     retain_nodes_of_non_current_workspaces(&tree, wins_and_ws);
     select_and_steal("Select window or container", wins_and_ws)
 }
 
-pub fn switch_workspace(fdata: &FocusData) -> Result<String, String> {
+pub fn switch_workspace(
+    fdata: &FocusData,
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(false);
-    let tree = t::get_tree(&root);
-    select_and_focus("Select workspace", &tree.get_workspaces(fdata))
+    let tree = t::get_tree(&root).with_format_overrides(formats);
+    select_and_focus("Select workspace", &tree.get_workspaces(fdata), fdata)
 }
 
-pub fn switch_output() -> Result<String, String> {
+pub fn switch_output(
+    fdata: &FocusData,
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(false);
-    let tree = t::get_tree(&root);
-    select_and_focus("Select output", &tree.get_outputs())
+    let tree = t::get_tree(&root)
+        .with_format_overrides(formats)
+        .with_outputs(get_outputs());
+    select_and_focus("Select output", &tree.get_outputs(), fdata)
 }
 
-pub fn switch_workspace_or_window(fdata: &FocusData) -> Result<String, String> {
+pub fn switch_workspace_or_window(
+    fdata: &FocusData,
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(true);
-    let tree = t::get_tree(&root);
+    let tree = t::get_tree(&root).with_format_overrides(formats);
     select_and_focus(
         "Select workspace or window",
         &tree.get_workspaces_and_windows(fdata),
+        fdata,
     )
 }
 
 pub fn switch_workspace_container_or_window(
     fdata: &FocusData,
-) -> Result<String, String> {
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(true);
-    let tree = t::get_tree(&root);
+    let tree = t::get_tree(&root).with_format_overrides(formats);
     select_and_focus(
         "Select workspace, container or window",
         &tree.get_workspaces_containers_and_windows(fdata),
+        fdata,
     )
 }
 
-pub fn switch_to(fdata: &FocusData) -> Result<String, String> {
+pub fn switch_to(
+    fdata: &FocusData,
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(true);
-    let tree = t::get_tree(&root);
+    let tree = t::get_tree(&root)
+        .with_format_overrides(formats)
+        .with_outputs(get_outputs());
     select_and_focus(
         "Select output, workspace, container or window",
         &tree.get_outputs_workspaces_containers_and_windows(fdata),
+        fdata,
     )
 }
 
-fn kill_process_by_pid(pid: Option<i32>) -> Result<String, String> {
+fn kill_process_by_pid(pid: Option<i32>) -> Result<String, SwayrError> {
     if let Some(pid) = pid {
         match std::process::Command::new("kill")
             .arg("-9")
@@ -1380,28 +4439,99 @@ fn kill_process_by_pid(pid: Option<i32>) -> Result<String, String> {
         {
             Err(err) => {
                 log::error!("Error killing process {pid}: {err}");
-                Err(err.to_string())
+                Err(err.to_string().into())
             }
             _ => Ok(format!("Killed process with pid {pid}.")),
         }
     } else {
         log::error!("Cannot kill window with no pid.");
-        Err("No pid to kill given.".to_owned())
+        Err("No pid to kill given.".into())
+    }
+}
+
+struct Confirmation(&'static str);
+
+impl DisplayFormat for Confirmation {
+    fn format_for_display(&self) -> String {
+        self.0.to_owned()
+    }
+
+    fn get_indent_level(&self) -> usize {
+        0
+    }
+}
+
+/// Asks for confirmation if some of the windows with the given `ids` match
+/// the `protect.criteria`.
+fn confirm_quitting_protected_windows(
+    ids: &[i64],
+    fdata: &FocusData,
+) -> Result<(), SwayrError> {
+    let protected =
+        layout::matching_window_ids(&CONFIG.get_protect_criteria(), fdata);
+    let n = ids.iter().filter(|id| protected.contains(id)).count();
+    if n == 0 {
+        return Ok(());
+    }
+
+    if confirm(&format!("Really quit {n} protected window(s)?")) {
+        Ok(())
+    } else {
+        Err(SwayrError::MenuAborted)
     }
 }
 
+/// Asks the yes/no question `prompt` using the menu.
+fn confirm(prompt: &str) -> bool {
+    let choices = [Confirmation("No"), Confirmation("Yes")];
+    matches!(
+        util::select_from_menu(prompt, &choices),
+        Ok(Confirmation("Yes"))
+    )
+}
+
+#[test]
+fn test_confirm() {
+    let menu = std::rc::Rc::new(util::MockMenu::new(&["Yes", "No", "", "y"]));
+    util::with_menu_backend(menu.clone(), || {
+        assert!(confirm("Really?"));
+        assert!(!confirm("Really?"));
+        assert!(!confirm("Really?"));
+        assert!(!confirm("Really?"));
+    });
+    let shown = menu.shown();
+    assert_eq!(4, shown.len());
+    assert_eq!(
+        (
+            "Really?".to_string(),
+            vec!["No".to_string(), "Yes".to_string()],
+            0
+        ),
+        shown[0]
+    );
+}
+
 fn select_and_quit(
     prompt: &str,
     choices: &[t::DisplayNode],
     kill: bool,
-) -> Result<String, String> {
-    match util::select_from_menu(prompt, choices) {
+    force: bool,
+    fdata: &FocusData,
+) -> Result<String, SwayrError> {
+    match util::select_candidate_from_menu(prompt, choices) {
         Ok(tn) => match tn.node.get_type() {
             ipc::Type::Workspace | ipc::Type::Container => {
-                for win in
-                    tn.node.iter().filter(|n| n.get_type() == ipc::Type::Window)
-                {
-                    match quit_window_by_id(win.id) {
+                let ids: Vec<i64> = tn
+                    .node
+                    .iter()
+                    .filter(|n| n.get_type() == ipc::Type::Window)
+                    .map(|n| n.id)
+                    .collect();
+                if !force {
+                    confirm_quitting_protected_windows(&ids, fdata)?;
+                }
+                for id in ids {
+                    match quit_window_by_id(id) {
                         Ok(_) => (),
                         e @ Err(_) => return e,
                     }
@@ -1413,6 +4543,9 @@ fn select_and_quit(
                 ))
             }
             ipc::Type::Window => {
+                if !force {
+                    confirm_quitting_protected_windows(&[tn.node.id], fdata)?;
+                }
                 if kill {
                     kill_process_by_pid(tn.node.pid)
                 } else {
@@ -1421,43 +4554,61 @@ fn select_and_quit(
             }
             t => {
                 log::error!("Cannot handle {t:?} in select_and_quit");
-                Err(format!("Cannot handle container of type {t:?}."))
+                Err(format!("Cannot handle container of type {t:?}.").into())
             }
         },
-        Err(err) => Err(err),
+        Err(input) if input.is_empty() => Err(SwayrError::MenuAborted),
+        Err(input) => {
+            Err(format!("Cannot quit non-matching input {input}.").into())
+        }
     }
 }
 
-pub fn quit_window(fdata: &FocusData, kill: bool) -> Result<String, String> {
+pub fn quit_window(
+    fdata: &FocusData,
+    kill: bool,
+    force: bool,
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(true);
-    let tree = t::get_tree(&root);
-    select_and_quit("Quit window", &tree.get_windows(fdata), kill)
+    let tree = t::get_tree(&root).with_format_overrides(formats);
+    select_and_quit("Quit window", &tree.get_windows(fdata), kill, force, fdata)
 }
 
-pub fn quit_workspace_or_window(fdata: &FocusData) -> Result<String, String> {
+pub fn quit_workspace_or_window(
+    fdata: &FocusData,
+    force: bool,
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(true);
-    let tree = t::get_tree(&root);
+    let tree = t::get_tree(&root).with_format_overrides(formats);
     select_and_quit(
         "Quit workspace or window",
         &tree.get_workspaces_and_windows(fdata),
         false,
+        force,
+        fdata,
     )
 }
 
 pub fn quit_workspace_container_or_window(
     fdata: &FocusData,
-) -> Result<String, String> {
+    force: bool,
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(true);
-    let tree = t::get_tree(&root);
+    let tree = t::get_tree(&root).with_format_overrides(formats);
     select_and_quit(
         "Quit workspace, container or window",
         &tree.get_workspaces_containers_and_windows(fdata),
         false,
+        force,
+        fdata,
     )
 }
 
-fn move_focused_to_workspace_1(ws_name: &str) -> Result<String, String> {
-    if DIGIT_AND_NAME.is_match(ws_name) {
+fn move_focused_to_workspace_1(ws_name: &str) -> Result<String, SwayrError> {
+    if is_numbered_workspace(ws_name) {
         run_sway_command(&[
             "move",
             "container",
@@ -1471,7 +4622,7 @@ fn move_focused_to_workspace_1(ws_name: &str) -> Result<String, String> {
     }
 }
 
-fn move_focused_to_container_or_window(id: i64) -> Result<String, String> {
+fn move_focused_to_container_or_window(id: i64) -> Result<String, SwayrError> {
     run_sway_command(&[
         &format!("[con_id={id}"),
         "mark",
@@ -1485,8 +4636,8 @@ fn move_focused_to_container_or_window(id: i64) -> Result<String, String> {
 fn select_and_move_focused_to(
     prompt: &str,
     choices: &[t::DisplayNode],
-) -> Result<String, String> {
-    match util::select_from_menu(prompt, choices) {
+) -> Result<String, SwayrError> {
+    match util::select_candidate_from_menu(prompt, choices) {
         Ok(tn) => match tn.node.get_type() {
             ipc::Type::Output => {
                 if tn.node.is_scratchpad() {
@@ -1510,9 +4661,11 @@ fn select_and_move_focused_to(
             }
             t => {
                 log::error!("Cannot move focused to {t:?}");
-                Err(format!("Cannot move focused to node of type {t:?}."))
+                Err(format!("Cannot move focused to node of type {t:?}.")
+                    .into())
             }
         },
+        Err(input) if input.is_empty() => Err(SwayrError::MenuAborted),
         Err(input) => {
             let ws_name = chop_workspace_shortcut(&input);
             move_focused_to_workspace_1(ws_name)
@@ -1520,28 +4673,201 @@ fn select_and_move_focused_to(
     }
 }
 
-pub fn move_focused_to_workspace(fdata: &FocusData) -> Result<String, String> {
+pub fn move_focused_to_workspace(
+    fdata: &FocusData,
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(true);
-    let tree = t::get_tree(&root);
+    let tree = t::get_tree(&root).with_format_overrides(formats);
     select_and_move_focused_to(
         "Move focused container to workspace",
         &tree.get_workspaces(fdata),
     )
 }
 
-pub fn move_focused_to(fdata: &FocusData) -> Result<String, String> {
+pub fn move_focused_to(
+    fdata: &FocusData,
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(true);
-    let tree = t::get_tree(&root);
+    let tree = t::get_tree(&root)
+        .with_format_overrides(formats)
+        .with_outputs(get_outputs());
     select_and_move_focused_to(
         "Move focused container to workspace or container",
         &tree.get_outputs_workspaces_containers_and_windows(fdata),
     )
 }
 
-pub fn swap_focused_with(fdata: &FocusData) -> Result<String, String> {
-    let root = ipc::get_root_node(true);
+/// Returns the number of the workspace `ws_name`, i.e., the first group of
+/// the `misc.workspace_number_pattern` `re`, or the whole name if it's only a
+/// number.
+fn workspace_number<'a>(
+    re: Option<&Regex>,
+    ws_name: &'a str,
+) -> Option<&'a str> {
+    if !ws_name.is_empty() && ws_name.chars().all(|c| c.is_ascii_digit()) {
+        return Some(ws_name);
+    }
+    let caps = re?.captures(ws_name)?;
+    caps.get(1).or_else(|| caps.get(0)).map(|m| m.as_str())
+}
+
+/// Returns the name of workspace `old` after renaming it to `new`.  If `new`
+/// has no number according to `re` but `old` has, that number is kept
+/// together with the separator of `old`, e.g., renaming `3:mail` to `web`
+/// results in `3:web`, or `12_mail` to `web` results in `12_web` with a
+/// pattern allowing `_`.
+fn workspace_rename_target(re: Option<&Regex>, old: &str, new: &str) -> String {
+    if workspace_number(re, new).is_some() {
+        return new.to_owned();
+    }
+    let Some(num) = workspace_number(re, old) else {
+        return new.to_owned();
+    };
+    let old_sep = re
+        .and_then(|re| re.captures(old))
+        .and_then(|caps| Some(&old[caps.get(1)?.end()..caps.get(2)?.start()]));
+    match old_sep {
+        Some(sep) => format!("{num}{sep}{new}"),
+        // The old name is just the number, so use a separator the pattern
+        // accepts.
+        None => [":", "_", "-", " "]
+            .iter()
+            .map(|sep| format!("{num}{sep}{new}"))
+            .find(|name| re.is_some_and(|re| re.is_match(name)))
+            .unwrap_or_else(|| format!("{num}:{new}")),
+    }
+}
+
+#[test]
+fn test_workspace_rename_target() {
+    let default =
+        workspace_number_regex(cfg::DEFAULT_WORKSPACE_NUMBER_PATTERN).unwrap();
+    let rename = |old, new| workspace_rename_target(Some(&default), old, new);
+    assert_eq!(rename("3:mail", "web"), "3:web");
+    assert_eq!(rename("3", "web"), "3:web");
+    assert_eq!(rename("3:mail", "4:web"), "4:web");
+    assert_eq!(rename("3:mail", "12"), "12");
+    assert_eq!(rename("mail", "web"), "web");
+
+    let two_digits = workspace_number_regex(r"^([0-9]{1,2})[:_-](.*)").unwrap();
+    let rename =
+        |old, new| workspace_rename_target(Some(&two_digits), old, new);
+    assert_eq!(rename("12_mail", "web"), "12_web");
+    assert_eq!(rename("7-mail", "web"), "7-web");
+    assert_eq!(rename("12_mail", "13_web"), "13_web");
+    assert_eq!(rename("12", "web"), "12:web");
+
+    let underscore = workspace_number_regex(r"^(\d+)_(.*)").unwrap();
+    assert_eq!(
+        workspace_rename_target(Some(&underscore), "12", "web"),
+        "12_web"
+    );
+    assert_eq!(workspace_rename_target(None, "3:mail", "web"), "web");
+    assert_eq!(workspace_number(Some(&two_digits), "12_mail"), Some("12"));
+    assert_eq!(workspace_number(None, "12_mail"), None);
+}
+
+fn rename_workspace_1(old: &str, new: &str) -> Result<String, SwayrError> {
+    let new = workspace_rename_target(DIGIT_AND_NAME.as_ref(), old, new);
+    if new == old {
+        return Ok(String::new());
+    }
+    run_sway_command(&[
+        "rename workspace",
+        &layout::sway_quote(old),
+        "to",
+        &layout::sway_quote(&new),
+    ])
+}
+
+pub fn rename_workspace_interactively(
+    fdata: &FocusData,
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(false);
+    let tree = t::get_tree(&root).with_format_overrides(formats);
+    let mut old = tree
+        .get_current_workspace()
+        .map(|ws| ws.get_name().to_owned())
+        .ok_or_else(|| {
+            SwayrError::NoMatch("No workspace is focused.".to_owned())
+        })?;
+    let workspaces = tree.get_workspaces(fdata);
+    loop {
+        match util::select_from_menu(
+            &format!("Rename workspace {old} to"),
+            &workspaces,
+        ) {
+            Ok(ws) => old = ws.node.get_name().to_owned(),
+            Err(input) if input.is_empty() => {
+                return Err(SwayrError::MenuAborted)
+            }
+            Err(input) => return rename_workspace_1(&old, &input),
+        }
+    }
+}
+
+pub fn rename_workspace(
+    fdata: &FocusData,
+    from_criteria: &str,
+    to_template: &str,
+) -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(false);
     let tree = t::get_tree(&root);
-    match util::select_from_menu(
+    let wins = tree.get_windows(fdata);
+    let crit = criteria::parse_criteria(from_criteria)?;
+    let pred = criteria::criterion_to_predicate(&crit, &wins);
+    let mut workspaces: Vec<&s::Node> = vec![];
+    for win in wins.iter().filter(|w| pred(w)) {
+        if let Some(ws) =
+            tree.get_parent_node_of_type(win.node.id, ipc::Type::Workspace)
+        {
+            if !workspaces.iter().any(|w| w.id == ws.id) {
+                workspaces.push(ws);
+            }
+        }
+    }
+    if workspaces.is_empty() {
+        return Err(SwayrError::NoMatch(format!(
+            "No window matches {from_criteria}."
+        )));
+    }
+
+    for ws in workspaces {
+        let old = ws.get_name();
+        let mut app_names: Vec<&str> = vec![];
+        for win in ws.nodes_of_type(ipc::Type::Window) {
+            let app_name = win.get_app_name();
+            if !app_names.contains(&app_name) {
+                app_names.push(app_name);
+            }
+        }
+        let new: String = subst_placeholders!(to_template, false, {
+            "name" => old,
+            "number" => workspace_number(DIGIT_AND_NAME.as_ref(), old)
+                .unwrap_or_default(),
+            "app_names" => app_names.join(", "),
+        });
+        if new.is_empty() {
+            return Err(format!(
+                "The template {to_template} gives an empty name for {old}."
+            )
+            .into());
+        }
+        rename_workspace_1(old, &new)?;
+    }
+    Ok(String::new())
+}
+
+pub fn swap_focused_with(
+    fdata: &FocusData,
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(true);
+    let tree = t::get_tree(&root).with_format_overrides(formats);
+    match util::select_candidate_from_menu(
         "Swap focused with",
         &tree.get_workspaces_containers_and_windows(fdata),
     ) {
@@ -1558,10 +4884,10 @@ pub fn swap_focused_with(fdata: &FocusData) -> Result<String, String> {
             t => {
                 let msg = format!("Cannot swap with container of type {t:?}.");
                 log::error!("{msg}");
-                Err(msg)
+                Err(msg.into())
             }
         },
-        Err(_) => Err("No swap target selected from menu.".to_owned()),
+        Err(_) => Err(SwayrError::MenuAborted),
     }
 }
 
@@ -1573,14 +4899,21 @@ pub enum Direction {
 fn focus_window_in_direction_1(
     wins: &[t::DisplayNode],
     dir: Direction,
+    count: usize,
     fdata: &FocusData,
     pred: impl Fn(&t::DisplayNode) -> bool,
-) -> Result<String, String> {
+) -> Result<String, SwayrError> {
+    if count == 0 {
+        return Err(SwayrError::DaemonError(
+            "count must be at least 1.".to_owned(),
+        ));
+    }
+
     let mut wins: Vec<&t::DisplayNode> =
         wins.iter().filter(|w| pred(w)).collect();
 
     if wins.is_empty() {
-        return Err("No matching windows.".to_owned());
+        return Err(SwayrError::NoMatch("No matching windows.".to_owned()));
     }
 
     wins.sort_by(|a, b| {
@@ -1589,73 +4922,127 @@ fn focus_window_in_direction_1(
         lru_a.cmp(&lru_b).reverse()
     });
 
-    let is_focused_window: Box<dyn Fn(&t::DisplayNode) -> bool> =
-        if !wins.iter().any(|w| w.node.focused) {
-            let last_focused_win_id = wins.first().unwrap().node.id;
-            Box::new(move |dn| dn.node.id == last_focused_win_id)
-        } else {
-            Box::new(|dn| dn.node.focused)
-        };
-
-    let mut iter: Box<dyn Iterator<Item = &&t::DisplayNode>> = match dir {
-        Direction::Forward => Box::new(wins.iter().rev().cycle()),
-        Direction::Backward => Box::new(wins.iter().cycle()),
-    };
+    // If no matching window is focused, the most recently focused one is
+    // taken as the starting point.
+    let current = wins.iter().position(|w| w.node.focused).unwrap_or(0);
+    let win = wins[step_in_lru_order(wins.len(), current, dir, count)];
+    focus_window_by_id(win.node.id)
+}
 
-    loop {
-        let win = iter.next().unwrap();
-        if is_focused_window(win) {
-            let win = iter.next().unwrap();
-            return focus_window_by_id(win.node.id);
-        }
+/// Returns the index `count` steps away from `current` in a list of `len`
+/// windows sorted by descending recency, wrapping around at both ends.
+/// Forward moves towards more recently used windows, backward towards less
+/// recently used ones.
+fn step_in_lru_order(
+    len: usize,
+    current: usize,
+    dir: Direction,
+    count: usize,
+) -> usize {
+    let step = count % len;
+    match dir {
+        Direction::Forward => (current + len - step) % len,
+        Direction::Backward => (current + step) % len,
     }
 }
 
+#[test]
+fn test_step_in_lru_order() {
+    // Index 0 is the most recently used window.
+    assert_eq!(step_in_lru_order(4, 2, Direction::Forward, 1), 1);
+    assert_eq!(step_in_lru_order(4, 2, Direction::Forward, 2), 0);
+    assert_eq!(step_in_lru_order(4, 2, Direction::Forward, 3), 3);
+    assert_eq!(step_in_lru_order(4, 0, Direction::Forward, 1), 3);
+    assert_eq!(step_in_lru_order(4, 2, Direction::Backward, 1), 3);
+    assert_eq!(step_in_lru_order(4, 2, Direction::Backward, 2), 0);
+    assert_eq!(step_in_lru_order(4, 3, Direction::Backward, 1), 0);
+    // Counts larger than the number of windows wrap around repeatedly.
+    assert_eq!(step_in_lru_order(4, 1, Direction::Backward, 9), 2);
+    assert_eq!(step_in_lru_order(4, 1, Direction::Forward, 9), 0);
+    assert_eq!(step_in_lru_order(1, 0, Direction::Forward, 5), 0);
+}
+
+/// Used by `next-matching-window` and `prev-matching-window` which have no
+/// `--count` option and thus always move one matching window.
 fn focus_matching_window_in_direction(
     dir: Direction,
     criteria: &str,
     fdata: &FocusData,
-) -> Result<String, String> {
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(false);
     let tree = t::get_tree(&root);
     let wins = tree.get_windows(fdata);
 
     let crits = criteria::parse_criteria(criteria)?;
     let pred = criteria::criterion_to_predicate(&crits, &wins);
-    focus_window_in_direction_1(&wins, dir, fdata, pred)
+    focus_window_in_direction_1(&wins, dir, 1, fdata, pred)
 }
 
 pub fn focus_window_in_direction(
     dir: Direction,
     consider_wins: &ConsiderWindows,
+    matching: Option<&str>,
+    count: usize,
     fdata: &FocusData,
     pred: impl Fn(&t::DisplayNode) -> bool,
-) -> Result<String, String> {
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(false);
     let tree = t::get_tree(&root);
     let mut wins = tree.get_windows(fdata);
 
-    if consider_wins == &ConsiderWindows::CurrentWorkspace {
-        if let Some(cur_ws) = tree.get_current_workspace() {
-            wins.retain(|w| {
-                tree.get_parent_node_of_type(w.node.id, ipc::Type::Workspace)
+    if let Some(criteria) = matching {
+        let crits = criteria::parse_criteria(criteria)?;
+        let matching_ids: HashSet<i64> = {
+            let matches = criteria::criterion_to_predicate(&crits, &wins);
+            wins.iter()
+                .filter(|w| matches(w))
+                .map(|w| w.node.id)
+                .collect()
+        };
+        wins.retain(|w| matching_ids.contains(&w.node.id));
+    }
+
+    match consider_wins {
+        ConsiderWindows::AllWorkspaces => (),
+        ConsiderWindows::CurrentWorkspace => {
+            if let Some(cur_ws) = tree.get_current_workspace() {
+                wins.retain(|w| {
+                    tree.get_parent_node_of_type(
+                        w.node.id,
+                        ipc::Type::Workspace,
+                    )
                     .unwrap()
-                    .id
-                    == cur_ws.id
+                    .id == cur_ws.id
+                });
+            } else {
+                return Err("No current workspace!".into());
+            };
+        }
+        ConsiderWindows::CurrentOutput => {
+            let cur_output_id =
+                match tree.get_current_workspace().and_then(|ws| {
+                    tree.get_parent_node_of_type(ws.id, ipc::Type::Output)
+                }) {
+                    Some(output) => output.id,
+                    None => return Err("No current output!".into()),
+                };
+            wins.retain(|w| {
+                tree.get_parent_node_of_type(w.node.id, ipc::Type::Output)
+                    .is_some_and(|o| o.id == cur_output_id)
             });
-        } else {
-            return Err("No current workspace!".to_owned());
-        };
+        }
     }
 
-    focus_window_in_direction_1(&wins, dir, fdata, pred)
+    focus_window_in_direction_1(&wins, dir, count, fdata, pred)
 }
 
 pub fn focus_window_of_same_layout_in_direction(
     dir: Direction,
     consider_wins: &ConsiderWindows,
+    matching: Option<&str>,
+    count: usize,
     fdata: &FocusData,
-) -> Result<String, String> {
+) -> Result<String, SwayrError> {
     let root = ipc::get_root_node(false);
     let tree = t::get_tree(&root);
     let wins = tree.get_windows(fdata);
@@ -1665,6 +5052,8 @@ pub fn focus_window_of_same_layout_in_direction(
         Some(cur_win) => focus_window_in_direction(
             dir,
             consider_wins,
+            matching,
+            count,
             fdata,
             if cur_win.node.is_floating() {
                 |dn: &t::DisplayNode| dn.node.is_floating()
@@ -1690,86 +5079,155 @@ pub fn focus_window_of_same_layout_in_direction(
                 always_true
             },
         ),
-        None => Err("There's no focused window.".to_owned()),
+        None => Err("There's no focused window.".into()),
     }
 }
 
+/// Used by `next-window-in-container` and `prev-window-in-container` which
+/// have no `--count` option and thus always move one window.
+fn focus_window_in_container_in_direction(
+    dir: Direction,
+    fdata: &FocusData,
+) -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(false);
+    let tree = t::get_tree(&root);
+    let wins = tree.get_windows(fdata);
+    let cur_win = match wins.iter().find(|w| w.node.focused) {
+        Some(w) => w,
+        None => return Err("There's no focused window.".into()),
+    };
+    let parent_id = match tree.get_parent_node(cur_win.node.id) {
+        Some(p) => p.id,
+        None => return Err("The focused window has no parent.".into()),
+    };
+    let floating = cur_win.node.is_floating();
+
+    // Floating and tiled windows may share the workspace as their parent, so
+    // only consider siblings of the same kind.
+    focus_window_in_direction_1(&wins, dir, 1, fdata, |dn: &t::DisplayNode| {
+        dn.node.is_floating() == floating
+            && tree.get_parent_node(dn.node.id).map(|p| p.id) == Some(parent_id)
+    })
+}
+
 fn tile_current_workspace(
     floating: &ConsiderFloating,
     shuffle: bool,
-) -> Result<String, String> {
+) -> Result<String, SwayrError> {
     layout::relayout_current_workspace(
         floating == &ConsiderFloating::IncludeFloating,
-        move |wins, con: &mut s::Connection| {
-            con.run_command("focus parent")?;
-            con.run_command("layout splith")?;
-
-            let mut placed_wins = vec![];
-            let mut rng = rand::thread_rng();
-            if shuffle {
-                wins.shuffle(&mut rng);
-            } else {
-                wins.reverse()
-            }
-            for win in wins {
-                if win.is_floating() {
-                    con.run_command(format!(
-                        "[con_id={}] floating disable",
-                        win.id
-                    ))?;
-                }
-                std::thread::sleep(std::time::Duration::from_millis(25));
-                con.run_command(format!(
-                    "[con_id={}] move to workspace current",
-                    win.id
-                ))?;
-                placed_wins.push(win);
-                if shuffle {
-                    std::thread::sleep(std::time::Duration::from_millis(25));
-                    if let Some(win) = placed_wins.choose(&mut rng) {
-                        con.run_command(format!("[con_id={}] focus", win.id))?;
-                    }
-                }
-            }
-            Ok(())
-        },
+        move |wins| tile_commands(wins, shuffle, &mut rand::thread_rng()),
     )
+    .map_err(SwayrError::from)
 }
 
-fn tab_current_workspace(
-    floating: &ConsiderFloating,
-) -> Result<String, String> {
-    layout::relayout_current_workspace(
-        floating == &ConsiderFloating::IncludeFloating,
-        move |wins, con: &mut s::Connection| {
-            con.run_command("focus parent")?;
-            con.run_command("layout tabbed")?;
-
-            let mut placed_wins = vec![];
-            wins.reverse();
-            for win in wins {
-                if win.is_floating() {
-                    con.run_command(format!(
-                        "[con_id={}] floating disable",
-                        win.id
-                    ))?;
-                }
+/// Returns the commands moving `wins` back from the temporary workspace into
+/// a horizontally split layout, in random order if `shuffle` is true.
+fn tile_commands(
+    wins: &mut [&s::Node],
+    shuffle: bool,
+    rng: &mut impl rand::Rng,
+) -> Vec<String> {
+    let mut cmds = vec!["focus parent".to_owned(), "layout splith".to_owned()];
 
-                std::thread::sleep(std::time::Duration::from_millis(25));
-                con.run_command(format!(
-                    "[con_id={}] move to workspace current",
-                    win.id
-                ))?;
-                placed_wins.push(win);
+    let mut placed_wins = vec![];
+    if shuffle {
+        wins.shuffle(rng);
+    } else {
+        wins.reverse()
+    }
+    for win in wins {
+        if win.is_floating() {
+            cmds.push(format!("[con_id={}] floating disable", win.id));
+        }
+        cmds.push(format!("[con_id={}] move to workspace current", win.id));
+        placed_wins.push(win);
+        if shuffle {
+            if let Some(win) = placed_wins.choose(rng) {
+                cmds.push(format!("[con_id={}] focus", win.id));
             }
-            Ok(())
-        },
+        }
+    }
+    cmds
+}
+
+fn tab_current_workspace(
+    floating: &ConsiderFloating,
+) -> Result<String, SwayrError> {
+    layout::relayout_current_workspace(
+        floating == &ConsiderFloating::IncludeFloating,
+        tab_commands,
     )
+    .map_err(SwayrError::from)
+}
+
+/// Returns the commands moving `wins` back from the temporary workspace into
+/// a tabbed layout.
+fn tab_commands(wins: &mut [&s::Node]) -> Vec<String> {
+    let mut cmds = vec!["focus parent".to_owned(), "layout tabbed".to_owned()];
+
+    wins.reverse();
+    for win in wins {
+        if win.is_floating() {
+            cmds.push(format!("[con_id={}] floating disable", win.id));
+        }
+        cmds.push(format!("[con_id={}] move to workspace current", win.id));
+    }
+    cmds
+}
+
+#[test]
+fn test_tile_and_tab_commands() {
+    use rand::SeedableRng;
+
+    let root: s::Node =
+        serde_json::from_str(include_str!("../tests/fixtures/tree.json"))
+            .unwrap();
+    // Windows 10 and 21 are tiled, 22 is floating.
+    let wins: Vec<&s::Node> = [10, 21, 22]
+        .iter()
+        .map(|id| root.iter().find(|n| n.id == *id).unwrap())
+        .collect();
+
+    assert_eq!(
+        vec![
+            "focus parent",
+            "layout tabbed",
+            "[con_id=22] floating disable",
+            "[con_id=22] move to workspace current",
+            "[con_id=21] move to workspace current",
+            "[con_id=10] move to workspace current",
+        ],
+        tab_commands(&mut wins.clone())
+    );
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    assert_eq!(
+        vec![
+            "focus parent",
+            "layout splith",
+            "[con_id=22] floating disable",
+            "[con_id=22] move to workspace current",
+            "[con_id=21] move to workspace current",
+            "[con_id=10] move to workspace current",
+        ],
+        tile_commands(&mut wins.clone(), false, &mut rng)
+    );
+
+    // Shuffling moves every window once and focuses a placed window after
+    // each move.
+    let cmds = tile_commands(&mut wins.clone(), true, &mut rng);
+    assert_eq!(["focus parent", "layout splith"], cmds[..2]);
+    for id in [10, 21, 22] {
+        let mv = format!("[con_id={id}] move to workspace current");
+        assert_eq!(1, cmds.iter().filter(|c| **c == mv).count());
+    }
+    assert_eq!(3, cmds.iter().filter(|c| c.ends_with("] focus")).count());
 }
 
 fn toggle_tab_tile_current_workspace(
     floating: &ConsiderFloating,
-) -> Result<String, String> {
+) -> Result<String, SwayrError> {
     let tree = ipc::get_root_node(false);
     let workspaces = tree.nodes_of_type(ipc::Type::Workspace);
     if let Some(cur_ws) = workspaces.iter().find(|w| w.is_current()) {
@@ -1779,8 +5237,115 @@ fn toggle_tab_tile_current_workspace(
             tab_current_workspace(floating)
         }
     } else {
-        Err("No current workspace!".to_owned())
+        Err("No current workspace!".into())
+    }
+}
+
+fn spread_current_workspace(
+    fdata: &FocusData,
+    n: usize,
+) -> Result<String, SwayrError> {
+    if n == 0 {
+        return Err("Cannot spread across zero workspaces.".into());
+    }
+    let root = ipc::get_root_node(false);
+    let workspaces = root.nodes_of_type(ipc::Type::Workspace);
+    let cur_ws = workspaces
+        .iter()
+        .find(|w| w.is_current())
+        .ok_or_else(|| "No current workspace!".to_owned())?;
+
+    let mut wins = cur_ws.nodes_of_type(ipc::Type::Window);
+    wins.sort_by_key(|w| std::cmp::Reverse(fdata.last_focus_tick(w.id)));
+
+    // Workspaces only exist while they have windows or are focused, so any
+    // number not used by an existing workspace denotes an empty one.
+    let used_nums: Vec<i32> = workspaces.iter().filter_map(|w| w.num).collect();
+    let target_nums: Vec<i32> = (cur_ws.num.unwrap_or(0).max(0) + 1..)
+        .filter(|num| !used_nums.contains(num))
+        .take(n)
+        .collect();
+
+    let mut moved = 0;
+    for (i, win) in wins.iter().enumerate() {
+        // Slot 0 is the current workspace, so the most recently used window
+        // stays where it is.
+        let slot = i % (n + 1);
+        if slot == 0 {
+            continue;
+        }
+        run_sway_command(&[
+            &format!("[con_id={}]", win.id),
+            "move to workspace number",
+            &target_nums[slot - 1].to_string(),
+        ])?;
+        moved += 1;
+    }
+
+    Ok(format!(
+        "Spread {moved} windows of workspace {} across workspaces {:?}.",
+        cur_ws.get_name(),
+        target_nums
+    ))
+}
+
+fn gather_app(
+    fdata: &FocusData,
+    name_or_criteria: &str,
+    workspace: Option<&str>,
+    tab: bool,
+) -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(false);
+    let tree = t::get_tree(&root);
+    let wins = tree.get_windows(fdata);
+    let ws_name = match workspace {
+        Some(ws_name) => ws_name.to_owned(),
+        None => tree
+            .get_current_workspace()
+            .ok_or_else(|| "No current workspace!".to_owned())?
+            .get_name()
+            .to_owned(),
+    };
+
+    let matching: Vec<&t::DisplayNode> = if name_or_criteria.starts_with('[') {
+        let crit = criteria::parse_criteria(name_or_criteria)?;
+        let pred = criteria::criterion_to_predicate(&crit, &wins);
+        wins.iter().filter(|w| pred(w)).collect()
+    } else {
+        wins.iter()
+            .filter(|w| w.node.get_app_name() == name_or_criteria)
+            .collect()
+    };
+    if matching.is_empty() {
+        return Err(SwayrError::NoMatch(format!(
+            "No window matches {name_or_criteria}."
+        )));
+    }
+
+    for win in &matching {
+        move_to_workspace_by_id(win.node.id, &ws_name)?;
+    }
+
+    if tab {
+        // Tabbing works on the current workspace, so restore the focus
+        // afterwards.
+        let focused_win = root
+            .iter()
+            .find(|n| n.focused && n.get_type() == ipc::Type::Window);
+        let cur_ws = tree.get_current_workspace().map(|ws| ws.get_name());
+        create_workspace(&ws_name)?;
+        tab_current_workspace(&ConsiderFloating::ExcludeFloating)?;
+        if let Some(win) = focused_win {
+            focus_window_by_id(win.id)?;
+        } else if let Some(cur_ws) = cur_ws {
+            create_workspace(cur_ws)?;
+        }
     }
+
+    Ok(format!(
+        "Gathered {} windows on workspace {ws_name}.",
+        matching.len()
+    ))
 }
 
 fn get_swaymsg_commands() -> Vec<SwaymsgCmd> {
@@ -1910,23 +5475,1000 @@ impl DisplayFormat for SwaymsgCmd {
     }
 }
 
-pub fn exec_swaymsg_command() -> Result<String, String> {
+/// Replaces the placeholders `{focused_id}`, `{focused_workspace}`, and
+/// `{selected_window_id}` in the swaymsg command `cmd`.  The latter lets the
+/// user select the window using the menu program.
+fn expand_swaymsg_command(
+    cmd: &str,
+    fdata: &FocusData,
+) -> Result<String, SwayrError> {
+    let mut cmd = cmd.to_owned();
+    if cmd.contains("{focused_id}") || cmd.contains("{focused_workspace}") {
+        let root = ipc::get_root_node(false);
+        if cmd.contains("{focused_id}") {
+            let focused = root.iter().find(|n| n.focused).ok_or_else(|| {
+                SwayrError::NoMatch("Nothing is focused.".to_owned())
+            })?;
+            cmd = cmd.replace("{focused_id}", &focused.id.to_string());
+        }
+        if cmd.contains("{focused_workspace}") {
+            let ws = t::get_tree(&root)
+                .get_current_workspace()
+                .map(|ws| ws.get_name().to_owned())
+                .ok_or_else(|| {
+                    SwayrError::NoMatch("No workspace is focused.".to_owned())
+                })?;
+            cmd = cmd.replace("{focused_workspace}", &ws);
+        }
+    }
+    if cmd.contains("{selected_window_id}") {
+        let root = ipc::get_root_node(false);
+        let tree = t::get_tree(&root);
+        let wins = tree.get_windows(fdata);
+        let id = match util::select_from_menu(
+            &format!("Select window for '{cmd}'"),
+            &wins,
+        ) {
+            Ok(win) => win.node.id,
+            Err(_) => return Err(SwayrError::MenuAborted),
+        };
+        cmd = cmd.replace("{selected_window_id}", &id.to_string());
+    }
+    Ok(cmd)
+}
+
+pub fn exec_swaymsg_command(fdata: &FocusData) -> Result<String, SwayrError> {
     let cmds = get_swaymsg_commands();
     let cmd = util::select_from_menu("Execute swaymsg command", &cmds);
     match cmd {
-        Ok(cmd) => run_sway_command_1(&cmd.cmd),
+        Ok(cmd) => {
+            run_sway_command_1(&expand_swaymsg_command(&cmd.cmd, fdata)?)
+        }
         Err(cmd) if !cmd.is_empty() => {
             let cmd = chop_sway_shortcut(&cmd);
-            run_sway_command_1(cmd)
+            run_sway_command_1(&expand_swaymsg_command(cmd, fdata)?)
+        }
+        Err(_) => Err(SwayrError::MenuAborted),
+    }
+}
+
+struct BindingMode {
+    name: String,
+    active: bool,
+}
+
+impl DisplayFormat for BindingMode {
+    fn format_for_display(&self) -> String {
+        if self.active {
+            format!("{} (active)", self.name)
+        } else {
+            self.name.clone()
+        }
+    }
+
+    fn get_indent_level(&self) -> usize {
+        0
+    }
+}
+
+pub fn switch_binding_mode() -> Result<String, SwayrError> {
+    let mut con = s::Connection::new()
+        .map_err(|e| SwayrError::SwayCommandFailed(e.to_string()))?;
+    let active = con
+        .get_binding_state()
+        .map_err(|e| SwayrError::SwayCommandFailed(e.to_string()))?;
+    let modes: Vec<BindingMode> = con
+        .get_binding_modes()
+        .map_err(|e| SwayrError::SwayCommandFailed(e.to_string()))?
+        .into_iter()
+        .map(|name| BindingMode {
+            active: name == active,
+            name,
+        })
+        .collect();
+
+    match util::select_from_menu("Switch to binding mode", &modes) {
+        Ok(mode) => run_sway_command(&["mode", &format!("\"{}\"", mode.name)]),
+        Err(input) if input.is_empty() => Err(SwayrError::MenuAborted),
+        Err(input) => Err(SwayrError::NoMatch(format!(
+            "No binding mode named {input}."
+        ))),
+    }
+}
+
+/// Window sets are implemented using marks `swayr_set:<name>:<con_id>` because
+/// a sway mark can only be set on one window.
+const WINDOW_SET_MARK_PREFIX: &str = "swayr_set:";
+/// Marks windows which were tiled before their set has been hidden in the
+/// scratchpad (where all windows are floating).
+const WINDOW_SET_TILED_MARK_PREFIX: &str = "swayr_set_tiled:";
+
+static WINDOW_SET_NAME: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[\w.-]+$").unwrap());
+
+fn window_set_mark(name: &str, id: i64) -> String {
+    format!("{WINDOW_SET_MARK_PREFIX}{name}:{id}")
+}
+
+fn check_window_set_name(name: &str) -> Result<(), SwayrError> {
+    if WINDOW_SET_NAME.is_match(name) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid window set name {name:?}.  Only letters, digits, _, ., \
+             and - are allowed."
+        )
+        .into())
+    }
+}
+
+/// Returns the names of the window sets `win` belongs to.
+fn get_window_set_names(win: &s::Node) -> Vec<&str> {
+    win.marks
+        .iter()
+        .filter_map(|m| m.strip_prefix(WINDOW_SET_MARK_PREFIX))
+        .filter_map(|m| m.rsplit_once(':').map(|(name, _)| name))
+        .collect()
+}
+
+/// Returns the windows of all window sets by set name.
+fn get_window_sets(root: &s::Node) -> BTreeMap<String, Vec<&s::Node>> {
+    let mut sets: BTreeMap<String, Vec<&s::Node>> = BTreeMap::new();
+    for win in root.nodes_of_type(ipc::Type::Window) {
+        for name in get_window_set_names(win) {
+            sets.entry(name.to_owned()).or_default().push(win);
+        }
+    }
+    sets
+}
+
+fn get_focused_window(root: &s::Node) -> Result<&s::Node, SwayrError> {
+    root.iter()
+        .find(|n| n.focused && n.get_type() == ipc::Type::Window)
+        .ok_or_else(|| SwayrError::NoMatch("No window is focused.".to_owned()))
+}
+
+pub fn add_focused_to_set(name: &str) -> Result<String, SwayrError> {
+    check_window_set_name(name)?;
+    let root = ipc::get_root_node(false);
+    let win = get_focused_window(&root)?;
+    if get_window_set_names(win).contains(&name) {
+        return Ok(format!("Window {} is already in set {name}.", win.id));
+    }
+    run_sway_command(&[
+        &format!("[con_id={}]", win.id),
+        "mark --add",
+        &window_set_mark(name, win.id),
+    ])
+}
+
+pub fn remove_focused_from_set(name: &str) -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(false);
+    let win = get_focused_window(&root)?;
+    if !get_window_set_names(win).contains(&name) {
+        return Err(SwayrError::NoMatch(format!(
+            "Window {} is not in set {name}.",
+            win.id
+        )));
+    }
+    run_sway_command(&[
+        &format!("[con_id={}]", win.id),
+        "unmark",
+        &window_set_mark(name, win.id),
+    ])
+}
+
+pub fn toggle_set_visibility(name: &str) -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(true);
+    let tree = t::get_tree(&root);
+    let sets = get_window_sets(&root);
+    let wins = sets.get(name).ok_or_else(|| {
+        SwayrError::NoMatch(format!("There is no window set {name}."))
+    })?;
+    let parking = CONFIG.get_window_sets_parking_workspace();
+    let is_hidden = |win: &s::Node| {
+        tree.get_parent_node_of_type(win.id, ipc::Type::Workspace)
+            .is_none_or(|ws| {
+                ws.is_scratchpad() || Some(ws.get_name()) == parking.as_deref()
+            })
+    };
+
+    let mut cmds = vec![];
+    if wins.iter().any(|w| !is_hidden(w)) {
+        for win in wins.iter().filter(|w| !is_hidden(w)) {
+            let con = format!("[con_id={}]", win.id);
+            match &parking {
+                Some(ws) => {
+                    cmds.push(format!("{con} move to workspace \"{ws}\""))
+                }
+                None => {
+                    if !win.is_floating() {
+                        cmds.push(format!(
+                            "{con} mark --add {WINDOW_SET_TILED_MARK_PREFIX}{}",
+                            win.id
+                        ));
+                    }
+                    cmds.push(format!("{con} move scratchpad"));
+                }
+            }
+        }
+    } else {
+        for win in wins {
+            let con = format!("[con_id={}]", win.id);
+            let tiled_mark =
+                format!("{WINDOW_SET_TILED_MARK_PREFIX}{}", win.id);
+            if parking.is_some() {
+                cmds.push(format!("{con} move to workspace current"));
+            } else {
+                cmds.push(format!("{con} scratchpad show"));
+            }
+            if win.marks.contains(&tiled_mark) {
+                cmds.push(format!("{con} floating disable"));
+                cmds.push(format!("{con} unmark {tiled_mark}"));
+            }
+        }
+    }
+    run_sway_command_1(&cmds.join("; "))
+}
+
+enum WindowSetAction {
+    Toggle,
+    AddFocused,
+    RemoveFocused,
+}
+
+struct WindowSetChoice {
+    action: WindowSetAction,
+    name: String,
+    size: usize,
+}
+
+impl DisplayFormat for WindowSetChoice {
+    fn format_for_display(&self) -> String {
+        match self.action {
+            WindowSetAction::Toggle => format!(
+                "Toggle visibility of window set {} ({} windows)",
+                self.name, self.size
+            ),
+            WindowSetAction::AddFocused => {
+                format!("Add focused window to window set {}", self.name)
+            }
+            WindowSetAction::RemoveFocused => {
+                format!("Remove focused window from window set {}", self.name)
+            }
+        }
+    }
+
+    fn get_indent_level(&self) -> usize {
+        0
+    }
+}
+
+pub fn manage_window_sets() -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(true);
+    let focused_sets = get_focused_window(&root)
+        .map(get_window_set_names)
+        .unwrap_or_default();
+    let mut choices = vec![];
+    for (name, wins) in get_window_sets(&root) {
+        let action = if focused_sets.contains(&name.as_str()) {
+            WindowSetAction::RemoveFocused
+        } else {
+            WindowSetAction::AddFocused
+        };
+        choices.push(WindowSetChoice {
+            action: WindowSetAction::Toggle,
+            name: name.clone(),
+            size: wins.len(),
+        });
+        choices.push(WindowSetChoice {
+            action,
+            name,
+            size: wins.len(),
+        });
+    }
+
+    match util::select_from_menu(
+        "Select window set action (or enter a new set name)",
+        &choices,
+    ) {
+        Ok(choice) => match choice.action {
+            WindowSetAction::Toggle => toggle_set_visibility(&choice.name),
+            WindowSetAction::AddFocused => add_focused_to_set(&choice.name),
+            WindowSetAction::RemoveFocused => {
+                remove_focused_from_set(&choice.name)
+            }
+        },
+        Err(input) if input.is_empty() => Err(SwayrError::MenuAborted),
+        Err(input) => add_focused_to_set(input.trim()),
+    }
+}
+
+#[test]
+fn test_get_window_set_names() {
+    let win: s::Node = serde_json::from_value(serde_json::json!({
+        "id": 17, "name": "w", "type": "con", "border": "none",
+        "current_border_width": 0, "layout": "none", "orientation": "none",
+        "percent": null, "urgent": false, "sticky": false,
+        "marks": ["swayr_set:chat:17", "foo", "swayr_set:mail:17",
+                  "swayr_set_tiled:17"],
+        "focused": false, "focus": [], "nodes": [], "floating_nodes": [],
+        "rect": {"x": 0, "y": 0, "width": 0, "height": 0},
+        "window_rect": {"x": 0, "y": 0, "width": 0, "height": 0},
+        "deco_rect": {"x": 0, "y": 0, "width": 0, "height": 0},
+        "geometry": {"x": 0, "y": 0, "width": 0, "height": 0},
+    }))
+    .unwrap();
+    assert_eq!(vec!["chat", "mail"], get_window_set_names(&win));
+}
+
+pub fn select_container_scope(
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(true);
+    let tree = t::get_tree(&root)
+        .with_format_overrides(formats)
+        .with_outputs(get_outputs());
+    // The focused node is usually a window but may also be a container or
+    // workspace after `focus parent`.
+    let focused = match root.iter().find(|n| n.focused) {
+        Some(n) => n,
+        None => return Err("Nothing is focused.".into()),
+    };
+    match util::select_candidate_from_menu(
+        "Select container scope",
+        &tree.get_ancestors(focused.id),
+    ) {
+        Ok(dn) => match dn.node.get_type() {
+            ipc::Type::Output => {
+                run_sway_command(&["focus output", dn.node.get_name()])
+            }
+            _ => focus_window_by_id(dn.node.id),
+        },
+        Err(_) => Err(SwayrError::MenuAborted),
+    }
+}
+
+#[derive(Clone)]
+enum WindowAction {
+    Focus,
+    MoveToWorkspace,
+    ToggleFloating,
+    ToggleFullscreen,
+    Mark,
+    Quit,
+    Kill,
+    CopyTitle,
+    Custom(cfg::WindowAction),
+}
+
+impl DisplayFormat for WindowAction {
+    fn format_for_display(&self) -> String {
+        match self {
+            WindowAction::Focus => "Focus",
+            WindowAction::MoveToWorkspace => "Move to workspace",
+            WindowAction::ToggleFloating => "Toggle floating",
+            WindowAction::ToggleFullscreen => "Toggle fullscreen",
+            WindowAction::Mark => "Toggle mark",
+            WindowAction::Quit => "Quit",
+            WindowAction::Kill => "Kill process",
+            WindowAction::CopyTitle => "Copy title to clipboard (wl-copy)",
+            WindowAction::Custom(action) => &action.name,
+        }
+        .to_owned()
+    }
+
+    fn get_indent_level(&self) -> usize {
+        0
+    }
+}
+
+struct WindowMark(String);
+
+impl DisplayFormat for WindowMark {
+    fn format_for_display(&self) -> String {
+        self.0.clone()
+    }
+
+    fn get_indent_level(&self) -> usize {
+        0
+    }
+}
+
+pub fn window_actions(
+    fdata: &FocusData,
+    formats: &FormatOverrides,
+) -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(true);
+    let tree = t::get_tree(&root).with_format_overrides(formats);
+    let wins = tree.get_windows(fdata);
+    let dn = match util::select_candidate_from_menu("Select window", &wins) {
+        Ok(dn) => dn,
+        Err(_) => return Err(SwayrError::MenuAborted),
+    };
+    let win = dn.node;
+
+    let mut actions = vec![
+        WindowAction::Focus,
+        WindowAction::MoveToWorkspace,
+        WindowAction::ToggleFloating,
+        WindowAction::ToggleFullscreen,
+        WindowAction::Mark,
+        WindowAction::Quit,
+        WindowAction::Kill,
+        WindowAction::CopyTitle,
+    ];
+    // The actions were validated when loading the config.
+    for action in CONFIG.get_window_actions() {
+        if let Ok(c) = criteria::parse_criteria(&action.criteria) {
+            if criteria::criterion_to_predicate(&c, &wins)(dn) {
+                actions.push(WindowAction::Custom(action));
+            }
+        }
+    }
+    let action = match util::select_from_menu(
+        &format!("Action for {}", win.get_name()),
+        &actions,
+    ) {
+        Ok(action) => action.clone(),
+        Err(_) => return Err(SwayrError::MenuAborted),
+    };
+
+    let con = format!("[con_id={}]", win.id);
+    match action {
+        WindowAction::Focus => focus_window_by_id(win.id),
+        WindowAction::MoveToWorkspace => {
+            let ws_name = match util::select_from_menu(
+                "Move to workspace",
+                &tree.get_workspaces(fdata),
+            ) {
+                Ok(ws) if !ws.node.is_scratchpad() => {
+                    ws.node.get_name().to_owned()
+                }
+                Ok(_) => {
+                    return run_sway_command(&[
+                        &con,
+                        "move container to scratchpad",
+                    ])
+                }
+                Err(input) if input.is_empty() => {
+                    return Err(SwayrError::MenuAborted)
+                }
+                Err(input) => chop_workspace_shortcut(&input).to_owned(),
+            };
+            if is_numbered_workspace(&ws_name) {
+                run_sway_command(&[
+                    &con,
+                    "move container to workspace number",
+                    &ws_name,
+                ])
+            } else {
+                run_sway_command(&[
+                    &con,
+                    "move container to workspace",
+                    &ws_name,
+                ])
+            }
+        }
+        WindowAction::ToggleFloating => {
+            run_sway_command(&[&con, "floating toggle"])
+        }
+        WindowAction::ToggleFullscreen => {
+            run_sway_command(&[&con, "fullscreen toggle"])
+        }
+        WindowAction::Mark => {
+            let marks: Vec<WindowMark> =
+                win.marks.iter().map(|m| WindowMark(m.clone())).collect();
+            let mark = match util::select_from_menu(
+                "Remove mark (or enter a new one)",
+                &marks,
+            ) {
+                Ok(WindowMark(mark)) => mark.clone(),
+                Err(input) if input.trim().is_empty() => {
+                    return Err(SwayrError::MenuAborted)
+                }
+                Err(input) => input.trim().to_owned(),
+            };
+            run_sway_command(&[&con, "mark --add --toggle", &mark])
+        }
+        WindowAction::Quit => {
+            confirm_quitting_protected_windows(&[win.id], fdata)?;
+            quit_window_by_id(win.id)
+        }
+        WindowAction::Kill => {
+            confirm_quitting_protected_windows(&[win.id], fdata)?;
+            kill_process_by_pid(win.pid)
+        }
+        WindowAction::CopyTitle => copy_to_clipboard(win.get_name()),
+        WindowAction::Custom(action) => run_custom_window_action(&action, dn),
+    }
+}
+
+/// Runs the custom window `action` for `win` without waiting for it.  The
+/// command is spawned directly rather than through a shell so that
+/// placeholder values like the window title are never interpreted.
+fn run_custom_window_action(
+    action: &cfg::WindowAction,
+    win: &t::DisplayNode,
+) -> Result<String, SwayrError> {
+    let cmd: Vec<String> = action
+        .command
+        .iter()
+        .map(|arg| win.subst_node_placeholders(arg, false))
+        .collect();
+    let Some((program, args)) = cmd.split_first() else {
+        return Err("The window action has no command.".to_owned().into());
+    };
+    let mut child = std::process::Command::new(program)
+        .args(args)
+        .spawn()
+        .map_err(|e| format!("Could not run {program}: {e}"))?;
+    // Reap the child in the background so that it doesn't become a zombie.
+    thread::spawn(move || child.wait());
+    Ok(String::new())
+}
+
+pub fn copy_window_info(
+    fdata: &FocusData,
+    criteria: Option<&String>,
+    format: &str,
+) -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(true);
+    let tree = t::get_tree(&root);
+    let wins = tree.get_windows(fdata);
+    let text = match criteria {
+        Some(_) => {
+            let wins = get_matching_windows(criteria, &wins)?;
+            if wins.is_empty() {
+                return Err(SwayrError::NoMatch(
+                    "No matching windows".to_owned(),
+                ));
+            }
+            wins.iter()
+                .map(|w| w.subst_node_placeholders(format, false))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        None => {
+            match util::select_candidate_from_menu("Copy info of window", &wins)
+            {
+                Ok(win) => win.subst_node_placeholders(format, false),
+                Err(_) => return Err(SwayrError::MenuAborted),
+            }
+        }
+    };
+    copy_to_clipboard(&text)
+}
+
+fn copy_to_clipboard(text: &str) -> Result<String, SwayrError> {
+    use std::io::Write;
+    let mut child = std::process::Command::new("wl-copy")
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Could not run wl-copy: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .map_err(|e| format!("Could not write to wl-copy: {e}"))?;
+    }
+    match child.wait() {
+        Ok(status) if status.success() => {
+            Ok(format!("Copied “{text}” to the clipboard."))
+        }
+        Ok(status) => Err(format!("wl-copy failed with {status}.").into()),
+        Err(e) => Err(format!("Could not wait for wl-copy: {e}").into()),
+    }
+}
+
+pub fn follow_matching(
+    fdata: &FocusData,
+    criteria: Option<&str>,
+    toggle: bool,
+) -> Result<String, SwayrError> {
+    let current = fdata.get_follow_criteria();
+    match criteria {
+        Some(c) if !(toggle && current.as_deref() == Some(c)) => {
+            criteria::parse_criteria(c)?;
+            fdata.set_follow_criteria(Some(c.to_owned()));
+            maybe_follow(fdata, None);
+            Ok(format!("Following windows matching {c}."))
+        }
+        _ => {
+            fdata.set_follow_criteria(None);
+            Ok(match current {
+                Some(c) => format!("Stopped following windows matching {c}."),
+                None => "Not following any windows.".to_owned(),
+            })
+        }
+    }
+}
+
+fn reload_sway(fdata: &FocusData) -> Result<String, SwayrError> {
+    run_sway_command_1("reload")?;
+    // Sway keeps IPC subscriptions across reloads, but check that swayrd's
+    // event loop still receives events by sending a tick through it.
+    let payload = format!(
+        "swayr-reload-{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos())
+    );
+    let events_ok = s::Connection::new()
+        .and_then(|mut con| con.send_tick(&payload))
+        .map_err(|e| log::error!("Could not send tick: {e}"))
+        .is_ok_and(|sent| sent)
+        && fdata.wait_for_sway_tick(&payload, Duration::from_secs(3));
+    let (removed, added) = daemon::reprime_focus_data(fdata);
+    let msg = format!(
+        "Reloaded sway; forgot {removed} vanished and added {added} missed \
+         nodes to the focus data."
+    );
+    if events_ok {
+        Ok(msg)
+    } else {
+        Err(SwayrError::DaemonError(format!(
+            "{msg}  But swayrd didn't receive sway's events within 3 seconds."
+        )))
+    }
+}
+
+fn completions_dynamic(
+    workspaces: bool,
+    marks: bool,
+    app_ids: bool,
+    outputs: bool,
+    commands: bool,
+) -> String {
+    let mut candidates = std::collections::BTreeSet::new();
+    if workspaces || marks || app_ids || outputs {
+        let root = ipc::get_root_node(false);
+        for node in root.iter().filter(|n| !n.is_scratchpad()) {
+            match node.try_get_type() {
+                Some(ipc::Type::Workspace) if workspaces => {
+                    candidates.insert(node.get_name().to_owned());
+                }
+                Some(ipc::Type::Output) if outputs => {
+                    candidates.insert(node.get_name().to_owned());
+                }
+                Some(ipc::Type::Window) if app_ids => {
+                    candidates.insert(node.get_app_name().to_owned());
+                }
+                _ => (),
+            }
+            if marks {
+                candidates.extend(node.marks.iter().cloned());
+            }
         }
-        Err(_) => {
-            Err("No command selected nor manually typed command given."
-                .to_owned())
+    }
+    if commands {
+        use clap::Subcommand;
+        let cmd =
+            SwayrCommand::augment_subcommands(clap::Command::new("swayr"));
+        candidates.extend(
+            cmd.get_subcommands()
+                .filter(|c| !c.is_hide_set())
+                .map(|c| c.get_name().to_owned()),
+        );
+    }
+    candidates.into_iter().collect::<Vec<_>>().join("\n")
+}
+
+fn toggle_do_not_disturb(
+    fdata: &FocusData,
+    clear_urgency: bool,
+) -> Result<String, SwayrError> {
+    if fdata.get_do_not_disturb().is_some() {
+        fdata.set_do_not_disturb(None);
+        return Ok("Do-not-disturb is off.".to_owned());
+    }
+    fdata.set_do_not_disturb(Some(DoNotDisturb { clear_urgency }));
+    if clear_urgency {
+        let root = ipc::get_root_node(false);
+        for win in root
+            .nodes_of_type(ipc::Type::Window)
+            .iter()
+            .filter(|w| w.urgent)
+        {
+            clear_urgency_hint(win.id);
         }
     }
+    Ok("Do-not-disturb is on.".to_owned())
+}
+
+/// Removes the urgency hint of the window with the given `id`.
+pub fn clear_urgency_hint(id: i64) {
+    if let Err(err) =
+        run_sway_command_1(&format!("[con_id={id}] urgent disable"))
+    {
+        log::error!("Could not clear urgency hint of window {id}: {err}");
+    }
 }
 
-pub fn configure_outputs() -> Result<String, String> {
+/// Re-focuses the most recently used window matching the `follow-matching`
+/// criteria unless the window with `focused_id` (or the currently focused one
+/// if `None`) matches them anyway.
+pub fn maybe_follow(fdata: &FocusData, focused_id: Option<i64>) {
+    let Some(criteria) = fdata.get_follow_criteria() else {
+        return;
+    };
+    let ids = layout::matching_window_ids(&[criteria], fdata);
+    let focused_id = focused_id.or_else(|| {
+        let root = ipc::get_root_node(false);
+        root.iter().find(|n| n.focused).map(|n| n.id)
+    });
+    // Our own corrective focus change results in a focus event for a matching
+    // window, so there's no loop unless some other window fights back.
+    if focused_id.is_some_and(|id| ids.contains(&id)) {
+        return;
+    }
+    let Some(target) = ids.iter().max_by_key(|id| fdata.last_focus_tick(**id))
+    else {
+        return;
+    };
+    if !fdata.record_follow_correction() {
+        log::error!("Stopped follow-matching because of a focus fight.");
+        return;
+    }
+    if let Err(err) = focus_window_by_id(*target) {
+        log::error!("Could not re-focus followed window {target}: {err}");
+    }
+}
+
+pub fn save_focus_bookmark(
+    fdata: &FocusData,
+    name: &str,
+) -> Result<String, SwayrError> {
+    let root = ipc::get_root_node(false);
+    let tree = t::get_tree(&root);
+    let win = get_focused_window(&root)?;
+    let parent_name = |typ: ipc::Type| {
+        tree.get_parent_node_of_type(win.id, typ.clone())
+            .map(|n| n.get_name().to_owned())
+            .ok_or_else(|| {
+                SwayrError::from(format!(
+                    "Window {} has no {typ:?} parent.",
+                    win.id
+                ))
+            })
+    };
+    let bookmark = FocusBookmark {
+        output: parent_name(ipc::Type::Output)?,
+        workspace: parent_name(ipc::Type::Workspace)?,
+        window_id: win.id,
+        app_name: win.get_app_name().to_owned(),
+        title: win.get_name().to_owned(),
+        floating_rect: win.is_floating().then_some((
+            win.rect.x,
+            win.rect.y,
+            win.rect.width,
+            win.rect.height,
+        )),
+    };
+    let msg = format!(
+        "Saved window {} on workspace {} of output {} as bookmark {name}.",
+        bookmark.window_id, bookmark.workspace, bookmark.output
+    );
+    fdata.set_focus_bookmark(name, bookmark);
+    Ok(msg)
+}
+
+struct FocusBookmarkChoice {
+    name: String,
+    bookmark: FocusBookmark,
+}
+
+impl DisplayFormat for FocusBookmarkChoice {
+    fn format_for_display(&self) -> String {
+        format!(
+            "{}: {} — “{}” on workspace {} of output {}",
+            self.name,
+            self.bookmark.app_name,
+            self.bookmark.title,
+            self.bookmark.workspace,
+            self.bookmark.output
+        )
+    }
+
+    fn get_indent_level(&self) -> usize {
+        0
+    }
+}
+
+/// Finds the bookmarked window or, if it has been closed, a window of the
+/// same app with the same title or at least of the same app.
+fn find_bookmarked_window<'a>(
+    root: &'a s::Node,
+    bookmark: &FocusBookmark,
+) -> Option<&'a s::Node> {
+    let wins = root.nodes_of_type(ipc::Type::Window);
+    let same_app = |w: &&&s::Node| w.get_app_name() == bookmark.app_name;
+    wins.iter()
+        .find(|w| w.id == bookmark.window_id)
+        .or_else(|| {
+            wins.iter()
+                .filter(same_app)
+                .find(|w| w.get_name() == bookmark.title)
+        })
+        .or_else(|| wins.iter().find(same_app))
+        .copied()
+}
+
+pub fn restore_focus_bookmark(
+    fdata: &FocusData,
+    name: Option<&str>,
+) -> Result<String, SwayrError> {
+    let (name, bookmark) = match name {
+        Some(name) => match fdata.get_focus_bookmark(name) {
+            Some(bookmark) => (name.to_owned(), bookmark),
+            None => {
+                return Err(SwayrError::NoMatch(format!(
+                    "There is no focus bookmark {name}."
+                )))
+            }
+        },
+        None => {
+            let choices: Vec<FocusBookmarkChoice> = fdata
+                .get_focus_bookmarks()
+                .into_iter()
+                .map(|(name, bookmark)| FocusBookmarkChoice { name, bookmark })
+                .collect();
+            match util::select_from_menu("Restore focus bookmark", &choices) {
+                Ok(c) => (c.name.clone(), c.bookmark.clone()),
+                Err(_) => return Err(SwayrError::MenuAborted),
+            }
+        }
+    };
+
+    let root = ipc::get_root_node(false);
+    match find_bookmarked_window(&root, &bookmark) {
+        Some(win) => {
+            let con = format!("[con_id={}]", win.id);
+            let mut cmds = vec![format!("{con} focus")];
+            if let (Some((x, y, w, h)), true) =
+                (bookmark.floating_rect, win.is_floating())
+            {
+                cmds.push(format!("{con} move absolute position {x} {y}"));
+                cmds.push(format!("{con} resize set {w} {h}"));
+            }
+            run_sway_command_1(&cmds.join("; "))
+                .map(|msg| format!("{msg} (Restored bookmark {name}.)"))
+        }
+        // The window is gone, so at least go to the workspace and create it
+        // on the right output if it doesn't exist anymore.
+        None => run_sway_command_1(&format!(
+            "focus output {}; workspace {}",
+            layout::sway_quote(&bookmark.output),
+            layout::sway_quote(&bookmark.workspace)
+        ))
+        .map(|msg| {
+            format!(
+                "{msg} (The window of bookmark {name} doesn't exist anymore.)"
+            )
+        }),
+    }
+}
+
+struct FloatPresetChoice {
+    name: String,
+    preset: cfg::FloatPreset,
+}
+
+impl DisplayFormat for FloatPresetChoice {
+    fn format_for_display(&self) -> String {
+        format!("{}: {}", self.name, self.preset.describe())
+    }
+
+    fn get_indent_level(&self) -> usize {
+        0
+    }
+}
+
+pub fn float_focused_preset(
+    preset: Option<&str>,
+) -> Result<String, SwayrError> {
+    let mut presets = CONFIG.get_float_presets();
+    let preset = match preset {
+        Some(name) => presets.remove(name).ok_or_else(|| {
+            SwayrError::NoMatch(format!("No float preset named {name}."))
+        })?,
+        None => {
+            let mut choices: Vec<FloatPresetChoice> = presets
+                .into_iter()
+                .map(|(name, preset)| FloatPresetChoice { name, preset })
+                .collect();
+            choices.sort_by(|a, b| a.name.cmp(&b.name));
+            match util::select_from_menu("Select float preset", &choices) {
+                Ok(choice) => choice.preset.clone(),
+                Err(input) if input.is_empty() => {
+                    return Err(SwayrError::MenuAborted)
+                }
+                Err(input) => {
+                    return Err(SwayrError::NoMatch(format!(
+                        "No float preset named {input}."
+                    )))
+                }
+            }
+        }
+    };
+    run_sway_command_1(&preset.get_sway_command())
+}
+
+struct TitleFormatChoice(String);
+
+impl DisplayFormat for TitleFormatChoice {
+    fn format_for_display(&self) -> String {
+        self.0.clone()
+    }
+
+    fn get_indent_level(&self) -> usize {
+        0
+    }
+}
+
+pub fn rename_focused_window(
+    title: Option<&str>,
+) -> Result<String, SwayrError> {
+    let title = match title {
+        Some(title) => title.to_owned(),
+        None => {
+            let mut choices = vec![TitleFormatChoice("%title".to_owned())];
+            for (_, format) in CONFIG.get_layout_title_formats() {
+                if !choices.iter().any(|c| c.0 == format) {
+                    choices.push(TitleFormatChoice(format));
+                }
+            }
+            match util::select_from_menu(
+                "Select or enter a title (format)",
+                &choices,
+            ) {
+                Ok(choice) => choice.0.clone(),
+                Err(input) if input.is_empty() => {
+                    return Err(SwayrError::MenuAborted)
+                }
+                Err(input) => input.trim().to_owned(),
+            }
+        }
+    };
+    run_sway_command_1(&format!("title_format {}", layout::sway_quote(&title)))
+}
+
+pub fn apply_appearance_preset(
+    preset: Option<&str>,
+    fdata: &FocusData,
+) -> Result<String, SwayrError> {
+    let mut presets: Vec<SwaymsgCmd> = CONFIG
+        .get_appearance_presets()
+        .into_iter()
+        .map(|(name, cmds)| SwaymsgCmd {
+            label: Some(name),
+            cmd: cmds.join("; "),
+        })
+        .collect();
+    presets.sort_by(|a, b| a.label.cmp(&b.label));
+    let cmd = match preset {
+        Some(name) => presets
+            .into_iter()
+            .find(|p| p.label.as_deref() == Some(name))
+            .map(|p| p.cmd)
+            .ok_or_else(|| {
+                SwayrError::NoMatch(format!("No appearance preset {name}."))
+            })?,
+        None => {
+            match util::select_from_menu("Select appearance preset", &presets) {
+                Ok(p) => p.cmd.clone(),
+                // Like with execute-swaymsg-command, other input is run as
+                // sway command.
+                Err(input) if !input.is_empty() => {
+                    chop_sway_shortcut(&input).to_owned()
+                }
+                Err(_) => return Err(SwayrError::MenuAborted),
+            }
+        }
+    };
+    run_sway_command_1(&expand_swaymsg_command(&cmd, fdata)?)
+}
+
+pub fn configure_outputs() -> Result<String, SwayrError> {
     let outputs = get_outputs();
 
     let mut cmds = vec![];
@@ -1980,8 +6522,8 @@ pub fn configure_outputs() -> Result<String, String> {
             cmd: c,
         })
         .collect();
-    let mut last_cmd_result: Result<String, String> =
-        Err("No output command selected.".to_owned());
+    let mut last_cmd_result: Result<String, SwayrError> =
+        Err(SwayrError::MenuAborted);
     loop {
         match util::select_from_menu("Output command", &cmds) {
             Ok(cmd) => match run_sway_command_1(&cmd.cmd) {
@@ -1998,3 +6540,26 @@ pub fn configure_outputs() -> Result<String, String> {
         }
     }
 }
+
+fn reset_state(
+    fdata: &FocusData,
+    what: &ResettableState,
+    switch_to_matching_data: &mut SwitchToMatchingData,
+) -> Result<String, SwayrError> {
+    let all = *what == ResettableState::All;
+    if all || *what == ResettableState::Lru {
+        fdata.reset_focus_ticks();
+    }
+    if all || *what == ResettableState::Sequence {
+        // The LAST_COMMAND is this one, so the next command starts a new
+        // sequence anyway.
+        switch_to_matching_data.reset(true);
+    }
+    if all || *what == ResettableState::Slots {
+        fdata.clear_focus_bookmarks();
+    }
+    if all || *what == ResettableState::History {
+        fdata.reset_history();
+    }
+    Ok(format!("Reset {what:?}."))
+}
@@ -16,6 +16,7 @@
 //! TOML configuration for swayr.
 
 use crate::shared::cfg;
+use crate::shared::icon;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::time::Duration;
@@ -28,12 +29,51 @@ pub struct Config {
     focus: Option<Focus>,
     misc: Option<Misc>,
     swaymsg_commands: Option<SwaymsgCommands>,
+    float_presets: Option<HashMap<String, FloatPreset>>,
+    appearance_presets: Option<HashMap<String, Vec<String>>>,
+    protect: Option<Protect>,
+    exclude: Option<Exclude>,
+    outputs: Option<Outputs>,
+    remote: Option<Remote>,
+    metrics: Option<Metrics>,
+    lock: Option<Lock>,
+    window_sets: Option<WindowSets>,
+    window_actions: Option<Vec<WindowAction>>,
+    notifications: Option<Notifications>,
+    on_workspace_empty: Option<Hook>,
+    on_focused_workspace_empty: Option<Hook>,
+    on_output_connect: Option<Hook>,
+    on_output_disconnect: Option<Hook>,
+    on_mode_change: Option<Hook>,
+    on_binding: Option<Hook>,
+    /// The sources of the settings keyed by `section.key`.
+    #[serde(skip)]
+    sources: HashMap<String, ConfigSource>,
+}
+
+/// Where the value of a setting comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigSource {
+    File,
+    Env,
+    Default,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Menu {
+    /// A built-in combination of executable and args for a well-known menu
+    /// program.
+    preset: Option<MenuPreset>,
     executable: Option<String>,
     args: Option<Vec<String>>,
+    output: Option<String>,
+    auto_accept_single: Option<bool>,
+    min_entries: Option<usize>,
+    filter_command: Option<Vec<String>>,
+    server: Option<Vec<String>>,
+    /// Seconds after which an unanswered `server` request is given up.
+    server_timeout: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,10 +82,13 @@ pub struct Format {
     workspace_format: Option<String>,
     container_format: Option<String>,
     window_format: Option<String>,
+    app_format: Option<String>,
     indent: Option<String>,
     urgency_start: Option<String>,
     urgency_end: Option<String>,
     html_escape: Option<bool>,
+    /// Sort urgent windows before all others in menus.
+    urgent_first: Option<bool>,
     icon_dirs: Option<Vec<String>>,
     fallback_icon: Option<String>,
 }
@@ -54,11 +97,48 @@ pub struct Format {
 pub struct Layout {
     auto_tile: Option<bool>,
     auto_tile_min_window_width_per_output_width: Option<Vec<[i32; 2]>>,
+    /// Criteria queries of floating windows which are tiled when they appear.
+    auto_unfloat: Option<Vec<String>>,
+    /// Criteria queries of windows never touched by auto_tile and
+    /// auto_unfloat.
+    never_touch: Option<Vec<String>>,
+    /// Title formats applied to matching windows when they appear.
+    title_format: Option<Vec<TitleFormat>>,
+}
+
+/// Windows matching `criteria` get sway's `title_format` set to `format` when
+/// they appear.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TitleFormat {
+    criteria: String,
+    format: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Focus {
     lockin_delay: Option<u64>,
+    /// Focus the most recently used window of a workspace when switching to
+    /// it using swayr.
+    restore_workspace_window: Option<bool>,
+    /// Boosts of windows in the LRU order.
+    boost: Option<Vec<FocusBoost>>,
+    /// End sequences of prev/next window commands when leaving the binding
+    /// mode with this name.
+    end_sequence_on_mode_exit: Option<String>,
+    /// Focus events of windows matching one of these criteria don't change
+    /// the LRU order.
+    ignore: Option<Vec<String>>,
+    /// The number of seconds after which the importance of a window unused
+    /// since then is halved.  Enables sorting by decayed importance.
+    decay_half_life: Option<u64>,
+}
+
+/// Windows matching `criteria` are sorted as if they had been used more
+/// recently by the factor `weight`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FocusBoost {
+    criteria: String,
+    weight: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -68,6 +148,156 @@ pub struct Misc {
 
     /// Inhibit LRU updates during sequences of window cycling commands
     seq_inhibit: Option<bool>,
+
+    /// Maximum number of entries of the workspace back/forward history.
+    workspace_history_depth: Option<usize>,
+
+    /// Maximum number of bytes of stdout and stderr each captured from the
+    /// shell commands of `for-each-window`.
+    for_each_window_max_output: Option<usize>,
+
+    /// The order of the commands offered by `execute-swayr-command`.
+    execute_swayr_command_order: Option<CommandOrder>,
+
+    /// The systemd user unit which `swayr` starts if swayrd isn't running.
+    systemd_unit: Option<String>,
+
+    /// Workspaces whose names match this regex are created and moved to
+    /// using `workspace number`.  The empty string disables that.
+    workspace_number_pattern: Option<String>,
+}
+
+/// The menu programs for which swayr has built-in settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MenuPreset {
+    Wofi,
+    Rofi,
+    Fuzzel,
+    Dmenu,
+}
+
+impl MenuPreset {
+    fn executable(self) -> String {
+        match self {
+            MenuPreset::Wofi => "wofi",
+            MenuPreset::Rofi => "rofi",
+            MenuPreset::Fuzzel => "fuzzel",
+            MenuPreset::Dmenu => "dmenu",
+        }
+        .to_owned()
+    }
+
+    fn args(self) -> Vec<String> {
+        let args: &[&str] = match self {
+            MenuPreset::Wofi => {
+                return Menu::default().args.expect("No menu.args defined.")
+            }
+            MenuPreset::Rofi => &[
+                "-dmenu",
+                "-i",
+                "-markup-rows",
+                "-show-icons",
+                "-p",
+                "{prompt}",
+            ],
+            MenuPreset::Fuzzel => &["--dmenu", "--prompt={prompt}: "],
+            MenuPreset::Dmenu => &["-i", "-l", "20", "-p", "{prompt}"],
+        };
+        args.iter().map(|a| a.to_string()).collect()
+    }
+}
+
+/// How the commands of `execute-swayr-command` are ordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandOrder {
+    /// Most frequently and recently used commands first.
+    Frecency,
+    /// The fixed built-in order.
+    Static,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Protect {
+    /// Criteria queries of windows which are only quit after confirmation.
+    criteria: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Exclude {
+    /// Criteria queries of windows which swayr ignores completely.
+    criteria: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Notifications {
+    /// The command showing a notification where `{message}` is replaced with
+    /// the rendered template.
+    command: Option<Vec<String>>,
+    /// Message templates keyed by swayr command name, e.g.,
+    /// `steal-window`, or `*` for all non-scripting commands.
+    templates: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Outputs {
+    /// Move workspaces back to their preferred output when it reappears.
+    restore_workspaces: Option<bool>,
+    /// Outputs in order of preference which receive the workspaces of a
+    /// disconnected output.
+    fallback: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Remote {
+    /// Whether swayrd also accepts commands over TCP.
+    enabled: Option<bool>,
+    /// The address the TCP listener binds to, e.g., `127.0.0.1:7685`.
+    bind: Option<String>,
+    /// The shared secret which clients must send in order to be allowed to
+    /// run commands which change the sway state.
+    token: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Metrics {
+    /// Whether swayrd collects metrics which are returned by `swayr metrics`.
+    enabled: Option<bool>,
+    /// The address on which the metrics are additionally served over HTTP,
+    /// e.g., `127.0.0.1:9185`.
+    bind: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Lock {
+    /// The names of screen locker processes.  While one of them runs, the
+    /// session is considered locked.
+    processes: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WindowSets {
+    /// The workspace hidden window sets are moved to instead of the
+    /// scratchpad.
+    parking_workspace: Option<String>,
+}
+
+/// Swayr commands run by swayrd when some sway event occurs.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Hook {
+    /// Each command is given as list of arguments, e.g.,
+    /// `["switch-to-urgent-or-lru-window"]`.
+    commands: Option<Vec<Vec<String>>>,
+}
+
+/// A custom action offered by `window-actions` for windows matching
+/// `criteria`.  It runs `command` with the window's placeholders substituted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowAction {
+    pub name: String,
+    pub criteria: String,
+    pub command: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -85,6 +315,106 @@ impl SwaymsgCommands {
     }
 }
 
+/// Size and position of a floating window in percent of its workspace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FloatPreset {
+    width: u32,
+    height: u32,
+    /// The window is centered if `x` or `y` is missing.
+    x: Option<u32>,
+    y: Option<u32>,
+}
+
+impl FloatPreset {
+    fn new(width: u32, height: u32, pos: Option<(u32, u32)>) -> FloatPreset {
+        FloatPreset {
+            width,
+            height,
+            x: pos.map(|p| p.0),
+            y: pos.map(|p| p.1),
+        }
+    }
+
+    /// The sway command floating the focused window and applying the preset.
+    pub fn get_sway_command(&self) -> String {
+        let position = match (self.x, self.y) {
+            (Some(x), Some(y)) => format!("{x} ppt {y} ppt"),
+            _ => "center".to_owned(),
+        };
+        format!(
+            "floating enable, resize set width {} ppt height {} ppt, \
+             move position {position}",
+            self.width, self.height
+        )
+    }
+
+    pub fn describe(&self) -> String {
+        let position = match (self.x, self.y) {
+            (Some(x), Some(y)) => format!("at {x}%, {y}%"),
+            _ => "centered".to_owned(),
+        };
+        format!("{}% × {}% {position}", self.width, self.height)
+    }
+}
+
+fn default_float_presets() -> HashMap<String, FloatPreset> {
+    HashMap::from([
+        ("center-60".to_owned(), FloatPreset::new(60, 60, None)),
+        ("center-80".to_owned(), FloatPreset::new(80, 80, None)),
+        (
+            "left-half".to_owned(),
+            FloatPreset::new(50, 100, Some((0, 0))),
+        ),
+        (
+            "right-half".to_owned(),
+            FloatPreset::new(50, 100, Some((50, 0))),
+        ),
+        (
+            "left-third".to_owned(),
+            FloatPreset::new(33, 100, Some((0, 0))),
+        ),
+        (
+            "right-third".to_owned(),
+            FloatPreset::new(33, 100, Some((67, 0))),
+        ),
+    ])
+}
+
+fn default_appearance_presets() -> HashMap<String, Vec<String>> {
+    let cmds = |cmds: &[&str]| -> Vec<String> {
+        cmds.iter().map(|c| c.to_string()).collect()
+    };
+    HashMap::from([
+        (
+            "compact".to_owned(),
+            cmds(&[
+                "gaps inner all set 0",
+                "gaps outer all set 0",
+                "default_border pixel 1",
+                "[tiling] border pixel 1",
+            ]),
+        ),
+        (
+            "comfortable".to_owned(),
+            cmds(&[
+                "gaps inner all set 10",
+                "gaps outer all set 5",
+                "default_border pixel 2",
+                "[tiling] border pixel 2",
+            ]),
+        ),
+        (
+            "presentation".to_owned(),
+            cmds(&[
+                "gaps inner all set 20",
+                "gaps outer all set 20",
+                "default_border none",
+                "[tiling] border none",
+            ]),
+        ),
+    ])
+}
+
 fn tilde_expand_file_names(file_names: Vec<String>) -> Vec<String> {
     let mut ret = vec![];
     for file_name in file_names {
@@ -102,10 +432,15 @@ fn tilde_expand_file_names(file_names: Vec<String>) -> Vec<String> {
 }
 
 impl Config {
+    pub fn get_menu_preset(&self) -> Option<MenuPreset> {
+        self.menu.as_ref().and_then(|m| m.preset)
+    }
+
     pub fn get_menu_executable(&self) -> String {
         self.menu
             .as_ref()
             .and_then(|m| m.executable.clone())
+            .or_else(|| self.get_menu_preset().map(MenuPreset::executable))
             .or_else(|| Menu::default().executable)
             .expect("No menu.executable defined!")
     }
@@ -114,10 +449,61 @@ impl Config {
         self.menu
             .as_ref()
             .and_then(|m| m.args.clone())
+            .or_else(|| self.get_menu_preset().map(MenuPreset::args))
             .or_else(|| Menu::default().args)
             .expect("No menu.args defined.")
     }
 
+    pub fn get_menu_output(&self) -> Option<String> {
+        self.menu
+            .as_ref()
+            .and_then(|m| m.output.clone())
+            .or_else(|| Menu::default().output)
+            .filter(|o| !o.is_empty())
+    }
+
+    pub fn is_menu_auto_accept_single(&self) -> bool {
+        self.menu
+            .as_ref()
+            .and_then(|m| m.auto_accept_single)
+            .or_else(|| Menu::default().auto_accept_single)
+            .expect("No menu.auto_accept_single defined.")
+    }
+
+    pub fn get_menu_filter_command(&self) -> Option<Vec<String>> {
+        self.menu
+            .as_ref()
+            .and_then(|m| m.filter_command.clone())
+            .or_else(|| Menu::default().filter_command)
+            .filter(|c| !c.is_empty())
+    }
+
+    pub fn get_menu_server(&self) -> Option<Vec<String>> {
+        self.menu
+            .as_ref()
+            .and_then(|m| m.server.clone())
+            .or_else(|| Menu::default().server)
+            .filter(|c| !c.is_empty())
+    }
+
+    pub fn get_menu_server_timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.menu
+                .as_ref()
+                .and_then(|m| m.server_timeout)
+                .or_else(|| Menu::default().server_timeout)
+                .expect("No menu.server_timeout defined."),
+        )
+    }
+
+    pub fn get_menu_min_entries(&self) -> usize {
+        self.menu
+            .as_ref()
+            .and_then(|m| m.min_entries)
+            .or_else(|| Menu::default().min_entries)
+            .expect("No menu.min_entries defined.")
+    }
+
     pub fn get_format_output_format(&self) -> String {
         self.format
             .as_ref()
@@ -150,6 +536,14 @@ impl Config {
             .expect("No format.window_format defined.")
     }
 
+    pub fn get_format_app_format(&self) -> String {
+        self.format
+            .as_ref()
+            .and_then(|f| f.app_format.clone())
+            .or_else(|| Format::default().app_format)
+            .expect("No format.app_format defined.")
+    }
+
     pub fn get_format_indent(&self) -> String {
         self.format
             .as_ref()
@@ -182,6 +576,14 @@ impl Config {
             .expect("No format.html_escape defined.")
     }
 
+    pub fn get_format_urgent_first(&self) -> bool {
+        self.format
+            .as_ref()
+            .and_then(|f| f.urgent_first)
+            .or_else(|| Format::default().urgent_first)
+            .expect("No format.urgent_first defined.")
+    }
+
     pub fn get_format_icon_dirs(&self) -> Vec<String> {
         self.format
             .as_ref()
@@ -215,6 +617,134 @@ impl Config {
             .expect("No layout.auto_tile_min_window_width_per_output_width defined.")
     }
 
+    pub fn get_layout_auto_unfloat(&self) -> Vec<String> {
+        self.layout
+            .as_ref()
+            .and_then(|l| l.auto_unfloat.clone())
+            .or_else(|| Layout::default().auto_unfloat)
+            .unwrap_or_default()
+    }
+
+    pub fn get_layout_never_touch(&self) -> Vec<String> {
+        self.layout
+            .as_ref()
+            .and_then(|l| l.never_touch.clone())
+            .or_else(|| Layout::default().never_touch)
+            .unwrap_or_default()
+    }
+
+    /// Returns the `(criteria, format)` pairs of `layout.title_format`.
+    pub fn get_layout_title_formats(&self) -> Vec<(String, String)> {
+        self.layout
+            .as_ref()
+            .and_then(|l| l.title_format.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|t| (t.criteria, t.format))
+            .collect()
+    }
+
+    pub fn get_protect_criteria(&self) -> Vec<String> {
+        self.protect
+            .as_ref()
+            .and_then(|p| p.criteria.clone())
+            .or_else(|| Protect::default().criteria)
+            .unwrap_or_default()
+    }
+
+    pub fn get_exclude_criteria(&self) -> Vec<String> {
+        self.exclude
+            .as_ref()
+            .and_then(|e| e.criteria.clone())
+            .or_else(|| Exclude::default().criteria)
+            .unwrap_or_default()
+    }
+
+    pub fn is_outputs_restore_workspaces(&self) -> bool {
+        self.outputs
+            .as_ref()
+            .and_then(|o| o.restore_workspaces)
+            .or_else(|| Outputs::default().restore_workspaces)
+            .expect("No outputs.restore_workspaces defined.")
+    }
+
+    pub fn get_outputs_fallback(&self) -> Vec<String> {
+        self.outputs
+            .as_ref()
+            .and_then(|o| o.fallback.clone())
+            .or_else(|| Outputs::default().fallback)
+            .unwrap_or_default()
+    }
+
+    pub fn get_notifications_command(&self) -> Vec<String> {
+        self.notifications
+            .as_ref()
+            .and_then(|n| n.command.clone())
+            .or_else(|| Notifications::default().command)
+            .expect("No notifications.command defined.")
+    }
+
+    pub fn get_notifications_templates(&self) -> HashMap<String, String> {
+        self.notifications
+            .as_ref()
+            .and_then(|n| n.templates.clone())
+            .or_else(|| Notifications::default().templates)
+            .expect("No notifications.templates defined.")
+    }
+
+    pub fn is_remote_enabled(&self) -> bool {
+        self.remote
+            .as_ref()
+            .and_then(|r| r.enabled)
+            .or_else(|| Remote::default().enabled)
+            .expect("No remote.enabled defined.")
+    }
+
+    pub fn get_remote_bind(&self) -> String {
+        self.remote
+            .as_ref()
+            .and_then(|r| r.bind.clone())
+            .or_else(|| Remote::default().bind)
+            .expect("No remote.bind defined.")
+    }
+
+    pub fn get_remote_token(&self) -> Option<String> {
+        self.remote
+            .as_ref()
+            .and_then(|r| r.token.clone())
+            .filter(|t| !t.is_empty())
+    }
+
+    pub fn is_metrics_enabled(&self) -> bool {
+        self.metrics
+            .as_ref()
+            .and_then(|m| m.enabled)
+            .or_else(|| Metrics::default().enabled)
+            .expect("No metrics.enabled defined.")
+    }
+
+    pub fn get_metrics_bind(&self) -> Option<String> {
+        self.metrics
+            .as_ref()
+            .and_then(|m| m.bind.clone())
+            .filter(|b| !b.is_empty())
+    }
+
+    pub fn get_lock_processes(&self) -> Vec<String> {
+        self.lock
+            .as_ref()
+            .and_then(|l| l.processes.clone())
+            .or_else(|| Lock::default().processes)
+            .unwrap_or_default()
+    }
+
+    pub fn get_window_sets_parking_workspace(&self) -> Option<String> {
+        self.window_sets
+            .as_ref()
+            .and_then(|w| w.parking_workspace.clone())
+            .filter(|ws| !ws.is_empty())
+    }
+
     pub fn get_focus_lockin_delay(&self) -> Duration {
         Duration::from_millis(
             self.focus
@@ -225,6 +755,59 @@ impl Config {
         )
     }
 
+    pub fn is_focus_restore_workspace_window(&self) -> bool {
+        self.focus
+            .as_ref()
+            .and_then(|f| f.restore_workspace_window)
+            .or_else(|| Focus::default().restore_workspace_window)
+            .expect("No focus.restore_workspace_window defined.")
+    }
+
+    pub fn get_focus_end_sequence_on_mode_exit(&self) -> Option<String> {
+        self.focus
+            .as_ref()
+            .and_then(|f| f.end_sequence_on_mode_exit.clone())
+    }
+
+    pub fn get_focus_decay_half_life(&self) -> Option<Duration> {
+        self.focus
+            .as_ref()
+            .and_then(|f| f.decay_half_life)
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+    }
+
+    pub fn get_focus_ignore(&self) -> Vec<String> {
+        self.focus
+            .as_ref()
+            .and_then(|f| f.ignore.clone())
+            .or_else(|| Focus::default().ignore)
+            .expect("No focus.ignore defined.")
+    }
+
+    /// Returns the `(criteria, weight)` pairs of `focus.boost` skipping ones
+    /// with non-positive weight.
+    pub fn get_focus_boosts(&self) -> Vec<(String, f64)> {
+        self.focus
+            .as_ref()
+            .and_then(|f| f.boost.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|b| {
+                if b.weight > 0.0 {
+                    true
+                } else {
+                    log::error!(
+                        "Ignoring focus.boost with weight {}.",
+                        b.weight
+                    );
+                    false
+                }
+            })
+            .map(|b| (b.criteria, b.weight))
+            .collect()
+    }
+
     pub fn get_misc_auto_nop_delay(&self) -> Option<Duration> {
         self.misc
             .as_ref()
@@ -232,6 +815,10 @@ impl Config {
             .map(Duration::from_millis)
     }
 
+    pub fn get_misc_systemd_unit(&self) -> Option<String> {
+        self.misc.as_ref().and_then(|m| m.systemd_unit.clone())
+    }
+
     pub fn get_misc_seq_inhibit(&self) -> bool {
         self.misc
             .as_ref()
@@ -240,6 +827,38 @@ impl Config {
             .expect("No misc.seq_inhibit defined.")
     }
 
+    pub fn get_misc_execute_swayr_command_order(&self) -> CommandOrder {
+        self.misc
+            .as_ref()
+            .and_then(|f| f.execute_swayr_command_order)
+            .or_else(|| Misc::default().execute_swayr_command_order)
+            .expect("No misc.execute_swayr_command_order defined.")
+    }
+
+    pub fn get_misc_workspace_number_pattern(&self) -> String {
+        self.misc
+            .as_ref()
+            .and_then(|m| m.workspace_number_pattern.clone())
+            .or_else(|| Misc::default().workspace_number_pattern)
+            .expect("No misc.workspace_number_pattern defined.")
+    }
+
+    pub fn get_misc_workspace_history_depth(&self) -> usize {
+        self.misc
+            .as_ref()
+            .and_then(|m| m.workspace_history_depth)
+            .or_else(|| Misc::default().workspace_history_depth)
+            .expect("No misc.workspace_history_depth defined.")
+    }
+
+    pub fn get_misc_for_each_window_max_output(&self) -> usize {
+        self.misc
+            .as_ref()
+            .and_then(|m| m.for_each_window_max_output)
+            .or_else(|| Misc::default().for_each_window_max_output)
+            .expect("No misc.for_each_window_max_output defined.")
+    }
+
     pub fn get_swaymsg_commands_commands(
         &self,
     ) -> Option<HashMap<String, String>> {
@@ -255,6 +874,52 @@ impl Config {
             |s| s.include_predefined,
         )
     }
+
+    pub fn get_float_presets(&self) -> HashMap<String, FloatPreset> {
+        self.float_presets
+            .clone()
+            .unwrap_or_else(default_float_presets)
+    }
+
+    pub fn get_appearance_presets(&self) -> HashMap<String, Vec<String>> {
+        self.appearance_presets
+            .clone()
+            .unwrap_or_else(default_appearance_presets)
+    }
+
+    pub fn get_on_workspace_empty_commands(&self) -> Vec<Vec<String>> {
+        get_hook_commands(&self.on_workspace_empty)
+    }
+
+    pub fn get_on_focused_workspace_empty_commands(&self) -> Vec<Vec<String>> {
+        get_hook_commands(&self.on_focused_workspace_empty)
+    }
+
+    pub fn get_on_output_connect_commands(&self) -> Vec<Vec<String>> {
+        get_hook_commands(&self.on_output_connect)
+    }
+
+    pub fn get_on_output_disconnect_commands(&self) -> Vec<Vec<String>> {
+        get_hook_commands(&self.on_output_disconnect)
+    }
+
+    pub fn get_on_mode_change_commands(&self) -> Vec<Vec<String>> {
+        get_hook_commands(&self.on_mode_change)
+    }
+
+    pub fn get_on_binding_commands(&self) -> Vec<Vec<String>> {
+        get_hook_commands(&self.on_binding)
+    }
+
+    pub fn get_window_actions(&self) -> Vec<WindowAction> {
+        self.window_actions.clone().unwrap_or_default()
+    }
+}
+
+fn get_hook_commands(hook: &Option<Hook>) -> Vec<Vec<String>> {
+    hook.as_ref()
+        .and_then(|h| h.commands.clone())
+        .unwrap_or_default()
 }
 
 impl Layout {
@@ -276,6 +941,7 @@ impl Layout {
 impl Default for Menu {
     fn default() -> Self {
         Menu {
+            preset: None,
             executable: Some("wofi".to_string()),
             args: Some(vec![
                 "--show=dmenu".to_string(),
@@ -287,6 +953,12 @@ impl Default for Menu {
                 "--height=40%".to_string(),
                 "--prompt={prompt}".to_string(),
             ]),
+            output: None,
+            auto_accept_single: Some(false),
+            min_entries: Some(0),
+            filter_command: None,
+            server: None,
+            server_timeout: Some(60),
         }
     }
 }
@@ -295,7 +967,8 @@ impl Default for Format {
     fn default() -> Self {
         Format {
             output_format: Some(
-                "{indent}<b>Output {name}</b>    \
+                "{indent}<b>Output {name}</b> \
+                 showing workspace {active_workspace}    \
                  <span alpha=\"20000\">({id})</span>"
                     .to_string(),
             ),
@@ -320,6 +993,11 @@ impl Default for Format {
                  <span alpha=\"20000\">({id})</span>"
                     .to_string(),
             ),
+            app_format: Some(
+                "img:{app_icon}:text:{urgency_start}<b>{app_name}</b>\
+                 {urgency_end} <i>({window_count} windows)</i>"
+                    .to_string(),
+            ),
             indent: Some("    ".to_string()),
             html_escape: Some(true),
             urgency_start: Some(
@@ -327,15 +1005,8 @@ impl Default for Format {
                     .to_string(),
             ),
             urgency_end: Some("</span>".to_string()),
-            icon_dirs: Some(vec![
-                "/usr/share/icons/hicolor/scalable/apps".to_string(),
-                "/usr/share/icons/hicolor/128x128/apps".to_string(),
-                "/usr/share/icons/hicolor/64x64/apps".to_string(),
-                "/usr/share/icons/hicolor/48x48/apps".to_string(),
-                "/usr/share/icons/Adwaita/64x64/apps".to_string(),
-                "/usr/share/icons/Adwaita/48x48/apps".to_string(),
-                "/usr/share/pixmaps".to_string(),
-            ]),
+            urgent_first: Some(true),
+            icon_dirs: Some(icon::default_icon_dirs()),
             fallback_icon: None,
         }
     }
@@ -366,6 +1037,9 @@ impl Default for Layout {
             auto_tile_min_window_width_per_output_width: Some(
                 resolution_min_width_vec,
             ),
+            auto_unfloat: None,
+            never_touch: None,
+            title_format: None,
         }
     }
 }
@@ -374,15 +1048,98 @@ impl Default for Focus {
     fn default() -> Self {
         Self {
             lockin_delay: Some(750),
+            restore_workspace_window: Some(false),
+            boost: None,
+            end_sequence_on_mode_exit: None,
+            ignore: Some(vec![]),
+            decay_half_life: None,
         }
     }
 }
 
+pub const DEFAULT_WORKSPACE_NUMBER_PATTERN: &str = r"^(\d):(.*)";
+
 impl Default for Misc {
     fn default() -> Self {
         Self {
             auto_nop_delay: None,
             seq_inhibit: Some(false),
+            workspace_history_depth: Some(50),
+            for_each_window_max_output: Some(64 * 1024),
+            execute_swayr_command_order: Some(CommandOrder::Frecency),
+            systemd_unit: None,
+            workspace_number_pattern: Some(
+                DEFAULT_WORKSPACE_NUMBER_PATTERN.to_owned(),
+            ),
+        }
+    }
+}
+
+impl Default for Protect {
+    fn default() -> Self {
+        Self {
+            criteria: Some(vec![]),
+        }
+    }
+}
+
+impl Default for Exclude {
+    fn default() -> Self {
+        Self {
+            criteria: Some(vec![]),
+        }
+    }
+}
+
+impl Default for Outputs {
+    fn default() -> Self {
+        Self {
+            restore_workspaces: Some(false),
+            fallback: Some(vec![]),
+        }
+    }
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self {
+            command: Some(
+                ["notify-send", "--app-name=swayr", "swayr", "{message}"]
+                    .map(String::from)
+                    .to_vec(),
+            ),
+            templates: Some(HashMap::new()),
+        }
+    }
+}
+
+impl Default for Remote {
+    fn default() -> Self {
+        Self {
+            enabled: Some(false),
+            bind: Some("127.0.0.1:7685".to_owned()),
+            token: None,
+        }
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            enabled: Some(false),
+            bind: None,
+        }
+    }
+}
+
+impl Default for Lock {
+    fn default() -> Self {
+        Self {
+            processes: Some(
+                ["swaylock", "gtklock", "waylock"]
+                    .map(String::from)
+                    .to_vec(),
+            ),
         }
     }
 }
@@ -396,12 +1153,270 @@ impl Default for Config {
             focus: Some(Focus::default()),
             misc: Some(Misc::default()),
             swaymsg_commands: Some(SwaymsgCommands::default()),
+            float_presets: Some(default_float_presets()),
+            appearance_presets: Some(default_appearance_presets()),
+            protect: Some(Protect::default()),
+            exclude: Some(Exclude::default()),
+            outputs: Some(Outputs::default()),
+            remote: Some(Remote::default()),
+            metrics: Some(Metrics::default()),
+            lock: Some(Lock::default()),
+            window_sets: Some(WindowSets::default()),
+            window_actions: None,
+            notifications: Some(Notifications::default()),
+            on_workspace_empty: None,
+            on_focused_workspace_empty: None,
+            on_output_connect: None,
+            on_output_disconnect: None,
+            on_mode_change: None,
+            on_binding: None,
+            sources: HashMap::new(),
         }
     }
 }
 
+/// The hand-maintained JSON schema of the config file.
+pub const CONFIG_SCHEMA: &str = include_str!("../etc/config.schema.json");
+
 pub fn load_config() -> Config {
-    cfg::load_config::<Config>("swayr")
+    let mut cfg = match cfg::load_valid_config::<Config>("swayr") {
+        Some(cfg) => apply_env_overrides(cfg, true),
+        None => apply_env_overrides(Config::default(), false),
+    };
+    retain_valid_window_actions(&mut cfg);
+    cfg
+}
+
+/// Drops the `window_actions` without name or command or with invalid
+/// criteria so that only usable ones are offered.
+fn retain_valid_window_actions(cfg: &mut Config) {
+    if let Some(actions) = cfg.window_actions.as_mut() {
+        actions.retain(|action| match validate_window_action(action) {
+            Ok(()) => true,
+            Err(err) => {
+                log::error!("Ignoring window action “{}”: {err}", action.name);
+                false
+            }
+        });
+    }
+}
+
+fn validate_window_action(action: &WindowAction) -> Result<(), String> {
+    if action.name.trim().is_empty() {
+        return Err("The name is empty.".to_owned());
+    }
+    if action.command.is_empty() {
+        return Err("The command is empty.".to_owned());
+    }
+    crate::criteria::parse_criteria(&action.criteria)?;
+    Ok(())
+}
+
+#[test]
+fn test_retain_valid_window_actions() {
+    let mut cfg: Config = toml::from_str(
+        r#"
+        [[window_actions]]
+        name = "Open in browser profile B"
+        criteria = '[app_id="firefox"]'
+        command = ["firefox", "-P", "B", "{title}"]
+
+        [[window_actions]]
+        name = "Broken"
+        criteria = '[app_id='
+        command = ["true"]
+
+        [[window_actions]]
+        name = "No command"
+        criteria = '[app_id="foot"]'
+        command = []
+        "#,
+    )
+    .unwrap();
+    retain_valid_window_actions(&mut cfg);
+    let names: Vec<String> = cfg
+        .get_window_actions()
+        .into_iter()
+        .map(|a| a.name)
+        .collect();
+    assert_eq!(names, vec!["Open in browser profile B"]);
+}
+
+/// Returns the name of the environment variable overriding `section.key`,
+/// e.g., `SWAYR_MENU_EXECUTABLE` for `menu.executable`.
+fn env_var_name(section: &str, key: &str) -> Option<String> {
+    let name = format!("SWAYR_{section}_{key}").to_uppercase();
+    if name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+/// Parses the value of an environment variable as TOML value falling back to
+/// a plain string, e.g., `false` is a boolean but `wofi` is a string.
+fn parse_env_value(val: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("v = {val}"))
+        .ok()
+        .and_then(|mut t| t.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(val.to_owned()))
+}
+
+fn as_sections(cfg: &Config) -> toml::Table {
+    match toml::Table::try_from(cfg) {
+        Ok(table) => table,
+        Err(err) => {
+            log::error!("Cannot convert config to TOML: {err}");
+            toml::Table::new()
+        }
+    }
+}
+
+/// Settings which have no default value but can be overridden nevertheless.
+const SETTINGS_WITHOUT_DEFAULT: [(&str, &str); 12] = [
+    ("focus", "decay_half_life"),
+    ("focus", "end_sequence_on_mode_exit"),
+    ("format", "fallback_icon"),
+    ("menu", "filter_command"),
+    ("menu", "output"),
+    ("menu", "preset"),
+    ("menu", "server"),
+    ("metrics", "bind"),
+    ("misc", "auto_nop_delay"),
+    ("misc", "systemd_unit"),
+    ("remote", "token"),
+    ("window_sets", "parking_workspace"),
+];
+
+/// Returns all `(section, key)` pairs which may be overridden by environment
+/// variables.
+fn overridable_settings() -> Vec<(String, String)> {
+    let mut settings = vec![];
+    for (section, val) in as_sections(&Config::default()) {
+        if let toml::Value::Table(t) = val {
+            settings.extend(t.keys().map(|key| (section.clone(), key.clone())));
+        }
+    }
+    settings.extend(
+        SETTINGS_WITHOUT_DEFAULT
+            .iter()
+            .map(|(section, key)| (section.to_string(), key.to_string())),
+    );
+    settings
+}
+
+/// Returns a copy of `table` where `section.key` is set to `val` if the result
+/// is a valid config.
+fn with_setting(
+    table: &toml::Table,
+    section: &str,
+    key: &str,
+    val: toml::Value,
+) -> Result<toml::Table, String> {
+    let mut table = table.clone();
+    if let toml::Value::Table(t) = table
+        .entry(section)
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+    {
+        t.insert(key.to_owned(), val);
+    }
+    match table.clone().try_into::<Config>() {
+        Ok(_) => Ok(table),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Applies the `SWAYR_*` environment variable overrides to `cfg` and records
+/// the sources of its settings.  Unless `from_file`, i.e., if the config file
+/// was invalid, the settings not overridden are defaults.
+fn apply_env_overrides(cfg: Config, from_file: bool) -> Config {
+    let mut table = as_sections(&cfg);
+    let mut sources = HashMap::new();
+    if from_file {
+        for (section, val) in &table {
+            if let toml::Value::Table(settings) = val {
+                for key in settings.keys() {
+                    sources
+                        .insert(format!("{section}.{key}"), ConfigSource::File);
+                }
+            }
+        }
+    }
+
+    let mut overridden = false;
+    for (section, key) in overridable_settings() {
+        let Some(var) = env_var_name(&section, &key) else {
+            continue;
+        };
+        let Ok(raw) = std::env::var(&var) else {
+            continue;
+        };
+        // Lists like menu.args may also be given as whitespace-separated
+        // words.
+        let words = toml::Value::Array(
+            raw.split_whitespace()
+                .map(|w| toml::Value::String(w.to_owned()))
+                .collect(),
+        );
+        match with_setting(&table, &section, &key, parse_env_value(&raw))
+            .or_else(|err| {
+                with_setting(&table, &section, &key, words).map_err(|_| err)
+            }) {
+            Ok(t) => {
+                log::debug!("Overriding {section}.{key} with ${var}.");
+                table = t;
+                sources.insert(format!("{section}.{key}"), ConfigSource::Env);
+                overridden = true;
+            }
+            Err(err) => log::error!("Ignoring invalid ${var}: {err}"),
+        }
+    }
+
+    let mut cfg = if overridden {
+        table.try_into::<Config>().unwrap_or(cfg)
+    } else {
+        cfg
+    };
+    cfg.sources = sources;
+    cfg
+}
+
+impl Config {
+    /// Returns all settings including the default ones as a JSON object
+    /// mapping `section.key` to the setting's value and source.
+    pub fn get_settings_with_sources(&self) -> serde_json::Value {
+        let mut effective = as_sections(&Config::default());
+        for (section, val) in as_sections(self) {
+            match (effective.get_mut(&section), val) {
+                (
+                    Some(toml::Value::Table(defaults)),
+                    toml::Value::Table(settings),
+                ) => defaults.extend(settings),
+                (_, val) => {
+                    effective.insert(section, val);
+                }
+            }
+        }
+
+        let mut settings = serde_json::Map::new();
+        for (section, val) in effective {
+            if let toml::Value::Table(t) = val {
+                for (key, val) in t {
+                    let name = format!("{section}.{key}");
+                    let source = self
+                        .sources
+                        .get(&name)
+                        .copied()
+                        .unwrap_or(ConfigSource::Default);
+                    settings.insert(
+                        name,
+                        serde_json::json!({ "value": val, "source": source }),
+                    );
+                }
+            }
+        }
+        serde_json::Value::Object(settings)
+    }
 }
 
 #[test]
@@ -409,3 +1424,78 @@ fn test_load_swayr_config() {
     let cfg = cfg::load_config::<Config>("swayr");
     println!("{:?}", cfg);
 }
+
+#[test]
+fn test_config_sources() {
+    let source = |from_file| {
+        apply_env_overrides(Config::default(), from_file)
+            .get_settings_with_sources()["menu.executable"]["source"]
+            .clone()
+    };
+    assert_eq!("file", source(true));
+    assert_eq!("default", source(false));
+}
+
+#[test]
+fn test_parse_env_value() {
+    assert_eq!(parse_env_value("true"), toml::Value::Boolean(true));
+    assert_eq!(parse_env_value("3000"), toml::Value::Integer(3000));
+    assert_eq!(parse_env_value("wofi"), toml::Value::String("wofi".into()));
+    assert_eq!(
+        parse_env_value(r#"["--show=dmenu", "-i"]"#),
+        toml::Value::Array(vec![
+            toml::Value::String("--show=dmenu".into()),
+            toml::Value::String("-i".into())
+        ])
+    );
+    assert_eq!(
+        env_var_name("menu", "executable").as_deref(),
+        Some("SWAYR_MENU_EXECUTABLE")
+    );
+    assert_eq!(env_var_name("float_presets", "center-60"), None);
+}
+
+#[test]
+fn test_with_setting() {
+    let table = as_sections(&Config::default());
+    assert!(with_setting(
+        &table,
+        "focus",
+        "lockin_delay",
+        parse_env_value("1")
+    )
+    .is_ok());
+    assert!(with_setting(
+        &table,
+        "focus",
+        "lockin_delay",
+        parse_env_value("x")
+    )
+    .is_err());
+    assert!(
+        with_setting(&table, "menu", "args", parse_env_value("-d -i")).is_err()
+    );
+    let t = with_setting(&table, "format", "fallback_icon", "i.png".into())
+        .unwrap();
+    let cfg = t.try_into::<Config>().unwrap();
+    assert_eq!(cfg.get_format_fallback_icon().as_deref(), Some("i.png"));
+}
+
+#[test]
+fn test_config_schema() {
+    let schema: serde_json::Value =
+        serde_json::from_str(CONFIG_SCHEMA).expect("Invalid config schema");
+    let config = serde_json::to_value(Config::default()).unwrap();
+    for (section, val) in config.as_object().unwrap() {
+        let section_schema = &schema["properties"][section];
+        assert!(section_schema.is_object(), "No schema of [{section}]");
+        let (Some(props), serde_json::Value::Object(settings)) =
+            (section_schema["properties"].as_object(), val)
+        else {
+            continue;
+        };
+        for key in settings.keys() {
+            assert!(props.contains_key(key), "No schema of {section}.{key}");
+        }
+    }
+}
@@ -17,6 +17,9 @@
 
 use crate::{shared::ipc, shared::ipc::NodeMethods, tree as t};
 use regex::Regex;
+use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
 use swayipc as s;
 
 #[derive(Debug)]
@@ -37,6 +40,41 @@ pub enum ShellTypeOrFocused {
     Focused,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    Width,
+    Height,
+    Area,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+impl Comparison {
+    fn compare(&self, a: f64, b: f64) -> bool {
+        match self {
+            Comparison::Less => a < b,
+            Comparison::LessOrEqual => a <= b,
+            Comparison::Equal => a == b,
+            Comparison::GreaterOrEqual => a >= b,
+            Comparison::Greater => a > b,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PixelsOrPercent {
+    Pixels(i64),
+    /// Relative to the output of the window.
+    Percent(f64),
+}
+
 #[derive(Debug)]
 pub enum Criterion {
     // And/Or/Not aren't specified by sway.
@@ -58,6 +96,121 @@ pub enum Criterion {
     Shell(ShellTypeOrFocused),
     Floating,
     Tiling,
+    /// Not specified by sway: compares the window's width, height, or area
+    /// with some value in pixels or percent of its output.
+    Geometry(Dimension, Comparison, PixelsOrPercent),
+    /// Not specified by sway: compares the time since the window has been
+    /// created with the given number of seconds.
+    Age(Comparison, u64),
+}
+
+impl fmt::Display for RegexOrFocused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegexOrFocused::Regex(rx) => write!(f, "\"{}\"", rx.as_str()),
+            RegexOrFocused::Focused => write!(f, "__focused__"),
+        }
+    }
+}
+
+impl fmt::Display for I64OrFocused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            I64OrFocused::I64(n) => write!(f, "{n}"),
+            I64OrFocused::Focused => write!(f, "__focused__"),
+        }
+    }
+}
+
+impl fmt::Display for ShellTypeOrFocused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShellTypeOrFocused::ShellType(s::ShellType::XdgShell) => {
+                write!(f, "\"xdg_shell\"")
+            }
+            ShellTypeOrFocused::ShellType(s::ShellType::Xwayland) => {
+                write!(f, "\"xwayland\"")
+            }
+            ShellTypeOrFocused::ShellType(_) => write!(f, "\"unknown\""),
+            ShellTypeOrFocused::Focused => write!(f, "__focused__"),
+        }
+    }
+}
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Dimension::Width => "width",
+            Dimension::Height => "height",
+            Dimension::Area => "area",
+        })
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Equal => "=",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Greater => ">",
+        })
+    }
+}
+
+impl fmt::Display for PixelsOrPercent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PixelsOrPercent::Pixels(px) => write!(f, "{px}"),
+            PixelsOrPercent::Percent(pct) => write!(f, "{pct}%"),
+        }
+    }
+}
+
+/// Writes the criterion in the syntax accepted by [`parse_criteria`].
+impl fmt::Display for Criterion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let write_list = |f: &mut fmt::Formatter<'_>,
+                          op: &str,
+                          crits: &[Criterion]|
+         -> fmt::Result {
+            write!(f, "[{op}")?;
+            for (i, c) in crits.iter().enumerate() {
+                if i > 0 || !op.is_empty() {
+                    write!(f, " ")?;
+                }
+                write!(f, "{c}")?;
+            }
+            write!(f, "]")
+        };
+        match self {
+            Criterion::And(crits) => write_list(f, "", crits),
+            Criterion::Or(crits) => write_list(f, "or", crits),
+            Criterion::Not(c) => write!(f, "not {c}"),
+            Criterion::BoolLiteral(b) => write!(f, "{b}"),
+            Criterion::AppId(v) => write!(f, "app_id={v}"),
+            Criterion::Class(v) => write!(f, "class={v}"),
+            Criterion::Instance(v) => write!(f, "instance={v}"),
+            Criterion::AppName(v) => write!(f, "app_name={v}"),
+            Criterion::Title(v) => write!(f, "title={v}"),
+            Criterion::ConMark(rx) => write!(f, "con_mark=\"{}\"", rx.as_str()),
+            Criterion::ConId(v) => write!(f, "con_id={v}"),
+            Criterion::Pid(pid) => write!(f, "pid={pid}"),
+            Criterion::Workspace(v) => write!(f, "workspace={v}"),
+            Criterion::Shell(v) => write!(f, "shell={v}"),
+            Criterion::Floating => write!(f, "floating"),
+            Criterion::Tiling => write!(f, "tiling"),
+            Criterion::Geometry(d, c, v) => write!(f, "{d}{c}{v}"),
+            Criterion::Age(c, secs) => {
+                let (n, unit) = [(86400, "d"), (3600, "h"), (60, "m")]
+                    .into_iter()
+                    .find(|(f, _)| *secs > 0 && secs % f == 0)
+                    .map_or((*secs, "s"), |(f, u)| (secs / f, u));
+                write!(f, "age{c}{n}{unit}")
+            }
+        }
+    }
 }
 
 fn regex_from_str(s: &str) -> Regex {
@@ -70,6 +223,12 @@ fn regex_from_str(s: &str) -> Regex {
     }
 }
 
+/// Returns a regex matching exactly the string `s`.
+fn literal_regex(s: &str) -> Regex {
+    Regex::new(&format!("^{}$", regex::escape(s)))
+        .expect("Escaped regex must be valid")
+}
+
 peg::parser! {
     grammar criteria_parser() for str {
         rule space() -> () = [' ' | '\t']* {}
@@ -80,9 +239,17 @@ peg::parser! {
         rule string_literal() -> String =
             "\"" s:[^'"']* "\"" { s.into_iter().collect() }
 
-        rule regex_or_focused() -> RegexOrFocused =
-            "__focused__" { RegexOrFocused::Focused }
-          / s:string_literal() { RegexOrFocused::Regex(regex_from_str(&s)) }
+        // `==` matches literally, `~=` is a case-insensitive regex match,
+        // and `=` a regex match.
+        rule regex_match() -> Regex =
+            "==" space() s:string_literal() { literal_regex(&s) }
+          / "~=" space() s:string_literal() {
+                regex_from_str(&format!("(?i){s}"))
+            }
+          / "=" space() s:string_literal() { regex_from_str(&s) }
+        rule regex_match_or_focused() -> RegexOrFocused =
+            "=" space() "__focused__" { RegexOrFocused::Focused }
+          / rx:regex_match() { RegexOrFocused::Regex(rx) }
 
         rule i64_focused() -> I64OrFocused =
             "__focused__" { I64OrFocused::Focused }
@@ -91,24 +258,24 @@ peg::parser! {
 
         rule tiling() -> Criterion = "tiling" { Criterion::Tiling }
         rule floating() -> Criterion = "floating" { Criterion::Floating }
-        rule app_id() -> Criterion = "app_id" space() "=" space()
-            rof:regex_or_focused() { Criterion::AppId(rof) }
-        rule app_name() -> Criterion = "app_name" space() "=" space()
-            rof:regex_or_focused() { Criterion::AppName(rof) }
-        rule class() -> Criterion = "class" space() "=" space()
-            rof:regex_or_focused() { Criterion::Class(rof) }
-        rule instance() -> Criterion = "instance" space() "=" space()
-            rof:regex_or_focused() { Criterion::Instance(rof) }
-        rule title() -> Criterion = "title" space() "=" space()
-            rof:regex_or_focused() { Criterion::Title(rof) }
-        rule con_mark() -> Criterion = "con_mark" space() "=" space()
-            s:string_literal() { Criterion::ConMark(regex_from_str(&s)) }
+        rule app_id() -> Criterion = "app_id" space()
+            rof:regex_match_or_focused() { Criterion::AppId(rof) }
+        rule app_name() -> Criterion = "app_name" space()
+            rof:regex_match_or_focused() { Criterion::AppName(rof) }
+        rule class() -> Criterion = "class" space()
+            rof:regex_match_or_focused() { Criterion::Class(rof) }
+        rule instance() -> Criterion = "instance" space()
+            rof:regex_match_or_focused() { Criterion::Instance(rof) }
+        rule title() -> Criterion = "title" space()
+            rof:regex_match_or_focused() { Criterion::Title(rof) }
+        rule con_mark() -> Criterion = "con_mark" space()
+            rx:regex_match() { Criterion::ConMark(rx) }
         rule con_id() -> Criterion = "con_id" space() "=" space()
             i:i64_or_focused() { Criterion::ConId(i) }
         rule pid() -> Criterion = "pid" space() "=" space()
             n:i32_literal() { Criterion::Pid(n) }
-        rule workspace() -> Criterion = "workspace" space() "=" space()
-            rof:regex_or_focused() { Criterion::Workspace(rof) }
+        rule workspace() -> Criterion = "workspace" space()
+            rof:regex_match_or_focused() { Criterion::Workspace(rof) }
         rule shell_type_or_focused() -> ShellTypeOrFocused =
             "\"xdg_shell\"" {ShellTypeOrFocused::ShellType(s::ShellType::XdgShell)}
           / "\"xwayland\""  {ShellTypeOrFocused::ShellType(s::ShellType::Xwayland)}
@@ -116,6 +283,43 @@ peg::parser! {
         rule shell() -> Criterion = "shell" space() "=" space()
             stof:shell_type_or_focused() { Criterion::Shell(stof) }
 
+        rule dimension() -> Dimension =
+            "width" { Dimension::Width }
+          / "height" { Dimension::Height }
+          / "area" { Dimension::Area }
+        rule comparison() -> Comparison =
+            "<=" { Comparison::LessOrEqual }
+          / ">=" { Comparison::GreaterOrEqual }
+          / "<" { Comparison::Less }
+          / ">" { Comparison::Greater }
+          / "=" { Comparison::Equal }
+        rule pixels_or_percent() -> PixelsOrPercent =
+            n:$(['0'..='9']+ ("." ['0'..='9']+)?) "%"
+            {? n.parse().map(PixelsOrPercent::Percent).or(Err("percent")) }
+          / n:i64_literal() { PixelsOrPercent::Pixels(n) }
+        rule geometry() -> Criterion =
+            d:dimension() space() c:comparison() space()
+            v:pixels_or_percent()
+            { Criterion::Geometry(d, c, v) }
+
+        rule age_seconds() -> u64 =
+            n:$(['0'..='9']+) u:$(['s' | 'm' | 'h' | 'd'])
+            {?
+                let factor = match u {
+                    "m" => 60,
+                    "h" => 3600,
+                    "d" => 86400,
+                    _ => 1,
+                };
+                n.parse::<u64>()
+                    .ok()
+                    .and_then(|n| n.checked_mul(factor))
+                    .ok_or("duration")
+            }
+        rule age() -> Criterion =
+            "age" space() c:comparison() space() secs:age_seconds()
+            { Criterion::Age(c, secs) }
+
         rule and() -> Criterion =
             "[" space() ("AND" / "and" / "&&")? space()
                 l:(criterion() ** space())
@@ -145,6 +349,8 @@ peg::parser! {
           / con_mark()
           / con_id()
           / pid()
+          / geometry()
+          / age()
 
         pub rule parse() -> Criterion =
             space() c:criterion() space()
@@ -152,8 +358,69 @@ peg::parser! {
   }
 }
 
-pub fn parse_criteria(criteria: &str) -> Result<Criterion, String> {
-    criteria_parser::parse(criteria).map_err(|e| e.to_string())
+/// The version of the criteria grammar.  It is incremented whenever a change
+/// makes previously valid criteria queries invalid or changes their meaning.
+pub const GRAMMAR_VERSION: u32 = 1;
+
+/// A syntax error in a criteria query.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CriteriaError {
+    /// The query which couldn't be parsed.
+    pub query: String,
+    /// The 1-based line of the error position.
+    pub line: usize,
+    /// The 1-based column of the error position.
+    pub column: usize,
+    /// The 0-based byte offset of the error position.
+    pub offset: usize,
+    /// The tokens which would have been valid at the error position.
+    pub expected: Vec<String>,
+}
+
+impl fmt::Display for CriteriaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid criteria at line {}, column {}: expected ",
+            self.line, self.column
+        )?;
+        if self.expected.len() == 1 {
+            write!(f, "{}", self.expected[0])?;
+        } else {
+            write!(f, "one of {}", self.expected.join(", "))?;
+        }
+        // Show the offending line with a marker below the error position.
+        let line = self.query.lines().nth(self.line - 1).unwrap_or_default();
+        let indent: String = line
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        write!(f, "\n  {line}\n  {indent}^")
+    }
+}
+
+impl std::error::Error for CriteriaError {}
+
+impl From<CriteriaError> for String {
+    fn from(err: CriteriaError) -> Self {
+        err.to_string()
+    }
+}
+
+pub fn parse_criteria(criteria: &str) -> Result<Criterion, CriteriaError> {
+    criteria_parser::parse(criteria).map_err(|e| {
+        let mut expected: Vec<String> =
+            e.expected.tokens().map(String::from).collect();
+        expected.sort();
+        CriteriaError {
+            query: criteria.to_owned(),
+            line: e.location.line,
+            column: e.location.column,
+            offset: e.location.offset,
+            expected,
+        }
+    })
 }
 
 fn is_some_and_rx_matches(s: Option<&String>, rx: &Regex) -> bool {
@@ -167,6 +434,35 @@ fn are_some_and_equal<T: std::cmp::PartialEq>(
     a.is_some() && b.is_some() && a.unwrap() == b.unwrap()
 }
 
+fn dimension_of_rect(dim: Dimension, rect: &s::Rect) -> f64 {
+    match dim {
+        Dimension::Width => rect.width as f64,
+        Dimension::Height => rect.height as f64,
+        Dimension::Area => rect.width as f64 * rect.height as f64,
+    }
+}
+
+fn eval_geometry(
+    w: &t::DisplayNode,
+    dim: Dimension,
+    cmp: Comparison,
+    val: &PixelsOrPercent,
+) -> bool {
+    let actual = dimension_of_rect(dim, &w.node.rect);
+    match val {
+        PixelsOrPercent::Pixels(px) => cmp.compare(actual, *px as f64),
+        PixelsOrPercent::Percent(pct) => {
+            match w.tree.get_parent_node_of_type(w.node.id, ipc::Type::Output) {
+                Some(output) => {
+                    let total = dimension_of_rect(dim, &output.rect);
+                    total > 0.0 && cmp.compare(100.0 * actual / total, *pct)
+                }
+                None => false,
+            }
+        }
+    }
+}
+
 fn eval_criterion<'a>(
     criterion: &'a Criterion,
     w: &'a t::DisplayNode,
@@ -286,6 +582,9 @@ fn eval_criterion<'a>(
         },
         Criterion::Floating => w.node.is_floating(),
         Criterion::Tiling => !w.node.is_floating(),
+        Criterion::Geometry(dim, cmp, val) => eval_geometry(w, *dim, *cmp, val),
+        Criterion::Age(cmp, secs) => t::get_window_age(w.node.id)
+            .is_some_and(|age| is_age_matching(age, *cmp, *secs)),
         Criterion::Title(val) => match val {
             RegexOrFocused::Regex(rx) => {
                 is_some_and_rx_matches(w.node.name.as_ref(), rx)
@@ -309,10 +608,16 @@ pub fn criterion_to_predicate<'a>(
     move |w: &t::DisplayNode| eval_criterion(criterion, w, focused)
 }
 
+/// Compares the whole seconds of `age` with `secs` so that, e.g., `age=30s`
+/// matches during the whole 31st second.
+fn is_age_matching(age: Duration, cmp: Comparison, secs: u64) -> bool {
+    cmp.compare(age.as_secs() as f64, secs as f64)
+}
+
 #[test]
 fn test_criteria_parser() {
     match criteria_parser::parse(
-        "[tiling floating app_id=__focused__ app_id=\"foot\" class=\"emacs\" instance = \"the.instance\" title=\"something with :;&$\" con_mark=\"^.*foo$\"\tapp_name=\"Hugo\" con_id = __focused__ con_id=17 pid=23223 shell=\"xdg_shell\" shell=\"xwayland\" shell=__focused__ workspace=\"test\" workspace=__focused__ true false TRUE FALSE]",
+        "[tiling floating app_id=__focused__ app_id=\"foot\" class=\"emacs\" instance = \"the.instance\" title=\"something with :;&$\" con_mark=\"^.*foo$\"\tapp_name=\"Hugo\" con_id = __focused__ con_id=17 pid=23223 shell=\"xdg_shell\" shell=\"xwayland\" shell=__focused__ workspace=\"test\" workspace=__focused__ width>1000 height <= 600 area>50% true false TRUE FALSE]",
     ) {
         Ok(c) => assert!(matches!(c, Criterion::And(..))),
         Err(err) => {
@@ -375,3 +680,100 @@ fn test_criteria_parser_not() {
         }
     }
 }
+
+#[test]
+fn test_criteria_parser_geometry() {
+    for (c, dim, cmp, val) in [
+        (
+            "width>1000",
+            Dimension::Width,
+            Comparison::Greater,
+            PixelsOrPercent::Pixels(1000),
+        ),
+        (
+            "height <= 600",
+            Dimension::Height,
+            Comparison::LessOrEqual,
+            PixelsOrPercent::Pixels(600),
+        ),
+        (
+            "area>=12.5%",
+            Dimension::Area,
+            Comparison::GreaterOrEqual,
+            PixelsOrPercent::Percent(12.5),
+        ),
+    ] {
+        match criteria_parser::parse(c) {
+            Ok(Criterion::Geometry(d, cm, v)) => {
+                assert_eq!((dim, cmp, val), (d, cm, v))
+            }
+            other => unreachable!("Unexpected parse result: {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_criteria_display_roundtrip() {
+    let query = "[tiling app_id=__focused__ class=\"emacs\" \
+                 [or title=\"a b\" con_mark=\"^.*foo$\"] not con_id=17 \
+                 pid=23223 shell=\"xwayland\" workspace=__focused__ \
+                 width>1000 area<=12.5% age>=90m age<120s [] [or] true]";
+    let printed = parse_criteria(query).unwrap().to_string();
+    assert_eq!(
+        printed,
+        "[tiling app_id=__focused__ class=\"emacs\" \
+         [or title=\"a b\" con_mark=\"^.*foo$\"] not con_id=17 \
+         pid=23223 shell=\"xwayland\" workspace=__focused__ \
+         width>1000 area<=12.5% age>=90m age<2m [] [or] true]"
+    );
+    assert_eq!(printed, parse_criteria(&printed).unwrap().to_string());
+}
+
+#[test]
+fn test_criteria_error() {
+    let err = parse_criteria("[tiling app_id\"foot\"]").unwrap_err();
+    assert_eq!((err.line, err.column, err.offset), (1, 15, 14));
+    assert!(err.expected.contains(&"\"=\"".to_string()));
+    assert!(err
+        .to_string()
+        .ends_with("\n  [tiling app_id\"foot\"]\n                ^"));
+}
+
+#[test]
+fn test_criteria_age_matching() {
+    let matches = |query: &str, millis: u64| match parse_criteria(query) {
+        Ok(Criterion::Age(cmp, secs)) => {
+            is_age_matching(Duration::from_millis(millis), cmp, secs)
+        }
+        other => unreachable!("Unexpected parse result: {:?}", other),
+    };
+    assert!(matches("age=30s", 30_000));
+    assert!(matches("age=30s", 30_999));
+    assert!(!matches("age=30s", 31_000));
+    assert!(matches("age>30s", 31_000));
+    assert!(!matches("age>30s", 30_500));
+    assert!(matches("age<1m", 59_999));
+    assert!(!matches("age<1m", 60_000));
+}
+
+#[test]
+fn test_criteria_match_operators() {
+    let regex_of = |query: &str| match parse_criteria(query) {
+        Ok(Criterion::AppId(RegexOrFocused::Regex(rx))) => rx,
+        other => unreachable!("Unexpected parse result: {:?}", other),
+    };
+    let rx = regex_of("app_id==\"org.gnome.Nautilus\"");
+    assert!(rx.is_match("org.gnome.Nautilus"));
+    assert!(!rx.is_match("orgXgnomeXNautilus"));
+    assert!(!rx.is_match("org.gnome.Nautilus2"));
+    let rx = regex_of("app_id ~= \"^firefox\"");
+    assert!(rx.is_match("Firefox-esr"));
+    assert!(!rx.is_match("librewolf"));
+    assert!(regex_of("app_id=\"^firefox\"").is_match("firefox"));
+    assert!(!regex_of("app_id=\"^firefox\"").is_match("Firefox"));
+    assert!(matches!(
+        parse_criteria("con_mark==\"a.b\""),
+        Ok(Criterion::ConMark(rx)) if rx.is_match("a.b") && !rx.is_match("axb")
+    ));
+    assert!(parse_criteria("app_id==__focused__").is_err());
+}
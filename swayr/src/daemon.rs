@@ -20,27 +20,51 @@ use crate::config::{self, Config};
 use crate::focus::FocusData;
 use crate::focus::FocusEvent;
 use crate::focus::FocusMessage;
+use crate::focus::WindowInfo;
 use crate::layout;
+use crate::metrics;
+use crate::shared::fmt::subst_placeholders;
+use crate::shared::ipc;
+use crate::shared::ipc::NodeMethods;
+use crate::tree;
 use crate::util;
 use once_cell::sync::Lazy;
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::io::{FromRawFd, RawFd};
 use std::os::unix::net::{UnixListener, UnixStream};
-use std::sync::RwLock;
+use std::rc::Rc;
 use std::sync::{mpsc, Condvar};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use swayipc as s;
 
 pub static CONFIG: Lazy<Config> = Lazy::new(config::load_config);
 
 pub fn run_daemon() {
+    // This modifies the environment, so it must be done before spawning any
+    // thread.
+    let activated_listener = take_activated_listener();
+
+    #[cfg(feature = "foreign-toplevel")]
+    if let Err(err) = s::Connection::new() {
+        log::warn!(
+            "Could not connect to sway ({err}), falling back to \
+             wlr-foreign-toplevel-management with reduced features."
+        );
+        return run_foreign_toplevel_daemon(activated_listener);
+    }
+
     let (focus_tx, focus_rx) = mpsc::channel();
-    let fdata = FocusData {
-        focus_tick_by_id: Arc::new(RwLock::new(HashMap::new())),
-        focus_chan: focus_tx,
-    };
+    let fdata = FocusData::new(focus_tx);
+    ipc::set_root_node_filter(tree::remove_excluded_windows);
+    util::start_menu_server();
+    for win in ipc::get_root_node(false).nodes_of_type(ipc::Type::Window) {
+        tree::record_window_creation(win.id);
+    }
 
     let lockin_delay = CONFIG.get_focus_lockin_delay();
     let auto_nop_delay = &CONFIG.get_misc_auto_nop_delay();
@@ -60,21 +84,205 @@ pub fn run_daemon() {
         });
     }
 
-    serve_client_requests(fdata, auto_nop_delay);
+    #[cfg(feature = "dbus")]
+    crate::dbus::serve(fdata.clone());
+
+    thread::spawn(metrics::serve_http);
+
+    serve_client_requests(fdata, auto_nop_delay, activated_listener);
 }
 
 fn connect_and_subscribe() -> s::Fallible<s::EventStream> {
     s::Connection::new()?.subscribe([
         s::EventType::Window,
         s::EventType::Workspace,
+        s::EventType::Output,
+        s::EventType::Mode,
+        s::EventType::Binding,
         s::EventType::Shutdown,
+        s::EventType::Tick,
     ])
 }
 
+/// Runs the given hook commands in a separate thread replacing `{key}` in
+/// their arguments with `value`.
+fn run_hook_commands(
+    commands: Vec<Vec<String>>,
+    key: &str,
+    value: &str,
+    fdata: &FocusData,
+) {
+    if commands.is_empty() {
+        return;
+    }
+    let placeholder = format!("{{{key}}}");
+    let commands: Vec<Vec<String>> = commands
+        .into_iter()
+        .map(|args| {
+            args.iter()
+                .map(|a| a.replace(&placeholder, value))
+                .collect()
+        })
+        .collect();
+    let fdata = fdata.clone();
+    thread::spawn(move || {
+        for args in commands {
+            match cmds::parse_swayr_command(&args) {
+                Ok(cmd) => {
+                    if let Err(err) =
+                        cmds::exec_swayr_cmd(cmds::ExecSwayrCmdArgs {
+                            cmd: &cmd,
+                            focus_data: &fdata,
+                        })
+                    {
+                        log::error!(
+                            "Error running hook command {args:?}: {err}"
+                        );
+                    }
+                }
+                Err(err) => {
+                    log::error!("Invalid hook command {args:?}: {err}")
+                }
+            }
+        }
+    });
+}
+
+/// Records the output of workspace `ws` as its preferred one unless the
+/// previously preferred output is gone, i.e., sway has moved the workspace
+/// away from it.
+fn record_workspace_output(ws: &s::Node, fdata: &FocusData) {
+    if !CONFIG.is_outputs_restore_workspaces()
+        && CONFIG.get_outputs_fallback().is_empty()
+    {
+        return;
+    }
+    let Some(output) = &ws.output else {
+        return;
+    };
+    match fdata.get_preferred_output(ws.get_name()) {
+        Some(prev) if &prev == output => (),
+        Some(prev) if !get_output_names().contains(&prev) => (),
+        _ => fdata.set_preferred_output(ws.get_name(), output),
+    }
+}
+
+fn get_output_names() -> HashSet<String> {
+    cmds::get_outputs().into_iter().map(|o| o.name).collect()
+}
+
+/// Runs the `on_output_connect` and `on_output_disconnect` hooks for the
+/// difference between `known_outputs` and the current outputs.
+fn handle_output_event(known_outputs: &mut HashSet<String>, fdata: &FocusData) {
+    let outputs = get_output_names();
+    let restore = CONFIG.is_outputs_restore_workspaces();
+    for name in outputs.difference(known_outputs) {
+        log::debug!("Output {name} has been connected.");
+        if restore {
+            layout::restore_workspaces_to_output(name, fdata);
+        }
+        run_hook_commands(
+            CONFIG.get_on_output_connect_commands(),
+            "output",
+            name,
+            fdata,
+        );
+    }
+    let fallback = CONFIG.get_outputs_fallback();
+    for name in known_outputs.difference(&outputs) {
+        log::debug!("Output {name} has been disconnected.");
+        layout::evacuate_workspaces_of_output(name, &fallback, &outputs, fdata);
+        run_hook_commands(
+            CONFIG.get_on_output_disconnect_commands(),
+            "output",
+            name,
+            fdata,
+        );
+    }
+    *known_outputs = outputs;
+}
+
+/// The number of sway events after which the focus data of vanished nodes is
+/// removed.
+const GC_INTERVAL: u64 = 1000;
+
+/// The focus tick above which all ticks are renormalized.
+const TICK_RENORMALIZATION_THRESHOLD: u64 = 1 << 48;
+
+/// Removes the focus data of nodes which don't exist anymore because their
+/// close event has been missed, and renormalizes the ticks if they grew too
+/// large.
+fn collect_focus_data_garbage(fdata: &FocusData, focus_counter: &mut u64) {
+    let root = ipc::get_root_node(true);
+    let live_ids: HashSet<i64> = root.iter().map(|n| n.id).collect();
+    let removed = fdata.retain_live_ids(&live_ids);
+    if removed > 0 {
+        log::debug!("Removed focus data of {removed} vanished nodes.");
+    }
+    let removed = tree::retain_formatted_windows(&live_ids);
+    if removed > 0 {
+        log::debug!("Removed menu entries of {removed} vanished windows.");
+    }
+    tree::retain_window_creation_times(&live_ids);
+    if *focus_counter > TICK_RENORMALIZATION_THRESHOLD {
+        *focus_counter = fdata.renormalize_ticks();
+        log::debug!("Renormalized focus ticks to max {focus_counter}.");
+    }
+}
+
+/// Re-primes the focus data from the current sway tree: forgets the data of
+/// vanished nodes and records the nodes and window infos swayrd has missed.
+/// Returns the number of forgotten and added nodes.
+pub fn reprime_focus_data(fdata: &FocusData) -> (usize, usize) {
+    let root = ipc::get_root_node(false);
+    let live_ids: HashSet<i64> = root.iter().map(|n| n.id).collect();
+    let removed = fdata.retain_live_ids(&live_ids);
+    tree::retain_formatted_windows(&live_ids);
+    tree::retain_window_creation_times(&live_ids);
+    let mut added = 0;
+    for node in root.iter().filter(|n| {
+        matches!(
+            n.try_get_type(),
+            Some(ipc::Type::Workspace | ipc::Type::Window)
+        )
+    }) {
+        if !fdata.has_id(node.id) {
+            added += 1;
+        }
+        fdata.ensure_id(node.id);
+        if node.get_type() == ipc::Type::Window {
+            tree::record_window_creation(node.id);
+            if !fdata.has_window_info(node.id) {
+                record_window_info(&root, node, fdata);
+            }
+        }
+    }
+    (removed, added)
+}
+
+/// Ends the current sequence of prev/next window commands when leaving the
+/// binding mode configured as `focus.end_sequence_on_mode_exit`, e.g., a
+/// dedicated switcher mode.
+fn end_sequence_on_mode_exit(
+    old_mode: &str,
+    new_mode: &str,
+    fdata: &FocusData,
+) {
+    if old_mode != new_mode
+        && CONFIG.get_focus_end_sequence_on_mode_exit().as_deref()
+            == Some(old_mode)
+    {
+        log::debug!("Left binding mode {old_mode}, ending sequence.");
+        fdata.send(FocusMessage::TickUpdateActivate);
+    }
+}
+
 pub fn monitor_sway_events(fdata: FocusData) {
     let mut focus_counter = 0;
+    let mut events_since_gc = 0;
     let mut resets = 0;
     let max_resets = 10;
+    let mut binding_mode = String::from("default");
 
     'reset: loop {
         if resets >= max_resets {
@@ -86,12 +294,17 @@ pub fn monitor_sway_events(fdata: FocusData) {
         match connect_and_subscribe() {
             Err(err) => {
                 log::warn!("Could not connect and subscribe: {err}");
+                metrics::record_event_loop_reset();
                 std::thread::sleep(std::time::Duration::from_secs(3));
             }
             Ok(iter) => {
+                let mut known_outputs = get_output_names();
                 for ev_result in iter {
                     let show_extra_props_state;
                     resets = 0;
+                    if let Ok(ev) = &ev_result {
+                        metrics::record_sway_event(event_type_name(ev));
+                    }
                     match ev_result {
                         Ok(ev) => match ev {
                             s::Event::Window(win_ev) => {
@@ -110,6 +323,42 @@ pub fn monitor_sway_events(fdata: FocusData) {
                                     focus_counter,
                                 );
                             }
+                            s::Event::Output(_) => {
+                                handle_output_event(&mut known_outputs, &fdata);
+                                show_extra_props_state = false;
+                            }
+                            s::Event::Mode(mode_ev) => {
+                                log::debug!(
+                                    "Binding mode changed to {}.",
+                                    mode_ev.change
+                                );
+                                end_sequence_on_mode_exit(
+                                    &binding_mode,
+                                    &mode_ev.change,
+                                    &fdata,
+                                );
+                                binding_mode.clone_from(&mode_ev.change);
+                                run_hook_commands(
+                                    CONFIG.get_on_mode_change_commands(),
+                                    "mode",
+                                    &mode_ev.change,
+                                    &fdata,
+                                );
+                                show_extra_props_state = false;
+                            }
+                            s::Event::Binding(binding_ev) => {
+                                run_hook_commands(
+                                    CONFIG.get_on_binding_commands(),
+                                    "command",
+                                    &binding_ev.binding.command,
+                                    &fdata,
+                                );
+                                show_extra_props_state = false;
+                            }
+                            s::Event::Tick(tick_ev) => {
+                                fdata.notify_sway_tick(&tick_ev.payload);
+                                show_extra_props_state = false;
+                            }
                             s::Event::Shutdown(sd_ev) => {
                                 log::debug!(
                                     "Sway shuts down with reason '{:?}'.",
@@ -125,9 +374,15 @@ pub fn monitor_sway_events(fdata: FocusData) {
                                 3,
                             ));
                             show_extra_props_state = false;
+                            metrics::record_event_loop_reset();
                             log::warn!("Resetting!");
                         }
                     }
+                    events_since_gc += 1;
+                    if events_since_gc >= GC_INTERVAL {
+                        events_since_gc = 0;
+                        collect_focus_data_garbage(&fdata, &mut focus_counter);
+                    }
                     if show_extra_props_state {
                         log::trace!(
                             "New extra_props state:\n{:#?}",
@@ -141,6 +396,68 @@ pub fn monitor_sway_events(fdata: FocusData) {
     log::debug!("Swayr daemon shutting down.")
 }
 
+/// The name of the type of `ev` used as label in the metrics.
+fn event_type_name(ev: &s::Event) -> &'static str {
+    match ev {
+        s::Event::Window(_) => "window",
+        s::Event::Workspace(_) => "workspace",
+        s::Event::Output(_) => "output",
+        s::Event::Mode(_) => "mode",
+        s::Event::Binding(_) => "binding",
+        s::Event::Tick(_) => "tick",
+        s::Event::Shutdown(_) => "shutdown",
+        _ => "other",
+    }
+}
+
+/// How long the result of scanning the processes for a screen locker is
+/// reused.
+const SESSION_LOCKED_TTL: Duration = Duration::from_secs(1);
+
+/// The time of the last scan for a screen locker and its result.
+static SESSION_LOCKED: Lazy<Mutex<Option<(Instant, bool)>>> =
+    Lazy::new(|| Mutex::new(None));
+
+/// Returns true if one of the `lock.processes`, i.e., a screen locker, is
+/// running.  Sway doesn't report session locks via IPC, so the processes are
+/// scanned at most once per [`SESSION_LOCKED_TTL`].
+pub fn is_session_locked() -> bool {
+    let processes = CONFIG.get_lock_processes();
+    if processes.is_empty() {
+        return false;
+    }
+    let mut cached = SESSION_LOCKED.lock().expect("Could not lock mutex");
+    match *cached {
+        Some((checked, locked)) if checked.elapsed() < SESSION_LOCKED_TTL => {
+            locked
+        }
+        _ => {
+            let locked = util::is_any_process_running(&processes);
+            *cached = Some((Instant::now(), locked));
+            locked
+        }
+    }
+}
+
+/// Returns true if the window `id` matches the `exclude.criteria`.  `root`
+/// must be an unfiltered tree, and it's only fetched if there are criteria.
+fn is_excluded<'a>(root: impl FnOnce() -> &'a s::Node, id: i64) -> bool {
+    if CONFIG.get_exclude_criteria().is_empty() {
+        return false;
+    }
+    tree::excluded_window_ids(root()).contains(&id)
+}
+
+/// Returns true if the window with the given `id` matches one of the
+/// `focus.ignore` criteria.  `root` is only fetched if there are criteria.
+fn is_focus_ignored<'a>(root: impl FnOnce() -> &'a s::Node, id: i64) -> bool {
+    let queries = CONFIG.get_focus_ignore();
+    if queries.is_empty() {
+        return false;
+    }
+    tree::window_ids_matching_any(root(), &queries).contains(&id)
+}
+
 fn handle_window_event(
     ev: Box<s::WindowEvent>,
     fdata: &FocusData,
@@ -149,33 +466,93 @@ fn handle_window_event(
     let s::WindowEvent {
         change, container, ..
     } = *ev;
+    // The tree is fetched at most once per event and shared by all hooks
+    // needing it.  It's unfiltered so that excluded windows can be detected.
+    // Only auto-tiling fetches its own tree because it must see the effects of
+    // the commands the other hooks ran.
+    let root_cell = once_cell::unsync::OnceCell::new();
+    let root = || root_cell.get_or_init(|| ipc::get_unfiltered_root_node(true));
+    if change != s::WindowChange::Close && is_excluded(root, container.id) {
+        log::debug!(
+            "Ignoring window event of excluded window {}",
+            container.id
+        );
+        return false;
+    }
+    fdata.notify_window_event();
+    if change != s::WindowChange::Focus {
+        // The menu entry of the window might have changed.
+        tree::forget_formatted_window(container.id);
+    }
     match change {
+        s::WindowChange::Focus if is_focus_ignored(root, container.id) => {
+            log::debug!(
+                "Not recording focus of ignored window {}",
+                container.id
+            );
+            false
+        }
         s::WindowChange::Focus => {
-            layout::maybe_auto_tile(&CONFIG);
+            layout::maybe_auto_tile(&CONFIG, fdata);
+            if !fdata.has_window_info(container.id) {
+                record_window_info(root(), &container, fdata);
+            }
             fdata.send(FocusMessage::FocusEvent(FocusEvent {
                 node_id: container.id,
                 ev_focus_ctr: focus_val,
             }));
+            #[cfg(feature = "dbus")]
+            crate::dbus::emit_focus_changed(container.id);
+            cmds::maybe_follow(fdata, Some(container.id));
             log::debug!("Handled window event type {:?}", change);
             true
         }
         s::WindowChange::New => {
-            layout::maybe_auto_tile(&CONFIG);
+            layout::maybe_auto_unfloat(&CONFIG, root(), &container);
+            layout::maybe_apply_title_format(&CONFIG, root(), &container);
+            layout::maybe_auto_tile(&CONFIG, fdata);
             fdata.ensure_id(container.id);
+            tree::record_window_creation(container.id);
+            record_window_info(root(), &container, fdata);
             log::debug!("Handled window event type {:?}", change);
             true
         }
         s::WindowChange::Close => {
+            maybe_run_focused_workspace_empty_hook(root, &container, fdata);
             fdata.remove_focus_data(container.id);
-            layout::maybe_auto_tile(&CONFIG);
+            fdata.window_closed(
+                container.id,
+                container.get_app_name(),
+                container.get_name(),
+            );
+            layout::maybe_auto_tile(&CONFIG, fdata);
             log::debug!("Handled window event type {:?}", change);
             true
         }
         s::WindowChange::Move | s::WindowChange::Floating => {
-            layout::maybe_auto_tile(&CONFIG);
+            layout::maybe_auto_tile(&CONFIG, fdata);
+            if change == s::WindowChange::Move {
+                record_window_info(root(), &container, fdata);
+            }
             log::debug!("Handled window event type {:?}", change);
             false // We don't affect the extra_props state here.
         }
+        s::WindowChange::Urgent => {
+            if container.urgent
+                && fdata.get_do_not_disturb().is_some_and(|d| d.clear_urgency)
+            {
+                log::debug!("Do-not-disturb: clearing urgency hint.");
+                cmds::clear_urgency_hint(container.id);
+                return false;
+            }
+            if container.urgent {
+                fdata.update_last_urgent_tick(container.id, focus_val);
+            }
+            #[cfg(feature = "dbus")]
+            crate::dbus::emit_urgency_changed(container.id, container.urgent);
+            log::debug!("Handled window event type {:?}", change);
+            false
+        }
         _ => {
             log::debug!("Unhandled window event type {:?}", change);
             false
@@ -183,6 +560,65 @@ fn handle_window_event(
     }
 }
 
+/// Runs the `on_focused_workspace_empty` hook if closing the window `closed`
+/// left the focused workspace without windows.  `root` must be an unfiltered
+/// tree, and it's only fetched if there are hook commands.  Excluded windows
+/// don't count as windows of the workspace.
+fn maybe_run_focused_workspace_empty_hook<'a>(
+    root: impl FnOnce() -> &'a s::Node,
+    closed: &s::Node,
+    fdata: &FocusData,
+) {
+    let commands = CONFIG.get_on_focused_workspace_empty_commands();
+    if commands.is_empty() {
+        return;
+    }
+    let root = root();
+    let Some(ws) = root
+        .nodes_of_type(ipc::Type::Workspace)
+        .into_iter()
+        .find(|ws| !ws.is_scratchpad() && ws.is_current())
+    else {
+        return;
+    };
+    // Depending on timing, the closed window may or may not still be part of
+    // the tree.
+    let was_on_ws = ws.iter().any(|n| n.id == closed.id)
+        || fdata.get_window_workspace(closed.id).as_deref()
+            == Some(ws.get_name());
+    let excluded = tree::excluded_window_ids(root);
+    let is_empty = ws
+        .nodes_of_type(ipc::Type::Window)
+        .iter()
+        .all(|w| w.id == closed.id || excluded.contains(&w.id));
+    if was_on_ws && is_empty {
+        run_hook_commands(commands, "workspace", ws.get_name(), fdata);
+    }
+}
+
+/// Records the information about `win` which is needed for reopening it after
+/// it has been closed, i.e., its command line and workspace which is looked up
+/// in `root`.
+fn record_window_info(root: &s::Node, win: &s::Node, fdata: &FocusData) {
+    let workspace = root
+        .nodes_of_type(ipc::Type::Workspace)
+        .into_iter()
+        .find(|ws| !ws.is_scratchpad() && ws.iter().any(|n| n.id == win.id))
+        .map(|ws| ws.get_name().to_owned());
+    fdata.set_window_info(
+        win.id,
+        WindowInfo {
+            app_name: win.get_app_name().to_owned(),
+            title: win.get_name().to_owned(),
+            workspace,
+            cmdline: win
+                .pid
+                .and_then(util::get_cmdline_of_pid)
+                .unwrap_or_default(),
+        },
+    );
+}
+
 fn handle_workspace_event(
     ev: Box<s::WorkspaceEvent>,
     fdata: &FocusData,
@@ -196,9 +632,19 @@ fn handle_workspace_event(
     } = *ev;
     match change {
         s::WorkspaceChange::Init | s::WorkspaceChange::Focus => {
-            let id = current
-                .expect("No current in Init or Focus workspace event")
-                .id;
+            let ws =
+                current.expect("No current in Init or Focus workspace event");
+            record_workspace_output(&ws, fdata);
+            if change == s::WorkspaceChange::Focus {
+                fdata.record_workspace_focus(
+                    ws.get_name(),
+                    CONFIG.get_misc_workspace_history_depth(),
+                );
+                if let Some(output) = &ws.output {
+                    fdata.record_output_workspace_focus(output, ws.get_name());
+                }
+            }
+            let id = ws.id;
             fdata.send(FocusMessage::FocusEvent(FocusEvent {
                 node_id: id,
                 ev_focus_ctr: focus_val,
@@ -206,9 +652,20 @@ fn handle_workspace_event(
             log::debug!("Handled workspace event type {:?}", change);
             true
         }
+        s::WorkspaceChange::Move => {
+            if let Some(ws) = current {
+                record_workspace_output(&ws, fdata);
+            }
+            false
+        }
         s::WorkspaceChange::Empty => {
-            fdata.remove_focus_data(
-                current.expect("No current in Empty workspace event").id,
+            let ws = current.expect("No current in Empty workspace event");
+            fdata.remove_focus_data(ws.id);
+            run_hook_commands(
+                CONFIG.get_on_workspace_empty_commands(),
+                "workspace",
+                ws.get_name(),
+                fdata,
             );
             log::debug!("Handled workspace event type {:?}", change);
             true
@@ -220,12 +677,8 @@ fn handle_workspace_event(
 pub fn serve_client_requests(
     fdata: FocusData,
     auto_nop_delay: &Option<Duration>,
+    activated_listener: Option<UnixListener>,
 ) {
-    match std::fs::remove_file(util::get_swayr_socket_path()) {
-        Ok(()) => log::debug!("Deleted stale socket from previous run."),
-        Err(e) => log::error!("Could not delete socket:\n{:?}", e),
-    }
-
     let pair = Arc::new((Mutex::new(()), Condvar::new()));
     let pair2 = pair.clone();
 
@@ -261,18 +714,27 @@ pub fn serve_client_requests(
         });
     }
 
-    let sock = util::get_swayr_socket_path();
-    log::debug!("swayrd starts listening on {sock}.");
-    match UnixListener::bind(sock) {
+    if CONFIG.is_remote_enabled() {
+        let fdata = fdata.clone();
+        let pair = pair.clone();
+        let auto_nop = auto_nop_delay.is_some();
+        thread::spawn(move || {
+            serve_remote_client_requests(&fdata, || {
+                if auto_nop {
+                    notify_auto_nop(&pair);
+                }
+            });
+        });
+    }
+
+    match get_listener(activated_listener) {
         Ok(listener) => {
             for stream in listener.incoming() {
                 match stream {
                     Ok(stream) => {
                         handle_client_request(stream, &fdata);
                         if auto_nop_delay.is_some() {
-                            let (lock, cvar) = &*pair;
-                            let _guard = lock.lock().unwrap();
-                            cvar.notify_one();
+                            notify_auto_nop(&pair);
                         }
                     }
                     Err(err) => {
@@ -288,24 +750,267 @@ pub fn serve_client_requests(
     }
 }
 
-fn handle_client_request(stream: UnixStream, fdata: &FocusData) {
+/// Serves the commands available without sway using the windows of the
+/// wlr-foreign-toplevel-management protocol.
+#[cfg(feature = "foreign-toplevel")]
+fn run_foreign_toplevel_daemon(activated_listener: Option<UnixListener>) {
+    let provider = match crate::toplevel::ForeignToplevelProvider::connect() {
+        Ok(provider) => provider,
+        Err(err) => {
+            log::error!("Could not get the compositor's windows: {err}");
+            return;
+        }
+    };
+    let provider = Arc::new(provider);
+    util::start_menu_server();
+    match get_listener(activated_listener) {
+        Ok(listener) => {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        // Every request gets its own thread so that an open
+                        // menu doesn't block other clients.
+                        let provider = provider.clone();
+                        thread::spawn(move || {
+                            handle_provider_request(stream, provider.as_ref())
+                        });
+                    }
+                    Err(err) => {
+                        log::error!("Error handling client request: {err}");
+                        break;
+                    }
+                }
+            }
+        }
+        Err(err) => {
+            log::error!("Could not bind socket: {err}")
+        }
+    }
+}
+
+#[cfg(feature = "foreign-toplevel")]
+fn handle_provider_request(
+    stream: UnixStream,
+    provider: &dyn crate::provider::WindowProvider,
+) {
     match serde_json::from_reader::<_, cmds::SwayrCommand>(&stream) {
         Ok(cmd) => {
             log::debug!("Received command: {:?}", cmd);
             if let Err(err) = stream.shutdown(std::net::Shutdown::Read) {
                 log::error!("Could not shutdown stream for read: {err}")
             }
-            let result = cmds::exec_swayr_cmd(cmds::ExecSwayrCmdArgs {
-                cmd: &cmd,
-                focus_data: fdata,
-            });
-            log::debug!("Executed command, returning result {result:?}");
-            if let Err(err) = serde_json::to_writer(&stream, &result) {
-                log::error!("Couldn't send result back to client: {err}");
+            respond(stream, crate::provider::exec_swayr_cmd(provider, &cmd));
+        }
+        Err(err) => {
+            log::error!("Could not read command from client: {err}");
+        }
+    }
+}
+
+/// The first file descriptor passed by systemd, see sd_listen_fds(3).
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// Returns the listening socket passed by systemd if swayrd has been started
+/// by socket activation.  Removes the `LISTEN_*` variables from the
+/// environment, so it must be called before any thread is spawned.
+fn take_activated_listener() -> Option<UnixListener> {
+    let pid = std::env::var("LISTEN_PID").ok()?.parse::<u32>().ok()?;
+    let fds = std::env::var("LISTEN_FDS").ok()?.parse::<u32>().ok()?;
+    // The variables must not be inherited by the processes swayrd starts.
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+    if pid != std::process::id() || fds == 0 {
+        return None;
+    }
+    if fds > 1 {
+        log::warn!("Got {fds} sockets from systemd, using only the first.");
+    }
+    // SAFETY: systemd passes the listening socket as fd 3 and nothing else
+    // in swayrd owns it.
+    Some(unsafe { UnixListener::from_raw_fd(SD_LISTEN_FDS_START) })
+}
+
+fn get_listener(
+    activated_listener: Option<UnixListener>,
+) -> io::Result<UnixListener> {
+    let sway_sock = std::env::var("SWAYSOCK")
+        .unwrap_or_else(|_| String::from("<unset SWAYSOCK>"));
+    if let Some(listener) = activated_listener {
+        log::warn!(
+            "swayrd listens on the socket passed by systemd \
+             and talks to sway at {sway_sock}."
+        );
+        return Ok(listener);
+    }
+
+    let sock = util::get_swayr_socket_path();
+    match std::fs::remove_file(&sock) {
+        Ok(()) => log::debug!("Deleted stale socket from previous run."),
+        Err(e) => log::error!("Could not delete socket:\n{:?}", e),
+    }
+    log::warn!(
+        "swayrd starts listening on {sock} and talks to sway at {sway_sock}."
+    );
+    UnixListener::bind(sock)
+}
+
+fn notify_auto_nop(pair: &(Mutex<()>, Condvar)) {
+    let (lock, cvar) = pair;
+    let _guard = lock.lock().unwrap();
+    cvar.notify_one();
+}
+
+/// Accepts commands over TCP if enabled in the `[remote]` config section.
+/// Clients which don't send the configured token may only run read-only
+/// commands.  Blocking commands are refused and menus are never shown for
+/// remote clients.
+fn serve_remote_client_requests(fdata: &FocusData, on_request: impl Fn()) {
+    let bind = CONFIG.get_remote_bind();
+    let token = CONFIG.get_remote_token();
+    if token.is_none() {
+        log::warn!(
+            "No remote.token configured, so remote clients may only run \
+             read-only commands."
+        );
+    }
+    log::debug!("swayrd starts listening for remote clients on {bind}.");
+    match TcpListener::bind(&bind) {
+        Ok(listener) => {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        handle_remote_client_request(stream, &token, fdata);
+                        on_request();
+                    }
+                    Err(err) => {
+                        log::error!("Error accepting remote client: {err}");
+                    }
+                }
             }
-            if let Err(err) = stream.shutdown(std::net::Shutdown::Write) {
-                log::error!("Could not shutdown stream for read: {err}");
+        }
+        Err(err) => {
+            log::error!("Could not bind remote listener to {bind}: {err}")
+        }
+    }
+}
+
+/// The time a remote client has for sending its command and receiving the
+/// result.  Clients are served one after the other, so a slow client delays
+/// all others by at most that.
+const REMOTE_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn handle_remote_client_request(
+    stream: TcpStream,
+    token: &Option<String>,
+    fdata: &FocusData,
+) {
+    let peer = stream
+        .peer_addr()
+        .map(|a| a.to_string())
+        .unwrap_or_else(|_| "unknown peer".to_owned());
+    if let Err(err) = stream
+        .set_read_timeout(Some(REMOTE_TIMEOUT))
+        .and_then(|()| stream.set_write_timeout(Some(REMOTE_TIMEOUT)))
+    {
+        log::error!("Could not set timeouts for {peer}: {err}");
+        return;
+    }
+    let req = match serde_json::from_reader::<_, cmds::RemoteRequest>(&stream) {
+        Ok(req) => req,
+        Err(err) => {
+            log::error!("Could not read command from {peer}: {err}");
+            return;
+        }
+    };
+    log::debug!("Received remote command from {peer}.");
+    let (cmd, authorized) = match req {
+        cmds::RemoteRequest::Authenticated {
+            token: client_token,
+            command,
+        } => {
+            let valid = token
+                .as_ref()
+                .is_some_and(|t| tokens_equal(t, &client_token));
+            if !valid {
+                log::warn!("Remote client {peer} sent an invalid token.");
+                respond(
+                    stream,
+                    Err(cmds::SwayrError::PermissionDenied(
+                        "Invalid token.".to_owned(),
+                    )),
+                );
+                return;
             }
+            (command, true)
+        }
+        cmds::RemoteRequest::Anonymous(command) => (command, false),
+    };
+    if cmd.is_blocking_command() {
+        log::warn!("Denied blocking command {cmd:?} of remote {peer}.");
+        respond(
+            stream,
+            Err(cmds::SwayrError::PermissionDenied(
+                "Blocking commands may not be run remotely.".to_owned(),
+            )),
+        );
+    } else if authorized || cmd.is_read_only_command() {
+        // Nobody could interact with a menu shown on the remote machine, so
+        // commands needing one act as if it had been aborted.
+        util::with_menu_backend(Rc::new(util::NoMenu), || {
+            dispatch_cmd(stream, cmd, fdata)
+        });
+    } else {
+        log::warn!("Denied command {cmd:?} of unauthenticated {peer}.");
+        respond(
+            stream,
+            Err(cmds::SwayrError::PermissionDenied(
+                "Only read-only commands may be run without token.".to_owned(),
+            )),
+        );
+    }
+}
+
+/// Compares the tokens in time independent of the position of the first
+/// difference.
+fn tokens_equal(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+#[test]
+fn test_tokens_equal() {
+    assert!(tokens_equal("s3cr3t", "s3cr3t"));
+    assert!(!tokens_equal("s3cr3t", "s3cr3T"));
+    assert!(!tokens_equal("s3cr3t", "s3cr3"));
+    assert!(!tokens_equal("", "s3cr3t"));
+}
+
+/// A connection to a client of swayrd, either over the UNIX socket or TCP.
+trait ClientStream: io::Read + io::Write + Send + 'static {
+    fn shutdown(&self, how: std::net::Shutdown) -> io::Result<()>;
+}
+
+impl ClientStream for UnixStream {
+    fn shutdown(&self, how: std::net::Shutdown) -> io::Result<()> {
+        UnixStream::shutdown(self, how)
+    }
+}
+
+impl ClientStream for TcpStream {
+    fn shutdown(&self, how: std::net::Shutdown) -> io::Result<()> {
+        TcpStream::shutdown(self, how)
+    }
+}
+
+fn handle_client_request(stream: UnixStream, fdata: &FocusData) {
+    match serde_json::from_reader::<_, cmds::SwayrCommand>(&stream) {
+        Ok(cmd) => {
+            log::debug!("Received command: {:?}", cmd);
+            dispatch_cmd(stream, cmd, fdata);
         }
         Err(err) => {
             log::error!("Could not read command from client: {err}");
@@ -313,6 +1018,187 @@ fn handle_client_request(stream: UnixStream, fdata: &FocusData) {
     }
 }
 
+fn dispatch_cmd<S: ClientStream>(
+    stream: S,
+    cmd: cmds::SwayrCommand,
+    fdata: &FocusData,
+) {
+    if let Err(err) = stream.shutdown(std::net::Shutdown::Read) {
+        log::error!("Could not shutdown stream for read: {err}")
+    }
+    if cmd.is_blocking_command() {
+        // Don't block other clients while this one is waiting.
+        let fdata = fdata.clone();
+        thread::spawn(move || {
+            exec_cmd_and_respond(stream, &cmd, &fdata);
+        });
+    } else {
+        exec_cmd_and_respond(stream, &cmd, fdata);
+    }
+}
+
+fn exec_cmd_and_respond<S: ClientStream>(
+    mut stream: S,
+    cmd: &cmds::SwayrCommand,
+    fdata: &FocusData,
+) {
+    if cmd.is_streamed() {
+        let start = Instant::now();
+        let mut writer = PartialResultWriter::new(&mut stream);
+        let result = cmds::exec_swayr_cmd_streaming(
+            cmds::ExecSwayrCmdArgs {
+                cmd,
+                focus_data: fdata,
+            },
+            &mut writer,
+        )
+        .and_then(|()| {
+            io::Write::flush(&mut writer)
+                .map_err(|e| cmds::SwayrError::DaemonError(e.to_string()))
+        });
+        metrics::record_command(&cmd.name(), result.is_ok(), start.elapsed());
+        // The output has been sent already, so only an error is left to send.
+        match result {
+            Ok(()) => shutdown_write(&stream),
+            Err(err) => respond(stream, Err(err)),
+        }
+        return;
+    }
+    let start = Instant::now();
+    let result = cmds::exec_swayr_cmd(cmds::ExecSwayrCmdArgs {
+        cmd,
+        focus_data: fdata,
+    });
+    metrics::record_command(&cmd.name(), result.is_ok(), start.elapsed());
+    log::debug!("Executed command, returning result {result:?}");
+    maybe_notify(cmd, &result);
+    respond(stream, result);
+}
+
+/// Shows the result of `cmd` as desktop notification using the
+/// `notifications.command` if there is a template for it.
+fn maybe_notify(
+    cmd: &cmds::SwayrCommand,
+    result: &Result<String, cmds::SwayrError>,
+) {
+    if matches!(result, Err(cmds::SwayrError::MenuAborted)) {
+        return;
+    }
+    let name = cmd.name();
+    let templates = CONFIG.get_notifications_templates();
+    let Some(template) = templates.get(&name).or_else(|| {
+        if cmd.is_scripting_command() {
+            None
+        } else {
+            templates.get("*")
+        }
+    }) else {
+        return;
+    };
+    let (status, text) = match result {
+        Ok(msg) => ("ok", msg.to_owned()),
+        Err(err) => ("error", err.to_string()),
+    };
+    let message: String = subst_placeholders!(template, false, {
+        "command" => name.as_str(),
+        "status" => status,
+        "result" => text.as_str(),
+    });
+    let args: Vec<String> = CONFIG
+        .get_notifications_command()
+        .iter()
+        .map(|a| a.replace("{message}", &message))
+        .collect();
+    if args.is_empty() {
+        return;
+    }
+    thread::spawn(move || {
+        if let Err(err) = std::process::Command::new(&args[0])
+            .args(&args[1..])
+            .status()
+        {
+            log::error!("Could not run notifications.command {args:?}: {err}");
+        }
+    });
+}
+
+fn respond<S: ClientStream>(
+    mut stream: S,
+    result: Result<String, cmds::SwayrError>,
+) {
+    if let Err(err) = serde_json::to_writer(&mut stream, &result) {
+        log::error!("Couldn't send result back to client: {err}");
+    }
+    shutdown_write(&stream);
+}
+
+fn shutdown_write<S: ClientStream>(stream: &S) {
+    if let Err(err) = stream.shutdown(std::net::Shutdown::Write) {
+        log::error!("Could not shutdown stream for write: {err}");
+    }
+}
+
+/// Sends every complete line written to it as partial result `Ok(line)` of a
+/// streamed command to the client.  The client concatenates the partial
+/// results, so an incomplete last line is sent on `flush`.
+struct PartialResultWriter<W: io::Write> {
+    stream: W,
+    line: Vec<u8>,
+}
+
+impl<W: io::Write> PartialResultWriter<W> {
+    fn new(stream: W) -> PartialResultWriter<W> {
+        PartialResultWriter {
+            stream,
+            line: vec![],
+        }
+    }
+
+    fn send_line(&mut self) -> io::Result<()> {
+        let line = String::from_utf8_lossy(&self.line).into_owned();
+        self.line.clear();
+        serde_json::to_writer(
+            &mut self.stream,
+            &Ok::<String, cmds::SwayrError>(line),
+        )?;
+        self.stream.flush()
+    }
+}
+
+impl<W: io::Write> io::Write for PartialResultWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &b in buf {
+            self.line.push(b);
+            if b == b'\n' {
+                self.send_line()?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.line.is_empty() {
+            Ok(())
+        } else {
+            self.send_line()
+        }
+    }
+}
+
+#[test]
+fn test_partial_result_writer() {
+    use std::io::Write;
+    let mut out = vec![];
+    let mut writer = PartialResultWriter::new(&mut out);
+    writer.write_all(b"{\"id\":1}\n{\"id\"").unwrap();
+    writer.write_all(b":2}\n").unwrap();
+    writer.flush().unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        r#"{"Ok":"{\"id\":1}\n"}{"Ok":"{\"id\":2}\n"}"#
+    );
+}
+
 #[derive(Debug)]
 enum InhibitState {
     FocusInhibit,
@@ -349,8 +1235,14 @@ fn focus_lock_in_handler(
 
     let update_focus = |fev: Option<FocusEvent>| {
         if let Some(fev) = fev {
+            // Keep the LRU order of before the session has been locked.
+            if is_session_locked() {
+                log::debug!("Session is locked, not locking-in focus.");
+                return;
+            }
             log::debug!("Locking-in focus on {}", fev.node_id);
-            fdata.update_last_focus_tick(fev.node_id, fev.ev_focus_ctr)
+            fdata.update_last_focus_tick(fev.node_id, fev.ev_focus_ctr);
+            fdata.record_focus_history(fev.node_id);
         }
     };
 
@@ -0,0 +1,113 @@
+// Copyright (C) 2021-2023  Tassilo Horn <tsdh@gnu.org>
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The optional D-Bus service `org.swayr.Daemon` of swayrd.
+
+use crate::cmds;
+use crate::focus::FocusData;
+use once_cell::sync::OnceCell;
+use zbus::blocking::connection;
+use zbus::object_server::SignalEmitter;
+
+pub const SERVICE_NAME: &str = "org.swayr.Daemon";
+pub const OBJECT_PATH: &str = "/org/swayr/Daemon";
+
+/// The connection used for emitting signals once the service is running.
+static CONNECTION: OnceCell<zbus::blocking::Connection> = OnceCell::new();
+
+struct SwayrDaemon {
+    fdata: FocusData,
+}
+
+impl SwayrDaemon {
+    fn exec(&self, cmd: cmds::SwayrCommand) -> zbus::fdo::Result<String> {
+        cmds::exec_swayr_cmd(cmds::ExecSwayrCmdArgs {
+            cmd: &cmd,
+            focus_data: &self.fdata,
+        })
+        .map_err(|err| zbus::fdo::Error::Failed(err.to_string()))
+    }
+}
+
+#[zbus::interface(name = "org.swayr.Daemon")]
+impl SwayrDaemon {
+    /// Executes the swayr command given like on the command line, e.g.,
+    /// `["next-window", "current-workspace"]`, and returns its result.
+    fn execute(&self, args: Vec<String>) -> zbus::fdo::Result<String> {
+        let cmd = cmds::parse_swayr_command(&args)
+            .map_err(zbus::fdo::Error::InvalidArgs)?;
+        self.exec(cmd)
+    }
+
+    /// Executes the swayr command given in its JSON form like sent over the
+    /// UNIX socket and returns its result.
+    fn execute_json(&self, json: &str) -> zbus::fdo::Result<String> {
+        let cmd = cmds::parse_swayr_command_json(json)
+            .map_err(|e| zbus::fdo::Error::InvalidArgs(e.to_string()))?;
+        self.exec(cmd)
+    }
+
+    /// Emitted when the window with the given con_id got the focus.
+    #[zbus(signal)]
+    async fn focus_changed(
+        emitter: &SignalEmitter<'_>,
+        id: i64,
+    ) -> zbus::Result<()>;
+
+    /// Emitted when the window with the given con_id became urgent or not.
+    #[zbus(signal)]
+    async fn urgency_changed(
+        emitter: &SignalEmitter<'_>,
+        id: i64,
+        urgent: bool,
+    ) -> zbus::Result<()>;
+}
+
+/// Registers the D-Bus service on the session bus.  The method calls are
+/// handled by zbus' own threads.
+pub fn serve(fdata: FocusData) {
+    let result = connection::Builder::session()
+        .and_then(|b| b.name(SERVICE_NAME))
+        .and_then(|b| b.serve_at(OBJECT_PATH, SwayrDaemon { fdata }))
+        .and_then(|b| b.build());
+    match result {
+        Ok(con) => {
+            log::info!("Serving D-Bus service {SERVICE_NAME}.");
+            let _ = CONNECTION.set(con);
+        }
+        Err(err) => log::error!("Could not start D-Bus service: {err}"),
+    }
+}
+
+fn emit<B>(signal: &str, body: &B)
+where
+    B: serde::Serialize + zbus::zvariant::DynamicType,
+{
+    if let Some(con) = CONNECTION.get() {
+        if let Err(err) =
+            con.emit_signal(None::<()>, OBJECT_PATH, SERVICE_NAME, signal, body)
+        {
+            log::error!("Could not emit D-Bus signal {signal}: {err}");
+        }
+    }
+}
+
+pub fn emit_focus_changed(id: i64) {
+    emit("FocusChanged", &(id,));
+}
+
+pub fn emit_urgency_changed(id: i64, urgent: bool) {
+    emit("UrgencyChanged", &(id, urgent));
+}
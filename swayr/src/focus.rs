@@ -15,19 +15,216 @@
 
 //! Structure to hold window focus timestamps used by swayrd
 
+use serde::Serialize;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::sync::mpsc;
 use std::sync::Arc;
 use std::sync::RwLock;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+use std::time::Instant;
+
+/// The maximum number of recently closed windows which are remembered.
+const MAX_CLOSED_WINDOWS: usize = 32;
+
+/// The maximum number of entries in the focus history.
+const MAX_FOCUS_HISTORY: usize = 100;
+
+/// `follow-matching` is switched off if it had to re-focus more often than
+/// this within a second which indicates a focus fight with some other window.
+const MAX_FOLLOW_CORRECTIONS_PER_SEC: usize = 10;
+
+/// The maximum number of workspaces remembered per output.
+const MAX_OUTPUT_WORKSPACES: usize = 100;
 
 /// Data tracking most recent focus events for Sway windows/containers
 #[derive(Clone)]
 pub struct FocusData {
     pub focus_tick_by_id: Arc<RwLock<HashMap<i64, u64>>>,
+    /// The time at which a node has last been focused, or has first been seen
+    /// if it has never been focused.
+    pub last_use_by_id: Arc<RwLock<HashMap<i64, Instant>>>,
     pub focus_chan: mpsc::Sender<FocusMessage>,
+    /// The tick at which a window most recently got its urgency hint.
+    pub urgent_tick_by_id: Arc<RwLock<HashMap<i64, u64>>>,
+    pub window_info_by_id: Arc<RwLock<HashMap<i64, WindowInfo>>>,
+    pub closed_windows: Arc<RwLock<VecDeque<WindowInfo>>>,
+    /// Counter of window events used to wake up threads waiting for windows.
+    pub window_event_ctr: Arc<(Mutex<u64>, Condvar)>,
+    pub workspace_history: Arc<RwLock<WorkspaceHistory>>,
+    /// The ids of the locked-in focused nodes, most recent first.
+    pub focus_history: Arc<RwLock<VecDeque<i64>>>,
+    /// The preferred output of each workspace by name.
+    pub workspace_outputs: Arc<RwLock<HashMap<String, String>>>,
+    /// The names of the workspaces focused on each output, most recent first.
+    pub output_workspace_lru: Arc<RwLock<HashMap<String, VecDeque<String>>>>,
+    /// The focus bookmarks by name.
+    pub focus_bookmarks: Arc<RwLock<HashMap<String, FocusBookmark>>>,
+    /// The usage of the swayr commands by their display string.
+    pub command_usage: Arc<RwLock<HashMap<String, CommandUsage>>>,
+    /// The state of `follow-matching` if active.
+    pub follow: Arc<Mutex<Option<FollowState>>>,
+    /// The payload of the most recent sway tick event received by swayrd's
+    /// event loop.
+    pub sway_tick: Arc<(Mutex<String>, Condvar)>,
+    /// The do-not-disturb mode if active.
+    pub do_not_disturb: Arc<RwLock<Option<DoNotDisturb>>>,
+}
+
+/// While do-not-disturb is active, urgent windows aren't preferred by the
+/// `switch-to-*-or-urgent-or-lru-window` commands.
+#[derive(Debug, Clone, Copy)]
+pub struct DoNotDisturb {
+    /// Also remove the urgency hints of windows.
+    pub clear_urgency: bool,
+}
+
+/// The criteria query of the windows `follow-matching` keeps the focus on and
+/// the times of its recent corrective focus changes.
+#[derive(Debug)]
+pub struct FollowState {
+    pub criteria: String,
+    corrections: VecDeque<Instant>,
+}
+
+/// Browser-like back/forward history of focused workspaces.
+#[derive(Debug, Default)]
+pub struct WorkspaceHistory {
+    entries: Vec<String>,
+    pos: usize,
+    /// The workspace we're navigating to using back/forward whose focus event
+    /// must not be recorded.
+    navigating_to: Option<String>,
+}
+
+impl WorkspaceHistory {
+    /// Records that workspace `name` got focused dropping all forward entries
+    /// and keeping at most `depth` entries.
+    pub fn record(&mut self, name: &str, depth: usize) {
+        if self.navigating_to.as_deref() == Some(name) {
+            self.navigating_to = None;
+            return;
+        }
+        self.navigating_to = None;
+        if self.entries.get(self.pos).is_some_and(|e| e == name) {
+            return;
+        }
+        self.entries.truncate(self.pos + 1);
+        self.entries.push(name.to_owned());
+        if self.entries.len() > depth {
+            self.entries.drain(..self.entries.len() - depth);
+        }
+        self.pos = self.entries.len().saturating_sub(1);
+    }
+
+    /// Moves back (or forward) in the history and returns the workspace which
+    /// should be focused.
+    pub fn step(&mut self, back: bool) -> Option<String> {
+        let pos = if back {
+            self.pos.checked_sub(1)?
+        } else if self.pos + 1 < self.entries.len() {
+            self.pos + 1
+        } else {
+            return None;
+        };
+        self.pos = pos;
+        self.navigating_to = Some(self.entries[pos].clone());
+        self.navigating_to.clone()
+    }
+
+    /// Undoes the last [`WorkspaceHistory::step`] if switching to the
+    /// workspace failed.
+    pub fn undo_step(&mut self, back: bool) {
+        self.navigating_to = None;
+        if back {
+            self.pos += 1;
+        } else {
+            self.pos -= 1;
+        }
+    }
+}
+
+/// Information about a window which must be recorded while it is still alive
+/// because it is needed after the window has been closed.
+#[derive(Clone, Debug, Serialize)]
+pub struct WindowInfo {
+    pub app_name: String,
+    pub title: String,
+    pub workspace: Option<String>,
+    /// The command line of the window's process as read from /proc.
+    pub cmdline: Vec<String>,
+}
+
+/// A saved focus context which can be restored later.
+#[derive(Clone, Debug, Serialize)]
+pub struct FocusBookmark {
+    pub output: String,
+    pub workspace: String,
+    pub window_id: i64,
+    /// Used for finding a replacement if the window has been closed.
+    pub app_name: String,
+    pub title: String,
+    /// The absolute position and size of the window if it was floating.
+    pub floating_rect: Option<(i32, i32, i32, i32)>,
+}
+
+/// How often and when a swayr command has been executed.
+#[derive(Clone, Copy, Debug)]
+pub struct CommandUsage {
+    pub count: u64,
+    pub last_used: Instant,
+}
+
+impl CommandUsage {
+    /// The usage count weighted by how recently the command has been used.
+    pub fn frecency(&self, now: Instant) -> f64 {
+        let hours = now.duration_since(self.last_used).as_secs() / 3600;
+        let weight = match hours {
+            0 => 4.0,
+            1..=23 => 2.0,
+            24..=167 => 1.0,
+            _ => 0.5,
+        };
+        self.count as f64 * weight
+    }
+}
+
+/// The sizes of the maps and queues of [`FocusData`].
+#[derive(Debug, Serialize)]
+pub struct FocusDataStats {
+    pub focus_ticks: usize,
+    pub urgent_ticks: usize,
+    pub window_infos: usize,
+    pub closed_windows: usize,
+    pub focus_history: usize,
 }
 
 impl FocusData {
+    pub fn new(focus_chan: mpsc::Sender<FocusMessage>) -> FocusData {
+        FocusData {
+            focus_tick_by_id: Arc::new(RwLock::new(HashMap::new())),
+            last_use_by_id: Arc::new(RwLock::new(HashMap::new())),
+            focus_chan,
+            urgent_tick_by_id: Arc::new(RwLock::new(HashMap::new())),
+            window_info_by_id: Arc::new(RwLock::new(HashMap::new())),
+            closed_windows: Arc::new(RwLock::new(VecDeque::new())),
+            window_event_ctr: Arc::new((Mutex::new(0), Condvar::new())),
+            workspace_history: Arc::new(RwLock::new(
+                WorkspaceHistory::default(),
+            )),
+            focus_history: Arc::new(RwLock::new(VecDeque::new())),
+            workspace_outputs: Arc::new(RwLock::new(HashMap::new())),
+            output_workspace_lru: Arc::new(RwLock::new(HashMap::new())),
+            focus_bookmarks: Arc::new(RwLock::new(HashMap::new())),
+            command_usage: Arc::new(RwLock::new(HashMap::new())),
+            follow: Arc::new(Mutex::new(None)),
+            sway_tick: Arc::new((Mutex::new(String::new()), Condvar::new())),
+            do_not_disturb: Arc::new(RwLock::new(None)),
+        }
+    }
+
     pub fn last_focus_tick(&self, id: i64) -> u64 {
         *self.focus_tick_by_id.read().unwrap().get(&id).unwrap_or(&0)
     }
@@ -36,12 +233,35 @@ impl FocusData {
         let mut write_lock = self.focus_tick_by_id.write().unwrap();
         if let Some(tick) = write_lock.get_mut(&id) {
             *tick = focus_val;
+            self.last_use_by_id
+                .write()
+                .unwrap()
+                .insert(id, Instant::now());
         }
         // else the node has since been closed before this focus event got locked in
     }
 
     pub fn remove_focus_data(&self, id: i64) {
         self.focus_tick_by_id.write().unwrap().remove(&id);
+        self.last_use_by_id.write().unwrap().remove(&id);
+        self.urgent_tick_by_id.write().unwrap().remove(&id);
+    }
+
+    pub fn last_use(&self, id: i64) -> Option<Instant> {
+        self.last_use_by_id.read().unwrap().get(&id).copied()
+    }
+
+    pub fn last_urgent_tick(&self, id: i64) -> u64 {
+        *self
+            .urgent_tick_by_id
+            .read()
+            .unwrap()
+            .get(&id)
+            .unwrap_or(&0)
+    }
+
+    pub fn update_last_urgent_tick(&self, id: i64, tick: u64) {
+        self.urgent_tick_by_id.write().unwrap().insert(id, tick);
     }
 
     /// Ensures that a given node_id is present in the ExtraProps map, this
@@ -51,6 +271,342 @@ impl FocusData {
         let mut write_lock = self.focus_tick_by_id.write().unwrap();
         if write_lock.get(&id).is_none() {
             write_lock.insert(id, 0);
+            self.last_use_by_id
+                .write()
+                .unwrap()
+                .insert(id, Instant::now());
+        }
+    }
+
+    /// Returns true if the node `id` is known, see [`FocusData::ensure_id`].
+    pub fn has_id(&self, id: i64) -> bool {
+        self.focus_tick_by_id.read().unwrap().contains_key(&id)
+    }
+
+    pub fn has_window_info(&self, id: i64) -> bool {
+        self.window_info_by_id.read().unwrap().contains_key(&id)
+    }
+
+    pub fn set_window_info(&self, id: i64, info: WindowInfo) {
+        self.window_info_by_id.write().unwrap().insert(id, info);
+    }
+
+    pub fn get_window_workspace(&self, id: i64) -> Option<String> {
+        self.window_info_by_id
+            .read()
+            .unwrap()
+            .get(&id)
+            .and_then(|info| info.workspace.clone())
+    }
+
+    /// Moves the info of the window with the given id to the front of the
+    /// recently closed windows.  `app_name` and `title` are the ones reported
+    /// at close time and supersede the recorded ones.
+    pub fn window_closed(&self, id: i64, app_name: &str, title: &str) {
+        let info = self.window_info_by_id.write().unwrap().remove(&id);
+        let info = match info {
+            Some(info) => WindowInfo {
+                app_name: app_name.to_owned(),
+                title: title.to_owned(),
+                ..info
+            },
+            None => WindowInfo {
+                app_name: app_name.to_owned(),
+                title: title.to_owned(),
+                workspace: None,
+                cmdline: vec![],
+            },
+        };
+        let mut closed = self.closed_windows.write().unwrap();
+        closed.push_front(info);
+        closed.truncate(MAX_CLOSED_WINDOWS);
+    }
+
+    pub fn get_closed_windows(&self) -> Vec<WindowInfo> {
+        self.closed_windows
+            .read()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Removes and returns the most recently closed window whose command line
+    /// is known.
+    pub fn take_last_reopenable_window(&self) -> Option<WindowInfo> {
+        let mut closed = self.closed_windows.write().unwrap();
+        let idx = closed.iter().position(|w| !w.cmdline.is_empty())?;
+        closed.remove(idx)
+    }
+
+    pub fn get_window_event_count(&self) -> u64 {
+        *self.window_event_ctr.0.lock().unwrap()
+    }
+
+    /// Wakes up all threads blocked in [`FocusData::wait_for_window_event`].
+    pub fn notify_window_event(&self) {
+        let (lock, cvar) = &*self.window_event_ctr;
+        *lock.lock().unwrap() += 1;
+        cvar.notify_all();
+    }
+
+    /// Blocks until the window event counter differs from `count` or the
+    /// `timeout` expired.  Returns `false` in the latter case.
+    pub fn wait_for_window_event(&self, count: u64, timeout: Duration) -> bool {
+        let (lock, cvar) = &*self.window_event_ctr;
+        let guard = lock.lock().unwrap();
+        let (_guard, result) = cvar
+            .wait_timeout_while(guard, timeout, |ctr| *ctr == count)
+            .unwrap();
+        !result.timed_out()
+    }
+
+    pub fn notify_sway_tick(&self, payload: &str) {
+        let (lock, cvar) = &*self.sway_tick;
+        payload.clone_into(&mut lock.lock().unwrap());
+        cvar.notify_all();
+    }
+
+    /// Blocks until a sway tick event with the given `payload` has been
+    /// received or the `timeout` expired.  Returns `false` in the latter case.
+    pub fn wait_for_sway_tick(&self, payload: &str, timeout: Duration) -> bool {
+        let (lock, cvar) = &*self.sway_tick;
+        let guard = lock.lock().unwrap();
+        let (_guard, result) = cvar
+            .wait_timeout_while(guard, timeout, |p| p != payload)
+            .unwrap();
+        !result.timed_out()
+    }
+
+    pub fn record_focus_history(&self, id: i64) {
+        let mut history = self.focus_history.write().unwrap();
+        if history.front() != Some(&id) {
+            history.push_front(id);
+            history.truncate(MAX_FOCUS_HISTORY);
+        }
+    }
+
+    pub fn get_focus_history(&self) -> Vec<i64> {
+        self.focus_history.read().unwrap().iter().copied().collect()
+    }
+
+    pub fn get_preferred_output(&self, workspace: &str) -> Option<String> {
+        self.workspace_outputs
+            .read()
+            .unwrap()
+            .get(workspace)
+            .cloned()
+    }
+
+    pub fn set_preferred_output(&self, workspace: &str, output: &str) {
+        self.workspace_outputs
+            .write()
+            .unwrap()
+            .insert(workspace.to_owned(), output.to_owned());
+    }
+
+    pub fn get_workspace_outputs(&self) -> HashMap<String, String> {
+        self.workspace_outputs.read().unwrap().clone()
+    }
+
+    /// Records that `workspace` has been focused on `output`.  Workspaces are
+    /// identified by name so that their recency survives them being
+    /// destroyed and re-created.
+    pub fn record_output_workspace_focus(&self, output: &str, workspace: &str) {
+        let mut lru = self.output_workspace_lru.write().unwrap();
+        // The workspace might have been moved from another output.
+        for names in lru.values_mut() {
+            names.retain(|n| n != workspace);
+        }
+        let names = lru.entry(output.to_owned()).or_default();
+        names.push_front(workspace.to_owned());
+        names.truncate(MAX_OUTPUT_WORKSPACES);
+    }
+
+    /// Returns the position of `workspace` in the recency order of the
+    /// workspaces of `output`, 0 being the most recently focused one.
+    pub fn get_output_workspace_recency(
+        &self,
+        output: &str,
+        workspace: &str,
+    ) -> Option<usize> {
+        self.output_workspace_lru
+            .read()
+            .unwrap()
+            .get(output)
+            .and_then(|names| names.iter().position(|n| n == workspace))
+    }
+
+    pub fn get_do_not_disturb(&self) -> Option<DoNotDisturb> {
+        *self.do_not_disturb.read().unwrap()
+    }
+
+    pub fn set_do_not_disturb(&self, dnd: Option<DoNotDisturb>) {
+        *self.do_not_disturb.write().unwrap() = dnd;
+    }
+
+    pub fn get_follow_criteria(&self) -> Option<String> {
+        self.follow
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|f| f.criteria.clone())
+    }
+
+    pub fn set_follow_criteria(&self, criteria: Option<String>) {
+        *self.follow.lock().unwrap() = criteria.map(|criteria| FollowState {
+            criteria,
+            corrections: VecDeque::new(),
+        });
+    }
+
+    /// Records a corrective focus change of `follow-matching`.  Returns false
+    /// and stops following if there were too many of them recently.
+    pub fn record_follow_correction(&self) -> bool {
+        let mut follow = self.follow.lock().unwrap();
+        let Some(state) = follow.as_mut() else {
+            return false;
+        };
+        let now = Instant::now();
+        state
+            .corrections
+            .retain(|t| now.duration_since(*t) < Duration::from_secs(1));
+        state.corrections.push_back(now);
+        if state.corrections.len() > MAX_FOLLOW_CORRECTIONS_PER_SEC {
+            *follow = None;
+            false
+        } else {
+            true
+        }
+    }
+
+    pub fn record_command_usage(&self, cmd: &str) {
+        let now = Instant::now();
+        self.command_usage
+            .write()
+            .unwrap()
+            .entry(cmd.to_owned())
+            .and_modify(|u| {
+                u.count += 1;
+                u.last_used = now;
+            })
+            .or_insert(CommandUsage {
+                count: 1,
+                last_used: now,
+            });
+    }
+
+    /// Returns the frecency scores of the given commands, zero for unused ones.
+    pub fn get_command_frecencies(&self, cmds: &[String]) -> Vec<f64> {
+        let now = Instant::now();
+        let usage = self.command_usage.read().unwrap();
+        cmds.iter()
+            .map(|c| usage.get(c).map(|u| u.frecency(now)).unwrap_or(0.0))
+            .collect()
+    }
+
+    pub fn get_focus_bookmark(&self, name: &str) -> Option<FocusBookmark> {
+        self.focus_bookmarks.read().unwrap().get(name).cloned()
+    }
+
+    pub fn set_focus_bookmark(&self, name: &str, bookmark: FocusBookmark) {
+        self.focus_bookmarks
+            .write()
+            .unwrap()
+            .insert(name.to_owned(), bookmark);
+    }
+
+    /// Returns all focus bookmarks sorted by name.
+    pub fn get_focus_bookmarks(&self) -> Vec<(String, FocusBookmark)> {
+        let mut bookmarks: Vec<(String, FocusBookmark)> = self
+            .focus_bookmarks
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, b)| (name.clone(), b.clone()))
+            .collect();
+        bookmarks.sort_by(|a, b| a.0.cmp(&b.0));
+        bookmarks
+    }
+
+    pub fn record_workspace_focus(&self, name: &str, depth: usize) {
+        self.workspace_history.write().unwrap().record(name, depth);
+    }
+
+    pub fn step_workspace_history(&self, back: bool) -> Option<String> {
+        self.workspace_history.write().unwrap().step(back)
+    }
+
+    pub fn undo_workspace_history_step(&self, back: bool) {
+        self.workspace_history.write().unwrap().undo_step(back);
+    }
+
+    /// Removes the data of all nodes whose ids are not in `live_ids`, i.e.,
+    /// of nodes whose close event has been missed.  Returns the number of
+    /// removed focus ticks.
+    pub fn retain_live_ids(&self, live_ids: &HashSet<i64>) -> usize {
+        let mut ticks = self.focus_tick_by_id.write().unwrap();
+        let len = ticks.len();
+        ticks.retain(|id, _| live_ids.contains(id));
+        self.last_use_by_id
+            .write()
+            .unwrap()
+            .retain(|id, _| live_ids.contains(id));
+        self.urgent_tick_by_id
+            .write()
+            .unwrap()
+            .retain(|id, _| live_ids.contains(id));
+        self.window_info_by_id
+            .write()
+            .unwrap()
+            .retain(|id, _| live_ids.contains(id));
+        self.focus_history
+            .write()
+            .unwrap()
+            .retain(|id| live_ids.contains(id));
+        len - ticks.len()
+    }
+
+    /// Forgets the recency of use of all nodes so that they're all treated
+    /// as never focused.  The nodes are kept so that their next focus is
+    /// recorded again.
+    pub fn reset_focus_ticks(&self) {
+        for tick in self.focus_tick_by_id.write().unwrap().values_mut() {
+            *tick = 0;
+        }
+        let now = Instant::now();
+        for last_use in self.last_use_by_id.write().unwrap().values_mut() {
+            *last_use = now;
+        }
+    }
+
+    /// Forgets the workspace back/forward history, the focus history, and the
+    /// workspaces recently shown on each output.
+    pub fn reset_history(&self) {
+        *self.workspace_history.write().unwrap() = WorkspaceHistory::default();
+        self.focus_history.write().unwrap().clear();
+        self.output_workspace_lru.write().unwrap().clear();
+    }
+
+    pub fn clear_focus_bookmarks(&self) {
+        self.focus_bookmarks.write().unwrap().clear();
+    }
+
+    /// Renumbers the focus and urgent ticks to `1..=n` keeping their order
+    /// and returns the new maximum tick.
+    pub fn renormalize_ticks(&self) -> u64 {
+        let mut focus_ticks = self.focus_tick_by_id.write().unwrap();
+        let mut urgent_ticks = self.urgent_tick_by_id.write().unwrap();
+        renormalize_ticks(&mut [&mut focus_ticks, &mut urgent_ticks])
+    }
+
+    pub fn get_stats(&self) -> FocusDataStats {
+        FocusDataStats {
+            focus_ticks: self.focus_tick_by_id.read().unwrap().len(),
+            urgent_ticks: self.urgent_tick_by_id.read().unwrap().len(),
+            window_infos: self.window_info_by_id.read().unwrap().len(),
+            closed_windows: self.closed_windows.read().unwrap().len(),
+            focus_history: self.focus_history.read().unwrap().len(),
         }
     }
 
@@ -65,6 +621,33 @@ impl FocusData {
     }
 }
 
+/// Renumbers the non-zero ticks of all `maps` to `1..=n` keeping their order.
+/// Zero means "never" and stays as is.  Returns `n`.
+fn renormalize_ticks(maps: &mut [&mut HashMap<i64, u64>]) -> u64 {
+    let mut ticks: Vec<u64> = maps
+        .iter()
+        .flat_map(|m| m.values().copied())
+        .filter(|t| *t > 0)
+        .collect();
+    ticks.sort_unstable();
+    ticks.dedup();
+    for m in maps.iter_mut() {
+        for tick in m.values_mut().filter(|t| **t > 0) {
+            *tick = ticks.binary_search(tick).unwrap() as u64 + 1;
+        }
+    }
+    ticks.len() as u64
+}
+
+#[test]
+fn test_renormalize_ticks() {
+    let mut focus = HashMap::from([(1, 0), (2, 1000), (3, 17), (4, 99999)]);
+    let mut urgent = HashMap::from([(2, 500), (4, 99999)]);
+    assert_eq!(4, renormalize_ticks(&mut [&mut focus, &mut urgent]));
+    assert_eq!(focus, HashMap::from([(1, 0), (2, 3), (3, 1), (4, 4)]));
+    assert_eq!(urgent, HashMap::from([(2, 2), (4, 4)]));
+}
+
 pub struct FocusEvent {
     pub node_id: i64,      // node receiving the focus
     pub ev_focus_ctr: u64, // Counter for this specific focus event
@@ -75,3 +658,107 @@ pub enum FocusMessage {
     TickUpdateActivate,
     FocusEvent(FocusEvent),
 }
+
+#[test]
+fn test_reset_focus_data() {
+    let (tx, _rx) = mpsc::channel();
+    let fdata = FocusData::new(tx);
+    fdata
+        .focus_tick_by_id
+        .write()
+        .unwrap()
+        .extend([(1, 5), (2, 7)]);
+    fdata.record_focus_history(2);
+    fdata.record_workspace_focus("1", 10);
+    fdata.reset_focus_ticks();
+    assert_eq!(fdata.last_focus_tick(2), 0);
+    // Focus events of known nodes are still recorded.
+    fdata.update_last_focus_tick(1, 8);
+    assert_eq!(fdata.last_focus_tick(1), 8);
+
+    fdata.reset_history();
+    assert!(fdata.get_focus_history().is_empty());
+    assert!(fdata.workspace_history.read().unwrap().entries.is_empty());
+}
+
+#[test]
+fn test_workspace_history() {
+    let mut hist = WorkspaceHistory::default();
+    for ws in ["1", "2", "3"] {
+        hist.record(ws, 10);
+    }
+    assert_eq!(hist.step(true).as_deref(), Some("2"));
+    hist.record("2", 10);
+    assert_eq!(hist.step(true).as_deref(), Some("1"));
+    hist.record("1", 10);
+    assert_eq!(hist.step(true), None);
+    assert_eq!(hist.step(false).as_deref(), Some("2"));
+    hist.record("2", 10);
+
+    // Focusing another workspace drops the forward entries.
+    hist.record("4", 10);
+    assert_eq!(hist.step(false), None);
+    assert_eq!(hist.entries, vec!["1", "2", "4"]);
+
+    hist.record("5", 2);
+    assert_eq!(hist.entries, vec!["4", "5"]);
+    assert_eq!(hist.pos, 1);
+}
+
+#[test]
+fn test_output_workspace_recency() {
+    let (tx, _rx) = mpsc::channel();
+    let fdata = FocusData::new(tx);
+    for (output, ws) in [("DP-1", "1"), ("DP-1", "2"), ("HDMI-A-1", "3")] {
+        fdata.record_output_workspace_focus(output, ws);
+    }
+    assert_eq!(Some(0), fdata.get_output_workspace_recency("DP-1", "2"));
+    assert_eq!(Some(1), fdata.get_output_workspace_recency("DP-1", "1"));
+    // Workspace 1 moved to HDMI-A-1.
+    fdata.record_output_workspace_focus("HDMI-A-1", "1");
+    assert_eq!(None, fdata.get_output_workspace_recency("DP-1", "1"));
+    assert_eq!(Some(1), fdata.get_output_workspace_recency("HDMI-A-1", "3"));
+}
+
+#[test]
+fn test_command_usage_frecency() {
+    let then = Instant::now();
+    let now = then + Duration::from_secs(200 * 3600);
+    let recent = CommandUsage {
+        count: 2,
+        last_used: now,
+    };
+    let old = CommandUsage {
+        count: 5,
+        last_used: then,
+    };
+    assert!(recent.frecency(now) > old.frecency(now));
+}
+
+#[test]
+fn test_follow_corrections() {
+    let (tx, _rx) = mpsc::channel();
+    let fdata = FocusData::new(tx);
+    assert!(!fdata.record_follow_correction());
+    fdata.set_follow_criteria(Some("[app_id=foot]".to_owned()));
+    for _ in 0..MAX_FOLLOW_CORRECTIONS_PER_SEC {
+        assert!(fdata.record_follow_correction());
+    }
+    // A focus fight stops following.
+    assert!(!fdata.record_follow_correction());
+    assert_eq!(None, fdata.get_follow_criteria());
+}
+
+#[test]
+fn test_wait_for_sway_tick() {
+    let (tx, _rx) = mpsc::channel();
+    let fdata = FocusData::new(tx);
+    assert!(!fdata.wait_for_sway_tick("a", Duration::from_millis(10)));
+    let fdata2 = fdata.clone();
+    let waiter = std::thread::spawn(move || {
+        fdata2.wait_for_sway_tick("a", Duration::from_secs(10))
+    });
+    std::thread::sleep(Duration::from_millis(10));
+    fdata.notify_sway_tick("a");
+    assert!(waiter.join().unwrap());
+}
@@ -15,13 +15,20 @@
 
 //! Functions and data structures of the swayrd daemon.
 
+use crate::cmds::NeighborDirection;
 use crate::config;
+use crate::criteria;
+use crate::focus::FocusData;
 use crate::shared::ipc;
 use crate::shared::ipc::NodeMethods;
+use crate::tree as t;
+use once_cell::sync::Lazy;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Mutex;
 use swayipc as s;
 
-pub fn auto_tile(res_to_min_width: &HashMap<i32, i32>) {
+pub fn auto_tile(res_to_min_width: &HashMap<i32, i32>, never_touch: &[i64]) {
     if let Ok(mut con) = s::Connection::new() {
         if let Ok(tree) = con.get_tree() {
             for output in &tree.nodes {
@@ -58,6 +65,7 @@ pub fn auto_tile(res_to_min_width: &HashMap<i32, i32>) {
                             .nodes
                             .iter()
                             .filter(|n| n.get_type() == ipc::Type::Window)
+                            .filter(|n| !never_touch.contains(&n.id))
                         {
                             // Width if we'd split once more.
                             let estimated_width =
@@ -118,69 +126,773 @@ pub fn auto_tile(res_to_min_width: &HashMap<i32, i32>) {
     }
 }
 
-pub fn maybe_auto_tile(config: &config::Config) {
+pub fn maybe_auto_tile(config: &config::Config, fdata: &FocusData) {
     if config.is_layout_auto_tile() {
         log::debug!("auto_tile: start");
         auto_tile(
             &config
                 .get_layout_auto_tile_min_window_width_per_output_width_as_map(
                 ),
+            &matching_window_ids(&config.get_layout_never_touch(), fdata),
         );
         log::debug!("auto_tile: end");
     }
 }
 
+/// Returns the ids of all windows matching at least one of the given criteria
+/// queries.
+pub fn matching_window_ids(criteria: &[String], fdata: &FocusData) -> Vec<i64> {
+    if criteria.is_empty() {
+        return vec![];
+    }
+    let root = ipc::get_root_node(false);
+    let tree = t::get_tree(&root);
+    let wins = tree.get_windows(fdata);
+    let mut ids = vec![];
+    for query in criteria {
+        match criteria::parse_criteria(query) {
+            Ok(crit) => {
+                let pred = criteria::criterion_to_predicate(&crit, &wins);
+                ids.extend(wins.iter().filter(|w| pred(w)).map(|w| w.node.id));
+            }
+            Err(err) => log::error!("Invalid criteria query {query}: {err}"),
+        }
+    }
+    ids
+}
+
+/// Tiles the floating window `win` if it matches `layout.auto_unfloat` but not
+/// `layout.never_touch`.  The criteria are matched against the windows of
+/// `root`.  Windows in the scratchpad are floating by design and left alone.
+pub fn maybe_auto_unfloat(
+    config: &config::Config,
+    root: &s::Node,
+    win: &s::Node,
+) {
+    if !win.is_floating() {
+        return;
+    }
+    let auto_unfloat = config.get_layout_auto_unfloat();
+    if auto_unfloat.is_empty()
+        || is_in_scratchpad(root, win.id)
+        || !t::window_ids_matching_any(root, &auto_unfloat).contains(&win.id)
+        || t::window_ids_matching_any(root, &config.get_layout_never_touch())
+            .contains(&win.id)
+    {
+        return;
+    }
+    log::debug!("Auto-unfloating window {}", win.id);
+    match s::Connection::new() {
+        Ok(mut con) => {
+            if let Err(err) =
+                con.run_command(format!("[con_id={}] floating disable", win.id))
+            {
+                log::error!("Couldn't unfloat window {}: {err}", win.id)
+            }
+        }
+        Err(err) => {
+            log::error!("Couldn't get connection for auto_unfloat: {err}")
+        }
+    }
+}
+
+fn is_in_scratchpad(root: &s::Node, id: i64) -> bool {
+    root.nodes_of_type(ipc::Type::Workspace)
+        .iter()
+        .any(|ws| ws.is_scratchpad() && ws.iter().any(|n| n.id == id))
+}
+
+/// Quotes `s` as argument of a sway command.
+pub fn sway_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[test]
+fn test_sway_quote() {
+    assert_eq!(sway_quote("%title"), r#""%title""#);
+    assert_eq!(sway_quote(r#"say "hi" \o/"#), r#""say \"hi\" \\o/""#);
+}
+
+/// Sets the title format of the new window `win` to the format of the first
+/// `layout.title_format` entry matching it among the windows of `root`.
+pub fn maybe_apply_title_format(
+    config: &config::Config,
+    root: &s::Node,
+    win: &s::Node,
+) {
+    let Some(format) = config
+        .get_layout_title_formats()
+        .into_iter()
+        .find(|(criteria, _)| {
+            t::window_ids_matching_any(root, std::slice::from_ref(criteria))
+                .contains(&win.id)
+        })
+        .map(|(_, format)| format)
+    else {
+        return;
+    };
+    log::debug!("Setting title format of window {} to {format}", win.id);
+    match s::Connection::new() {
+        Ok(mut con) => {
+            if let Err(err) = con.run_command(format!(
+                "[con_id={}] title_format {}",
+                win.id,
+                sway_quote(&format)
+            )) {
+                log::error!("Couldn't set title format of {}: {err}", win.id)
+            }
+        }
+        Err(err) => {
+            log::error!("Couldn't get connection for title_format: {err}")
+        }
+    }
+}
+
 const SWAYR_TMP_WORKSPACE: &str = "✨";
 
+/// Re-layouts the current workspace by moving its windows to a temporary
+/// workspace and running the commands returned by `insert_wins_cmds` which
+/// move them back.  All moves are sent to sway as a single batch, and the
+/// commands sway didn't run because of an invalid one are run one at a time.
 pub fn relayout_current_workspace<F>(
     include_floating: bool,
-    insert_win_fn: F,
+    insert_wins_cmds: F,
 ) -> Result<String, String>
 where
-    F: Fn(&mut [&s::Node], &mut s::Connection) -> s::Fallible<()>,
+    F: Fn(&mut [&s::Node]) -> Vec<String>,
 {
     let root = ipc::get_root_node(false);
     let workspaces: Vec<&s::Node> = root
         .iter()
         .filter(|n| n.get_type() == ipc::Type::Workspace)
         .collect();
-    match workspaces.iter().find(|ws| ws.is_current()) {
-        Some(cur_ws) => match s::Connection::new() {
-            Ok(mut con) => {
-                let mut moved_wins: Vec<&s::Node> = vec![];
-                let mut focused_win = None;
-                for win in
-                    cur_ws.iter().filter(|n| n.get_type() == ipc::Type::Window)
-                {
-                    if win.focused {
-                        focused_win = Some(win);
-                    }
-                    if !include_floating && win.is_floating() {
-                        continue;
-                    }
-                    moved_wins.push(win);
-                    con.run_command(format!(
-                        "[con_id={}] move to workspace {}",
-                        win.id, SWAYR_TMP_WORKSPACE
-                    ))
-                    .map_err(|err| err.to_string())?;
-                }
+    let cur_ws = workspaces
+        .iter()
+        .find(|ws| ws.is_current())
+        .ok_or_else(|| "No workspace is focused.".to_string())?;
+
+    let mut moved_wins: Vec<&s::Node> = vec![];
+    let mut focused_win = None;
+    for win in cur_ws.iter().filter(|n| n.get_type() == ipc::Type::Window) {
+        if win.focused {
+            focused_win = Some(win);
+        }
+        if !include_floating && win.is_floating() {
+            continue;
+        }
+        moved_wins.push(win);
+    }
+    let ids: Vec<i64> = moved_wins.iter().map(|w| w.id).collect();
+    let mut cmds: Vec<String> = ids
+        .iter()
+        .map(|id| {
+            format!("[con_id={id}] move to workspace {SWAYR_TMP_WORKSPACE}")
+        })
+        .collect();
+    cmds.extend(insert_wins_cmds(moved_wins.as_mut_slice()));
 
-                insert_win_fn(moved_wins.as_mut_slice(), &mut con)
-                    .map_err(|err| err.to_string())?;
-                std::thread::sleep(std::time::Duration::from_millis(25));
+    let mut con = s::Connection::new().map_err(|e| e.to_string())?;
+    run_commands_tolerantly(&cmds, |payload| {
+        con.run_command(payload).map_err(|e| e.to_string())
+    })?;
+    // Sway replies only after having executed the batch, so the windows are
+    // in place now.
 
-                if let Some(win) = focused_win {
-                    con.run_command(format!("[con_id={}] focus", win.id))
-                        .map_err(|err| err.to_string())?;
-                }
-                Ok(format!(
-                    "Re-layouted current workspace {}.",
-                    cur_ws.get_name()
+    if let Some(win) = focused_win {
+        run_commands(&mut con, &[format!("[con_id={}] focus", win.id)])?;
+    }
+    Ok(format!(
+        "Re-layouted current workspace {}.",
+        cur_ws.get_name()
+    ))
+}
+
+/// A mark used temporarily for moving containers around.
+const SWAYR_TMP_MARK: &str = "_swayr_tmp";
+
+/// Moves all non-empty workspaces selected by `pred` to `output`.
+fn move_workspaces_to_output<P>(output: &str, pred: P) -> Result<(), String>
+where
+    P: Fn(&s::Node) -> bool,
+{
+    let root = ipc::get_root_node(false);
+    let cmds: Vec<String> = root
+        .iter()
+        .filter(|n| {
+            n.get_type() == ipc::Type::Workspace
+                && n.output.as_deref() != Some(output)
+                && !(n.nodes.is_empty() && n.floating_nodes.is_empty())
+                && pred(n)
+        })
+        .map(|ws| {
+            log::debug!("Moving workspace {} to {output}", ws.get_name());
+            format!(
+                "[workspace=\"^{}$\"] move workspace to output \"{output}\"",
+                regex::escape(ws.get_name())
+            )
+        })
+        .collect();
+    if cmds.is_empty() {
+        return Ok(());
+    }
+    let mut con = s::Connection::new().map_err(|e| e.to_string())?;
+    run_commands(&mut con, &cmds)
+}
+
+/// Moves the workspaces whose preferred output is `output` back to it.
+pub fn restore_workspaces_to_output(output: &str, fdata: &FocusData) {
+    let preferred = fdata.get_workspace_outputs();
+    if let Err(err) = move_workspaces_to_output(output, |ws| {
+        preferred.get(ws.get_name()).map(|o| o.as_str()) == Some(output)
+    }) {
+        log::error!("Couldn't restore workspaces to {output}: {err}");
+    }
+}
+
+/// Moves the workspaces whose preferred output is the disconnected `output`
+/// to the first connected one of the `fallback` outputs.
+pub fn evacuate_workspaces_of_output(
+    output: &str,
+    fallback: &[String],
+    connected: &HashSet<String>,
+    fdata: &FocusData,
+) {
+    let Some(target) = fallback
+        .iter()
+        .find(|o| o.as_str() != output && connected.contains(*o))
+    else {
+        return;
+    };
+    let preferred = fdata.get_workspace_outputs();
+    if let Err(err) = move_workspaces_to_output(target, |ws| {
+        preferred.get(ws.get_name()).map(|o| o.as_str()) == Some(output)
+    }) {
+        log::error!("Couldn't move workspaces of {output} to {target}: {err}");
+    }
+}
+
+/// The outcome of a command of a batch, `None` if sway didn't run it.
+type BatchOutcome = Option<s::Fallible<()>>;
+
+/// Runs `cmds` as a single IPC message and returns each command together with
+/// its outcome.  Sway executes `;`-separated commands in order, and each
+/// command has its own criteria.
+fn run_command_batch<'a>(
+    con: &mut s::Connection,
+    cmds: &'a [String],
+) -> Result<Vec<(&'a String, BatchOutcome)>, String> {
+    if cmds.is_empty() {
+        return Ok(vec![]);
+    }
+    let payload = cmds.join("; ");
+    log::debug!("Running sway commands: {payload}");
+    let outcomes = con.run_command(payload).map_err(|e| e.to_string())?;
+    Ok(pair_with_outcomes(cmds, outcomes))
+}
+
+/// Pairs each of the `cmds` with its outcome.  Sway stops executing a batch
+/// at the first invalid command, so the commands after it have no outcome.
+fn pair_with_outcomes<T>(
+    cmds: &[String],
+    outcomes: Vec<T>,
+) -> Vec<(&String, Option<T>)> {
+    let mut outcomes = outcomes.into_iter();
+    cmds.iter().map(|cmd| (cmd, outcomes.next())).collect()
+}
+
+#[test]
+fn test_pair_with_outcomes() {
+    let cmds = ["a".to_owned(), "b".to_owned(), "c".to_owned()];
+    let pairs = pair_with_outcomes(&cmds, vec![Ok(()), Err("invalid")]);
+    assert_eq!(
+        vec![
+            (&cmds[0], Some(Ok(()))),
+            (&cmds[1], Some(Err("invalid"))),
+            (&cmds[2], None)
+        ],
+        pairs
+    );
+}
+
+/// Runs `cmds` as a single batch using `run` which sends a payload to sway and
+/// returns the outcomes of its commands.  Failing commands, e.g., a `focus
+/// parent` on an emptied workspace, are only logged.  Sway stops a batch at
+/// the first invalid command, so the commands after it are run one at a time
+/// then, like before batching, and no windows are left behind on the
+/// temporary workspace of a relayout.
+fn run_commands_tolerantly<R, E>(
+    cmds: &[String],
+    mut run: R,
+) -> Result<(), String>
+where
+    R: FnMut(String) -> Result<Vec<Result<(), E>>, String>,
+    E: std::fmt::Display,
+{
+    if cmds.is_empty() {
+        return Ok(());
+    }
+    let payload = cmds.join("; ");
+    log::debug!("Running sway commands: {payload}");
+    let outcomes = run(payload)?;
+    for (cmd, outcome) in pair_with_outcomes(cmds, outcomes) {
+        let outcome = match outcome {
+            Some(outcome) => outcome,
+            None => {
+                log::debug!("Running sway command '{cmd}' on its own.");
+                run(cmd.clone())?.into_iter().next().unwrap_or(Ok(()))
+            }
+        };
+        if let Err(err) = outcome {
+            log::debug!("Sway command '{cmd}' failed: {err}");
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_run_commands_tolerantly() {
+    let cmds = ["a".to_owned(), "b".to_owned(), "c".to_owned()];
+    let mut payloads = vec![];
+    let result = run_commands_tolerantly(&cmds, |payload| {
+        payloads.push(payload.clone());
+        Ok(match payload.as_str() {
+            // Sway stops at the invalid b and doesn't run c.
+            "a; b; c" => vec![Ok(()), Err("invalid")],
+            _ => vec![Ok(())],
+        })
+    });
+    assert_eq!(Ok(()), result);
+    assert_eq!(vec!["a; b; c", "c"], payloads);
+}
+
+fn run_commands(
+    con: &mut s::Connection,
+    cmds: &[String],
+) -> Result<(), String> {
+    for (cmd, outcome) in run_command_batch(con, cmds)? {
+        match outcome {
+            Some(outcome) => outcome
+                .map_err(|e| format!("Sway command '{cmd}' failed: {e}"))?,
+            None => {
+                return Err(format!(
+                    "Sway command '{cmd}' wasn't run because an earlier one \
+                     was invalid."
                 ))
             }
-            Err(err) => Err(err.to_string()),
-        },
-        None => Err("No workspace is focused.".to_string()),
+        }
+    }
+    Ok(())
+}
+
+fn get_focused_tiled_window(root: &s::Node) -> Result<&s::Node, String> {
+    match root
+        .iter()
+        .find(|n| n.focused && n.get_type() == ipc::Type::Window)
+    {
+        Some(win) if win.is_floating() => {
+            Err("The focused window is floating.".to_owned())
+        }
+        Some(win) => Ok(win),
+        None => Err("There's no focused window.".to_owned()),
+    }
+}
+
+/// Moves the focused window out of its parent container into its grandparent
+/// container (or workspace).
+pub fn promote_focused() -> Result<String, String> {
+    let root = ipc::get_root_node(false);
+    let tree = t::get_tree(&root);
+    let win = get_focused_tiled_window(&root)?;
+    let parent = tree
+        .get_parent_node(win.id)
+        .ok_or_else(|| format!("Window {} has no parent.", win.id))?;
+    if parent.get_type() == ipc::Type::Workspace {
+        return Err(format!(
+            "Window {} is already a direct child of its workspace.",
+            win.id
+        ));
+    }
+    let grandparent = tree
+        .get_parent_node(parent.id)
+        .ok_or_else(|| format!("Container {} has no parent.", parent.id))?;
+
+    let cmds = if grandparent.get_type() == ipc::Type::Workspace {
+        // Workspaces cannot be marked but moving a window to another
+        // workspace and back appends it to the workspace's children.
+        vec![
+            format!(
+                "[con_id={}] move to workspace {SWAYR_TMP_WORKSPACE}",
+                win.id
+            ),
+            format!("[con_id={}] move to workspace current", win.id),
+        ]
+    } else {
+        // Moving to the mark of a non-window container adds the moved window
+        // as its last child.
+        vec![
+            format!("[con_id={}] mark --add {SWAYR_TMP_MARK}", grandparent.id),
+            format!("[con_id={}] move to mark {SWAYR_TMP_MARK}", win.id),
+            format!("[con_id={}] unmark {SWAYR_TMP_MARK}", grandparent.id),
+        ]
+    };
+    let mut con = s::Connection::new().map_err(|e| e.to_string())?;
+    run_commands(&mut con, &cmds)?;
+    run_commands(&mut con, &[format!("[con_id={}] focus", win.id)])?;
+    Ok(format!(
+        "Promoted window {} out of container {}.",
+        win.id, parent.id
+    ))
+}
+
+/// Wraps the focused window and its neighbor in the given direction into a
+/// new split container whose layout is `layout` or the one matching the
+/// direction.
+pub fn demote_focused_into(
+    dir: &NeighborDirection,
+    layout: Option<&str>,
+) -> Result<String, String> {
+    let root = ipc::get_root_node(false);
+    let tree = t::get_tree(&root);
+    let win = get_focused_tiled_window(&root)?;
+    let parent = tree
+        .get_parent_node(win.id)
+        .ok_or_else(|| format!("Window {} has no parent.", win.id))?;
+
+    let (horizontal, backward, dir_name) = match dir {
+        NeighborDirection::Left => (true, true, "left"),
+        NeighborDirection::Right => (true, false, "right"),
+        NeighborDirection::Up => (false, true, "up"),
+        NeighborDirection::Down => (false, false, "down"),
+    };
+    let parent_horizontal = match parent.layout {
+        s::NodeLayout::SplitH | s::NodeLayout::Tabbed => true,
+        s::NodeLayout::SplitV | s::NodeLayout::Stacked => false,
+        l => return Err(format!("Cannot handle parent layout {l:?}.")),
+    };
+    let idx = parent
+        .nodes
+        .iter()
+        .position(|n| n.id == win.id)
+        .ok_or_else(|| format!("Window {} not found in parent.", win.id))?;
+    let neighbor = if horizontal != parent_horizontal {
+        None
+    } else if backward {
+        idx.checked_sub(1).and_then(|i| parent.nodes.get(i))
+    } else {
+        parent.nodes.get(idx + 1)
+    }
+    .ok_or_else(|| format!("Window {} has no {dir_name} neighbor.", win.id))?;
+
+    let mut cmds = vec![
+        format!(
+            "[con_id={}] {}",
+            win.id,
+            if horizontal { "splith" } else { "splitv" }
+        ),
+        format!("[con_id={}] mark --add {SWAYR_TMP_MARK}", win.id),
+        // Moving to the mark of a window inserts the moved container right
+        // after that window, i.e., into the new split container.
+        format!("[con_id={}] move to mark {SWAYR_TMP_MARK}", neighbor.id),
+        format!("[con_id={}] unmark {SWAYR_TMP_MARK}", win.id),
+    ];
+    if backward {
+        cmds.push(format!("[con_id={}] move {dir_name}", neighbor.id));
+    }
+    if let Some(layout) = layout {
+        cmds.push(format!("[con_id={}] layout {layout}", win.id));
     }
+    cmds.push(format!("[con_id={}] focus", win.id));
+
+    let mut con = s::Connection::new().map_err(|e| e.to_string())?;
+    run_commands(&mut con, &cmds)?;
+    Ok(format!(
+        "Demoted window {} and its {dir_name} neighbor {} into a new container.",
+        win.id, neighbor.id
+    ))
+}
+
+/// The prefix of the workspaces holding the other windows of a workspace
+/// whose focused window has been maximized.
+const SWAYR_MAXIMIZE_PARKING_PREFIX: &str = "✨max:";
+
+/// Everything needed for putting a maximized window back to its place.
+#[derive(Debug, PartialEq)]
+struct Maximized {
+    win_id: i64,
+    parking_ws: String,
+    /// The nearest ancestor of the window which still exists after moving the
+    /// window away, i.e., its workspace or a container with other children.
+    ancestor_id: i64,
+    /// The children of the ancestor which came after the window.
+    following_ids: Vec<i64>,
+    /// The layouts of the containers between the ancestor and the window
+    /// which vanish when the window is moved away, outermost first.
+    lost_layouts: Vec<s::NodeLayout>,
+    /// The window's width or height in its ancestor's split direction.
+    size: Option<(&'static str, i32)>,
+}
+
+/// The maximized windows by their con ids.  Unlike workspace names, those
+/// don't change when the workspace is renamed while maximized.
+static MAXIMIZED: Lazy<Mutex<HashMap<i64, Maximized>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Makes the focused window the only one on its workspace by moving all
+/// other windows to a parking workspace, or restores the previous arrangement
+/// if the current workspace's window has been maximized already.
+pub fn toggle_maximize_focused() -> Result<String, String> {
+    let root = ipc::get_root_node(false);
+    let tree = t::get_tree(&root);
+    let ws = tree
+        .get_current_workspace()
+        .ok_or_else(|| "No workspace is focused.".to_owned())?;
+    let mut maximized = MAXIMIZED.lock().expect("Could not lock mutex");
+    let mut con = s::Connection::new().map_err(|e| e.to_string())?;
+    // If the maximized window has been closed meanwhile, the parking
+    // workspace is still named after the current one.
+    let key = maximized
+        .iter()
+        .find(|(id, m)| {
+            ws.iter().any(|n| n.id == **id)
+                || m.parking_ws
+                    == format!(
+                        "{SWAYR_MAXIMIZE_PARKING_PREFIX}{}",
+                        ws.get_name()
+                    )
+        })
+        .map(|(id, _)| *id);
+    match key.and_then(|id| maximized.remove(&id)) {
+        Some(m) => {
+            run_commands(
+                &mut con,
+                &restore_maximized_commands(&root, ws, &m)?,
+            )?;
+            Ok(format!("Restored workspace {}.", ws.get_name()))
+        }
+        None => {
+            let (m, cmds) = maximize_focused_commands(&tree, ws)?;
+            run_commands(&mut con, &cmds)?;
+            let msg = format!(
+                "Maximized window {} on workspace {}.",
+                m.win_id,
+                ws.get_name()
+            );
+            maximized.insert(m.win_id, m);
+            Ok(msg)
+        }
+    }
+}
+
+/// Returns what's needed for restoring the focused window of `ws` later and
+/// the commands maximizing it.
+fn maximize_focused_commands(
+    tree: &t::Tree,
+    ws: &s::Node,
+) -> Result<(Maximized, Vec<String>), String> {
+    let win = get_focused_tiled_window(ws)?;
+    if ws.nodes_of_type(ipc::Type::Window).len() < 2 {
+        return Err(format!(
+            "Window {} is the only window on workspace {}.",
+            win.id,
+            ws.get_name()
+        ));
+    }
+
+    let mut lost_layouts = vec![];
+    let mut child = win;
+    let ancestor = loop {
+        let parent = tree
+            .get_parent_node(child.id)
+            .ok_or_else(|| format!("Container {} has no parent.", child.id))?;
+        if parent.get_type() == ipc::Type::Workspace || parent.nodes.len() > 1 {
+            break parent;
+        }
+        lost_layouts.push(parent.layout);
+        child = parent;
+    };
+    lost_layouts.reverse();
+    let idx = ancestor
+        .nodes
+        .iter()
+        .position(|n| n.id == child.id)
+        .ok_or_else(|| {
+            format!("Container {} not found in parent.", child.id)
+        })?;
+    let size = match ancestor.layout {
+        _ if !lost_layouts.is_empty() => None,
+        s::NodeLayout::SplitH => Some(("width", win.rect.width)),
+        s::NodeLayout::SplitV => Some(("height", win.rect.height)),
+        _ => None,
+    };
+
+    let name = ws.get_name();
+    let parking_ws = format!("{SWAYR_MAXIMIZE_PARKING_PREFIX}{name}");
+    let cmds = vec![
+        format!(
+            "rename workspace {} to {}",
+            sway_quote(name),
+            sway_quote(&parking_ws)
+        ),
+        format!("[con_id={}] move to workspace {}", win.id, sway_quote(name)),
+        format!("workspace {}", sway_quote(name)),
+        format!("[con_id={}] focus", win.id),
+    ];
+    let m = Maximized {
+        win_id: win.id,
+        parking_ws,
+        ancestor_id: ancestor.id,
+        following_ids: ancestor.nodes[idx + 1..].iter().map(|n| n.id).collect(),
+        lost_layouts,
+        size,
+    };
+    Ok((m, cmds))
+}
+
+/// Returns the commands moving the windows of the parking workspace of `m`
+/// back to `ws` and its maximized window back to its place.
+fn restore_maximized_commands(
+    root: &s::Node,
+    ws: &s::Node,
+    m: &Maximized,
+) -> Result<Vec<String>, String> {
+    let parking = root
+        .iter()
+        .find(|n| {
+            n.get_type() == ipc::Type::Workspace && n.get_name() == m.parking_ws
+        })
+        .ok_or_else(|| {
+            format!("The parking workspace {} doesn't exist.", m.parking_ws)
+        })?;
+    let parking_name = sway_quote(&m.parking_ws);
+
+    let mut cmds = vec![];
+    if ws.iter().any(|n| n.id == m.win_id) {
+        let con = format!("[con_id={}]", m.win_id);
+        match parking.iter().find(|n| n.id == m.ancestor_id) {
+            Some(ancestor) if ancestor.get_type() != ipc::Type::Workspace => {
+                // Moving to the mark of a non-window container adds the moved
+                // window as its last child.
+                cmds.push(format!(
+                    "[con_id={}] mark --add {SWAYR_TMP_MARK}",
+                    ancestor.id
+                ));
+                cmds.push(format!("{con} move to mark {SWAYR_TMP_MARK}"));
+                cmds.push(format!(
+                    "[con_id={}] unmark {SWAYR_TMP_MARK}",
+                    ancestor.id
+                ));
+            }
+            _ => cmds.push(format!("{con} move to workspace {parking_name}")),
+        }
+        // Swapping with the following children in reverse order moves the
+        // window back to its old index while keeping the others' order.
+        if let Some(ancestor) = parking.iter().find(|n| n.id == m.ancestor_id) {
+            for id in m.following_ids.iter().rev() {
+                if ancestor.nodes.iter().any(|n| n.id == *id) {
+                    cmds.push(format!("{con} swap container with con_id {id}"));
+                }
+            }
+        }
+        for layout in &m.lost_layouts {
+            match layout {
+                s::NodeLayout::SplitV | s::NodeLayout::Stacked => {
+                    cmds.push(format!("{con} splitv"))
+                }
+                _ => cmds.push(format!("{con} splith")),
+            }
+            match layout {
+                s::NodeLayout::Tabbed => {
+                    cmds.push(format!("{con} layout tabbed"))
+                }
+                s::NodeLayout::Stacked => {
+                    cmds.push(format!("{con} layout stacking"))
+                }
+                _ => (),
+            }
+        }
+        if let Some((dimension, px)) = m.size {
+            cmds.push(format!("{con} resize set {dimension} {px} px"));
+        }
+    }
+    // Windows opened while maximized join the others.
+    for win in ws
+        .iter()
+        .filter(|n| n.get_type() == ipc::Type::Window && n.id != m.win_id)
+    {
+        cmds.push(format!(
+            "[con_id={}] move to workspace {parking_name}",
+            win.id
+        ));
+    }
+    cmds.push(format!("workspace {parking_name}"));
+    cmds.push(format!(
+        "rename workspace {parking_name} to {}",
+        sway_quote(ws.get_name())
+    ));
+    cmds.push(format!("[con_id={}] focus", m.win_id));
+    Ok(cmds)
+}
+
+#[test]
+fn test_maximize_and_restore_commands() {
+    let mut root: s::Node =
+        serde_json::from_str(include_str!("../tests/fixtures/tree.json"))
+            .unwrap();
+    let ws_of =
+        |root: &s::Node, id| root.iter().find(|n| n.id == id).unwrap().clone();
+
+    // Window 10 is focused on workspace 1 (id 5) before 11 and 12.
+    let ws = ws_of(&root, 5);
+    let (m, cmds) =
+        maximize_focused_commands(&t::get_tree(&root), &ws).unwrap();
+    assert_eq!(
+        Maximized {
+            win_id: 10,
+            parking_ws: "✨max:1".to_owned(),
+            ancestor_id: 5,
+            following_ids: vec![11, 12],
+            lost_layouts: vec![],
+            size: Some(("width", 1920)),
+        },
+        m
+    );
+    assert_eq!(
+        vec![
+            r#"rename workspace "1" to "✨max:1""#,
+            r#"[con_id=10] move to workspace "1""#,
+            r#"workspace "1""#,
+            "[con_id=10] focus",
+        ],
+        cmds
+    );
+
+    // Simulate the maximized state: the renamed parking workspace holds the
+    // other windows, and a new workspace holds window 10 and window 15 opened
+    // meanwhile.
+    let output = root.nodes.iter_mut().find(|o| o.id == 4).unwrap();
+    let parking = output.nodes.iter_mut().find(|w| w.id == 5).unwrap();
+    parking.name = Some("✨max:1".to_owned());
+    let win = parking.nodes.remove(0);
+    let mut new_win = parking.nodes[0].clone();
+    new_win.id = 15;
+    let mut ws = parking.clone();
+    ws.id = 7;
+    ws.name = Some("1".to_owned());
+    ws.nodes = vec![win, new_win];
+    output.nodes.push(ws);
+    let ws = ws_of(&root, 7);
+
+    assert_eq!(
+        vec![
+            r#"[con_id=10] move to workspace "✨max:1""#,
+            "[con_id=10] swap container with con_id 12",
+            "[con_id=10] swap container with con_id 11",
+            "[con_id=10] resize set width 1920 px",
+            r#"[con_id=15] move to workspace "✨max:1""#,
+            r#"workspace "✨max:1""#,
+            r#"rename workspace "✨max:1" to "1""#,
+            "[con_id=10] focus",
+        ],
+        restore_maximized_commands(&root, &ws, &m).unwrap()
+    );
 }
@@ -23,8 +23,15 @@ pub mod cmds;
 pub mod config;
 pub mod criteria;
 pub mod daemon;
+#[cfg(feature = "dbus")]
+pub mod dbus;
 pub mod focus;
 pub mod layout;
+pub mod metrics;
+pub mod ordering;
+pub mod provider;
 pub mod shared;
+#[cfg(feature = "foreign-toplevel")]
+pub mod toplevel;
 pub mod tree;
 pub mod util;
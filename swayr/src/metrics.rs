@@ -0,0 +1,243 @@
+// Copyright (C) 2021-2023  Tassilo Horn <tsdh@gnu.org>
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Counters about swayrd's operation in the Prometheus text format, see the
+//! `[metrics]` config section.
+
+use crate::daemon::CONFIG;
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Default)]
+struct Metrics {
+    /// Keyed by command name and status (`ok` or `error`).
+    commands: BTreeMap<(String, &'static str), u64>,
+    /// The summed up execution time and number of executions keyed by command
+    /// name.
+    command_durations: BTreeMap<String, (Duration, u64)>,
+    menu_invocations: u64,
+    /// Keyed by event type, e.g., `window`.
+    sway_events: BTreeMap<&'static str, u64>,
+    event_loop_resets: u64,
+}
+
+static METRICS: Lazy<Mutex<Metrics>> =
+    Lazy::new(|| Mutex::new(Metrics::default()));
+
+fn update(f: impl FnOnce(&mut Metrics)) {
+    if CONFIG.is_metrics_enabled() {
+        f(&mut METRICS.lock().expect("Could not lock mutex"));
+    }
+}
+
+/// Records an execution of the command `name` which took `duration`.
+pub fn record_command(name: &str, ok: bool, duration: Duration) {
+    update(|m| {
+        let status = if ok { "ok" } else { "error" };
+        *m.commands.entry((name.to_owned(), status)).or_default() += 1;
+        let (sum, count) =
+            m.command_durations.entry(name.to_owned()).or_default();
+        *sum += duration;
+        *count += 1;
+    });
+}
+
+/// Records that the menu program (or `menu.server`) has been asked for a
+/// selection.
+pub fn record_menu_invocation() {
+    update(|m| m.menu_invocations += 1);
+}
+
+/// Records a sway event of the given type, e.g., `window`.
+pub fn record_sway_event(event_type: &'static str) {
+    update(|m| *m.sway_events.entry(event_type).or_default() += 1);
+}
+
+/// Records that the sway event subscription had to be re-established.
+pub fn record_event_loop_reset() {
+    update(|m| m.event_loop_resets += 1);
+}
+
+/// Escapes a label value according to the Prometheus text format.
+fn escape_label(val: &str) -> String {
+    val.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn header(s: &mut String, name: &str, typ: &str, help: &str) {
+    let _ = writeln!(s, "# HELP {name} {help}");
+    let _ = writeln!(s, "# TYPE {name} {typ}");
+}
+
+fn render_metrics(m: &Metrics) -> String {
+    let mut s = String::new();
+
+    header(
+        &mut s,
+        "swayr_commands_total",
+        "counter",
+        "Number of swayr commands executed by swayrd.",
+    );
+    for ((name, status), n) in &m.commands {
+        let _ = writeln!(
+            s,
+            "swayr_commands_total{{command=\"{}\",status=\"{status}\"}} {n}",
+            escape_label(name)
+        );
+    }
+
+    header(
+        &mut s,
+        "swayr_command_duration_seconds",
+        "summary",
+        "Execution time of swayr commands including menu interaction.",
+    );
+    for (name, (sum, count)) in &m.command_durations {
+        let name = escape_label(name);
+        let _ = writeln!(
+            s,
+            "swayr_command_duration_seconds_sum{{command=\"{name}\"}} {}",
+            sum.as_secs_f64()
+        );
+        let _ = writeln!(
+            s,
+            "swayr_command_duration_seconds_count{{command=\"{name}\"}} \
+             {count}"
+        );
+    }
+
+    header(
+        &mut s,
+        "swayr_menu_invocations_total",
+        "counter",
+        "Number of menus shown.",
+    );
+    let _ = writeln!(s, "swayr_menu_invocations_total {}", m.menu_invocations);
+
+    header(
+        &mut s,
+        "swayr_sway_events_total",
+        "counter",
+        "Number of sway events received by swayrd.",
+    );
+    for (typ, n) in &m.sway_events {
+        let _ = writeln!(s, "swayr_sway_events_total{{type=\"{typ}\"}} {n}");
+    }
+
+    header(
+        &mut s,
+        "swayr_event_loop_resets_total",
+        "counter",
+        "Number of times the sway event subscription has been re-established.",
+    );
+    let _ =
+        writeln!(s, "swayr_event_loop_resets_total {}", m.event_loop_resets);
+    s
+}
+
+/// Returns all metrics in the Prometheus text format, or `None` if
+/// `metrics.enabled` is `false`.
+pub fn render() -> Option<String> {
+    if CONFIG.is_metrics_enabled() {
+        Some(render_metrics(
+            &METRICS.lock().expect("Could not lock mutex"),
+        ))
+    } else {
+        None
+    }
+}
+
+/// The time an HTTP client has for sending its request and for reading the
+/// response.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn handle_http_request(mut stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(HTTP_TIMEOUT))?;
+    stream.set_write_timeout(Some(HTTP_TIMEOUT))?;
+    // Every request gets the metrics, so just skip the request head.
+    let mut reader = BufReader::new(&stream);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 && line.trim_end() != "" {
+        line.clear();
+    }
+    let body = render().unwrap_or_default();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Serves the metrics over HTTP on `metrics.bind` if metrics are enabled and
+/// the address is set, e.g., for scraping by Prometheus.
+pub fn serve_http() {
+    if !CONFIG.is_metrics_enabled() {
+        return;
+    }
+    let Some(bind) = CONFIG.get_metrics_bind() else {
+        return;
+    };
+    log::debug!("swayrd serves metrics on {bind}.");
+    match TcpListener::bind(&bind) {
+        Ok(listener) => {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(err) = handle_http_request(stream) {
+                            log::error!("Error serving metrics: {err}");
+                        }
+                    }
+                    Err(err) => {
+                        log::error!("Error accepting metrics client: {err}");
+                    }
+                }
+            }
+        }
+        Err(err) => {
+            log::error!("Could not bind metrics listener to {bind}: {err}")
+        }
+    }
+}
+
+#[test]
+fn test_render_metrics() {
+    let mut m = Metrics::default();
+    m.commands.insert(("next-window".to_owned(), "ok"), 2);
+    m.command_durations
+        .insert("next-window".to_owned(), (Duration::from_millis(500), 2));
+    m.sway_events.insert("window", 7);
+    m.menu_invocations = 1;
+    let text = render_metrics(&m);
+    assert!(text.contains(
+        "swayr_commands_total{command=\"next-window\",status=\"ok\"} 2\n"
+    ));
+    assert!(text.contains(
+        "swayr_command_duration_seconds_sum{command=\"next-window\"} 0.5\n"
+    ));
+    assert!(text.contains("swayr_sway_events_total{type=\"window\"} 7\n"));
+    assert!(text.contains("swayr_menu_invocations_total 1\n"));
+    assert!(text.contains("swayr_event_loop_resets_total 0\n"));
+    assert!(text.contains("# TYPE swayr_commands_total counter\n"));
+    assert_eq!("a\\\"b\\\\", escape_label("a\"b\\"));
+}
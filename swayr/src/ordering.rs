@@ -0,0 +1,190 @@
+// Copyright (C) 2021-2023  Tassilo Horn <tsdh@gnu.org>
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Ordering of nodes by urgency and recency of use.
+//!
+//! The recency of use can be biased using weights so that some windows appear
+//! earlier than their exact focus recency would dictate.  Alternatively, the
+//! importance of a window can decay with the time since it has last been used
+//! so that windows unused for long sink below newly created ones.
+
+use std::cmp;
+use std::time::Duration;
+
+/// The score of a node at position `rank` (0 is most recently used) in the
+/// LRU order with the given boost `weight`.  Lower scores come first.
+pub fn boosted_score(rank: usize, weight: f64) -> f64 {
+    (rank + 1) as f64 / weight
+}
+
+/// Sorts `v` by boosted recency of use, i.e., by [`boosted_score`] where the
+/// rank of each element is determined by its focus `tick`.  Elements with
+/// equal scores are ordered by recency.
+pub fn sort_by_boosted_recency<T>(
+    v: &mut [T],
+    tick: impl Fn(&T) -> u64,
+    weight: impl Fn(&T) -> f64,
+) {
+    let mut ticks: Vec<u64> = v.iter().map(&tick).collect();
+    ticks.sort_unstable_by(|a, b| b.cmp(a));
+    let score = |x: &T| {
+        let t = tick(x);
+        boosted_score(ticks.partition_point(|other| *other > t), weight(x))
+    };
+    v.sort_by(|a, b| {
+        score(a)
+            .partial_cmp(&score(b))
+            .unwrap_or(cmp::Ordering::Equal)
+            .then_with(|| tick(b).cmp(&tick(a)))
+    });
+}
+
+/// The importance of a node last used (or created) `age` ago with the given
+/// boost `weight`.  It halves every `half_life`.  Higher importances come
+/// first.
+pub fn decayed_importance(
+    age: Duration,
+    half_life: Duration,
+    weight: f64,
+) -> f64 {
+    weight * 0.5_f64.powf(age.as_secs_f64() / half_life.as_secs_f64())
+}
+
+/// Stably sorts `v` by [`decayed_importance`].  Elements with unknown `age`
+/// come last.
+pub fn sort_by_decayed_importance<T>(
+    v: &mut [T],
+    age: impl Fn(&T) -> Option<Duration>,
+    weight: impl Fn(&T) -> f64,
+    half_life: Duration,
+) {
+    let importance = |x: &T| {
+        age(x).map_or(0.0, |age| decayed_importance(age, half_life, weight(x)))
+    };
+    v.sort_by(|a, b| {
+        importance(b)
+            .partial_cmp(&importance(a))
+            .unwrap_or(cmp::Ordering::Equal)
+    });
+}
+
+/// Sorts `v` by boosted recency of use and, if a `half_life` is given,
+/// then by decayed importance.  This is the LRU order used by all window
+/// selections.
+pub fn sort_by_recency<T>(
+    v: &mut [T],
+    tick: impl Fn(&T) -> u64,
+    age: impl Fn(&T) -> Option<Duration>,
+    weight: impl Fn(&T) -> f64,
+    half_life: Option<Duration>,
+) {
+    sort_by_boosted_recency(v, tick, &weight);
+    if let Some(half_life) = half_life {
+        // Stable, so elements of equal importance stay in recency order.
+        sort_by_decayed_importance(v, age, weight, half_life);
+    }
+}
+
+/// Sorts `v` so that urgent elements come first and otherwise like
+/// [`sort_by_recency`].
+pub fn sort_by_urgency_and_recency<T>(
+    v: &mut [T],
+    urgent: impl Fn(&T) -> bool,
+    tick: impl Fn(&T) -> u64,
+    age: impl Fn(&T) -> Option<Duration>,
+    weight: impl Fn(&T) -> f64,
+    half_life: Option<Duration>,
+) {
+    sort_by_recency(v, tick, age, weight, half_life);
+    // Stable, so the recency order is retained within both groups.
+    v.sort_by_key(|x| !urgent(x));
+}
+
+#[test]
+fn test_sort_by_boosted_recency() {
+    // (id, tick, weight)
+    let mut v = vec![(1, 10, 1.0), (2, 40, 1.0), (3, 30, 1.0), (4, 20, 1.0)];
+    sort_by_boosted_recency(&mut v, |x| x.1, |x| x.2);
+    assert_eq!(v.iter().map(|x| x.0).collect::<Vec<_>>(), vec![2, 3, 4, 1]);
+
+    // Rank 3 with weight 2 scores 2 and ties with rank 1 which is more
+    // recent.
+    v[3].2 = 2.0;
+    sort_by_boosted_recency(&mut v, |x| x.1, |x| x.2);
+    assert_eq!(v.iter().map(|x| x.0).collect::<Vec<_>>(), vec![2, 3, 1, 4]);
+
+    // With weight 4, it scores 1 and ties with the most recent one.
+    v.iter_mut().find(|x| x.0 == 1).unwrap().2 = 4.0;
+    sort_by_boosted_recency(&mut v, |x| x.1, |x| x.2);
+    assert_eq!(v.iter().map(|x| x.0).collect::<Vec<_>>(), vec![2, 1, 3, 4]);
+}
+
+#[test]
+fn test_sort_by_urgency_and_recency() {
+    // (id, tick, urgent)
+    let mut v = vec![(1, 10, false), (2, 40, false), (3, 30, true)];
+    sort_by_urgency_and_recency(
+        &mut v,
+        |x| x.2,
+        |x| x.1,
+        |_| None,
+        |_| 1.0,
+        None,
+    );
+    assert_eq!(v.iter().map(|x| x.0).collect::<Vec<_>>(), vec![3, 2, 1]);
+}
+
+#[test]
+fn test_sort_by_recency() {
+    let hour = Duration::from_secs(3600);
+    // (id, tick, age in hours); 3 has never been focused but is new.
+    let mut v = vec![(1, 10, Some(5)), (2, 40, Some(2)), (3, 0, Some(0))];
+    sort_by_recency(&mut v, |x| x.1, |x| x.2.map(|h| h * hour), |_| 1.0, None);
+    assert_eq!(v.iter().map(|x| x.0).collect::<Vec<_>>(), vec![2, 1, 3]);
+    sort_by_recency(
+        &mut v,
+        |x| x.1,
+        |x| x.2.map(|h| h * hour),
+        |_| 1.0,
+        Some(hour),
+    );
+    assert_eq!(v.iter().map(|x| x.0).collect::<Vec<_>>(), vec![3, 2, 1]);
+}
+
+#[test]
+fn test_sort_by_decayed_importance() {
+    let hour = Duration::from_secs(3600);
+    // (id, age in hours, weight)
+    let mut v = vec![(1, Some(5), 1.0), (2, None, 1.0), (3, Some(1), 1.0)];
+    sort_by_decayed_importance(
+        &mut v,
+        |x| x.1.map(|h| h * hour),
+        |x| x.2,
+        hour,
+    );
+    assert_eq!(v.iter().map(|x| x.0).collect::<Vec<_>>(), vec![3, 1, 2]);
+
+    // A weight of 8 compensates three half-lives.
+    v.iter_mut().find(|x| x.0 == 1).unwrap().1 = Some(4);
+    v.iter_mut().find(|x| x.0 == 1).unwrap().2 = 8.0;
+    sort_by_decayed_importance(
+        &mut v,
+        |x| x.1.map(|h| h * hour),
+        |x| x.2,
+        hour,
+    );
+    assert_eq!(v.iter().map(|x| x.0).collect::<Vec<_>>(), vec![3, 1, 2]);
+    assert_eq!(decayed_importance(4 * hour, hour, 8.0), 0.5);
+}
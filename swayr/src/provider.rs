@@ -0,0 +1,186 @@
+// Copyright (C) 2021-2023  Tassilo Horn <tsdh@gnu.org>
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! An abstraction over the windows of compositors other than sway.  Those
+//! only tell swayrd the titles and app_ids of their windows and which one is
+//! activated, so only the commands needing nothing more are available.
+
+use crate::cmds::{SwayrCommand, SwayrError};
+use crate::ordering;
+use crate::util::{self, DisplayFormat};
+use serde::Serialize;
+
+/// A window as reported by a [`WindowProvider`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProviderWindow {
+    pub id: i64,
+    pub app_id: String,
+    pub title: String,
+    pub focused: bool,
+    /// Increases whenever the window gets focused, 0 if it never was.
+    pub last_focus_tick: u64,
+    /// Increases with every new window, so newer windows have larger values.
+    pub creation_tick: u64,
+}
+
+impl DisplayFormat for ProviderWindow {
+    fn format_for_display(&self) -> String {
+        format!("{} — {}", self.app_id, self.title)
+    }
+
+    fn get_indent_level(&self) -> usize {
+        0
+    }
+}
+
+/// A source of windows which can also focus them.
+pub trait WindowProvider: Send + Sync {
+    /// Returns all windows in no particular order.
+    fn get_windows(&self) -> Vec<ProviderWindow>;
+
+    fn focus_window(&self, id: i64) -> Result<(), String>;
+}
+
+/// Returns the windows of `provider`, the most recently used first.
+fn get_lru_windows(provider: &dyn WindowProvider) -> Vec<ProviderWindow> {
+    let mut wins = provider.get_windows();
+    // Never focused windows are ordered newest first.
+    wins.sort_by_key(|w| std::cmp::Reverse(w.creation_tick));
+    ordering::sort_by_boosted_recency(
+        &mut wins,
+        |w| w.last_focus_tick,
+        |_| 1.0,
+    );
+    wins
+}
+
+fn focus_window(
+    provider: &dyn WindowProvider,
+    win: &ProviderWindow,
+) -> Result<String, SwayrError> {
+    provider
+        .focus_window(win.id)
+        .map(|()| String::new())
+        .map_err(SwayrError::DaemonError)
+}
+
+fn switch_window(provider: &dyn WindowProvider) -> Result<String, SwayrError> {
+    let mut wins = get_lru_windows(provider);
+    // Like with sway, the current window comes last.
+    wins.sort_by_key(|w| w.focused);
+    match util::select_candidate_from_menu("Switch to window", &wins) {
+        Ok(win) => focus_window(provider, win),
+        Err(input) if input.is_empty() => Err(SwayrError::MenuAborted),
+        Err(input) => {
+            Err(SwayrError::NoMatch(format!("No window matches {input}.")))
+        }
+    }
+}
+
+fn switch_to_lru_window(
+    provider: &dyn WindowProvider,
+) -> Result<String, SwayrError> {
+    match get_lru_windows(provider).iter().find(|w| !w.focused) {
+        Some(win) => focus_window(provider, win),
+        None => {
+            Err(SwayrError::NoMatch("There is no other window.".to_owned()))
+        }
+    }
+}
+
+/// Executes `cmd` with the windows of `provider`.  Commands which need more
+/// than the windows' titles, app_ids, and focus aren't supported.
+pub fn exec_swayr_cmd(
+    provider: &dyn WindowProvider,
+    cmd: &SwayrCommand,
+) -> Result<String, SwayrError> {
+    log::info!("Running SwayrCommand {cmd:?} without sway");
+    match cmd {
+        SwayrCommand::Nop => Ok(String::new()),
+        SwayrCommand::Ping => Ok(serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "backend": "wlr-foreign-toplevel",
+        })
+        .to_string()),
+        SwayrCommand::SwitchWindow { .. } => switch_window(provider),
+        SwayrCommand::SwitchToUrgentOrLRUWindow { .. } => {
+            switch_to_lru_window(provider)
+        }
+        SwayrCommand::GetWindowsAsJson { .. } => {
+            serde_json::to_string_pretty(&get_lru_windows(provider))
+                .map_err(|e| SwayrError::DaemonError(e.to_string()))
+        }
+        _ => Err(SwayrError::DaemonError(format!(
+            "The command {} needs sway.",
+            cmd.name()
+        ))),
+    }
+}
+
+#[cfg(test)]
+struct MockProvider {
+    windows: Vec<ProviderWindow>,
+    focused: std::sync::Mutex<Vec<i64>>,
+}
+
+#[cfg(test)]
+impl WindowProvider for MockProvider {
+    fn get_windows(&self) -> Vec<ProviderWindow> {
+        self.windows.clone()
+    }
+
+    fn focus_window(&self, id: i64) -> Result<(), String> {
+        self.focused.lock().unwrap().push(id);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_exec_swayr_cmd_with_provider() {
+    let win = |id, focused, last_focus_tick, creation_tick| ProviderWindow {
+        id,
+        app_id: format!("app{id}"),
+        title: format!("Window {id}"),
+        focused,
+        last_focus_tick,
+        creation_tick,
+    };
+    let provider = MockProvider {
+        windows: vec![
+            win(1, false, 2, 1),
+            win(2, true, 3, 2),
+            // Ids may be reused, so 3 is newer than 5.
+            win(3, false, 0, 5),
+            win(4, false, 1, 3),
+            win(5, false, 0, 4),
+        ],
+        focused: std::sync::Mutex::new(vec![]),
+    };
+    let ids: Vec<i64> =
+        get_lru_windows(&provider).iter().map(|w| w.id).collect();
+    assert_eq!(ids, vec![2, 1, 4, 3, 5]);
+
+    let cmd = crate::cmds::parse_swayr_command(&[
+        "switch-to-urgent-or-lru-window".to_owned(),
+    ])
+    .unwrap();
+    assert_eq!(exec_swayr_cmd(&provider, &cmd), Ok(String::new()));
+    assert_eq!(*provider.focused.lock().unwrap(), vec![1]);
+
+    assert!(matches!(
+        exec_swayr_cmd(&provider, &SwayrCommand::PromoteFocused),
+        Err(SwayrError::DaemonError(_))
+    ));
+}
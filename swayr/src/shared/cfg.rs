@@ -56,6 +56,14 @@ where
 }
 
 pub fn load_config<T>(project: &str) -> T
+where
+    T: Serialize + DeserializeOwned + Default,
+{
+    load_valid_config(project).unwrap_or_default()
+}
+
+/// Like `load_config` but returns `None` if the config file is invalid.
+pub fn load_valid_config<T>(project: &str) -> Option<T>
 where
     T: Serialize + DeserializeOwned + Default,
 {
@@ -91,10 +99,18 @@ where
         log::debug!("Created new config in {}.", path.to_string_lossy());
     }
 
-    load_config_file(&path)
+    load_valid_config_file(&path)
 }
 
 pub fn load_config_file<T>(config_file: &Path) -> T
+where
+    T: Serialize + DeserializeOwned + Default,
+{
+    load_valid_config_file(config_file).unwrap_or_default()
+}
+
+/// Like `load_config_file` but returns `None` if the config file is invalid.
+pub fn load_valid_config_file<T>(config_file: &Path) -> Option<T>
 where
     T: Serialize + DeserializeOwned + Default,
 {
@@ -115,11 +131,11 @@ where
     let mut buf: String = String::new();
     file.read_to_string(&mut buf).unwrap();
     match toml::from_str::<T>(&buf) {
-        Ok(cfg) => cfg,
+        Ok(cfg) => Some(cfg),
         Err(err) => {
             log::error!("Invalid config: {err}");
             log::error!("Using default configuration.");
-            T::default()
+            None
         }
     }
 }
@@ -18,8 +18,11 @@ use regex::Regex;
 use rt_format::{
     Format, FormatArgument, NoNamedArguments, ParsedFormat, Specifier,
 };
-use std::fmt::{self, Display};
+use std::collections::HashMap;
+use std::fmt::{self, Display, Write};
+use std::sync::{Arc, Mutex};
 
+#[derive(Clone)]
 pub enum FmtArg {
     I64(i64),
     I32(i32),
@@ -200,6 +203,22 @@ fn test_placeholder_rx() {
     assert_eq!(caps.name("clipstr").unwrap().as_str(), "...");
 }
 
+/// Resolves the placeholder `name` which may consist of alternatives separated
+/// by `|` like in `{app_id|class|"?"}`.  The first alternative resolved by
+/// `lookup` wins, and a quoted alternative is a literal default.
+pub fn resolve_placeholder(
+    name: &str,
+    lookup: impl Fn(&str) -> Option<FmtArg>,
+) -> Option<FmtArg> {
+    name.split('|').map(str::trim).find_map(|alt| {
+        if alt.len() >= 2 && alt.starts_with('"') && alt.ends_with('"') {
+            Some(FmtArg::from(&alt[1..alt.len() - 1]))
+        } else {
+            lookup(alt)
+        }
+    })
+}
+
 pub fn maybe_html_escape(do_it: bool, text: String) -> String {
     if do_it {
         text.replace('<', "&lt;")
@@ -210,31 +229,209 @@ pub fn maybe_html_escape(do_it: bool, text: String) -> String {
     }
 }
 
+enum Segment {
+    Literal(String),
+    Placeholder {
+        /// The placeholder as written which is kept if it can't be resolved.
+        raw: String,
+        name: String,
+        /// The rt_format format string, e.g., `{:>10.10}`, if one is given.
+        fmt_str: Option<String>,
+        clipped_str: String,
+    },
+}
+
+/// A format string parsed into literal text and placeholders so that it can
+/// be rendered repeatedly without matching `PLACEHOLDER_RX` again.
+pub struct Template {
+    segments: Vec<Segment>,
+    literal_len: usize,
+}
+
+/// The maximum number of templates kept by `Template::cached`.
+const MAX_CACHED_TEMPLATES: usize = 64;
+
+static TEMPLATES: Lazy<Mutex<HashMap<String, Arc<Template>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+impl Template {
+    pub fn parse(fmt: &str) -> Template {
+        let mut segments = vec![];
+        let mut literal_len = 0;
+        let mut last = 0;
+        for caps in PLACEHOLDER_RX.captures_iter(fmt) {
+            let m = caps.get(0).unwrap();
+            if m.start() > last {
+                segments
+                    .push(Segment::Literal(fmt[last..m.start()].to_owned()));
+                literal_len += m.start() - last;
+            }
+            segments.push(Segment::Placeholder {
+                raw: m.as_str().to_owned(),
+                name: caps["name"].to_owned(),
+                fmt_str: caps.name("fmtstr").map(|m| m.as_str().to_owned()),
+                clipped_str: caps
+                    .name("clipstr")
+                    .map_or("", |m| m.as_str())
+                    .to_owned(),
+            });
+            last = m.end();
+        }
+        if last < fmt.len() {
+            segments.push(Segment::Literal(fmt[last..].to_owned()));
+            literal_len += fmt.len() - last;
+        }
+        Template {
+            segments,
+            literal_len,
+        }
+    }
+
+    /// Returns the parsed template of `fmt`, parsing it only on first use.
+    pub fn cached(fmt: &str) -> Arc<Template> {
+        let mut templates = TEMPLATES.lock().expect("Could not lock mutex");
+        if let Some(template) = templates.get(fmt) {
+            return template.clone();
+        }
+        if templates.len() >= MAX_CACHED_TEMPLATES {
+            templates.clear();
+        }
+        let template = Arc::new(Template::parse(fmt));
+        templates.insert(fmt.to_owned(), template.clone());
+        template
+    }
+
+    /// Renders the template resolving placeholders with `lookup`, see
+    /// `resolve_placeholder`.
+    pub fn render(
+        &self,
+        html_escape: bool,
+        lookup: impl Fn(&str) -> Option<FmtArg>,
+    ) -> String {
+        let mut buf = String::new();
+        self.render_into(&mut buf, html_escape, |_, _| false, lookup);
+        buf
+    }
+
+    /// Appends the rendered template to `buf`.  Before `lookup` is asked,
+    /// `verbatim` may append the value of a plain placeholder like
+    /// `{indent}` itself, i.e., without formatting and escaping, in which
+    /// case it returns true.
+    pub fn render_into(
+        &self,
+        buf: &mut String,
+        html_escape: bool,
+        verbatim: impl Fn(&str, &mut String) -> bool,
+        lookup: impl Fn(&str) -> Option<FmtArg>,
+    ) {
+        buf.reserve(self.literal_len);
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => buf.push_str(text),
+                Segment::Placeholder {
+                    raw,
+                    name,
+                    fmt_str,
+                    clipped_str,
+                } => {
+                    if fmt_str.is_none() && verbatim(name, buf) {
+                        continue;
+                    }
+                    match resolve_placeholder(name, &lookup) {
+                        // The common case of a placeholder without format
+                        // string needs no rt_format parsing.
+                        Some(val) if fmt_str.is_none() && !html_escape => {
+                            let _ = write!(buf, "{val}");
+                        }
+                        Some(val) => buf.push_str(&maybe_html_escape(
+                            html_escape,
+                            rt_format(
+                                fmt_str.as_deref().unwrap_or("{}"),
+                                val,
+                                clipped_str,
+                            ),
+                        )),
+                        None => buf.push_str(raw),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_template() {
+    let template = Template::parse("<{a}> {b:{:.3}…} {c} {a|\"x\"}!");
+    let lookup = |name: &str| match name {
+        "a" => Some(FmtArg::from("<1>")),
+        "b" => Some(FmtArg::from("sway")),
+        _ => None,
+    };
+    assert_eq!("<<1>> sw… {c} <1>!", template.render(false, lookup));
+    let mut buf = String::from(">");
+    template.render_into(
+        &mut buf,
+        false,
+        |name, buf| {
+            name == "c" && {
+                buf.push_str("<c>");
+                true
+            }
+        },
+        lookup,
+    );
+    assert_eq!("><<1>> sw… <c> <1>!", buf);
+    assert!(Arc::ptr_eq(
+        &Template::cached("{a}"),
+        &Template::cached("{a}")
+    ));
+}
+
 macro_rules! subst_placeholders {
     ( $fmt_str:expr, $html_escape:expr,
       { $( $($pat:pat_param)|+ => $exp:expr, )+ }
     ) => {
-        $crate::shared::fmt::PLACEHOLDER_RX
-            .replace_all($fmt_str, |caps: &regex::Captures| {
-                let value: String = match &caps["name"] {
-                    $(
-                        $( $pat )|+ => {
-                            let val = $crate::shared::fmt::FmtArg::from($exp);
-                            let fmt_str = caps.name("fmtstr")
-                                .map_or("{}", |m| m.as_str());
-                            let clipped_str = caps.name("clipstr")
-                                .map_or("", |m| m.as_str());
-                            $crate::shared::fmt::maybe_html_escape(
-                                $html_escape,
-                                $crate::shared::fmt::rt_format(fmt_str, val, clipped_str),
-                            )
-                        }
-                    )+
-                        _ => caps[0].to_string(),
-                };
-                value
-            }).into()
+        $crate::shared::fmt::subst_placeholders!(
+            $fmt_str, $html_escape,
+            { $( $($pat)|+ => $exp, )+ },
+            |_: &str| None
+        )
     };
+    ( $fmt_str:expr, $html_escape:expr,
+      { $( $($pat:pat_param)|+ => $exp:expr, )+ },
+      $fallback:expr
+    ) => {
+        $crate::shared::fmt::subst_placeholders!(
+            $fmt_str, $html_escape,
+            { $( $($pat)|+ => $exp, )+ },
+            $fallback,
+            |_: &str, _: &mut String| false
+        )
+    };
+    ( $fmt_str:expr, $html_escape:expr,
+      { $( $($pat:pat_param)|+ => $exp:expr, )+ },
+      $fallback:expr,
+      $verbatim:expr
+    ) => {{
+        let lookup = |name: &str| -> Option<$crate::shared::fmt::FmtArg> {
+            match name {
+                $(
+                    $( $pat )|+ => Some(
+                        $crate::shared::fmt::FmtArg::from($exp)
+                    ),
+                )+
+                _ => ($fallback)(name),
+            }
+        };
+        let mut buf = String::new();
+        $crate::shared::fmt::Template::cached($fmt_str).render_into(
+            &mut buf,
+            $html_escape,
+            $verbatim,
+            lookup,
+        );
+        buf
+    }};
 }
 
 pub(crate) use subst_placeholders;
@@ -250,4 +447,18 @@ fn test_subst_placeholders() {
     });
 
     assert_eq!("1, 2 = 2", x);
+
+    let fmt_str = "{x|a}, {x|\"?\"}, {x|y}";
+    let x: String = subst_placeholders!(fmt_str, html_escape, {
+        "a" => "1".to_string(),
+    });
+    assert_eq!("1, ?, {x|y}", x);
+
+    let x: String = subst_placeholders!(
+        "{a} {b.c}",
+        html_escape,
+        { "a" => 1, },
+        |name: &str| (name == "b.c").then(|| FmtArg::from("bc"))
+    );
+    assert_eq!("1 bc", x);
 }
@@ -0,0 +1,244 @@
+// Copyright (C) 2021-2023  Tassilo Horn <tsdh@gnu.org>
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Lookup of application icons shared by swayr and swayrbar.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path as p;
+
+pub fn default_icon_dirs() -> Vec<String> {
+    vec![
+        "/usr/share/icons/hicolor/scalable/apps".to_string(),
+        "/usr/share/icons/hicolor/128x128/apps".to_string(),
+        "/usr/share/icons/hicolor/64x64/apps".to_string(),
+        "/usr/share/icons/hicolor/48x48/apps".to_string(),
+        "/usr/share/icons/Adwaita/64x64/apps".to_string(),
+        "/usr/share/icons/Adwaita/48x48/apps".to_string(),
+        "/usr/share/pixmaps".to_string(),
+    ]
+}
+
+fn desktop_entry_folders() -> Vec<Box<p::Path>> {
+    let mut dirs: Vec<Box<p::Path>> = vec![];
+
+    // XDG_DATA_HOME/applications
+    if let Some(dd) = directories::BaseDirs::new() {
+        let mut pb = dd.data_local_dir().to_path_buf();
+        pb.push("applications/");
+        dirs.push(pb.into_boxed_path());
+    }
+
+    let default_dirs =
+        ["/usr/local/share/applications/", "/usr/share/applications/"];
+    for dir in default_dirs {
+        dirs.push(p::Path::new(dir).to_path_buf().into_boxed_path());
+    }
+
+    if let Ok(xdg_data_dirs) = std::env::var("XDG_DATA_DIRS") {
+        for mut dir in std::env::split_paths(&xdg_data_dirs) {
+            dir.push("applications/");
+            dirs.push(dir.into_boxed_path());
+        }
+    }
+
+    dirs.sort();
+    dirs.dedup();
+
+    for path in &dirs {
+        log::debug!("found desktop entry folder: {}", path.display());
+    }
+
+    dirs
+}
+
+fn desktop_entries() -> Vec<Box<p::Path>> {
+    let mut entries = vec![];
+    for dir in desktop_entry_folders() {
+        if let Ok(readdir) = dir.read_dir() {
+            for entry in readdir.flatten() {
+                let path = entry.path();
+                if path.is_file()
+                    && path.extension().map(|ext| ext == "desktop")
+                        == Some(true)
+                {
+                    entries.push(path.to_path_buf().into_boxed_path());
+                }
+            }
+        }
+    }
+    entries
+}
+
+fn check_icon(dir: &str, icon_name: String, test_no: u8) -> Option<p::PathBuf> {
+    let mut pb = p::PathBuf::from(dir);
+    pb.push(&icon_name);
+    let icon_file = pb.as_path();
+    if icon_file.is_file() {
+        log::debug!(
+            "({test_no}) Icon name '{icon_name}' -> {}",
+            icon_file.display()
+        );
+        Some(pb)
+    } else {
+        None
+    }
+}
+
+fn find_icon(icon_name: &str, icon_dirs: &[String]) -> Option<p::PathBuf> {
+    let p = p::Path::new(icon_name);
+    if p.is_absolute() && p.is_file() {
+        log::debug!("(1) Icon name '{icon_name}' -> {}", p.display());
+        return Some(p.to_path_buf());
+    }
+
+    for dir in icon_dirs {
+        for ext in &["svg", "png"] {
+            if let f @ Some(_) =
+                check_icon(dir, icon_name.to_owned() + "." + ext, 2)
+            {
+                return f;
+            }
+
+            // Apparently, some apps declare things like
+            // Icon=multimedia-volume-control but the icon is actually named
+            // multimedia-volume-control-symbolic.png or
+            // multimedia-volume-control-symbolic.symbolic.png.
+            if let f @ Some(_) =
+                check_icon(dir, icon_name.to_owned() + "-symbolic." + ext, 3)
+            {
+                return f;
+            }
+
+            if let f @ Some(_) = check_icon(
+                dir,
+                icon_name.to_owned() + "-symbolic.symbolic." + ext,
+                4,
+            ) {
+                return f;
+            }
+        }
+    }
+
+    log::warn!("(5) No icon for name {icon_name}");
+    None
+}
+
+static WM_CLASS_OR_ICON_RX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(StartupWMClass|Icon)=(.+)").unwrap());
+static REV_DOMAIN_NAME_RX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(?:[a-zA-Z0-9-]+\.)+([a-zA-Z0-9-]+)$").unwrap());
+
+pub fn get_app_id_to_icon_map(
+    icon_dirs: &[String],
+) -> HashMap<String, p::PathBuf> {
+    let mut map: HashMap<String, p::PathBuf> = HashMap::new();
+
+    for e in desktop_entries() {
+        if let Ok(f) = std::fs::File::open(&e) {
+            let buf = std::io::BufReader::new(f);
+            let mut wm_class: Option<String> = None;
+            let mut icon: Option<p::PathBuf> = None;
+
+            // Get App-Id and Icon from desktop file.
+            for line in buf.lines() {
+                if wm_class.is_some() && icon.is_some() {
+                    break;
+                }
+                if let Ok(line) = line {
+                    if let Some(cap) = WM_CLASS_OR_ICON_RX.captures(&line) {
+                        let key = cap.get(1).unwrap().as_str();
+                        let value = cap.get(2).unwrap().as_str();
+                        if "StartupWMClass" == key {
+                            wm_class.replace(value.to_string());
+                        } else if let Some(icon_file) =
+                            find_icon(value, icon_dirs)
+                        {
+                            icon.replace(icon_file);
+                        }
+                    }
+                }
+            }
+
+            if let Some(icon) = icon {
+                // Sometimes the StartupWMClass is the app_id, e.g. FF Dev
+                // Edition has StartupWMClass firefoxdeveloperedition although
+                // the desktop file is named firefox-developer-edition.
+                if let Some(wm_class) = wm_class {
+                    map.insert(wm_class, icon.clone());
+                }
+
+                // Some apps have a reverse domain name desktop file, e.g.,
+                // org.gnome.eog.desktop but reports as just eog.
+                let desktop_file_name = String::from(
+                    e.with_extension("").file_name().unwrap().to_string_lossy(),
+                );
+                if let Some(caps) =
+                    REV_DOMAIN_NAME_RX.captures(&desktop_file_name)
+                {
+                    map.insert(
+                        caps.get(1).unwrap().as_str().to_string(),
+                        icon.clone(),
+                    );
+                }
+
+                // The usual case is that the app with foo.desktop also has the
+                // app_id foo.
+                map.insert(desktop_file_name.clone(), icon);
+            }
+        }
+    }
+
+    log::debug!(
+        "Desktop entries to icon files ({} entries):\n{:#?}",
+        map.len(),
+        map
+    );
+    map
+}
+
+static APP_NAME_AND_VERSION_RX: Lazy<Regex> =
+    Lazy::new(|| Regex::new("(.+)(-[0-9.]+)").unwrap());
+
+/// Returns the icon of the app with the given app_id or window class from
+/// `map`.  Version suffixes like in `foo-1.2` are ignored if needed.
+pub fn get_icon<'a>(
+    map: &'a HashMap<String, p::PathBuf>,
+    app_name: &str,
+) -> Option<&'a p::PathBuf> {
+    map.get(app_name).or_else(|| {
+        let app_name_no_version =
+            APP_NAME_AND_VERSION_RX.replace(app_name, "$1");
+        map.get(app_name_no_version.as_ref())
+            .or_else(|| map.get(&app_name_no_version.to_lowercase()))
+    })
+}
+
+#[test]
+fn test_get_icon() {
+    let map = HashMap::from([(
+        "gimp".to_owned(),
+        p::PathBuf::from("/usr/share/pixmaps/gimp.png"),
+    )]);
+    for app_name in ["gimp", "gimp-2.10", "GIMP-2.10"] {
+        assert_eq!(
+            Some(&p::PathBuf::from("/usr/share/pixmaps/gimp.png")),
+            get_icon(&map, app_name)
+        );
+    }
+    assert_eq!(None, get_icon(&map, "inkscape"));
+}
@@ -17,7 +17,7 @@
 
 use std::{cell::RefCell, sync::Mutex};
 
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use serde::{Deserialize, Serialize};
 use swayipc as s;
 
@@ -28,7 +28,27 @@ static SWAY_IPC_CONNECTION: Lazy<Mutex<RefCell<s::Connection>>> =
         ))
     });
 
+/// Post-processing of the trees returned by `get_root_node`, e.g., removal of
+/// windows which should be ignored.
+static ROOT_NODE_FILTER: OnceCell<fn(&mut s::Node)> = OnceCell::new();
+
+pub fn set_root_node_filter(filter: fn(&mut s::Node)) {
+    if ROOT_NODE_FILTER.set(filter).is_err() {
+        log::warn!("The root node filter has already been set.");
+    }
+}
+
 pub fn get_root_node(include_scratchpad: bool) -> s::Node {
+    let mut root = get_unfiltered_root_node(include_scratchpad);
+    if let Some(filter) = ROOT_NODE_FILTER.get() {
+        filter(&mut root);
+    }
+    root
+}
+
+/// Like `get_root_node` but without applying the root node filter.  Nodes
+/// whose type cannot be determined are removed nevertheless.
+pub fn get_unfiltered_root_node(include_scratchpad: bool) -> s::Node {
     let mut root = match SWAY_IPC_CONNECTION.lock() {
         Ok(cell) => cell.borrow_mut().get_tree().expect("Couldn't get tree"),
         Err(err) => panic!("{}", err),
@@ -37,9 +57,31 @@ pub fn get_root_node(include_scratchpad: bool) -> s::Node {
     if !include_scratchpad {
         root.nodes.retain(|o| !o.is_scratchpad());
     }
+    // Some surfaces like panels or launchers show up in the tree in odd ways
+    // with certain sway versions.
+    retain_nodes(&mut root, &|n| {
+        let known = n.try_get_type().is_some();
+        if !known {
+            log::warn!(
+                "Ignoring node with id {} of unknown type {:?}",
+                n.id,
+                n.node_type
+            );
+        }
+        known
+    });
     root
 }
 
+/// Removes all nodes below `node` (recursively) not satisfying `pred`.
+pub fn retain_nodes(node: &mut s::Node, pred: &impl Fn(&s::Node) -> bool) {
+    node.nodes.retain(pred);
+    node.floating_nodes.retain(pred);
+    for n in node.nodes.iter_mut().chain(node.floating_nodes.iter_mut()) {
+        retain_nodes(n, pred);
+    }
+}
+
 /// Immutable Node Iterator
 ///
 /// Iterates nodes in depth-first order, tiled nodes before floating nodes.
@@ -84,6 +126,7 @@ pub enum Type {
 pub trait NodeMethods {
     fn iter(&self) -> NodeIter;
     fn get_type(&self) -> Type;
+    fn try_get_type(&self) -> Option<Type>;
     fn get_app_name(&self) -> &str;
     fn nodes_of_type(&self, t: Type) -> Vec<&s::Node>;
     fn get_name(&self) -> &str;
@@ -98,11 +141,20 @@ impl NodeMethods for s::Node {
     }
 
     fn get_type(&self) -> Type {
+        self.try_get_type().unwrap_or_else(|| {
+            panic!(
+                "Don't know type of node with id {} and node_type {:?}\n{:?}",
+                self.id, self.node_type, self
+            )
+        })
+    }
+
+    fn try_get_type(&self) -> Option<Type> {
         match self.node_type {
-            s::NodeType::Root => Type::Root,
-            s::NodeType::Output => Type::Output,
-            s::NodeType::Workspace => Type::Workspace,
-            s::NodeType::FloatingCon => Type::Window,
+            s::NodeType::Root => Some(Type::Root),
+            s::NodeType::Output => Some(Type::Output),
+            s::NodeType::Workspace => Some(Type::Workspace),
+            s::NodeType::FloatingCon => Some(Type::Window),
             _ => {
                 if self.node_type == s::NodeType::Con
                     && self.name.is_none()
@@ -112,7 +164,7 @@ impl NodeMethods for s::Node {
                     && self.window_properties.is_none()
                     && self.layout != s::NodeLayout::None
                 {
-                    Type::Container
+                    Some(Type::Container)
                 } else if (self.node_type == s::NodeType::Con
                     || self.node_type == s::NodeType::FloatingCon)
                     // Apparently there can be windows without app_id, name,
@@ -124,12 +176,9 @@ impl NodeMethods for s::Node {
                 // package.
                 //&& self.shell.is_some()
                 {
-                    Type::Window
+                    Some(Type::Window)
                 } else {
-                    panic!(
-                        "Don't know type of node with id {} and node_type {:?}\n{:?}",
-                        self.id, self.node_type, self
-                    )
+                    None
                 }
             }
         }
@@ -15,4 +15,5 @@
 
 pub mod cfg;
 pub mod fmt;
+pub mod icon;
 pub mod ipc;
@@ -0,0 +1,229 @@
+// Copyright (C) 2021-2023  Tassilo Horn <tsdh@gnu.org>
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A [`WindowProvider`] using the wlr-foreign-toplevel-management protocol
+//! so that swayrd works with reduced features on wlroots compositors other
+//! than sway, e.g., river or labwc.
+
+use crate::provider::{ProviderWindow, WindowProvider};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{wl_registry, wl_seat};
+use wayland_client::{
+    event_created_child, Connection, Dispatch, Proxy, QueueHandle,
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1 as handle,
+    zwlr_foreign_toplevel_manager_v1 as manager,
+};
+
+/// The `activated` value of the toplevel handle's state array.
+const STATE_ACTIVATED: u32 = 2;
+
+struct Toplevel {
+    handle: handle::ZwlrForeignToplevelHandleV1,
+    app_id: String,
+    title: String,
+    activated: bool,
+    last_focus_tick: u64,
+    creation_tick: u64,
+}
+
+#[derive(Default)]
+struct Toplevels {
+    by_id: HashMap<u32, Toplevel>,
+    tick: u64,
+    /// Counts the toplevels ever created.  The protocol ids can't be used for
+    /// that because the ids of destroyed objects are reused.
+    creation_tick: u64,
+}
+
+/// Receives the compositor's events.
+struct Dispatcher {
+    toplevels: Arc<Mutex<Toplevels>>,
+}
+
+pub struct ForeignToplevelProvider {
+    conn: Connection,
+    seat: wl_seat::WlSeat,
+    toplevels: Arc<Mutex<Toplevels>>,
+}
+
+impl ForeignToplevelProvider {
+    /// Connects to the compositor given by `WAYLAND_DISPLAY` and dispatches
+    /// its events in a separate thread.
+    pub fn connect() -> Result<ForeignToplevelProvider, String> {
+        let conn = Connection::connect_to_env().map_err(|e| e.to_string())?;
+        let (globals, mut queue) = registry_queue_init::<Dispatcher>(&conn)
+            .map_err(|e| e.to_string())?;
+        let qh = queue.handle();
+        let _manager: manager::ZwlrForeignToplevelManagerV1 =
+            globals.bind(&qh, 1..=3, ()).map_err(|e| {
+                format!(
+                    "The compositor doesn't support \
+                     wlr-foreign-toplevel-management: {e}"
+                )
+            })?;
+        let seat: wl_seat::WlSeat =
+            globals.bind(&qh, 1..=1, ()).map_err(|e| e.to_string())?;
+
+        let toplevels = Arc::new(Mutex::new(Toplevels::default()));
+        let mut dispatcher = Dispatcher {
+            toplevels: toplevels.clone(),
+        };
+        // Receive the initial toplevels before serving any command.
+        queue
+            .roundtrip(&mut dispatcher)
+            .map_err(|e| e.to_string())?;
+        thread::spawn(move || loop {
+            if let Err(err) = queue.blocking_dispatch(&mut dispatcher) {
+                log::error!("Lost the connection to the compositor: {err}");
+                break;
+            }
+        });
+        Ok(ForeignToplevelProvider {
+            conn,
+            seat,
+            toplevels,
+        })
+    }
+}
+
+impl WindowProvider for ForeignToplevelProvider {
+    fn get_windows(&self) -> Vec<ProviderWindow> {
+        let toplevels = self.toplevels.lock().expect("Could not lock mutex");
+        toplevels
+            .by_id
+            .iter()
+            .map(|(id, t)| ProviderWindow {
+                id: *id as i64,
+                app_id: t.app_id.clone(),
+                title: t.title.clone(),
+                focused: t.activated,
+                last_focus_tick: t.last_focus_tick,
+                creation_tick: t.creation_tick,
+            })
+            .collect()
+    }
+
+    fn focus_window(&self, id: i64) -> Result<(), String> {
+        let toplevels = self.toplevels.lock().expect("Could not lock mutex");
+        let toplevel = u32::try_from(id)
+            .ok()
+            .and_then(|id| toplevels.by_id.get(&id))
+            .ok_or_else(|| format!("There is no window with id {id}."))?;
+        toplevel.handle.activate(&self.seat);
+        self.conn.flush().map_err(|e| e.to_string())
+    }
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for Dispatcher {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_seat::WlSeat, ()> for Dispatcher {
+    fn event(
+        _: &mut Self,
+        _: &wl_seat::WlSeat,
+        _: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<manager::ZwlrForeignToplevelManagerV1, ()> for Dispatcher {
+    fn event(
+        state: &mut Self,
+        _: &manager::ZwlrForeignToplevelManagerV1,
+        event: manager::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let manager::Event::Toplevel { toplevel } = event {
+            let mut toplevels =
+                state.toplevels.lock().expect("Could not lock mutex");
+            toplevels.creation_tick += 1;
+            let creation_tick = toplevels.creation_tick;
+            toplevels.by_id.insert(
+                toplevel.id().protocol_id(),
+                Toplevel {
+                    handle: toplevel,
+                    app_id: String::new(),
+                    title: String::new(),
+                    activated: false,
+                    last_focus_tick: 0,
+                    creation_tick,
+                },
+            );
+        }
+    }
+
+    event_created_child!(Dispatcher, manager::ZwlrForeignToplevelManagerV1, [
+        manager::EVT_TOPLEVEL_OPCODE => (handle::ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<handle::ZwlrForeignToplevelHandleV1, ()> for Dispatcher {
+    fn event(
+        state: &mut Self,
+        proxy: &handle::ZwlrForeignToplevelHandleV1,
+        event: handle::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let mut toplevels =
+            state.toplevels.lock().expect("Could not lock mutex");
+        let id = proxy.id().protocol_id();
+        if let handle::Event::Closed = event {
+            toplevels.by_id.remove(&id);
+            proxy.destroy();
+            return;
+        }
+        let Toplevels { by_id, tick, .. } = &mut *toplevels;
+        let Some(toplevel) = by_id.get_mut(&id) else {
+            return;
+        };
+        match event {
+            handle::Event::Title { title } => toplevel.title = title,
+            handle::Event::AppId { app_id } => toplevel.app_id = app_id,
+            handle::Event::State { state } => {
+                let activated = state
+                    .chunks_exact(4)
+                    .map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+                    .any(|s| s == STATE_ACTIVATED);
+                if activated && !toplevel.activated {
+                    *tick += 1;
+                    toplevel.last_focus_tick = *tick;
+                }
+                toplevel.activated = activated;
+            }
+            _ => (),
+        }
+    }
+}
@@ -15,31 +15,129 @@
 
 //! Convenience data structures built from the IPC structs.
 
+use crate::cmds::FormatOverrides;
+use crate::criteria;
 use crate::daemon::CONFIG;
 use crate::focus::FocusData;
+use crate::ordering;
 use crate::shared::fmt::subst_placeholders;
+use crate::shared::fmt::FmtArg;
+use crate::shared::icon;
 use crate::shared::ipc;
 use crate::shared::ipc::NodeMethods;
 use crate::util::DisplayFormat;
 use once_cell::sync::Lazy;
-use regex::Regex;
 use serde::Serialize;
 use std::cell::RefCell;
 use std::cmp;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 use std::path as p;
 use std::rc::Rc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use swayipc as s;
 
 pub type AppIdToIconMap = Lazy<HashMap<String, p::PathBuf>>;
-pub static APP_ID_TO_ICON_MAP: AppIdToIconMap = Lazy::new(|| {
-    crate::util::get_app_id_to_icon_map(&CONFIG.get_format_icon_dirs())
-});
+pub static APP_ID_TO_ICON_MAP: AppIdToIconMap =
+    Lazy::new(|| icon::get_app_id_to_icon_map(&CONFIG.get_format_icon_dirs()));
+
+/// The formatted menu entries of windows keyed by window id and a hash of the
+/// format and everything the window's entry depends on.  The placeholder
+/// substitution is expensive, so swayrd reuses the entries of windows which
+/// haven't changed since the last menu invocation.
+static FORMATTED_WINDOWS: Lazy<Mutex<HashMap<i64, HashMap<u64, String>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// The maximum number of cached entries per window, i.e., the number of
+/// different formats used for some window.
+const MAX_FORMATTED_ENTRIES_PER_WINDOW: usize = 8;
+
+/// Forgets the cached menu entries of the window with the given `id`.
+pub fn forget_formatted_window(id: i64) {
+    FORMATTED_WINDOWS.lock().unwrap().remove(&id);
+}
+
+/// The times when swayrd has seen windows being created keyed by window id.
+/// Windows which already existed when swayrd started count from then.
+static WINDOW_CREATION_TIMES: Lazy<Mutex<HashMap<i64, Instant>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Records that the window with the given `id` has been created now unless
+/// its creation has already been recorded.
+pub fn record_window_creation(id: i64) {
+    WINDOW_CREATION_TIMES
+        .lock()
+        .unwrap()
+        .entry(id)
+        .or_insert_with(Instant::now);
+}
+
+/// Forgets the creation times of all windows whose ids are not in
+/// `live_ids`.
+pub fn retain_window_creation_times(live_ids: &HashSet<i64>) {
+    WINDOW_CREATION_TIMES
+        .lock()
+        .unwrap()
+        .retain(|id, _| live_ids.contains(id));
+}
+
+/// The window to which the current sequence of
+/// `switch-to-*-or-urgent-or-lru-window` commands eventually returns.
+static SWITCH_SEQUENCE_ORIGIN: Mutex<Option<i64>> = Mutex::new(None);
+
+pub fn set_switch_sequence_origin(id: Option<i64>) {
+    *SWITCH_SEQUENCE_ORIGIN.lock().unwrap() = id;
+}
+
+fn is_switch_sequence_origin(id: i64) -> bool {
+    *SWITCH_SEQUENCE_ORIGIN.lock().unwrap() == Some(id)
+}
+
+/// Returns the time since the creation of the window with the given `id`.
+pub fn get_window_age(id: i64) -> Option<Duration> {
+    WINDOW_CREATION_TIMES
+        .lock()
+        .unwrap()
+        .get(&id)
+        .map(|t| t.elapsed())
+}
+
+/// Formats `age` in its largest unit, e.g., `42s`, `5m`, `3h`, or `2d`.
+pub fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+#[test]
+fn test_format_age() {
+    assert_eq!(format_age(Duration::from_secs(42)), "42s");
+    assert_eq!(format_age(Duration::from_secs(150)), "2m");
+    assert_eq!(format_age(Duration::from_secs(3 * 3600 + 59)), "3h");
+    assert_eq!(format_age(Duration::from_secs(2 * 86400 + 7)), "2d");
+}
+
+/// Forgets the cached menu entries of all windows whose ids are not in
+/// `live_ids`.  Returns the number of forgotten windows.
+pub fn retain_formatted_windows(live_ids: &HashSet<i64>) -> usize {
+    let mut cache = FORMATTED_WINDOWS.lock().unwrap();
+    let len = cache.len();
+    cache.retain(|id, _| live_ids.contains(id));
+    len - cache.len()
+}
 
 pub struct Tree<'a> {
     root: &'a s::Node,
     id_node: HashMap<i64, &'a s::Node>,
     id_parent: HashMap<i64, i64>,
+    format_overrides: FormatOverrides,
+    boost_weights: once_cell::sync::OnceCell<HashMap<i64, f64>>,
+    outputs: HashMap<String, s::Output>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -59,6 +157,9 @@ pub struct DisplayNode<'a> {
     indent_level: IndentLevel,
     pub swayr_icon: Option<std::path::PathBuf>,
     pub swayr_type: ipc::Type,
+    /// The recency of a workspace among the workspaces of its output.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub swayr_output_recency: Option<usize>,
 }
 
 impl<'a> DisplayNode<'a> {
@@ -67,7 +168,22 @@ impl<'a> DisplayNode<'a> {
         fmt: &str,
         html_escape: bool,
     ) -> String {
-        subst_placeholders!(fmt, html_escape, {
+        self.subst_node_placeholders_1(fmt, html_escape, |_, _| false)
+            .0
+    }
+
+    /// Substitutes the placeholders in `fmt` and also returns if some of them
+    /// referred to arbitrary JSON fields of the node, e.g., `{rect.width}`.
+    /// Plain placeholders handled by `verbatim` are inserted as is.
+    fn subst_node_placeholders_1(
+        &self,
+        fmt: &str,
+        html_escape: bool,
+        verbatim: impl Fn(&str, &mut String) -> bool,
+    ) -> (String, bool) {
+        // Only serialized if some placeholder refers to a JSON field.
+        let json = std::cell::OnceCell::new();
+        let text = subst_placeholders!(fmt, html_escape, {
             "id" => self.node.id,
             "pid" => self.node.pid
             .map_or("<no pid>".to_owned(), |pid| pid.to_string()),
@@ -83,18 +199,159 @@ impl<'a> DisplayNode<'a> {
             .get_parent_node_of_type(self.node.id, ipc::Type::Workspace)
             .map_or("<no_workspace>", |w| w.get_name()),
             "marks" => format_marks(&self.node.marks),
-        })
+            "is_origin" => is_switch_sequence_origin(self.node.id).to_string(),
+            "age" => get_window_age(self.node.id)
+            .map_or("<no_age>".to_owned(), format_age),
+            "active_workspace" | "output_active_workspace" =>
+                self.get_output_active_workspace(),
+            "scale" => self.get_output_info()
+            .and_then(|o| o.scale)
+            .map_or("<no_scale>".to_owned(), |s| s.to_string()),
+            "resolution" => self.get_output_info()
+            .and_then(|o| o.current_mode.as_ref())
+            .map_or("<no_resolution>".to_owned(), |m| {
+                format!("{}x{}", m.width, m.height)
+            }),
+            "transform" => self.get_output_info()
+            .and_then(|o| o.transform.as_deref())
+            .unwrap_or("<no_transform>"),
+        },
+        |path: &str| json_path_value(
+            json.get_or_init(|| {
+                serde_json::to_value(self).unwrap_or(serde_json::Value::Null)
+            }),
+            path,
+        ),
+        verbatim);
+        (text, json.get().is_some())
+    }
+
+    /// Returns the output node itself or the output the node belongs to.
+    fn get_output_node(&self) -> Option<&s::Node> {
+        if self.node.get_type() == ipc::Type::Output {
+            Some(self.node)
+        } else {
+            self.tree
+                .get_parent_node_of_type(self.node.id, ipc::Type::Output)
+                .copied()
+        }
+    }
+
+    /// Returns the output information of the node's output if the tree has
+    /// been created `with_outputs`.
+    fn get_output_info(&self) -> Option<&s::Output> {
+        self.get_output_node()
+            .and_then(|o| self.tree.outputs.get(o.get_name()))
+    }
+
+    /// Returns the name of the workspace currently shown on the node's
+    /// output.
+    fn get_output_active_workspace(&self) -> &str {
+        self.get_output_info()
+            .and_then(|o| o.current_workspace.as_deref())
+            .or_else(|| {
+                self.get_output_node().and_then(|o| {
+                    o.nodes
+                        .iter()
+                        .find(|ws| ws.visible == Some(true))
+                        .map(|ws| ws.get_name())
+                })
+            })
+            .unwrap_or("<no_workspace>")
+    }
+
+    /// Like `subst_node_placeholders` but reuses the result of a previous
+    /// call for the same window, format, and window properties.
+    fn subst_window_placeholders_cached(
+        &self,
+        fmt: &str,
+        html_escape: bool,
+        verbatim: impl Fn(&str, &mut String) -> bool,
+    ) -> String {
+        let key = self.format_cache_key(fmt, html_escape);
+        if let Some(entry) = FORMATTED_WINDOWS
+            .lock()
+            .unwrap()
+            .get(&self.node.id)
+            .and_then(|entries| entries.get(&key))
+        {
+            return entry.clone();
+        }
+
+        let (entry, uses_json_fields) =
+            self.subst_node_placeholders_1(fmt, html_escape, verbatim);
+        // The cache key doesn't cover arbitrary JSON fields.
+        if uses_json_fields {
+            return entry;
+        }
+        let mut cache = FORMATTED_WINDOWS.lock().unwrap();
+        let entries = cache.entry(self.node.id).or_default();
+        if entries.len() >= MAX_FORMATTED_ENTRIES_PER_WINDOW {
+            entries.clear();
+        }
+        entries.insert(key, entry.clone());
+        entry
+    }
+
+    /// Hashes the format and all properties which are substituted for the
+    /// placeholders by `subst_node_placeholders`.
+    fn format_cache_key(&self, fmt: &str, html_escape: bool) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        fmt.hash(&mut hasher);
+        html_escape.hash(&mut hasher);
+        self.node.pid.hash(&mut hasher);
+        self.node.get_app_name().hash(&mut hasher);
+        format!("{:?}", self.node.layout).hash(&mut hasher);
+        self.node.get_name().hash(&mut hasher);
+        self.node.marks.hash(&mut hasher);
+        self.node.urgent.hash(&mut hasher);
+        is_switch_sequence_origin(self.node.id).hash(&mut hasher);
+        self.get_indent_level().hash(&mut hasher);
+        self.swayr_icon.hash(&mut hasher);
+        get_window_age(self.node.id)
+            .map(format_age)
+            .hash(&mut hasher);
+        self.get_output_active_workspace().hash(&mut hasher);
+        if let Some(o) = self.get_output_info() {
+            o.scale.map(f64::to_bits).hash(&mut hasher);
+            o.current_mode
+                .map(|m| (m.width, m.height))
+                .hash(&mut hasher);
+            o.transform.hash(&mut hasher);
+        }
+        for typ in [ipc::Type::Output, ipc::Type::Workspace] {
+            self.tree
+                .get_parent_node_of_type(self.node.id, typ)
+                .map(|n| n.get_name())
+                .hash(&mut hasher);
+        }
+        hasher.finish()
     }
 }
 
 impl<'a> Tree<'a> {
+    /// Uses the given formats instead of the configured ones when displaying
+    /// the nodes of this tree.
+    pub fn with_format_overrides(mut self, formats: &FormatOverrides) -> Self {
+        self.format_overrides = formats.clone();
+        self
+    }
+
     fn get_node_by_id(&self, id: i64) -> &&s::Node {
         self.id_node
             .get(&id)
             .unwrap_or_else(|| panic!("No node with id {id}"))
     }
 
-    fn get_parent_node(&self, id: i64) -> Option<&&s::Node> {
+    /// Uses the given output information for the output placeholders like
+    /// `{scale}` or `{resolution}` which aren't part of the tree.
+    pub fn with_outputs(mut self, outputs: Vec<s::Output>) -> Self {
+        self.outputs =
+            outputs.into_iter().map(|o| (o.name.clone(), o)).collect();
+        self
+    }
+
+    pub fn get_parent_node(&self, id: i64) -> Option<&&s::Node> {
         self.id_parent.get(&id).map(|pid| self.get_node_by_id(*pid))
     }
 
@@ -113,6 +370,20 @@ impl<'a> Tree<'a> {
         }
     }
 
+    /// Returns the ancestors of the node with the given `id` from its output
+    /// down to the node itself.
+    pub fn get_ancestors(&self, id: i64) -> Vec<DisplayNode<'_>> {
+        let mut v: Vec<&s::Node> = vec![self.get_node_by_id(id)];
+        while let Some(p) = self.get_parent_node(v.last().unwrap().id) {
+            if p.get_type() == ipc::Type::Root {
+                break;
+            }
+            v.push(p);
+        }
+        v.reverse();
+        self.as_display_nodes(&v, IndentLevel::TreeDepth(1))
+    }
+
     fn sorted_nodes_of_type_1(
         &self,
         node: &'a s::Node,
@@ -150,11 +421,37 @@ impl<'a> Tree<'a> {
                         None
                     },
                     swayr_type: t,
+                    swayr_output_recency: None,
                 }
             })
             .collect()
     }
 
+    /// Returns the names of the top-level fields of display nodes serialized
+    /// to JSON.
+    pub fn display_node_json_fields(&self) -> Vec<String> {
+        // Any node has all fields, so pick a leaf which is cheap to serialize.
+        let node = self
+            .root
+            .iter()
+            .find(|n| n.nodes.is_empty() && n.floating_nodes.is_empty())
+            .unwrap_or(self.root);
+        let dn = DisplayNode {
+            node,
+            tree: self,
+            indent_level: IndentLevel::Fixed(0),
+            swayr_icon: None,
+            swayr_type: node.get_type(),
+            swayr_output_recency: Some(0),
+        };
+        match serde_json::to_value(&dn) {
+            Ok(serde_json::Value::Object(map)) => {
+                map.into_iter().map(|(k, _)| k).collect()
+            }
+            _ => vec![],
+        }
+    }
+
     pub fn get_current_workspace(&self) -> Option<&s::Node> {
         self.root
             .iter()
@@ -170,14 +467,60 @@ impl<'a> Tree<'a> {
         self.as_display_nodes(&outputs, IndentLevel::Fixed(0))
     }
 
+    fn get_output_name(&self, id: i64) -> &str {
+        self.get_parent_node_of_type(id, ipc::Type::Output)
+            .map(|o| o.get_name())
+            .unwrap_or_default()
+    }
+
+    /// Returns the workspaces grouped by output.  The outputs are ordered by
+    /// their most recently used workspace, and the workspaces of each output
+    /// are ordered by urgency and their recency on that output.  The current
+    /// workspace comes last in its output's block.
     pub fn get_workspaces(&self, fdata: &FocusData) -> Vec<DisplayNode> {
-        let mut v = self.sorted_nodes_of_type(ipc::Type::Workspace, fdata);
-        if !v.is_empty() {
-            v.rotate_left(1);
+        let sorted = self.sorted_nodes_of_type(ipc::Type::Workspace, fdata);
+        let mut blocks: Vec<(&str, Vec<&s::Node>)> = vec![];
+        for ws in sorted {
+            let output = self.get_output_name(ws.id);
+            match blocks.iter_mut().find(|(o, _)| *o == output) {
+                Some((_, block)) => block.push(ws),
+                None => blocks.push((output, vec![ws])),
+            }
+        }
+
+        let mut v = vec![];
+        for (idx, (output, mut block)) in blocks.into_iter().enumerate() {
+            // Stable, so workspaces never focused on this output keep their
+            // global order.
+            block.sort_by_key(|ws| {
+                (
+                    !ws.urgent,
+                    fdata
+                        .get_output_workspace_recency(output, ws.get_name())
+                        .unwrap_or(usize::MAX),
+                )
+            });
+            if idx == 0 {
+                block.rotate_left(1);
+            }
+            v.append(&mut block);
         }
         self.as_display_nodes(&v, IndentLevel::Fixed(0))
     }
 
+    /// Returns the recency of the workspace `ws` on its output, see
+    /// [`FocusData::get_output_workspace_recency`].
+    pub fn get_output_workspace_recency(
+        &self,
+        ws: &s::Node,
+        fdata: &FocusData,
+    ) -> Option<usize> {
+        fdata.get_output_workspace_recency(
+            self.get_output_name(ws.id),
+            ws.get_name(),
+        )
+    }
+
     pub fn get_windows(&self, fdata: &FocusData) -> Vec<DisplayNode> {
         let mut v = self.sorted_nodes_of_type(ipc::Type::Window, fdata);
         // Rotate, but only non-urgent windows.  Those should stay at the front
@@ -192,8 +535,13 @@ impl<'a> Tree<'a> {
                     break;
                 }
             }
-            if !v.is_empty() {
-                v.rotate_left(1);
+            // Move the most recently used window to the end.  Without boosts,
+            // that's simply the first one.
+            if let Some(idx) = (0..v.len()).max_by_key(|i| {
+                (fdata.last_focus_tick(v[*i].id), cmp::Reverse(*i))
+            }) {
+                let mru = v.remove(idx);
+                v.push(mru);
                 x.append(&mut v);
             }
         } else {
@@ -202,6 +550,35 @@ impl<'a> Tree<'a> {
         self.as_display_nodes(&x, IndentLevel::Fixed(0))
     }
 
+    /// Returns the applications of the windows grouped by app name in the
+    /// order of `get_windows`, i.e., the application of the most recently
+    /// used window comes first unless it's the focused window.
+    pub fn get_apps(&self, fdata: &FocusData) -> Vec<AppGroup<'_>> {
+        let mut apps: Vec<AppGroup> = vec![];
+        for win in self.get_windows(fdata) {
+            let app_name = win.node.get_app_name();
+            match apps.iter_mut().find(|a| a.app_name == app_name) {
+                Some(app) => app.windows.push(win),
+                None => apps.push(AppGroup {
+                    app_name: app_name.to_owned(),
+                    windows: vec![win],
+                }),
+            }
+        }
+        apps
+    }
+
+    /// Returns the windows with the given ids in the given order skipping ids
+    /// of nodes which aren't windows or don't exist anymore.
+    pub fn get_windows_by_ids(&self, ids: &[i64]) -> Vec<DisplayNode<'_>> {
+        let v: Vec<&s::Node> = ids
+            .iter()
+            .filter_map(|id| self.id_node.get(id).copied())
+            .filter(|n| n.get_type() == ipc::Type::Window)
+            .collect();
+        self.as_display_nodes(&v, IndentLevel::Fixed(0))
+    }
+
     pub fn get_workspaces_and_windows(
         &self,
         fdata: &FocusData,
@@ -228,17 +605,56 @@ impl<'a> Tree<'a> {
         v: &mut [&s::Node],
         fdata: &FocusData,
     ) {
-        v.sort_by(|a, b| {
-            if a.urgent && !b.urgent {
-                cmp::Ordering::Less
-            } else if !a.urgent && b.urgent {
-                cmp::Ordering::Greater
-            } else {
-                let lru_a = fdata.last_focus_tick(a.id);
-                let lru_b = fdata.last_focus_tick(b.id);
-                lru_a.cmp(&lru_b).reverse()
+        let urgent_first = self
+            .format_overrides
+            .urgent_first
+            .unwrap_or_else(|| CONFIG.get_format_urgent_first());
+        let now = Instant::now();
+        ordering::sort_by_urgency_and_recency(
+            v,
+            |n| urgent_first && n.urgent,
+            |n| fdata.last_focus_tick(n.id),
+            |n| fdata.last_use(n.id).map(|t| now.duration_since(t)),
+            |n| self.get_boost_weight(n.id),
+            CONFIG.get_focus_decay_half_life(),
+        );
+    }
+
+    /// Returns the boost weight of the node with the given id according to
+    /// the `focus.boost` config.  Weights of multiple matching boosts are
+    /// multiplied.
+    pub fn get_boost_weight(&self, id: i64) -> f64 {
+        self.boost_weights
+            .get_or_init(|| self.compute_boost_weights())
+            .get(&id)
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    fn compute_boost_weights(&self) -> HashMap<i64, f64> {
+        let mut weights = HashMap::new();
+        let boosts = CONFIG.get_focus_boosts();
+        if boosts.is_empty() {
+            return weights;
+        }
+        let wins = self.as_display_nodes(
+            &self.root.nodes_of_type(ipc::Type::Window),
+            IndentLevel::Fixed(0),
+        );
+        for (query, weight) in boosts {
+            match criteria::parse_criteria(&query) {
+                Ok(crit) => {
+                    let pred = criteria::criterion_to_predicate(&crit, &wins);
+                    for w in wins.iter().filter(|w| pred(w)) {
+                        *weights.entry(w.node.id).or_insert(1.0) *= weight;
+                    }
+                }
+                Err(err) => {
+                    log::error!("Invalid focus.boost criteria {query}: {err}")
+                }
             }
-        });
+        }
+        weights
     }
 
     fn push_subtree_sorted(
@@ -309,16 +725,9 @@ impl<'a> Tree<'a> {
 
 fn get_icon(node: &s::Node) -> Option<std::path::PathBuf> {
     if node.get_type() == ipc::Type::Window {
-        let icon = APP_ID_TO_ICON_MAP.get(node.get_app_name()).or_else(|| {
-            let app_name_no_version =
-                APP_NAME_AND_VERSION_RX.replace(node.get_app_name(), "$1");
-            APP_ID_TO_ICON_MAP
-                .get(app_name_no_version.as_ref())
-                .or_else(|| {
-                    APP_ID_TO_ICON_MAP.get(&app_name_no_version.to_lowercase())
-                })
-        });
-        if let Some(i) = icon {
+        if let Some(i) =
+            icon::get_icon(&APP_ID_TO_ICON_MAP, node.get_app_name())
+        {
             Some(i.to_owned())
         } else {
             log::warn!("No icon for app {}", node.get_app_name());
@@ -358,11 +767,94 @@ pub fn get_tree(root: &s::Node) -> Tree {
         root,
         id_node,
         id_parent,
+        format_overrides: FormatOverrides::default(),
+        boost_weights: once_cell::sync::OnceCell::new(),
+        outputs: HashMap::new(),
     }
 }
 
-static APP_NAME_AND_VERSION_RX: Lazy<Regex> =
-    Lazy::new(|| Regex::new("(.+)(-[0-9.]+)").unwrap());
+/// Returns the ids of the windows below `root` matching one of the
+/// `exclude.criteria`.
+pub fn excluded_window_ids(root: &s::Node) -> HashSet<i64> {
+    window_ids_matching_any(root, &CONFIG.get_exclude_criteria())
+}
+
+/// Returns the ids of the windows below `root` matching one of the given
+/// criteria queries.  Invalid queries are logged and skipped.
+pub fn window_ids_matching_any(
+    root: &s::Node,
+    queries: &[String],
+) -> HashSet<i64> {
+    if queries.is_empty() {
+        return HashSet::new();
+    }
+    let tree = get_tree(root);
+    let wins = tree.as_display_nodes(
+        &root.nodes_of_type(ipc::Type::Window),
+        IndentLevel::Fixed(0),
+    );
+    let mut ids = HashSet::new();
+    for query in queries {
+        match criteria::parse_criteria(query) {
+            Ok(crit) => {
+                let pred = criteria::criterion_to_predicate(&crit, &wins);
+                ids.extend(wins.iter().filter(|w| pred(w)).map(|w| w.node.id));
+            }
+            Err(err) => log::error!("Invalid criteria {query}: {err}"),
+        }
+    }
+    ids
+}
+
+/// Removes the windows matching the `exclude.criteria` so that they don't
+/// show up in menus, JSON output, etc.
+pub fn remove_excluded_windows(root: &mut s::Node) {
+    let ids = excluded_window_ids(root);
+    if !ids.is_empty() {
+        ipc::retain_nodes(root, &|n| !ids.contains(&n.id));
+    }
+}
+
+/// Returns the value of the field denoted by the dotted `path`, e.g.,
+/// `rect.width` or `nodes.0.name`, in `json` unless it's missing or null.
+/// Objects and arrays are returned as JSON.
+fn json_path_value(json: &serde_json::Value, path: &str) -> Option<FmtArg> {
+    use serde_json::Value;
+    let val = path.split('.').try_fold(json, |val, key| match val {
+        Value::Object(map) => map.get(key),
+        Value::Array(vec) => key.parse::<usize>().ok().and_then(|i| vec.get(i)),
+        _ => None,
+    })?;
+    match val {
+        Value::Null => None,
+        Value::String(s) => Some(FmtArg::from(s.as_str())),
+        Value::Number(n) => Some(match n.as_i64() {
+            Some(i) => FmtArg::from(i),
+            None => FmtArg::from(n.as_f64().unwrap_or(f64::NAN)),
+        }),
+        Value::Bool(b) => Some(FmtArg::from(b.to_string())),
+        Value::Object(_) | Value::Array(_) => {
+            Some(FmtArg::from(val.to_string()))
+        }
+    }
+}
+
+#[test]
+fn test_json_path_value() {
+    let json = serde_json::json!({
+        "app_id": null,
+        "rect": { "width": 800, "height": 600 },
+        "window_properties": { "class": "Gimp" },
+        "marks": ["a", "b"],
+    });
+    let get = |path| json_path_value(&json, path).map(|v| v.to_string());
+    assert_eq!(get("rect.width").as_deref(), Some("800"));
+    assert_eq!(get("window_properties.class").as_deref(), Some("Gimp"));
+    assert_eq!(get("marks.1").as_deref(), Some("b"));
+    assert_eq!(get("marks").as_deref(), Some(r#"["a","b"]"#));
+    assert_eq!(get("app_id"), None);
+    assert_eq!(get("rect.depth"), None);
+}
 
 fn format_marks(marks: &[String]) -> String {
     if marks.is_empty() {
@@ -372,6 +864,57 @@ fn format_marks(marks: &[String]) -> String {
     }
 }
 
+/// The windows of one application as shown by `switch-app`.
+pub struct AppGroup<'a> {
+    pub app_name: String,
+    pub windows: Vec<DisplayNode<'a>>,
+}
+
+impl DisplayFormat for AppGroup<'_> {
+    fn format_for_display(&self) -> String {
+        let urgent = self.windows.iter().any(|w| w.node.urgent);
+        let icon = self
+            .windows
+            .iter()
+            .find_map(|w| w.swayr_icon.clone())
+            .or_else(|| {
+                CONFIG
+                    .get_format_fallback_icon()
+                    .map(std::path::PathBuf::from)
+            })
+            .map(|i| i.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let verbatim = |name: &str, buf: &mut String| {
+            match name {
+                "app_icon" => buf.push_str(&icon),
+                "urgency_start" if urgent => {
+                    buf.push_str(&CONFIG.get_format_urgency_start())
+                }
+                "urgency_end" if urgent => {
+                    buf.push_str(&CONFIG.get_format_urgency_end())
+                }
+                "urgency_start" | "urgency_end" => (),
+                _ => return false,
+            }
+            true
+        };
+        subst_placeholders!(
+            &CONFIG.get_format_app_format(),
+            CONFIG.get_format_html_escape(),
+            {
+                "app_name" => self.app_name.as_str(),
+                "window_count" => self.windows.len() as i64,
+            },
+            |_: &str| None,
+            verbatim
+        )
+    }
+
+    fn get_indent_level(&self) -> usize {
+        0
+    }
+}
+
 impl DisplayFormat for DisplayNode<'_> {
     fn format_for_display(&self) -> String {
         let indent = CONFIG.get_format_indent();
@@ -384,44 +927,57 @@ impl DisplayFormat for DisplayNode<'_> {
             .as_ref()
             .map(|i| std::path::Path::new(i).to_owned());
 
+        let overrides = &self.tree.format_overrides;
         let fmt = match self.node.get_type() {
             ipc::Type::Root => String::from("Cannot format Root"),
-            ipc::Type::Output => CONFIG.get_format_output_format(),
-            ipc::Type::Workspace => CONFIG.get_format_workspace_format(),
-            ipc::Type::Container => CONFIG.get_format_container_format(),
-            ipc::Type::Window => CONFIG.get_format_window_format(),
+            ipc::Type::Output => overrides
+                .output_format
+                .clone()
+                .unwrap_or_else(|| CONFIG.get_format_output_format()),
+            ipc::Type::Workspace => overrides
+                .workspace_format
+                .clone()
+                .unwrap_or_else(|| CONFIG.get_format_workspace_format()),
+            ipc::Type::Container => overrides
+                .container_format
+                .clone()
+                .unwrap_or_else(|| CONFIG.get_format_container_format()),
+            ipc::Type::Window => overrides
+                .window_format
+                .clone()
+                .unwrap_or_else(|| CONFIG.get_format_window_format()),
         };
-        let fmt = fmt
-            .replace(
-                "{indent}",
-                indent.repeat(self.get_indent_level()).as_str(),
-            )
-            .replace(
-                "{urgency_start}",
-                if self.node.urgent {
-                    urgency_start.as_str()
-                } else {
-                    ""
-                },
-            )
-            .replace(
-                "{urgency_end}",
-                if self.node.urgent {
-                    urgency_end.as_str()
-                } else {
-                    ""
-                },
-            )
-            .replace(
-                "{app_icon}",
-                self.swayr_icon
-                    .as_ref()
-                    .or(fallback_icon.as_ref())
-                    .map(|i| i.to_string_lossy().into_owned())
-                    .unwrap_or_default()
-                    .as_str(),
-            );
-        self.subst_node_placeholders(&fmt, html_escape)
+        // These are inserted as is rather than being formatted and escaped
+        // like the other placeholders.
+        let verbatim = |name: &str, buf: &mut String| {
+            match name {
+                "indent" => {
+                    for _ in 0..self.get_indent_level() {
+                        buf.push_str(&indent);
+                    }
+                }
+                "urgency_start" if self.node.urgent => {
+                    buf.push_str(&urgency_start)
+                }
+                "urgency_end" if self.node.urgent => buf.push_str(&urgency_end),
+                "urgency_start" | "urgency_end" => (),
+                "app_icon" => {
+                    if let Some(icon) =
+                        self.swayr_icon.as_ref().or(fallback_icon.as_ref())
+                    {
+                        buf.push_str(&icon.to_string_lossy());
+                    }
+                }
+                _ => return false,
+            }
+            true
+        };
+        if self.node.get_type() == ipc::Type::Window {
+            self.subst_window_placeholders_cached(&fmt, html_escape, verbatim)
+        } else {
+            self.subst_node_placeholders_1(&fmt, html_escape, verbatim)
+                .0
+        }
     }
 
     fn get_indent_level(&self) -> usize {
@@ -15,14 +15,31 @@
 
 //! Utility functions including selection between choices using a menu program.
 
-use once_cell::sync::Lazy;
-use regex::Regex;
-
+use crate::config::MenuPreset;
 use crate::daemon::CONFIG;
-use std::collections::HashMap;
-use std::io::{BufRead, Write};
+use crate::metrics;
+use once_cell::sync::{Lazy, OnceCell};
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{BufRead, BufReader, Write};
 use std::path as p;
 use std::process as proc;
+use std::rc::Rc;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::Duration;
+use swayipc as s;
+
+/// The suffix distinguishing the sockets of several swayrd instances running
+/// with the same `WAYLAND_DISPLAY`, e.g., in a nested debug session.
+static SOCKET_SUFFIX: OnceCell<String> = OnceCell::new();
+
+pub fn set_socket_suffix(suffix: String) {
+    if SOCKET_SUFFIX.set(suffix).is_err() {
+        log::warn!("The socket suffix has already been set.");
+    }
+}
 
 pub fn get_swayr_socket_path() -> String {
     // We prefer checking the env variable instead of
@@ -32,7 +49,7 @@ pub fn get_swayr_socket_path() -> String {
     let xdg_runtime_dir = std::env::var("XDG_RUNTIME_DIR");
     let wayland_display = std::env::var("WAYLAND_DISPLAY");
     format!(
-        "{}/swayr-{}.sock",
+        "{}/swayr-{}{}.sock",
         match xdg_runtime_dir {
             Ok(val) => val,
             Err(_e) => {
@@ -46,191 +63,578 @@ pub fn get_swayr_socket_path() -> String {
                 log::error!("Couldn't get WAYLAND_DISPLAY!");
                 String::from("unknown")
             }
+        },
+        match SOCKET_SUFFIX.get() {
+            Some(suffix) => format!("-{suffix}"),
+            None => String::new(),
         }
     )
 }
 
-fn desktop_entry_folders() -> Vec<Box<p::Path>> {
-    let mut dirs: Vec<Box<p::Path>> = vec![];
+/// Returns the pids of all running processes named `name`.
+pub fn get_pids_of_process(name: &str) -> Vec<i32> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return vec![];
+    };
+    entries
+        .filter_map(|e| e.ok()?.file_name().to_str()?.parse::<i32>().ok())
+        .filter(|pid| {
+            std::fs::read_to_string(format!("/proc/{pid}/comm"))
+                .is_ok_and(|comm| comm.trim_end() == name)
+        })
+        .collect()
+}
 
-    // XDG_DATA_HOME/applications
-    if let Some(dd) = directories::BaseDirs::new() {
-        let mut pb = dd.data_local_dir().to_path_buf();
-        pb.push("applications/");
-        dirs.push(pb.into_boxed_path());
-    }
+/// Returns true if a process with one of the given `names` is running.
+pub fn is_any_process_running(names: &[String]) -> bool {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return false;
+    };
+    entries
+        .filter_map(|e| e.ok()?.file_name().to_str()?.parse::<i32>().ok())
+        .any(|pid| {
+            std::fs::read_to_string(format!("/proc/{pid}/comm"))
+                .is_ok_and(|comm| names.iter().any(|n| comm.trim_end() == n))
+        })
+}
 
-    let default_dirs =
-        ["/usr/local/share/applications/", "/usr/share/applications/"];
-    for dir in default_dirs {
-        dirs.push(p::Path::new(dir).to_path_buf().into_boxed_path());
-    }
+#[test]
+fn test_is_any_process_running() {
+    let comm = std::fs::read_to_string("/proc/self/comm").unwrap();
+    assert!(is_any_process_running(&[comm.trim_end().to_owned()]));
+    assert!(!is_any_process_running(&["no-such-process".to_owned()]));
+}
 
-    if let Ok(xdg_data_dirs) = std::env::var("XDG_DATA_DIRS") {
-        for mut dir in std::env::split_paths(&xdg_data_dirs) {
-            dir.push("applications/");
-            dirs.push(dir.into_boxed_path());
-        }
+/// Returns the command line of the process with the given `pid` as read from
+/// `/proc/<pid>/cmdline`.
+pub fn get_cmdline_of_pid(pid: i32) -> Option<Vec<String>> {
+    let bytes = std::fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+    parse_cmdline(&bytes)
+}
+
+/// Splits the NUL-terminated arguments of a `/proc/<pid>/cmdline` file.
+/// Empty arguments are kept.
+fn parse_cmdline(bytes: &[u8]) -> Option<Vec<String>> {
+    if bytes.is_empty() {
+        return None;
     }
+    Some(
+        bytes
+            .strip_suffix(&[0])
+            .unwrap_or(bytes)
+            .split(|b| *b == 0)
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect(),
+    )
+}
 
-    dirs.sort();
-    dirs.dedup();
+#[test]
+fn test_parse_cmdline() {
+    assert_eq!(None, parse_cmdline(b""));
+    assert_eq!(
+        Some(vec!["foot".to_owned(), "-T".to_owned(), String::new()]),
+        parse_cmdline(b"foot\0-T\0\0")
+    );
+    assert_eq!(
+        Some(vec!["sh".to_owned(), "-c".to_owned(), "cmd".to_owned()]),
+        parse_cmdline(b"sh\0-c\0cmd")
+    );
+}
 
-    for path in &dirs {
-        log::debug!("found desktop entry folder: {}", path.display());
+/// Quotes `arg` so that it is passed verbatim as a single argument by a POSIX
+/// shell.
+pub fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:@%+".contains(c))
+    {
+        arg.to_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
     }
+}
 
-    dirs
+#[test]
+fn test_shell_quote() {
+    assert_eq!(shell_quote("firefox"), "firefox");
+    assert_eq!(shell_quote("--name=foo"), "--name=foo");
+    assert_eq!(shell_quote(""), "''");
+    assert_eq!(shell_quote("two words"), "'two words'");
+    assert_eq!(shell_quote("it's"), "'it'\\''s'");
 }
 
-fn desktop_entries() -> Vec<Box<p::Path>> {
-    let mut entries = vec![];
-    for dir in desktop_entry_folders() {
-        if let Ok(readdir) = dir.read_dir() {
-            for entry in readdir.flatten() {
-                let path = entry.path();
-                if path.is_file()
-                    && path.extension().map(|ext| ext == "desktop")
-                        == Some(true)
-                {
-                    entries.push(path.to_path_buf().into_boxed_path());
+/// Splits `s` into arguments like a POSIX shell does, i.e., at unquoted
+/// whitespace while honoring single quotes, double quotes, and backslash
+/// escapes.  This is the inverse of [`shell_quote`].
+pub fn shell_split(s: &str) -> Result<Vec<String>, String> {
+    let mut args = vec![];
+    let mut arg: Option<String> = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(a) = arg.take() {
+                    args.push(a);
+                }
+            }
+            '\'' => {
+                let a = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => a.push(c),
+                        None => return Err(format!("Unclosed ' in {s:?}")),
+                    }
+                }
+            }
+            '"' => {
+                let a = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) if "\"\\$`".contains(c) => a.push(c),
+                            Some(c) => {
+                                a.push('\\');
+                                a.push(c);
+                            }
+                            None => {
+                                return Err(format!("Unclosed \" in {s:?}"))
+                            }
+                        },
+                        Some(c) => a.push(c),
+                        None => return Err(format!("Unclosed \" in {s:?}")),
+                    }
                 }
             }
+            '\\' => match chars.next() {
+                Some(c) => arg.get_or_insert_with(String::new).push(c),
+                None => return Err(format!("Trailing \\ in {s:?}")),
+            },
+            c => arg.get_or_insert_with(String::new).push(c),
         }
     }
-    entries
+    args.extend(arg);
+    Ok(args)
 }
 
-fn check_icon(dir: &str, icon_name: String, test_no: u8) -> Option<p::PathBuf> {
-    let mut pb = p::PathBuf::from(dir);
-    pb.push(&icon_name);
-    let icon_file = pb.as_path();
-    if icon_file.is_file() {
-        log::debug!(
-            "({test_no}) Icon name '{icon_name}' -> {}",
-            icon_file.display()
-        );
-        Some(pb)
-    } else {
-        None
+#[test]
+fn test_shell_split() {
+    assert_eq!(
+        shell_split("next-window  all-workspaces").unwrap(),
+        vec!["next-window", "all-workspaces"]
+    );
+    assert_eq!(
+        shell_split(r#"switch-to-matching --criteria '[app_id="foot"]'"#)
+            .unwrap(),
+        vec!["switch-to-matching", "--criteria", r#"[app_id="foot"]"#]
+    );
+    assert_eq!(
+        shell_split(r#"a "b \"c\"" d\ e ''"#).unwrap(),
+        vec!["a", r#"b "c""#, "d e", ""]
+    );
+    for arg in ["firefox", "two words", "it's", ""] {
+        assert_eq!(shell_split(&shell_quote(arg)).unwrap(), vec![arg]);
     }
+    assert!(shell_split("'unclosed").is_err());
 }
 
-fn find_icon(icon_name: &str, icon_dirs: &[String]) -> Option<p::PathBuf> {
-    let p = p::Path::new(icon_name);
-    if p.is_absolute() && p.is_file() {
-        log::debug!("(1) Icon name '{icon_name}' -> {}", p.display());
-        return Some(p.to_path_buf());
+/// Returns how well `query` fuzzily matches `text` ignoring case, or `None`
+/// if the characters of `query` don't occur in that order in `text`.  Each
+/// matched character scores, and characters matched at the start of a word or
+/// right after the previously matched character score extra.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<u32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut start = 0;
+    let mut prev: Option<usize> = None;
+    for qc in query.to_lowercase().chars() {
+        let idx = (start..text.len()).find(|i| text[*i] == qc)?;
+        score += 10;
+        if idx == 0 || !text[idx - 1].is_alphanumeric() {
+            score += 10;
+        }
+        if prev.is_some_and(|p| p + 1 == idx) {
+            score += 15;
+        }
+        prev = Some(idx);
+        start = idx + 1;
     }
+    Some(score)
+}
+
+#[test]
+fn test_fuzzy_score() {
+    assert_eq!(None, fuzzy_score("xyz", "Firefox"));
+    assert_eq!(None, fuzzy_score("ff", "Foot"));
+    assert!(fuzzy_score("ff", "Firefox").is_some());
+    assert!(fuzzy_score("fire", "Firefox") > fuzzy_score("fx", "Firefox"));
+    assert!(
+        fuzzy_score("mail", "Inbox - Mail") > fuzzy_score("mail", "my ailment")
+    );
+}
 
-    for dir in icon_dirs {
-        for ext in &["svg", "png"] {
-            if let f @ Some(_) =
-                check_icon(dir, icon_name.to_owned() + "." + ext, 2)
-            {
-                return f;
+pub trait DisplayFormat {
+    fn format_for_display(&self) -> String;
+    fn get_indent_level(&self) -> usize;
+}
+
+/// Makes the display strings in `strs` unique by appending zero-width spaces
+/// to duplicates.  In case of rofi's icon escape sequence
+/// "\u0000icon\u001f/path/to/icon.png", they are inserted before it because
+/// rofi returns only the text.
+fn make_unique(strs: &mut [String]) {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for s in strs.iter_mut() {
+        let mut candidate = s.clone();
+        loop {
+            let text = candidate.split('\0').next().unwrap_or_default();
+            let count = seen.entry(text.to_owned()).or_insert(0);
+            *count += 1;
+            if *count == 1 {
+                break;
             }
+            let (text, rest) = candidate.split_at(text.len());
+            candidate = format!("{text}\u{200b}{rest}");
+        }
+        *s = candidate;
+    }
+}
 
-            // Apparently, some apps declare things like
-            // Icon=multimedia-volume-control but the icon is actually named
-            // multimedia-volume-control-symbolic.png or
-            // multimedia-volume-control-symbolic.symbolic.png.
-            if let f @ Some(_) =
-                check_icon(dir, icon_name.to_owned() + "-symbolic." + ext, 3)
-            {
-                return f;
+#[test]
+fn test_make_unique() {
+    let mut strs: Vec<String> =
+        ["a", "b", "a", "a\u{200b}", "a\0icon\x1fx.png"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+    make_unique(&mut strs);
+    assert_eq!(
+        strs,
+        vec![
+            "a",
+            "b",
+            "a\u{200b}",
+            "a\u{200b}\u{200b}",
+            "a\u{200b}\u{200b}\u{200b}\0icon\x1fx.png"
+        ]
+    );
+}
+
+/// Returns the name of the output the menu should be shown on according to
+/// `menu.output`, i.e., the given name or the focused output's name if it is
+/// "focused".
+fn get_menu_output() -> Option<String> {
+    match CONFIG.get_menu_output()?.as_str() {
+        "focused" => match s::Connection::new()
+            .and_then(|mut con| con.get_workspaces())
+        {
+            Ok(workspaces) => workspaces
+                .into_iter()
+                .find(|ws| ws.focused)
+                .map(|ws| ws.output),
+            Err(err) => {
+                log::error!("Could not get the focused output: {err}");
+                None
             }
+        },
+        name => Some(name.to_owned()),
+    }
+}
+
+/// Returns the arguments which make the menu program `menu_exec` appear on
+/// the given `output`.  Unknown programs get no extra arguments.
+fn menu_output_args(menu_exec: &str, output: &str) -> Vec<String> {
+    let program = p::Path::new(menu_exec)
+        .file_name()
+        .and_then(|f| f.to_str())
+        .unwrap_or(menu_exec);
+    match program {
+        "rofi" => vec!["-monitor".to_owned(), output.to_owned()],
+        "wofi" => vec![format!("--monitor={output}")],
+        "fuzzel" | "tofi" => vec![format!("--output={output}")],
+        "bemenu" => vec!["--monitor".to_owned(), output.to_owned()],
+        "wmenu" => vec!["-o".to_owned(), output.to_owned()],
+        _ => {
+            log::debug!(
+                "Don't know how to show {program} on output {output}, \
+                 ignoring menu.output."
+            );
+            vec![]
+        }
+    }
+}
+
+#[test]
+fn test_menu_output_args() {
+    assert_eq!(
+        vec!["-monitor", "DP-1"],
+        menu_output_args("/usr/bin/rofi", "DP-1")
+    );
+    assert_eq!(
+        vec!["--output=HDMI-A-1"],
+        menu_output_args("fuzzel", "HDMI-A-1")
+    );
+    assert_eq!(vec!["--monitor=DP-1"], menu_output_args("wofi", "DP-1"));
+    assert!(menu_output_args("my-menu", "DP-1").is_empty());
+}
+
+/// Pipes the choices `strs` as JSON lines `{"id": <index>, "text": <str>}`
+/// through the `menu.filter_command` and returns the indices of the choices
+/// it printed in the order it printed them.  It may print the JSON objects
+/// it got or just their ids, one per line.
+fn filter_choices(
+    filter_cmd: &[String],
+    strs: &[String],
+) -> Result<Vec<usize>, String> {
+    let mut child = proc::Command::new(&filter_cmd[0])
+        .args(&filter_cmd[1..])
+        .stdin(proc::Stdio::piped())
+        .stdout(proc::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Could not run {}: {e}", filter_cmd[0]))?;
+
+    let input: String = strs
+        .iter()
+        .enumerate()
+        .map(|(id, text)| {
+            serde_json::json!({ "id": id, "text": text }).to_string() + "\n"
+        })
+        .collect();
+    let mut stdin = child.stdin.take().expect("Failed to open stdin");
+    // Write in another thread so that a filter printing before it has read
+    // all its input cannot dead-lock.
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Could not read output: {e}"))?;
+    if let Ok(Err(err)) = writer.join() {
+        log::warn!("Could not write all choices to the filter: {err}");
+    }
+    if !output.status.success() {
+        return Err(format!("Filter exited with {}", output.status));
+    }
 
-            if let f @ Some(_) = check_icon(
-                dir,
-                icon_name.to_owned() + "-symbolic.symbolic." + ext,
-                4,
-            ) {
-                return f;
+    let mut seen = HashSet::new();
+    let mut indices = vec![];
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let id = match serde_json::from_str::<serde_json::Value>(line) {
+            Ok(serde_json::Value::Number(n)) => n.as_u64(),
+            Ok(serde_json::Value::Object(o)) => {
+                o.get("id").and_then(|id| id.as_u64())
             }
+            _ => None,
+        };
+        match id {
+            Some(id) if (id as usize) < strs.len() => {
+                if seen.insert(id) {
+                    indices.push(id as usize);
+                }
+            }
+            _ => log::warn!("Ignoring invalid filter output line {line:?}"),
         }
     }
+    Ok(indices)
+}
 
-    log::warn!("(5) No icon for name {icon_name}");
-    None
+#[test]
+fn test_filter_choices() {
+    let strs: Vec<String> =
+        ["foot", "emacs", "firefox"].map(String::from).to_vec();
+    // Keep only the choices containing an f, in reverse order.
+    let cmd: Vec<String> = ["sh", "-c", "grep '\"text\":\"f' | tac"]
+        .map(String::from)
+        .to_vec();
+    assert_eq!(Ok(vec![2, 0]), filter_choices(&cmd, &strs));
+    let cmd: Vec<String> =
+        ["sh", "-c", "cat >/dev/null; echo 1; echo 1; echo 7"]
+            .map(String::from)
+            .to_vec();
+    assert_eq!(Ok(vec![1]), filter_choices(&cmd, &strs));
 }
 
-static WM_CLASS_OR_ICON_RX: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r"(StartupWMClass|Icon)=(.+)").unwrap());
-static REV_DOMAIN_NAME_RX: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r"^(?:[a-zA-Z0-9-]+\.)+([a-zA-Z0-9-]+)$").unwrap());
+/// A menu which presents the given entries with the one at `default_index`
+/// preselected and returns the selected or entered text, or an empty string if
+/// the menu was aborted.
+pub trait MenuBackend {
+    fn show(
+        &self,
+        prompt: &str,
+        strs: &[String],
+        default_index: usize,
+    ) -> String;
 
-pub fn get_app_id_to_icon_map(
-    icon_dirs: &[String],
-) -> HashMap<String, p::PathBuf> {
-    let mut map: HashMap<String, p::PathBuf> = HashMap::new();
+    /// The settings to use instead of the configured ones, if any.
+    fn settings(&self) -> Option<MenuSettings> {
+        None
+    }
+}
 
-    for e in desktop_entries() {
-        if let Ok(f) = std::fs::File::open(&e) {
-            let buf = std::io::BufReader::new(f);
-            let mut wm_class: Option<String> = None;
-            let mut icon: Option<p::PathBuf> = None;
+/// The default [`MenuBackend`] which queries the `menu.server` if one is
+/// configured and otherwise runs the `menu.executable`.
+pub struct ProgramMenu;
 
-            // Get App-Id and Icon from desktop file.
-            for line in buf.lines() {
-                if wm_class.is_some() && icon.is_some() {
-                    break;
-                }
-                if let Ok(line) = line {
-                    if let Some(cap) = WM_CLASS_OR_ICON_RX.captures(&line) {
-                        let key = cap.get(1).unwrap().as_str();
-                        let value = cap.get(2).unwrap().as_str();
-                        if "StartupWMClass" == key {
-                            wm_class.replace(value.to_string());
-                        } else if let Some(icon_file) =
-                            find_icon(value, icon_dirs)
-                        {
-                            icon.replace(icon_file);
-                        }
+impl MenuBackend for ProgramMenu {
+    fn show(
+        &self,
+        prompt: &str,
+        strs: &[String],
+        default_index: usize,
+    ) -> String {
+        metrics::record_menu_invocation();
+        match CONFIG.get_menu_server() {
+            Some(server_cmd) => {
+                match query_menu_server(
+                    &server_cmd,
+                    prompt,
+                    strs,
+                    default_index,
+                    CONFIG.get_menu_server_timeout(),
+                ) {
+                    Ok(choice) => choice,
+                    Err(err) => {
+                        log::error!(
+                            "Error querying menu.server, \
+                             falling back to menu.executable: {err}"
+                        );
+                        run_menu_program(prompt, strs, default_index)
                     }
                 }
             }
+            None => run_menu_program(prompt, strs, default_index),
+        }
+    }
+}
 
-            if let Some(icon) = icon {
-                // Sometimes the StartupWMClass is the app_id, e.g. FF Dev
-                // Edition has StartupWMClass firefoxdeveloperedition although
-                // the desktop file is named firefox-developer-edition.
-                if let Some(wm_class) = wm_class {
-                    map.insert(wm_class, icon.clone());
-                }
-
-                // Some apps have a reverse domain name desktop file, e.g.,
-                // org.gnome.eog.desktop but reports as just eog.
-                let desktop_file_name = String::from(
-                    e.with_extension("").file_name().unwrap().to_string_lossy(),
-                );
-                if let Some(caps) =
-                    REV_DOMAIN_NAME_RX.captures(&desktop_file_name)
-                {
-                    map.insert(
-                        caps.get(1).unwrap().as_str().to_string(),
-                        icon.clone(),
-                    );
-                }
+/// A [`MenuBackend`] for tests which answers with scripted texts in order
+/// (and as if aborted once they are used up) and records what it was shown.
+/// It uses its own [`MenuSettings`], so tests don't depend on the config.
+#[derive(Default)]
+pub struct MockMenu {
+    answers: RefCell<VecDeque<String>>,
+    shown: RefCell<Vec<(String, Vec<String>, usize)>>,
+    settings: MenuSettings,
+}
 
-                // The usual case is that the app with foo.desktop also has the
-                // app_id foo.
-                map.insert(desktop_file_name.clone(), icon);
-            }
+impl MockMenu {
+    pub fn new(answers: &[&str]) -> MockMenu {
+        MockMenu {
+            answers: RefCell::new(
+                answers.iter().map(|a| a.to_string()).collect(),
+            ),
+            shown: RefCell::new(vec![]),
+            settings: MenuSettings::default(),
         }
     }
 
-    log::debug!(
-        "Desktop entries to icon files ({} entries):\n{:#?}",
-        map.len(),
-        map
-    );
-    map
+    pub fn with_settings(self, settings: MenuSettings) -> MockMenu {
+        MockMenu { settings, ..self }
+    }
+
+    /// Returns the prompts, entries, and default indices of all menus shown
+    /// so far.
+    pub fn shown(&self) -> Vec<(String, Vec<String>, usize)> {
+        self.shown.borrow().clone()
+    }
 }
 
-pub trait DisplayFormat {
-    fn format_for_display(&self) -> String;
-    fn get_indent_level(&self) -> usize;
+impl MenuBackend for MockMenu {
+    fn show(
+        &self,
+        prompt: &str,
+        strs: &[String],
+        default_index: usize,
+    ) -> String {
+        self.shown.borrow_mut().push((
+            prompt.to_string(),
+            strs.to_vec(),
+            default_index,
+        ));
+        self.answers.borrow_mut().pop_front().unwrap_or_default()
+    }
+
+    fn settings(&self) -> Option<MenuSettings> {
+        Some(self.settings.clone())
+    }
+}
+
+/// A [`MenuBackend`] which never shows a menu but acts as if it had been
+/// aborted.  It's used where nobody could interact with the menu.
+pub struct NoMenu;
+
+impl MenuBackend for NoMenu {
+    fn show(
+        &self,
+        prompt: &str,
+        _strs: &[String],
+        _default_index: usize,
+    ) -> String {
+        log::warn!("Refusing to show the menu {prompt:?}.");
+        String::new()
+    }
+}
+
+thread_local! {
+    static MENU_BACKEND: RefCell<Option<Rc<dyn MenuBackend>>> =
+        const { RefCell::new(None) };
+}
+
+/// Restores the previous menu backend when dropped, even if the code using
+/// another one panicked.
+struct RestoreMenuBackend(Option<Rc<dyn MenuBackend>>);
+
+impl Drop for RestoreMenuBackend {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        MENU_BACKEND.with(|b| b.replace(previous));
+    }
+}
+
+/// Runs `f` with `backend` used by [`select_from_menu`] on the current thread
+/// instead of the [`ProgramMenu`].
+pub fn with_menu_backend<R>(
+    backend: Rc<dyn MenuBackend>,
+    f: impl FnOnce() -> R,
+) -> R {
+    let _restore =
+        RestoreMenuBackend(MENU_BACKEND.with(|b| b.replace(Some(backend))));
+    f()
+}
+
+/// The menu settings affecting which choices are shown, if any.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MenuSettings {
+    /// Don't show the menu and abort if there are fewer choices.
+    pub min_entries: usize,
+    /// Select a single choice without showing the menu.
+    pub auto_accept_single: bool,
+    pub filter_command: Option<Vec<String>>,
+    pub preset: Option<MenuPreset>,
+}
+
+impl MenuSettings {
+    /// The settings of menus which must always be shown, e.g., confirmations
+    /// or menus prompting for text.
+    pub fn from_config() -> MenuSettings {
+        MenuSettings {
+            min_entries: 0,
+            auto_accept_single: false,
+            filter_command: CONFIG.get_menu_filter_command(),
+            preset: CONFIG.get_menu_preset(),
+        }
+    }
+
+    /// The settings of menus presenting the candidates of select commands
+    /// like `switch-window` where `menu.min_entries` and
+    /// `menu.auto_accept_single` apply, too.
+    pub fn for_candidates_from_config() -> MenuSettings {
+        MenuSettings {
+            min_entries: CONFIG.get_menu_min_entries(),
+            auto_accept_single: CONFIG.is_menu_auto_accept_single(),
+            ..MenuSettings::from_config()
+        }
+    }
 }
 
 pub fn select_from_menu<'b, TS>(
@@ -240,16 +644,120 @@ pub fn select_from_menu<'b, TS>(
 where
     TS: DisplayFormat + Sized,
 {
-    let mut map: HashMap<String, &TS> = HashMap::new();
-    let mut strs: Vec<String> = vec![];
-    for c in choices {
-        let s = c.format_for_display();
-        strs.push(s.clone());
+    select_from_menu_with_default(prompt, choices, 0)
+}
+
+/// Like [`select_from_menu`] but with the choice at index `default`
+/// preselected (if the menu program supports that).
+pub fn select_from_menu_with_default<'b, TS>(
+    prompt: &str,
+    choices: &'b [TS],
+    default: usize,
+) -> Result<&'b TS, String>
+where
+    TS: DisplayFormat + Sized,
+{
+    select_from_current_menu(
+        MenuSettings::from_config,
+        prompt,
+        choices,
+        default,
+    )
+}
+
+/// Like [`select_from_menu`] but for the candidates of select commands, so
+/// `menu.min_entries` and `menu.auto_accept_single` apply.
+pub fn select_candidate_from_menu<'b, TS>(
+    prompt: &str,
+    choices: &'b [TS],
+) -> Result<&'b TS, String>
+where
+    TS: DisplayFormat + Sized,
+{
+    select_candidate_from_menu_with_default(prompt, choices, 0)
+}
+
+/// Like [`select_candidate_from_menu`] but with the choice at index `default`
+/// preselected.
+pub fn select_candidate_from_menu_with_default<'b, TS>(
+    prompt: &str,
+    choices: &'b [TS],
+    default: usize,
+) -> Result<&'b TS, String>
+where
+    TS: DisplayFormat + Sized,
+{
+    select_from_current_menu(
+        MenuSettings::for_candidates_from_config,
+        prompt,
+        choices,
+        default,
+    )
+}
+
+/// Selects using the current thread's menu backend where `config_settings`
+/// provides the settings unless the backend has its own.
+fn select_from_current_menu<'b, TS>(
+    config_settings: fn() -> MenuSettings,
+    prompt: &str,
+    choices: &'b [TS],
+    default: usize,
+) -> Result<&'b TS, String>
+where
+    TS: DisplayFormat + Sized,
+{
+    let backend: Rc<dyn MenuBackend> = MENU_BACKEND
+        .with(|b| b.borrow().clone())
+        .unwrap_or_else(|| Rc::new(ProgramMenu));
+    let settings = backend.settings().unwrap_or_else(config_settings);
+    select_from_menu_with(backend.as_ref(), &settings, prompt, choices, default)
+}
 
+/// Lets the user select one of the `choices` using the given `backend` and
+/// `settings` where the choice at index `default` is preselected.  Returns the
+/// entered text as error if it doesn't denote one of the `choices`, i.e., an
+/// empty string if the menu was aborted.
+pub fn select_from_menu_with<'b, TS>(
+    backend: &dyn MenuBackend,
+    settings: &MenuSettings,
+    prompt: &str,
+    choices: &'b [TS],
+    default: usize,
+) -> Result<&'b TS, String>
+where
+    TS: DisplayFormat + Sized,
+{
+    if choices.len() == 1 && settings.auto_accept_single {
+        return Ok(&choices[0]);
+    }
+    if choices.len() < settings.min_entries {
+        log::debug!("Not showing the menu for only {} choices.", choices.len());
+        return Err(String::new());
+    }
+
+    let preset = settings.preset;
+    let mut strs: Vec<String> = choices
+        .iter()
+        .map(|c| {
+            let s = c.format_for_display();
+            match preset {
+                Some(preset) => adapt_to_menu_preset(preset, &s),
+                None => s,
+            }
+        })
+        .collect();
+    // Two choices may be displayed identically, e.g., windows of the same app
+    // with the same title, but each needs its own entry in the map below.
+    make_unique(&mut strs);
+
+    let mut map: HashMap<String, &TS> = HashMap::new();
+    for (c, s) in choices.iter().zip(strs.iter().cloned()) {
         // Workaround: rofi has "\u0000icon\u001f/path/to/icon.png" as image
         // escape sequence which comes after the actual text but returns only
         // the text, not the escape sequence.
-        if s.contains('\0') {
+        if s.contains('\0')
+            && !matches!(preset, Some(MenuPreset::Wofi | MenuPreset::Dmenu))
+        {
             if let Some(prefix) = s.split('\0').next() {
                 map.insert(prefix.to_string(), c);
             }
@@ -258,12 +766,212 @@ where
         map.insert(s, c);
     }
 
+    let mut default_index = default;
+    if let Some(filter_cmd) = &settings.filter_command {
+        match filter_choices(filter_cmd, &strs) {
+            Ok(indices) => {
+                default_index =
+                    indices.iter().position(|i| *i == default).unwrap_or(0);
+                strs = indices.into_iter().map(|i| strs[i].clone()).collect()
+            }
+            Err(err) => log::error!(
+                "Error running menu.filter_command, using all choices: {err}"
+            ),
+        }
+        if strs.len() == 1 && settings.auto_accept_single {
+            return map.get(&strs[0]).copied().ok_or_else(String::new);
+        }
+    }
+
+    let choice = backend.show(prompt, &strs, default_index);
+    map.get(&choice).copied().ok_or(choice)
+}
+
+#[test]
+fn test_select_from_menu_with() {
+    struct Choice(&'static str);
+    impl DisplayFormat for Choice {
+        fn format_for_display(&self) -> String {
+            self.0.to_string()
+        }
+
+        fn get_indent_level(&self) -> usize {
+            0
+        }
+    }
+
+    let choices = [Choice("foot"), Choice("emacs"), Choice("foot")];
+    let menu = MockMenu::new(&["emacs", "foot\u{200b}", "", "vim"]);
+    let settings = MenuSettings::default();
+    let select = || select_from_menu_with(&menu, &settings, ">", &choices, 1);
+    assert!(std::ptr::eq(&choices[1], select().unwrap()));
+    assert!(std::ptr::eq(&choices[2], select().unwrap()));
+    assert_eq!(Err(String::new()), select().map(|c| c.0));
+    assert_eq!(Err("vim".to_string()), select().map(|c| c.0));
+    let shown = menu.shown();
+    assert_eq!(4, shown.len());
+    assert_eq!(">", shown[0].0);
+    assert_eq!(vec!["foot", "emacs", "foot\u{200b}"], shown[0].1);
+    assert_eq!(1, shown[0].2);
+}
+
+#[test]
+fn test_select_from_menu_with_candidate_settings() {
+    #[derive(Debug, PartialEq)]
+    struct Choice(&'static str);
+    impl DisplayFormat for Choice {
+        fn format_for_display(&self) -> String {
+            self.0.to_string()
+        }
+
+        fn get_indent_level(&self) -> usize {
+            0
+        }
+    }
+
+    let settings = MenuSettings {
+        min_entries: 3,
+        auto_accept_single: true,
+        ..MenuSettings::default()
+    };
+    let one = [Choice("a")];
+    let two = [Choice("a"), Choice("b")];
+    let menu = MockMenu::new(&["b"]);
+    // A single choice is accepted even though there are fewer than
+    // min_entries.
+    assert_eq!(
+        Ok(&one[0]),
+        select_from_menu_with(&menu, &settings, ">", &one, 0)
+    );
+    assert_eq!(
+        Err(String::new()),
+        select_from_menu_with(&menu, &settings, ">", &two, 0)
+    );
+    assert!(menu.shown().is_empty());
+    // Without them, e.g., for confirmations, the menu is always shown.
+    let settings = MenuSettings::default();
+    assert_eq!(
+        Ok(&two[1]),
+        select_from_menu_with(&menu, &settings, ">", &two, 0)
+    );
+    assert_eq!(1, menu.shown().len());
+    // The NoMenu acts as if the menu had been aborted.
+    assert_eq!(
+        Err(String::new()),
+        select_from_menu_with(&NoMenu, &settings, ">", &two, 0)
+    );
+}
+
+#[test]
+fn test_with_menu_backend_restores_on_panic() {
+    let result = std::panic::catch_unwind(|| {
+        with_menu_backend(Rc::new(MockMenu::new(&[])), || panic!("boom"))
+    });
+    assert!(result.is_err());
+    assert!(MENU_BACKEND.with(|b| b.borrow().is_none()));
+}
+
+static WOFI_IMAGE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^img:([^:]*):text:").unwrap());
+static MARKUP_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]*>").unwrap());
+
+/// Adapts the display string `s` which may use wofi's icon syntax and pango
+/// markup (as the default formats do) to what the program of `preset`
+/// supports.
+fn adapt_to_menu_preset(preset: MenuPreset, s: &str) -> String {
+    let (icon, text) = match WOFI_IMAGE.captures(s) {
+        Some(caps) => (caps.get(1).map(|m| m.as_str()), &s[caps[0].len()..]),
+        None => (None, s),
+    };
+    let with_rofi_icon = |text: String| match icon {
+        Some(icon) if !icon.is_empty() => format!("{text}\0icon\x1f{icon}"),
+        _ => text,
+    };
+    match preset {
+        MenuPreset::Wofi => s.to_owned(),
+        MenuPreset::Rofi => with_rofi_icon(text.to_owned()),
+        MenuPreset::Fuzzel => with_rofi_icon(strip_markup(text)),
+        MenuPreset::Dmenu => strip_markup(text),
+    }
+}
+
+fn strip_markup(s: &str) -> String {
+    MARKUP_TAG
+        .replace_all(s, "")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[test]
+fn test_adapt_to_menu_preset() {
+    let s = "img:/i/foot.svg:text:<i>foot</i> — <b>“a &amp; b”</b>";
+    assert_eq!(adapt_to_menu_preset(MenuPreset::Wofi, s), s);
+    assert_eq!(
+        adapt_to_menu_preset(MenuPreset::Rofi, s),
+        "<i>foot</i> — <b>“a &amp; b”</b>\0icon\x1f/i/foot.svg"
+    );
+    assert_eq!(
+        adapt_to_menu_preset(MenuPreset::Fuzzel, s),
+        "foot — “a & b”\0icon\x1f/i/foot.svg"
+    );
+    assert_eq!(adapt_to_menu_preset(MenuPreset::Dmenu, s), "foot — “a & b”");
+    assert_eq!(adapt_to_menu_preset(MenuPreset::Rofi, "img::text:x"), "x");
+}
+
+/// Returns the arguments which make the menu program `menu_exec` preselect
+/// the entry at `index`.  Nothing is needed for the first entry, and programs
+/// without such an option get no extra arguments.
+fn menu_default_index_args(menu_exec: &str, index: usize) -> Vec<String> {
+    if index == 0 {
+        return vec![];
+    }
+    let program = p::Path::new(menu_exec)
+        .file_name()
+        .and_then(|f| f.to_str())
+        .unwrap_or(menu_exec);
+    match program {
+        "rofi" => vec!["-selected-row".to_owned(), index.to_string()],
+        "fuzzel" => vec![format!("--select-index={index}")],
+        _ => vec![],
+    }
+}
+
+#[test]
+fn test_menu_default_index_args() {
+    assert_eq!(
+        vec!["-selected-row", "3"],
+        menu_default_index_args("/usr/bin/rofi", 3)
+    );
+    assert_eq!(
+        vec!["--select-index=2"],
+        menu_default_index_args("fuzzel", 2)
+    );
+    assert!(menu_default_index_args("fuzzel", 0).is_empty());
+    assert!(menu_default_index_args("wofi", 2).is_empty());
+}
+
+/// Runs the `menu.executable` with the given `strs` as input and returns the
+/// selected or entered text.
+fn run_menu_program(
+    prompt: &str,
+    strs: &[String],
+    default_index: usize,
+) -> String {
     let menu_exec = CONFIG.get_menu_executable();
-    let args: Vec<String> = CONFIG
+    let mut args: Vec<String> = CONFIG
         .get_menu_args()
         .iter()
-        .map(|a| a.replace("{prompt}", prompt))
+        .map(|a| {
+            a.replace("{prompt}", prompt)
+                .replace("{count}", &strs.len().to_string())
+                .replace("{default_index}", &default_index.to_string())
+        })
         .collect();
+    if let Some(output) = get_menu_output() {
+        args.extend(menu_output_args(&menu_exec, &output));
+    }
+    args.extend(menu_default_index_args(&menu_exec, default_index));
 
     let mut menu = proc::Command::new(&menu_exec)
         .args(args)
@@ -288,5 +996,166 @@ where
     let choice = String::from_utf8_lossy(&output.stdout);
     let mut choice = String::from(choice);
     choice.pop(); // Remove trailing \n from choice.
-    map.get(&choice).copied().ok_or(choice)
+    choice
+}
+
+/// A long-running `menu.server` process which is fed one request per line on
+/// its stdin and answers each with one line on its stdout.
+struct MenuServer {
+    child: proc::Child,
+    stdin: proc::ChildStdin,
+    /// The lines of the server's stdout read by a separate thread so that
+    /// answers can be waited for with a timeout.  `None` means end of file.
+    answers: mpsc::Receiver<Result<Option<String>, String>>,
+}
+
+impl Drop for MenuServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+static MENU_SERVER: Lazy<Mutex<Option<MenuServer>>> =
+    Lazy::new(|| Mutex::new(None));
+
+fn spawn_menu_server(server_cmd: &[String]) -> Result<MenuServer, String> {
+    let mut child = proc::Command::new(&server_cmd[0])
+        .args(&server_cmd[1..])
+        .stdin(proc::Stdio::piped())
+        .stdout(proc::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Could not run {}: {e}", server_cmd[0]))?;
+    let stdin = child.stdin.take().expect("Failed to open stdin");
+    let mut stdout = BufReader::new(child.stdout.take().expect("No stdout"));
+    let (sender, answers) = mpsc::channel();
+    // The thread ends when the server is killed on drop.
+    thread::spawn(move || loop {
+        let mut line = String::new();
+        let answer = match stdout.read_line(&mut line) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(line)),
+            Err(e) => Err(format!("Could not read answer: {e}")),
+        };
+        let done = !matches!(answer, Ok(Some(_)));
+        if sender.send(answer).is_err() || done {
+            break;
+        }
+    });
+    log::debug!("Started menu server {server_cmd:?}.");
+    Ok(MenuServer {
+        child,
+        stdin,
+        answers,
+    })
+}
+
+/// Starts the `menu.server` if one is configured so that it's already warm
+/// when the first menu is requested.
+pub fn start_menu_server() {
+    if let Some(server_cmd) = CONFIG.get_menu_server() {
+        let mut server = MENU_SERVER.lock().expect("Could not lock mutex");
+        if server.is_none() {
+            match spawn_menu_server(&server_cmd) {
+                Ok(s) => *server = Some(s),
+                Err(err) => log::error!("Could not start menu.server: {err}"),
+            }
+        }
+    }
+}
+
+/// Sends a request to the `menu.server` (starting it if needed) and returns
+/// its answer.  The request is a single line of JSON of the form `{"prompt":
+/// "...", "output": "...", "choices": ["...", ...], "count": n,
+/// "default_index": i}` where `output` is `null` if `menu.output` isn't set.
+/// The answer must be a single line containing the selected or entered text,
+/// or an empty line if the menu was aborted.
+///
+/// On errors or if there's no answer within `timeout`, the server is killed
+/// and will be restarted on the next request.
+fn query_menu_server(
+    server_cmd: &[String],
+    prompt: &str,
+    strs: &[String],
+    default_index: usize,
+    timeout: Duration,
+) -> Result<String, String> {
+    let mut guard = MENU_SERVER.lock().expect("Could not lock mutex");
+    if guard.is_none() {
+        *guard = Some(spawn_menu_server(server_cmd)?);
+    }
+    let server = guard.as_mut().unwrap();
+
+    let request = serde_json::json!({
+        "prompt": prompt,
+        "output": get_menu_output(),
+        "choices": strs,
+        "count": strs.len(),
+        "default_index": default_index,
+    })
+    .to_string()
+        + "\n";
+    let result = server
+        .stdin
+        .write_all(request.as_bytes())
+        .and_then(|_| server.stdin.flush())
+        .map_err(|e| format!("Could not write request: {e}"))
+        .and_then(|_| match server.answers.recv_timeout(timeout) {
+            Ok(Ok(Some(line))) => Ok(line),
+            Ok(Ok(None)) => Err("The menu server exited.".to_string()),
+            Ok(Err(err)) => Err(err),
+            Err(mpsc::RecvTimeoutError::Timeout) => Err(format!(
+                "The menu server didn't answer within {}s.",
+                timeout.as_secs()
+            )),
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                Err("The menu server exited.".to_string())
+            }
+        });
+
+    match result {
+        Ok(mut line) => {
+            if line.ends_with('\n') {
+                line.pop();
+            }
+            Ok(line)
+        }
+        Err(err) => {
+            // Drop (and thereby kill) the broken server.
+            *guard = None;
+            Err(err)
+        }
+    }
+}
+
+#[test]
+fn test_query_menu_server() {
+    let timeout = Duration::from_millis(500);
+    let strs: Vec<String> = ["foot", "emacs"].map(String::from).to_vec();
+    // Answer each request with its second choice.
+    let cmd: Vec<String> = [
+        "sh",
+        "-c",
+        "while read -r l; do echo \"$l\" | sed 's/.*,\"\\(.*\\)\"\\].*/\\1/'; done",
+    ]
+    .map(String::from)
+    .to_vec();
+    assert_eq!(
+        Ok("emacs".to_string()),
+        query_menu_server(&cmd, ">", &strs, 0, timeout)
+    );
+    assert_eq!(
+        Ok("emacs".to_string()),
+        query_menu_server(&cmd, ">", &strs, 0, timeout)
+    );
+    *MENU_SERVER.lock().unwrap() = None;
+    let cmd: Vec<String> = ["true"].map(String::from).to_vec();
+    assert!(query_menu_server(&cmd, ">", &strs, 0, timeout).is_err());
+    assert!(MENU_SERVER.lock().unwrap().is_none());
+    // A hanging server is killed after the timeout.
+    let cmd: Vec<String> = ["sleep", "10"].map(String::from).to_vec();
+    let start = std::time::Instant::now();
+    assert!(query_menu_server(&cmd, ">", &strs, 0, timeout).is_err());
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(MENU_SERVER.lock().unwrap().is_none());
 }
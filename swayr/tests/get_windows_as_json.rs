@@ -0,0 +1,248 @@
+// Copyright (C) 2021-2023  Tassilo Horn <tsdh@gnu.org>
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Golden tests of the JSON output of `get-windows-as-json` which is used by
+//! external scripts.  Set `SWAYR_UPDATE_FIXTURES=1` to regenerate the
+//! expected outputs after intentional changes.
+
+use std::path::PathBuf;
+use std::sync::{mpsc, Once};
+use swayipc as s;
+use swayr::cmds;
+use swayr::focus::FocusData;
+use swayr::shared::ipc::NodeMethods;
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+/// Makes the results independent of the swayr config of the machine running
+/// the tests, e.g., its `focus.boost` or `exclude` settings.  swayr is pointed
+/// to an empty config before it's loaded, and `SWAYR_*` config overrides are
+/// removed.
+fn use_empty_config() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        let dir = std::env::temp_dir().join("swayr-test-config");
+        std::fs::create_dir_all(dir.join("swayr")).unwrap();
+        std::fs::write(dir.join("swayr").join("config.toml"), "").unwrap();
+        std::env::set_var("XDG_CONFIG_HOME", &dir);
+        for (var, _) in std::env::vars_os() {
+            if var.to_string_lossy().starts_with("SWAYR_")
+                && var != "SWAYR_UPDATE_FIXTURES"
+            {
+                std::env::remove_var(var);
+            }
+        }
+    });
+}
+
+fn load_tree(include_scratchpad: bool) -> s::Node {
+    use_empty_config();
+    let json = std::fs::read_to_string(fixture_path("tree.json")).unwrap();
+    let mut root: s::Node = serde_json::from_str(&json).unwrap();
+    if !include_scratchpad {
+        root.nodes.retain(|o| !o.is_scratchpad());
+    }
+    root
+}
+
+/// Returns the windows as JSON sorted by id, so that the result doesn't
+/// depend on the LRU order.  The `swayr_icon` depends on the installed icons,
+/// so only its presence is checked.
+fn get_windows_as_json(
+    include_scratchpad: bool,
+    criteria: Option<&str>,
+    filter: &cmds::NodeFilter,
+) -> serde_json::Value {
+    let root = load_tree(include_scratchpad);
+    let (tx, _rx) = mpsc::channel();
+    let fdata = FocusData::new(tx);
+    let json = cmds::windows_as_json(
+        &root,
+        &fdata,
+        &criteria.map(|c| c.to_owned()),
+        false,
+        None,
+        filter,
+        cmds::JsonStyle::Pretty,
+    )
+    .unwrap();
+    let mut wins: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+    for win in wins.iter_mut() {
+        let obj = win.as_object_mut().unwrap();
+        assert!(obj.contains_key("swayr_icon"), "No swayr_icon in {obj:?}");
+        obj.remove("swayr_icon");
+    }
+    wins.sort_by_key(|w| w["id"].as_i64());
+    serde_json::Value::Array(wins)
+}
+
+fn assert_golden(actual: &serde_json::Value, name: &str) {
+    let path = fixture_path(name);
+    if std::env::var_os("SWAYR_UPDATE_FIXTURES").is_some() {
+        let json = serde_json::to_string_pretty(actual).unwrap() + "\n";
+        std::fs::write(&path, json).unwrap();
+    }
+    let expected: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(&expected, actual, "Output differs from {name}");
+}
+
+#[test]
+fn test_windows_without_scratchpad() {
+    let wins = get_windows_as_json(false, None, &Default::default());
+    assert_golden(&wins, "windows.json");
+}
+
+#[test]
+fn test_windows_with_scratchpad() {
+    let wins = get_windows_as_json(true, None, &Default::default());
+    assert_golden(&wins, "windows_with_scratchpad.json");
+}
+
+#[test]
+fn test_swayr_type_and_shape() {
+    let wins = get_windows_as_json(true, None, &Default::default());
+    let wins = wins.as_array().unwrap();
+    assert_eq!(7, wins.len());
+    for win in wins {
+        assert_eq!("Window", win["swayr_type"]);
+        for key in ["id", "name", "app_id", "pid", "shell", "fullscreen_mode"] {
+            assert!(win.get(key).is_some(), "No {key} in {win}");
+        }
+    }
+}
+
+#[test]
+fn test_windows_matching_criteria() {
+    // The Xwayland Emacs window has no app_id but is found by its class.
+    let wins = get_windows_as_json(
+        false,
+        Some("[app_name=\"Emacs\"]"),
+        &Default::default(),
+    );
+    let ids: Vec<i64> = wins
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|w| w["id"].as_i64().unwrap())
+        .collect();
+    assert_eq!(vec![11], ids);
+}
+
+#[test]
+fn test_windows_matching_geometry_criteria() {
+    let ids = |criteria: &str| -> Vec<i64> {
+        get_windows_as_json(false, Some(criteria), &Default::default())
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|w| w["id"].as_i64().unwrap())
+            .collect()
+    };
+    // Only the floating window 22 is smaller than its output.
+    assert_eq!(vec![22], ids("[area<50%]"));
+    assert_eq!(vec![22], ids("[width<=640 height<600]"));
+    assert_eq!(vec![10, 11, 13, 14, 21], ids("[width>=100%]"));
+}
+
+#[test]
+fn test_containers_with_depth_and_workspace_filter() {
+    let filter = cmds::NodeFilter {
+        include_containers: true,
+        max_depth: Some(1),
+        workspace: Some("^1$".to_owned()),
+    };
+    let nodes = get_windows_as_json(false, None, &filter);
+    let nodes: Vec<(i64, &str)> = nodes
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|n| (n["id"].as_i64().unwrap(), n["swayr_type"].as_str().unwrap()))
+        .collect();
+    // The foot windows inside the tabbed container 12 are too deep.
+    assert_eq!(
+        vec![
+            (5, "Workspace"),
+            (10, "Window"),
+            (11, "Window"),
+            (12, "Container")
+        ],
+        nodes
+    );
+}
+
+#[test]
+fn test_windows_as_json_lines() {
+    let root = load_tree(true);
+    let (tx, _rx) = mpsc::channel();
+    let fdata = FocusData::new(tx);
+    let as_json = |style| {
+        cmds::windows_as_json(
+            &root,
+            &fdata,
+            &None,
+            false,
+            Some(&["id".to_owned(), "app_id".to_owned()]),
+            &Default::default(),
+            style,
+        )
+        .unwrap()
+    };
+    let array: Vec<serde_json::Value> =
+        serde_json::from_str(&as_json(cmds::JsonStyle::Pretty)).unwrap();
+    let compact = as_json(cmds::JsonStyle::Compact);
+    assert!(!compact.contains('\n'));
+    assert_eq!(
+        array,
+        serde_json::from_str::<Vec<serde_json::Value>>(&compact).unwrap()
+    );
+    let lines: Vec<serde_json::Value> = as_json(cmds::JsonStyle::Lines)
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(array, lines);
+}
+
+#[test]
+fn test_windows_as_json_unknown_fields() {
+    let root = load_tree(false);
+    let (tx, _rx) = mpsc::channel();
+    let fdata = FocusData::new(tx);
+    let as_json = |fields: &[&str]| {
+        let fields: Vec<String> =
+            fields.iter().map(|f| f.to_string()).collect();
+        cmds::windows_as_json(
+            &root,
+            &fdata,
+            &None,
+            false,
+            Some(&fields),
+            &Default::default(),
+            cmds::JsonStyle::Compact,
+        )
+    };
+    assert!(as_json(&["id", "swayr_type", "swayr_output_recency"]).is_ok());
+    match as_json(&["id", "app_idd", "nmae"]) {
+        Err(cmds::SwayrError::DaemonError(msg)) => {
+            assert_eq!("Unknown fields: app_idd, nmae", msg)
+        }
+        other => panic!("Unexpected result {other:?}"),
+    }
+}
@@ -16,8 +16,10 @@
 //! `swayrbar` lib.
 
 use crate::config;
+use crate::control;
 use crate::module;
 use crate::module::{BarModuleFn, RefreshReason};
+use crate::sink;
 use env_logger::Env;
 use serde_json;
 use std::io;
@@ -43,12 +45,36 @@ If not specified, the default config ~/.config/swayrbar/config.toml or
 /etc/xdg/swayrbar/config.toml is used."
     )]
     config_file: Option<String>,
+    /// Control the running swayrbar instances instead of starting a new one.
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    #[clap(flatten)]
+    Control(control::ControlCommand),
+    /// Print the JSON schema of swayrbar's config.toml for validation and
+    /// completion in editors.
+    PrintConfigSchema,
 }
 
 pub fn start(opts: Opts) {
     env_logger::Builder::from_env(Env::default().default_filter_or("warn"))
         .init();
 
+    match opts.command {
+        Some(Command::Control(cmd)) => {
+            let reached = control::send_control_command(&cmd);
+            p::exit(if reached { 0 } else { 1 });
+        }
+        Some(Command::PrintConfigSchema) => {
+            println!("{}", config::CONFIG_SCHEMA);
+            p::exit(0);
+        }
+        None => (),
+    }
+
     let config = match opts.config_file {
         None => config::load_config(),
         Some(config_file) => {
@@ -57,10 +83,34 @@ pub fn start(opts: Opts) {
         }
     };
     let refresh_interval = config.refresh_interval;
+    let json_sink = config.json_sink.clone();
     let mods: Arc<Vec<Box<dyn BarModuleFn>>> = Arc::new(create_modules(config));
     let mods_for_input = mods.clone();
+    let module_ids: Vec<String> = mods
+        .iter()
+        .map(|m| {
+            let cfg = m.get_config();
+            control::module_id(&cfg.name, &cfg.instance)
+        })
+        .collect();
+    let disabled = control::DisabledModules::default();
+    disabled.write().unwrap().extend(
+        mods.iter()
+            .map(|m| m.get_config())
+            .filter(|cfg| !cfg.is_enabled())
+            .map(|cfg| control::module_id(&cfg.name, &cfg.instance)),
+    );
 
     let (sender, receiver) = sync_channel(16);
+    let sender_for_control = sender.clone();
+    let disabled_for_control = disabled.clone();
+    thread::spawn(move || {
+        control::serve_control_requests(
+            module_ids,
+            disabled_for_control,
+            sender_for_control,
+        )
+    });
     let sender_for_ticker = sender.clone();
     thread::spawn(move || {
         tick_periodically(refresh_interval, sender_for_ticker)
@@ -69,16 +119,17 @@ pub fn start(opts: Opts) {
     let sender_for_input = sender.clone();
     thread::spawn(move || handle_input(mods_for_input, sender_for_input));
 
-    let window_mods_active = mods
-        .iter()
-        .any(|m| m.get_config().name == crate::module::window::NAME);
-    if window_mods_active {
-        // There's at least one window module, so subscribe to focus events for
-        // immediate refreshes.
+    let sway_event_mods_active = mods.iter().any(|m| {
+        let name = &m.get_config().name;
+        name == crate::module::window::NAME || name == crate::module::cmd::NAME
+    });
+    if sway_event_mods_active {
+        // There's at least one window or cmd module, so subscribe to sway
+        // events for immediate refreshes.
         thread::spawn(move || handle_sway_events(sender));
     }
 
-    generate_status(&mods, receiver);
+    generate_status(&mods, &disabled, json_sink.as_deref(), receiver);
 }
 
 fn tick_periodically(refresh_interval: u64, sender: SyncSender<RefreshReason>) {
@@ -196,6 +247,13 @@ fn handle_click(
                 });
             }
         }
+        let cfg = m.get_config();
+        if name == cfg.name
+            && instance == cfg.instance
+            && m.handle_click(&button_str)
+        {
+            return Some(RefreshReason::ClickEvent { name, instance });
+        }
     }
 
     None
@@ -231,6 +289,9 @@ fn sway_subscribe() -> si::Fallible<si::EventStream> {
         si::EventType::Window,
         si::EventType::Shutdown,
         si::EventType::Workspace,
+        si::EventType::Mode,
+        si::EventType::Output,
+        si::EventType::Binding,
     ])
 }
 
@@ -274,6 +335,27 @@ fn handle_sway_events(sender: SyncSender<RefreshReason>) {
                                     RefreshReason::SwayWorkspaceEvent(ev),
                                 );
                             }
+                            si::Event::Mode(ev) => {
+                                log::debug!("Mode event: {ev:?}");
+                                send_refresh_event(
+                                    &sender,
+                                    RefreshReason::SwayModeEvent(ev),
+                                );
+                            }
+                            si::Event::Output(ev) => {
+                                log::debug!("Output event: {ev:?}");
+                                send_refresh_event(
+                                    &sender,
+                                    RefreshReason::SwayOutputEvent(ev),
+                                );
+                            }
+                            si::Event::Binding(ev) => {
+                                log::debug!("Binding event: {ev:?}");
+                                send_refresh_event(
+                                    &sender,
+                                    RefreshReason::SwayBindingEvent(ev),
+                                );
+                            }
                             si::Event::Shutdown(sd_ev) => {
                                 log::debug!(
                                     "Sway shuts down with reason '{:?}'.",
@@ -297,10 +379,37 @@ fn handle_sway_events(sender: SyncSender<RefreshReason>) {
     }
 }
 
-fn generate_status_1(mods: &[Box<dyn BarModuleFn>], reason: RefreshReason) {
+fn generate_status_1(
+    mods: &[Box<dyn BarModuleFn>],
+    disabled: &control::DisabledModules,
+    json_sink: Option<&sink::JsonSink>,
+    reason: RefreshReason,
+) {
+    let disabled = disabled.read().unwrap();
+    let mut all_blocks = vec![];
     let mut blocks = vec![];
     for m in mods {
-        blocks.push(m.build(&reason));
+        // Disabled modules are built nevertheless so that their state stays
+        // up-to-date.
+        let block = m.build(&reason);
+        let cfg = m.get_config();
+        module::publish_values(
+            &cfg.name,
+            &cfg.instance,
+            m.get_placeholder_values(),
+        );
+        if !disabled.contains(&control::module_id(&cfg.name, &cfg.instance)) {
+            blocks.push(block.clone());
+        }
+        all_blocks.push(block);
+    }
+    if let Some(sink) = json_sink {
+        if let Err(err) = sink.write_blocks(&all_blocks) {
+            log::error!(
+                "Could not write to json_sink {:?}: {err}",
+                sink.path()
+            );
+        }
     }
     let json = serde_json::to_string_pretty(&blocks)
         .unwrap_or_else(|_| "".to_string());
@@ -309,14 +418,17 @@ fn generate_status_1(mods: &[Box<dyn BarModuleFn>], reason: RefreshReason) {
 
 fn generate_status(
     mods: &[Box<dyn BarModuleFn>],
+    disabled: &control::DisabledModules,
+    json_sink: Option<&str>,
     receiver: Receiver<RefreshReason>,
 ) {
+    let json_sink = json_sink.map(|path| sink::JsonSink::new(Path::new(path)));
     println!("{{\"version\": 1, \"click_events\": true}}");
     // status_command should output an infinite array meaning we emit an
     // opening [ and never the closing bracket.
     println!("[");
 
     for ev in receiver.iter() {
-        generate_status_1(mods, ev)
+        generate_status_1(mods, disabled, json_sink.as_ref(), ev)
     }
 }
@@ -24,6 +24,9 @@ use std::collections::HashMap;
 pub struct Config {
     /// The status is refreshed every `refresh_interval` milliseconds.
     pub refresh_interval: u64,
+    /// A file or named pipe to which the blocks of all modules are written as
+    /// JSON on every refresh.
+    pub json_sink: Option<String>,
     /// The list of modules to display in the given order, each one specified
     /// as `"<module_type>/<instance>"`.
     pub modules: Vec<ModuleConfig>,
@@ -35,19 +38,61 @@ pub struct ModuleConfig {
     pub instance: String,
     pub format: String,
     pub html_escape: Option<bool>,
+    /// Maps placeholder names to thresholds.  The module is displayed as
+    /// urgent if any of these values is greater or equal to its threshold.
+    pub urgent_thresholds: Option<HashMap<String, f64>>,
+    /// Timezones the date module cycles through on click.
+    pub timezones: Option<Vec<TimezoneFormat>>,
+    /// The wireless interface of the nmcli and iwctl modules.
+    pub interface: Option<String>,
+    /// The output whose focused window the window module shows instead of
+    /// the globally focused window.
+    pub output: Option<String>,
+    /// The minimum number of milliseconds between two runs of the external
+    /// tools of modules like pactl, nmcli, iwctl, or cmd.
+    pub min_query_interval: Option<u64>,
     pub on_click: Option<HashMap<String, Vec<String>>>,
+    /// Disabled modules are hidden until they are enabled using `swayrbar
+    /// enable-module <name>/<instance>`.
+    pub enabled: Option<bool>,
+    /// Whether the cmd module also re-runs its command on sway binding and
+    /// output events instead of only periodically.
+    pub refresh_on_sway_events: Option<bool>,
+}
+
+/// A timezone with its label and optional format used by the date module.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimezoneFormat {
+    pub label: String,
+    /// `Local` or an IANA timezone name like `Europe/Berlin`.
+    pub timezone: String,
+    /// Overrides the module's format if given.
+    pub format: Option<String>,
 }
 
 impl ModuleConfig {
     pub fn is_html_escape(&self) -> bool {
         self.html_escape.unwrap_or(false)
     }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(true)
+    }
+
+    pub fn is_refresh_on_sway_events(&self) -> bool {
+        self.refresh_on_sway_events.unwrap_or(false)
+    }
+
+    pub fn get_min_query_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.min_query_interval.unwrap_or(250))
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             refresh_interval: 1000,
+            json_sink: None,
             modules: vec![
                 crate::module::window::BarModuleWindow::default_config(
                     "0".to_owned(),
@@ -69,6 +114,9 @@ impl Default for Config {
     }
 }
 
+/// The hand-maintained JSON schema of the config file.
+pub const CONFIG_SCHEMA: &str = include_str!("../etc/config.schema.json");
+
 pub fn load_config() -> Config {
     cfg::load_config::<Config>("swayrbar")
 }
@@ -78,3 +126,22 @@ fn test_load_swayrbar_config() {
     let cfg = cfg::load_config::<Config>("swayrbar");
     println!("{:?}", cfg);
 }
+
+#[test]
+fn test_config_schema() {
+    let schema: serde_json::Value =
+        serde_json::from_str(CONFIG_SCHEMA).expect("Invalid config schema");
+    let props = schema["properties"].as_object().unwrap();
+    let config = serde_json::to_value(Config::default()).unwrap();
+    for key in config.as_object().unwrap().keys() {
+        assert!(props.contains_key(key), "No schema of {key}");
+    }
+    let module_props = schema["properties"]["modules"]["items"]["properties"]
+        .as_object()
+        .unwrap();
+    for module in config["modules"].as_array().unwrap() {
+        for key in module.as_object().unwrap().keys() {
+            assert!(module_props.contains_key(key), "No schema of {key}");
+        }
+    }
+}
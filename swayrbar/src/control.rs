@@ -0,0 +1,232 @@
+// Copyright (C) 2022-2023  Tassilo Horn <tsdh@gnu.org>
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Control socket for enabling and disabling modules of a running swayrbar.
+
+use crate::module::RefreshReason;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::mpsc::SyncSender;
+use std::sync::{Arc, RwLock};
+
+/// Commands for controlling running swayrbar instances.
+#[derive(clap::Subcommand, Debug, Serialize, Deserialize)]
+pub enum ControlCommand {
+    /// Show the given module, e.g., `nmcli/0`, of all running swayrbar
+    /// instances.
+    EnableModule {
+        /// The module given as `<name>/<instance>`.
+        module: String,
+    },
+    /// Hide the given module of all running swayrbar instances.  It is still
+    /// refreshed so that it shows up-to-date information when enabled again.
+    DisableModule {
+        /// The module given as `<name>/<instance>`.
+        module: String,
+    },
+    /// Hide the given module if it is shown, otherwise show it.
+    ToggleModule {
+        /// The module given as `<name>/<instance>`.
+        module: String,
+    },
+}
+
+/// The `<name>/<instance>` ids of the modules which are currently hidden.
+pub type DisabledModules = Arc<RwLock<HashSet<String>>>;
+
+pub fn module_id(name: &str, instance: &str) -> String {
+    format!("{name}/{instance}")
+}
+
+fn get_runtime_dir() -> PathBuf {
+    PathBuf::from(
+        std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_owned()),
+    )
+}
+
+fn get_socket_prefix() -> String {
+    format!(
+        "swayrbar-{}-",
+        std::env::var("WAYLAND_DISPLAY")
+            .unwrap_or_else(|_| "unknown".to_owned())
+    )
+}
+
+/// The socket of this swayrbar process.  There may be several ones, e.g., one
+/// per output.
+fn get_socket_path() -> PathBuf {
+    get_runtime_dir().join(format!(
+        "{}{}.sock",
+        get_socket_prefix(),
+        std::process::id()
+    ))
+}
+
+/// Applies `cmd` to `disabled` and returns the response for the client.
+fn apply_command(
+    cmd: &ControlCommand,
+    module_ids: &[String],
+    disabled: &DisabledModules,
+) -> Result<String, String> {
+    let module = match cmd {
+        ControlCommand::EnableModule { module }
+        | ControlCommand::DisableModule { module }
+        | ControlCommand::ToggleModule { module } => module,
+    };
+    if !module_ids.contains(module) {
+        return Err(format!("No module {module}."));
+    }
+
+    let mut disabled = disabled.write().unwrap();
+    let enable = match cmd {
+        ControlCommand::EnableModule { .. } => true,
+        ControlCommand::DisableModule { .. } => false,
+        ControlCommand::ToggleModule { .. } => disabled.contains(module),
+    };
+    if enable {
+        disabled.remove(module);
+        Ok(format!("Enabled module {module}."))
+    } else {
+        disabled.insert(module.clone());
+        Ok(format!("Disabled module {module}."))
+    }
+}
+
+fn handle_client(
+    mut stream: UnixStream,
+    module_ids: &[String],
+    disabled: &DisabledModules,
+) -> Result<bool, String> {
+    let mut buf = String::new();
+    stream
+        .read_to_string(&mut buf)
+        .map_err(|e| format!("Could not read command: {e}"))?;
+    let (changed, response) = match serde_json::from_str(&buf) {
+        Ok(cmd) => match apply_command(&cmd, module_ids, disabled) {
+            Ok(msg) => (true, msg),
+            Err(msg) => (false, msg),
+        },
+        Err(err) => (false, format!("Invalid command {buf:?}: {err}")),
+    };
+    stream
+        .write_all(response.as_bytes())
+        .map_err(|e| format!("Could not send response: {e}"))?;
+    Ok(changed)
+}
+
+/// Serves control commands and requests a refresh whenever the set of
+/// disabled modules has changed.
+pub fn serve_control_requests(
+    module_ids: Vec<String>,
+    disabled: DisabledModules,
+    sender: SyncSender<RefreshReason>,
+) {
+    let path = get_socket_path();
+    // A stale socket of a former process with the same pid.
+    let _ = std::fs::remove_file(&path);
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("Could not bind control socket {path:?}: {err}");
+            return;
+        }
+    };
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => match handle_client(stream, &module_ids, &disabled) {
+                Ok(true) => {
+                    if let Err(err) = sender.send(RefreshReason::ControlEvent) {
+                        log::error!("Error at send: {err}");
+                    }
+                }
+                Ok(false) => (),
+                Err(err) => log::error!("Error handling control client: {err}"),
+            },
+            Err(err) => log::error!("Error accepting control client: {err}"),
+        }
+    }
+}
+
+/// Sends `cmd` to all running swayrbar instances and prints their responses.
+/// Returns false if no instance could be reached.
+pub fn send_control_command(cmd: &ControlCommand) -> bool {
+    let json = serde_json::to_string(cmd).expect("Cannot serialize command");
+    let prefix = get_socket_prefix();
+    let entries = match std::fs::read_dir(get_runtime_dir()) {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("Could not read the runtime directory: {err}");
+            return false;
+        }
+    };
+
+    let mut reached = false;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !(name.starts_with(&prefix) && name.ends_with(".sock")) {
+            continue;
+        }
+        let path = entry.path();
+        let mut stream = match UnixStream::connect(&path) {
+            Ok(stream) => stream,
+            Err(err) if err.kind() == std::io::ErrorKind::ConnectionRefused => {
+                // The swayrbar owning this socket isn't running anymore.
+                let _ = std::fs::remove_file(&path);
+                continue;
+            }
+            Err(err) => {
+                eprintln!("Could not connect to {path:?}: {err}");
+                continue;
+            }
+        };
+        let mut response = String::new();
+        let result = stream
+            .write_all(json.as_bytes())
+            .and_then(|_| stream.shutdown(std::net::Shutdown::Write))
+            .and_then(|_| stream.read_to_string(&mut response));
+        match result {
+            Ok(_) => {
+                reached = true;
+                println!("{name}: {response}");
+            }
+            Err(err) => eprintln!("Could not talk to {path:?}: {err}"),
+        }
+    }
+    if !reached {
+        eprintln!("No running swayrbar found.");
+    }
+    reached
+}
+
+#[test]
+fn test_apply_command() {
+    let ids = vec!["nmcli/0".to_owned(), "date/0".to_owned()];
+    let disabled = DisabledModules::default();
+    let toggle = ControlCommand::ToggleModule {
+        module: "nmcli/0".to_owned(),
+    };
+    assert!(apply_command(&toggle, &ids, &disabled).is_ok());
+    assert!(disabled.read().unwrap().contains("nmcli/0"));
+    assert!(apply_command(&toggle, &ids, &disabled).is_ok());
+    assert!(disabled.read().unwrap().is_empty());
+    let unknown = ControlCommand::DisableModule {
+        module: "battery/0".to_owned(),
+    };
+    assert!(apply_command(&unknown, &ids, &disabled).is_err());
+}
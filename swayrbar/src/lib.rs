@@ -15,5 +15,7 @@
 
 pub mod bar;
 pub mod config;
+pub mod control;
 pub mod module;
 pub mod shared;
+pub mod sink;
@@ -13,9 +13,13 @@
 // You should have received a copy of the GNU General Public License along with
 // this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use once_cell::sync::Lazy;
 use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use crate::config;
+use crate::shared::fmt::FmtArg;
 use swaybar_types as s;
 use swayipc as si;
 
@@ -30,9 +34,121 @@ pub mod window;
 #[derive(Debug)]
 pub enum RefreshReason {
     TimerEvent,
-    ClickEvent { name: String, instance: String },
+    ClickEvent {
+        name: String,
+        instance: String,
+    },
     SwayWindowEvent(Box<si::WindowEvent>),
     SwayWorkspaceEvent(Box<si::WorkspaceEvent>),
+    SwayModeEvent(si::ModeEvent),
+    SwayOutputEvent(si::OutputEvent),
+    SwayBindingEvent(si::BindingEvent),
+    /// Modules have been enabled or disabled using the control socket.
+    ControlEvent,
+}
+
+/// Limits how often a module runs its external tools.  When refreshes happen
+/// in quick succession, e.g., a sway event coinciding with a timer tick, the
+/// module keeps showing its cached values instead of re-running the tools.
+#[derive(Default)]
+pub struct QueryThrottle {
+    last_query: Option<Instant>,
+}
+
+impl QueryThrottle {
+    /// Returns true and remembers the current time if at least
+    /// `min_interval` has passed since the last query.
+    pub fn should_query(&mut self, min_interval: Duration) -> bool {
+        let now = Instant::now();
+        match self.last_query {
+            Some(last) if now.duration_since(last) < min_interval => false,
+            _ => {
+                self.last_query = Some(now);
+                true
+            }
+        }
+    }
+
+    /// Like `should_query` but a refresh because of a click on the module is
+    /// never throttled so that its effect, e.g., muting, is shown right away.
+    pub fn should_query_for(
+        &mut self,
+        reason: &RefreshReason,
+        min_interval: Duration,
+    ) -> bool {
+        if matches!(reason, RefreshReason::ClickEvent { .. }) {
+            self.last_query = Some(Instant::now());
+            true
+        } else {
+            self.should_query(min_interval)
+        }
+    }
+}
+
+#[test]
+fn test_query_throttle() {
+    let mut throttle = QueryThrottle::default();
+    assert!(throttle.should_query(Duration::from_secs(60)));
+    assert!(!throttle.should_query(Duration::from_secs(60)));
+    assert!(throttle.should_query(Duration::ZERO));
+    let click = RefreshReason::ClickEvent {
+        name: "pactl".to_owned(),
+        instance: "0".to_owned(),
+    };
+    assert!(throttle.should_query_for(&click, Duration::from_secs(60)));
+    assert!(!throttle
+        .should_query_for(&RefreshReason::TimerEvent, Duration::from_secs(60)));
+}
+
+/// The placeholder values of all modules keyed by `<module name>.<placeholder>`
+/// and `<module name>.<instance>.<placeholder>` so that the format of one
+/// module can refer to the values of another one.
+static SHARED_VALUES: Lazy<Mutex<HashMap<String, FmtArg>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Publishes the placeholder `values` of the module with the given `name` and
+/// `instance`.  Without instance, the values of the module published last
+/// win.
+pub fn publish_values(name: &str, instance: &str, values: Vec<(&str, FmtArg)>) {
+    let mut shared = SHARED_VALUES.lock().expect("Could not lock mutex");
+    for (placeholder, value) in values {
+        shared
+            .insert(format!("{name}.{instance}.{placeholder}"), value.clone());
+        shared.insert(format!("{name}.{placeholder}"), value);
+    }
+}
+
+/// Returns the value of a placeholder like `battery.state_of_charge` which
+/// has been published by another module.
+pub fn get_shared_value(name: &str) -> Option<FmtArg> {
+    if !name.contains('.') {
+        return None;
+    }
+    SHARED_VALUES
+        .lock()
+        .expect("Could not lock mutex")
+        .get(name)
+        .cloned()
+}
+
+#[test]
+fn test_shared_values() {
+    publish_values("test", "0", vec![("answer", FmtArg::from(42))]);
+    publish_values("test", "1", vec![("answer", FmtArg::from(23))]);
+    assert!(matches!(
+        get_shared_value("test.answer"),
+        Some(FmtArg::I32(23))
+    ));
+    assert!(matches!(
+        get_shared_value("test.0.answer"),
+        Some(FmtArg::I32(42))
+    ));
+    assert!(matches!(
+        get_shared_value("test.1.answer"),
+        Some(FmtArg::I32(23))
+    ));
+    assert!(get_shared_value("test.question").is_none());
+    assert!(get_shared_value("answer").is_none());
 }
 
 pub trait BarModuleFn: Sync + Send {
@@ -55,7 +171,19 @@ pub trait BarModuleFn: Sync + Send {
         }
     }
 
+    /// Handles a click with `button` which has no `on_click` command.
+    /// Returns true if the module needs to be refreshed.
+    fn handle_click(&self, _button: &str) -> bool {
+        false
+    }
+
     fn build(&self, reason: &RefreshReason) -> s::Block;
 
+    /// Returns the current values of the placeholders which the formats of
+    /// other modules may refer to as `{<module name>.<placeholder>}`.
+    fn get_placeholder_values(&self) -> Vec<(&'static str, FmtArg)> {
+        vec![]
+    }
+
     fn subst_cmd_args<'a>(&'a self, cmd: &'a [String]) -> Vec<String>;
 }
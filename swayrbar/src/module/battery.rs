@@ -16,8 +16,8 @@
 //! The battery `swayrbar` module.
 
 use crate::config;
-use crate::module::{BarModuleFn, RefreshReason};
-use crate::shared::fmt::subst_placeholders;
+use crate::module::{self, BarModuleFn, RefreshReason};
+use crate::shared::fmt::{subst_placeholders, FmtArg};
 use battery as bat;
 use std::collections::HashSet;
 use std::sync::Mutex;
@@ -101,7 +101,15 @@ fn subst_placeholders(fmt: &str, html_escape: bool, state: &State) -> String {
         "state_of_charge" => state.state_of_charge,
         "state_of_health" => state.state_of_health,
         "state" => state.state.as_str(),
-    })
+    }, module::get_shared_value)
+}
+
+fn placeholder_values(state: &State) -> Vec<(&'static str, FmtArg)> {
+    vec![
+        ("state_of_charge", state.state_of_charge.into()),
+        ("state_of_health", state.state_of_health.into()),
+        ("state", state.state.as_str().into()),
+    ]
 }
 
 pub fn create(config: config::ModuleConfig) -> Box<dyn BarModuleFn> {
@@ -123,7 +131,14 @@ impl BarModuleFn for BarModuleBattery {
             instance,
             format: "🔋 Bat: {state_of_charge:{:5.1}}%, {state}, Health: {state_of_health:{:5.1}}%".to_owned(),
             html_escape: Some(false),
+            urgent_thresholds: None,
+            timezones: None,
+            interface: None,
+            output: None,
+            min_query_interval: None,
             on_click: None,
+            enabled: None,
+            refresh_on_sway_events: None,
         }
     }
 
@@ -163,6 +178,11 @@ impl BarModuleFn for BarModuleBattery {
         }
     }
 
+    fn get_placeholder_values(&self) -> Vec<(&'static str, FmtArg)> {
+        let state = self.state.lock().expect("Could not lock state.");
+        placeholder_values(&state)
+    }
+
     fn subst_cmd_args<'a>(&'a self, cmd: &'a [String]) -> Vec<String> {
         let state = self.state.lock().expect("Could not lock state.");
         cmd.iter()
@@ -16,17 +16,18 @@
 //! The cmd `swayrbar` module.
 
 use crate::config;
-use crate::module::{BarModuleFn, RefreshReason};
+use crate::module::{BarModuleFn, QueryThrottle, RefreshReason};
 use crate::shared::fmt::maybe_html_escape;
 use std::process::Command;
 use std::string::String;
 use std::sync::Mutex;
 use swaybar_types as s;
 
-const NAME: &str = "cmd";
+pub const NAME: &str = "cmd";
 
 struct State {
     cached_text: String,
+    throttle: QueryThrottle,
 }
 
 pub struct BarModuleCmd {
@@ -49,6 +50,7 @@ pub fn create(config: config::ModuleConfig) -> Box<dyn BarModuleFn> {
         config,
         state: Mutex::new(State {
             cached_text: String::new(),
+            throttle: QueryThrottle::default(),
         }),
     })
 }
@@ -63,7 +65,14 @@ impl BarModuleFn for BarModuleCmd {
             instance,
             format: String::new(),
             html_escape: Some(true),
+            urgent_thresholds: None,
+            timezones: None,
+            interface: None,
+            output: None,
+            min_query_interval: None,
             on_click: None,
+            enabled: None,
+            refresh_on_sway_events: None,
         }
     }
 
@@ -79,8 +88,18 @@ impl BarModuleFn for BarModuleCmd {
             RefreshReason::ClickEvent { name, instance } => {
                 name == &self.config.name && instance == &self.config.instance
             }
+            // The command's output might depend on something which a key
+            // binding or an output change affects, e.g., the brightness.
+            // That's opt-in because the command might be expensive.
+            RefreshReason::SwayOutputEvent(_)
+            | RefreshReason::SwayBindingEvent(_) => {
+                self.config.is_refresh_on_sway_events()
+            }
             _ => false,
-        } {
+        } && state
+            .throttle
+            .should_query_for(reason, self.config.get_min_query_interval())
+        {
             state.cached_text = maybe_html_escape(
                 self.config.is_html_escape(),
                 refresh_state(&self.config.format),
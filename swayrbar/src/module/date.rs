@@ -18,12 +18,15 @@
 use std::sync::Mutex;
 
 use crate::module::config;
-use crate::module::{BarModuleFn, RefreshReason};
+use crate::module::{self, BarModuleFn, RefreshReason};
+use crate::shared::fmt::Template;
 use swaybar_types as s;
 
 const NAME: &str = "date";
 
 struct State {
+    /// The index of the current entry of the `timezones` config option.
+    tz_index: usize,
     cached_text: String,
 }
 
@@ -36,10 +39,58 @@ fn chrono_format(s: &str) -> String {
     chrono::Local::now().format(s).to_string()
 }
 
+/// Formats the current time in the timezone of `tz` using its format or
+/// `default_format`.  `{label}` in the format is replaced with the label.
+fn chrono_format_tz(
+    tz: &config::TimezoneFormat,
+    default_format: &str,
+) -> String {
+    let fmt = tz
+        .format
+        .as_deref()
+        .unwrap_or(default_format)
+        .replace("{label}", &tz.label);
+    if tz.timezone == "Local" {
+        return chrono_format(&fmt);
+    }
+    match tz.timezone.parse::<chrono_tz::Tz>() {
+        Ok(zone) => chrono::Utc::now()
+            .with_timezone(&zone)
+            .format(&fmt)
+            .to_string(),
+        Err(err) => {
+            log::error!("Invalid timezone {}: {err}", tz.timezone);
+            chrono_format(&fmt)
+        }
+    }
+}
+
+impl BarModuleDate {
+    fn format(&self, fmt: &str, state: &State, html_escape: bool) -> String {
+        let text = match self
+            .config
+            .timezones
+            .as_ref()
+            .and_then(|tzs| tzs.get(state.tz_index))
+        {
+            Some(tz) => chrono_format_tz(tz, fmt),
+            None => chrono_format(fmt),
+        };
+        // The date has no placeholders of its own but may refer to the
+        // values of other modules like `{battery.state_of_charge}`.
+        if text.contains('{') {
+            Template::parse(&text).render(html_escape, module::get_shared_value)
+        } else {
+            text
+        }
+    }
+}
+
 pub fn create(cfg: config::ModuleConfig) -> Box<dyn BarModuleFn> {
     Box::new(BarModuleDate {
         config: cfg,
         state: Mutex::new(State {
+            tz_index: 0,
             cached_text: String::new(),
         }),
     })
@@ -52,7 +103,14 @@ impl BarModuleFn for BarModuleDate {
             instance,
             format: "⏰ %F %X".to_owned(),
             html_escape: Some(false),
+            urgent_thresholds: None,
+            timezones: None,
+            interface: None,
+            output: None,
+            min_query_interval: None,
             on_click: None,
+            enabled: None,
+            refresh_on_sway_events: None,
         }
     }
 
@@ -60,11 +118,35 @@ impl BarModuleFn for BarModuleDate {
         &self.config
     }
 
+    fn handle_click(&self, button: &str) -> bool {
+        let n = self.config.timezones.as_ref().map_or(0, |tzs| tzs.len());
+        if n < 2 {
+            return false;
+        }
+        let mut state = self.state.lock().expect("Could not lock state.");
+        state.tz_index = match button {
+            "Left" | "WheelUp" => (state.tz_index + 1) % n,
+            "Right" | "WheelDown" => (state.tz_index + n - 1) % n,
+            _ => return false,
+        };
+        true
+    }
+
     fn build(&self, reason: &RefreshReason) -> s::Block {
         let mut state = self.state.lock().expect("Could not lock state.");
 
-        if matches!(reason, RefreshReason::TimerEvent) {
-            state.cached_text = chrono_format(&self.config.format);
+        if match reason {
+            RefreshReason::TimerEvent => true,
+            RefreshReason::ClickEvent { name, instance } => {
+                name == &self.config.name && instance == &self.config.instance
+            }
+            _ => false,
+        } {
+            state.cached_text = self.format(
+                &self.config.format,
+                &state,
+                self.config.is_html_escape(),
+            );
         }
 
         s::Block {
@@ -89,6 +171,19 @@ impl BarModuleFn for BarModuleDate {
     }
 
     fn subst_cmd_args<'a>(&'a self, cmd: &'a [String]) -> Vec<String> {
-        cmd.iter().map(|arg| chrono_format(arg)).collect()
+        let state = self.state.lock().expect("Could not lock state.");
+        cmd.iter()
+            .map(|arg| self.format(arg, &state, false))
+            .collect()
     }
 }
+
+#[test]
+fn test_chrono_format_tz() {
+    let tz = config::TimezoneFormat {
+        label: "UTC".to_owned(),
+        timezone: "UTC".to_owned(),
+        format: Some("{label} %Z".to_owned()),
+    };
+    assert_eq!("UTC UTC", chrono_format_tz(&tz, "%F"));
+}
@@ -16,8 +16,8 @@
 //! The pactl `swayrbar` module.
 
 use crate::config;
-use crate::module::{BarModuleFn, RefreshReason};
-use crate::shared::fmt::subst_placeholders;
+use crate::module::{self, BarModuleFn, QueryThrottle, RefreshReason};
+use crate::shared::fmt::{subst_placeholders, FmtArg};
 use once_cell::sync::Lazy;
 use regex::Regex;
 use std::collections::HashMap;
@@ -33,6 +33,7 @@ struct State {
     volume_source: u8,
     muted_source: bool,
     cached_text: String,
+    throttle: QueryThrottle,
 }
 
 pub static VOLUME_RX: Lazy<Regex> =
@@ -95,7 +96,19 @@ fn subst_placeholders(fmt: &str, html_escape: bool, state: &State) -> String {
                 ""
             }
         },
-    })
+    }, module::get_shared_value)
+}
+
+fn placeholder_values(state: &State) -> Vec<(&'static str, FmtArg)> {
+    vec![
+        ("volume", state.volume.into()),
+        ("muted", if state.muted { " muted" } else { "" }.into()),
+        ("volume_source", state.volume_source.into()),
+        (
+            "muted_source",
+            if state.muted_source { " muted" } else { "" }.into(),
+        ),
+    ]
 }
 
 pub fn create(config: config::ModuleConfig) -> Box<dyn BarModuleFn> {
@@ -107,6 +120,7 @@ pub fn create(config: config::ModuleConfig) -> Box<dyn BarModuleFn> {
             volume_source: 255_u8,
             muted_source: false,
             cached_text: String::new(),
+            throttle: QueryThrottle::default(),
         }),
     })
 }
@@ -121,6 +135,11 @@ impl BarModuleFn for BarModulePactl {
             instance,
             format: "🔈 Vol: {volume:{:3}}%{muted}".to_owned(),
             html_escape: Some(true),
+            urgent_thresholds: None,
+            timezones: None,
+            interface: None,
+            output: None,
+            min_query_interval: None,
             on_click: Some(HashMap::from([
                 ("Left".to_owned(), vec!["pavucontrol".to_owned()]),
                 (
@@ -151,6 +170,8 @@ impl BarModuleFn for BarModulePactl {
                     ],
                 ),
             ])),
+            enabled: None,
+            refresh_on_sway_events: None,
         }
     }
 
@@ -167,7 +188,10 @@ impl BarModuleFn for BarModulePactl {
                 name == &self.config.name && instance == &self.config.instance
             }
             _ => false,
-        } {
+        } && state
+            .throttle
+            .should_query_for(reason, self.config.get_min_query_interval())
+        {
             refresh_state(
                 &mut state,
                 &self.config.format,
@@ -196,6 +220,11 @@ impl BarModuleFn for BarModulePactl {
         }
     }
 
+    fn get_placeholder_values(&self) -> Vec<(&'static str, FmtArg)> {
+        let state = self.state.lock().expect("Could not lock state.");
+        placeholder_values(&state)
+    }
+
     fn subst_cmd_args<'a>(&'a self, cmd: &'a [String]) -> Vec<String> {
         let state = self.state.lock().expect("Could not lock state.");
         cmd.iter()
@@ -16,8 +16,8 @@
 //! The sysinfo `swayrbar` module.
 
 use crate::config;
-use crate::module::{BarModuleFn, RefreshReason};
-use crate::shared::fmt::subst_placeholders;
+use crate::module::{self, BarModuleFn, RefreshReason};
+use crate::shared::fmt::{subst_placeholders, FmtArg};
 use std::collections::HashMap;
 use std::sync::Mutex;
 use std::sync::Once;
@@ -32,6 +32,8 @@ struct State {
     load_avg_1: f64,
     load_avg_5: f64,
     load_avg_15: f64,
+    cpu_freq: u64,
+    cpu_cores: String,
     cached_text: String,
 }
 
@@ -68,6 +70,38 @@ fn get_cpu_usage(sys: &mut si::System, upd: &OnceRefresher) -> f32 {
     sys.global_cpu_usage()
 }
 
+/// Returns the average frequency of all CPU cores in MHz.
+fn get_cpu_frequency(sys: &mut si::System, upd: &OnceRefresher) -> u64 {
+    upd.refresh_cpu(sys);
+    let cpus = sys.cpus();
+    if cpus.is_empty() {
+        0
+    } else {
+        cpus.iter().map(|c| c.frequency()).sum::<u64>() / cpus.len() as u64
+    }
+}
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Returns a sparkline of the given percentages, one bar per value.
+fn sparkline(percentages: impl Iterator<Item = f32>) -> String {
+    percentages
+        .map(|p| {
+            let idx = (p.clamp(0.0, 100.0) / 100.0 * (SPARKS.len() - 1) as f32)
+                .round() as usize;
+            SPARKS[idx]
+        })
+        .collect()
+}
+
+fn get_cpu_cores_sparkline(
+    sys: &mut si::System,
+    upd: &OnceRefresher,
+) -> String {
+    upd.refresh_cpu(sys);
+    sparkline(sys.cpus().iter().map(|c| c.cpu_usage()))
+}
+
 fn get_memory_usage(sys: &mut si::System, upd: &OnceRefresher) -> f64 {
     upd.refresh_memory(sys);
     sys.used_memory() as f64 * 100_f64 / sys.total_memory() as f64
@@ -101,6 +135,8 @@ fn refresh_state(
     state.load_avg_1 = get_load_average(LoadAvg::One);
     state.load_avg_5 = get_load_average(LoadAvg::Five);
     state.load_avg_15 = get_load_average(LoadAvg::Fifteen);
+    state.cpu_freq = get_cpu_frequency(sys, &updater);
+    state.cpu_cores = get_cpu_cores_sparkline(sys, &updater);
     state.cached_text = subst_placeholders(fmt_str, html_escape, state);
 }
 
@@ -108,10 +144,54 @@ fn subst_placeholders(fmt: &str, html_escape: bool, state: &State) -> String {
     subst_placeholders!(fmt, html_escape, {
         "cpu_usage" => state.cpu_usage,
         "mem_usage" => state.mem_usage,
-        "load_avg_1" => state.load_avg_1,
-        "load_avg_5" => state.load_avg_5,
-        "load_avg_15" => state.load_avg_15,
-    })
+        "load_avg_1" | "load1" => state.load_avg_1,
+        "load_avg_5" | "load5" => state.load_avg_5,
+        "load_avg_15" | "load15" => state.load_avg_15,
+        "cpu_freq" => state.cpu_freq as i64,
+        "cpu_cores" => state.cpu_cores.as_str(),
+    }, module::get_shared_value)
+}
+
+fn placeholder_values(state: &State) -> Vec<(&'static str, FmtArg)> {
+    vec![
+        ("cpu_usage", state.cpu_usage.into()),
+        ("mem_usage", state.mem_usage.into()),
+        ("load_avg_1", state.load_avg_1.into()),
+        ("load1", state.load_avg_1.into()),
+        ("load_avg_5", state.load_avg_5.into()),
+        ("load5", state.load_avg_5.into()),
+        ("load_avg_15", state.load_avg_15.into()),
+        ("load15", state.load_avg_15.into()),
+        ("cpu_freq", (state.cpu_freq as i64).into()),
+        ("cpu_cores", state.cpu_cores.as_str().into()),
+    ]
+}
+
+/// Returns the numeric value of the placeholder `name` if there is one.
+fn get_value(name: &str, state: &State) -> Option<f64> {
+    match name {
+        "cpu_usage" => Some(state.cpu_usage as f64),
+        "mem_usage" => Some(state.mem_usage),
+        "load_avg_1" | "load1" => Some(state.load_avg_1),
+        "load_avg_5" | "load5" => Some(state.load_avg_5),
+        "load_avg_15" | "load15" => Some(state.load_avg_15),
+        "cpu_freq" => Some(state.cpu_freq as f64),
+        _ => None,
+    }
+}
+
+fn is_urgent(thresholds: &HashMap<String, f64>, state: &State) -> bool {
+    thresholds
+        .iter()
+        .any(|(name, threshold)| match get_value(name, state) {
+            Some(val) => val >= *threshold,
+            None => {
+                log::error!(
+                    "No numeric {NAME} value {name} for urgent_thresholds."
+                );
+                false
+            }
+        })
 }
 
 pub fn create(config: config::ModuleConfig) -> Box<dyn BarModuleFn> {
@@ -124,6 +204,8 @@ pub fn create(config: config::ModuleConfig) -> Box<dyn BarModuleFn> {
             load_avg_1: 0.0,
             load_avg_5: 0.0,
             load_avg_15: 0.0,
+            cpu_freq: 0,
+            cpu_cores: String::new(),
             cached_text: String::new(),
         }),
     })
@@ -136,9 +218,16 @@ impl BarModuleFn for BarModuleSysInfo {
             instance,
             format: "💻 CPU: {cpu_usage:{:5.1}}% Mem: {mem_usage:{:5.1}}% Load: {load_avg_1:{:5.2}} / {load_avg_5:{:5.2}} / {load_avg_15:{:5.2}}".to_owned(),
             html_escape: Some(false),
+            urgent_thresholds: None,
+            timezones: None,
+            interface: None,
+            output: None,
+            min_query_interval: None,
             on_click: Some(HashMap::from([
                ("Left".to_owned(),
                 vec!["foot".to_owned(), "htop".to_owned()])])),
+            enabled: None,
+            refresh_on_sway_events: None,
         }
     }
 
@@ -174,12 +263,21 @@ impl BarModuleFn for BarModuleSysInfo {
             border_left: None,
             border_right: None,
             min_width: None,
-            urgent: None,
+            urgent: self
+                .config
+                .urgent_thresholds
+                .as_ref()
+                .map(|t| is_urgent(t, &state)),
             separator: Some(true),
             separator_block_width: None,
         }
     }
 
+    fn get_placeholder_values(&self) -> Vec<(&'static str, FmtArg)> {
+        let state = self.state.lock().expect("Could not lock state.");
+        placeholder_values(&state)
+    }
+
     fn subst_cmd_args<'a>(&'a self, cmd: &'a [String]) -> Vec<String> {
         let state = self.state.lock().expect("Could not lock state.");
         cmd.iter()
@@ -187,3 +285,9 @@ impl BarModuleFn for BarModuleSysInfo {
             .collect()
     }
 }
+
+#[test]
+fn test_sparkline() {
+    assert_eq!("▁▅█", sparkline([0.0, 50.0, 100.0].into_iter()));
+    assert_eq!("▁█", sparkline([-3.0, 120.0].into_iter()));
+}
@@ -1,6 +1,6 @@
 use crate::config;
-use crate::module::{BarModuleFn, RefreshReason};
-use crate::shared::fmt::subst_placeholders;
+use crate::module::{self, BarModuleFn, QueryThrottle, RefreshReason};
+use crate::shared::fmt::{subst_placeholders, FmtArg};
 use once_cell::sync::Lazy;
 use regex::Regex;
 use std::sync::Mutex;
@@ -8,9 +8,11 @@ use swaybar_types as s;
 
 struct State {
     cached_text: String,
+    iface: Option<String>,
     signal: Option<String>,
     name: Option<String>,
     bars: Option<String>,
+    throttle: QueryThrottle,
 }
 
 pub enum WifiTool {
@@ -23,18 +25,43 @@ static IWCTL_CONN_NETWORK: Lazy<Regex> =
 static IWCTL_RSSI: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"\s*RSSI\s+(-\d+) dBm\s*$").unwrap());
 
+/// Returns the names of the wireless network interfaces in alphabetical
+/// order.
+fn wireless_interfaces() -> Vec<String> {
+    let mut ifaces: Vec<String> = std::fs::read_dir("/sys/class/net")
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter(|e| e.path().join("wireless").exists())
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    ifaces.sort();
+    ifaces
+}
+
 impl WifiTool {
-    fn run(&self) -> Result<String, String> {
+    fn run(&self, iface: Option<&str>) -> Result<String, String> {
         let cmd;
-        let args;
+        let mut args: Vec<&str>;
         match self {
             WifiTool::Nmcli => {
                 cmd = "nmcli";
-                args = "-c no -g IN-USE,SSID,SIGNAL,BARS dev wifi".split(' ');
+                args = "-c no -g IN-USE,SSID,SIGNAL,BARS dev wifi list"
+                    .split(' ')
+                    .collect();
+                if let Some(iface) = iface {
+                    args.extend(["ifname", iface]);
+                }
             }
             WifiTool::Iwctl => {
                 cmd = "iwctl";
-                args = "station wlan0 show".split(' ');
+                args = vec![
+                    "station",
+                    iface.ok_or("No wireless interface found")?,
+                    "show",
+                ];
             }
         }
         let output = std::process::Command::new(cmd)
@@ -53,11 +80,24 @@ impl WifiTool {
         Ok(String::from_utf8(output.stdout).unwrap())
     }
 
-    fn run_and_set_state(&self, state: &mut State) {
+    /// Runs the tool for the `configured_iface` or, if that's not given, the
+    /// first wireless interface (iwctl) or all of them (nmcli).
+    fn run_and_set_state(
+        &self,
+        state: &mut State,
+        configured_iface: Option<&str>,
+    ) {
         state.name = None;
         state.signal = None;
         state.bars = None;
-        if let Ok(output) = self.run() {
+        state.iface = configured_iface
+            .map(str::to_owned)
+            .or_else(|| wireless_interfaces().into_iter().next());
+        let iface = match self {
+            WifiTool::Nmcli => configured_iface,
+            WifiTool::Iwctl => state.iface.as_deref(),
+        };
+        if let Ok(output) = self.run(iface) {
             match self {
                 WifiTool::Nmcli => {
                     if let Some(line) =
@@ -130,6 +170,7 @@ fn subst_placeholders(
     unit: &str,
 ) -> String {
     subst_placeholders!(fmt, html_escape, {
+        "iface" => state.iface.as_deref().unwrap_or_default(),
         "name" => {
             match &state.name {
                 None => "No wi-fi",
@@ -148,16 +189,26 @@ fn subst_placeholders(
                 Some(bars) => " ".to_owned() + bars,
             }
         },
-    })
+    }, module::get_shared_value)
+}
+
+fn placeholder_values(state: &State) -> Vec<(&'static str, FmtArg)> {
+    vec![
+        ("iface", state.iface.as_deref().unwrap_or_default().into()),
+        ("name", state.name.as_deref().unwrap_or("No wi-fi").into()),
+        ("signal", state.signal.as_deref().unwrap_or_default().into()),
+        ("bars", state.bars.as_deref().unwrap_or_default().into()),
+    ]
 }
 
 fn refresh_state(
     tool: &WifiTool,
     state: &mut State,
+    iface: Option<&str>,
     fmt_str: &str,
     html_escape: bool,
 ) {
-    tool.run_and_set_state(state);
+    tool.run_and_set_state(state, iface);
     state.cached_text =
         subst_placeholders(fmt_str, html_escape, state, tool.get_signal_unit());
 }
@@ -171,9 +222,11 @@ pub fn create(
         config,
         state: Mutex::new(State {
             cached_text: String::new(),
+            iface: None,
             signal: None,
             name: None,
             bars: None,
+            throttle: QueryThrottle::default(),
         }),
     })
 }
@@ -188,7 +241,14 @@ impl BarModuleFn for BarModuleWifi {
             instance,
             format: "📡 Wi-fi: {name}{bars}{signal}".to_owned(),
             html_escape: Some(false),
+            urgent_thresholds: None,
+            timezones: None,
+            interface: None,
+            output: None,
+            min_query_interval: None,
             on_click: None,
+            enabled: None,
+            refresh_on_sway_events: None,
         }
     }
 
@@ -199,10 +259,15 @@ impl BarModuleFn for BarModuleWifi {
     fn build(&self, reason: &RefreshReason) -> s::Block {
         let mut state = self.state.lock().expect("Could not lock state.");
 
-        if matches!(reason, RefreshReason::TimerEvent) {
+        if matches!(reason, RefreshReason::TimerEvent)
+            && state
+                .throttle
+                .should_query_for(reason, self.config.get_min_query_interval())
+        {
             refresh_state(
                 &self.tool,
                 &mut state,
+                self.config.interface.as_deref(),
                 &self.config.format,
                 self.config.is_html_escape(),
             );
@@ -229,6 +294,11 @@ impl BarModuleFn for BarModuleWifi {
         }
     }
 
+    fn get_placeholder_values(&self) -> Vec<(&'static str, FmtArg)> {
+        let state = self.state.lock().expect("Could not lock state.");
+        placeholder_values(&state)
+    }
+
     fn subst_cmd_args<'a>(&'a self, cmd: &'a [String]) -> Vec<String> {
         let state = self.state.lock().expect("Could not lock state.");
         cmd.iter()
@@ -15,13 +15,16 @@
 
 //! The window `swayrbar` module.
 
+use once_cell::sync::Lazy;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Mutex;
 use std::time::{Duration, Instant};
 
 use crate::config;
-use crate::module::{BarModuleFn, RefreshReason};
-use crate::shared::fmt::subst_placeholders;
+use crate::module::{self, BarModuleFn, RefreshReason};
+use crate::shared::fmt::{subst_placeholders, FmtArg};
+use crate::shared::icon;
 use crate::shared::ipc;
 use crate::shared::ipc::NodeMethods;
 use swaybar_types as s;
@@ -33,10 +36,17 @@ const INITIAL_PID: i32 = -128;
 const NO_WINDOW_PID: i32 = -1;
 const UNKNOWN_PID: i32 = -2;
 
+/// The same app_id to icon mapping swayr uses for its menus.  It's only
+/// computed if some format actually uses the `{icon}` placeholder.
+static APP_ID_TO_ICON_MAP: Lazy<HashMap<String, PathBuf>> =
+    Lazy::new(|| icon::get_app_id_to_icon_map(&icon::default_icon_dirs()));
+
 struct State {
     name: String,
     app_name: String,
+    icon: String,
     pid: i32,
+    binding_mode: String,
     cached_text: String,
     showing_title_of_non_focused_window_since: Option<Instant>,
 }
@@ -56,6 +66,13 @@ fn refresh_state_1(
         Some(win) => {
             win.get_name().clone_into(&mut state.name);
             win.get_app_name().clone_into(&mut state.app_name);
+            state.icon = if fmt_str.contains("{icon") {
+                icon::get_icon(&APP_ID_TO_ICON_MAP, &state.app_name)
+                    .map(|i| i.display().to_string())
+                    .unwrap_or_default()
+            } else {
+                String::new()
+            };
             state.pid = win.pid.unwrap_or(UNKNOWN_PID);
             state.cached_text = subst_placeholders(fmt_str, html_escape, state);
 
@@ -73,26 +90,90 @@ fn refresh_state_1(
         None => {
             state.name.clear();
             state.app_name.clear();
+            state.icon.clear();
             state.pid = NO_WINDOW_PID;
             state.cached_text.clear();
         }
     };
 }
 
-fn refresh_state(state: &mut State, fmt_str: &str, html_escape: bool) {
+/// Returns the window having the focus on the output named `output`, i.e.,
+/// the one which would get focused when focusing that output.
+fn get_focused_window_of_output<'a>(
+    root: &'a si::Node,
+    output: &str,
+) -> Option<&'a si::Node> {
+    let mut node = root.nodes.iter().find(|n| {
+        n.get_type() == ipc::Type::Output && n.name.as_deref() == Some(output)
+    })?;
+    while node.get_type() != ipc::Type::Window {
+        let id = node.focus.first()?;
+        node = node
+            .nodes
+            .iter()
+            .chain(node.floating_nodes.iter())
+            .find(|n| n.id == *id)?;
+    }
+    Some(node)
+}
+
+/// Shows the globally focused window or the one focused on `output`.
+fn refresh_window(
+    state: &mut State,
+    fmt_str: &str,
+    html_escape: bool,
+    output: Option<&str>,
+) {
     let root = ipc::get_root_node(false);
-    let focused_win = root
-        .iter()
-        .find(|n| n.focused && n.get_type() == ipc::Type::Window);
-    refresh_state_1(state, fmt_str, html_escape, focused_win);
+    match output {
+        Some(output) => {
+            let win = get_focused_window_of_output(&root, output);
+            refresh_state_1(state, fmt_str, html_escape, win);
+            // The output's focused window is usually not the globally focused
+            // one but still no "wrong" title.
+            state.showing_title_of_non_focused_window_since = None;
+        }
+        None => {
+            let focused_win = root
+                .iter()
+                .find(|n| n.focused && n.get_type() == ipc::Type::Window);
+            refresh_state_1(state, fmt_str, html_escape, focused_win);
+        }
+    }
+}
+
+fn refresh_state(
+    state: &mut State,
+    fmt_str: &str,
+    html_escape: bool,
+    output: Option<&str>,
+) {
+    match si::Connection::new().and_then(|mut con| con.get_binding_state()) {
+        Ok(mode) => state.binding_mode = mode,
+        Err(err) => log::error!("Could not get binding state: {err}"),
+    }
+    refresh_window(state, fmt_str, html_escape, output);
 }
 
 fn subst_placeholders(s: &str, html_escape: bool, state: &State) -> String {
     subst_placeholders!(s, html_escape, {
         "title" | "name"  => state.name.clone(),
         "app_name" => state.app_name.clone(),
+        "icon" => state.icon.clone(),
         "pid" => state.pid,
-    })
+        "binding_mode" => state.binding_mode.clone(),
+    }, module::get_shared_value)
+}
+
+fn placeholder_values(state: &State) -> Vec<(&'static str, FmtArg)> {
+    vec![
+        ("title", state.name.as_str().into()),
+        ("name", state.name.as_str().into()),
+        ("app_name", state.app_name.as_str().into()),
+        ("icon", state.icon.as_str().into()),
+        ("pid", state.pid.into()),
+        ("binding_mode", state.binding_mode.as_str().into()),
+    ]
 }
 
 pub fn create(config: config::ModuleConfig) -> Box<dyn BarModuleFn> {
@@ -101,7 +182,9 @@ pub fn create(config: config::ModuleConfig) -> Box<dyn BarModuleFn> {
         state: Mutex::new(State {
             name: String::new(),
             app_name: String::new(),
+            icon: String::new(),
             pid: INITIAL_PID,
+            binding_mode: String::new(),
             cached_text: String::new(),
             showing_title_of_non_focused_window_since: None,
         }),
@@ -115,6 +198,11 @@ impl BarModuleFn for BarModuleWindow {
             instance,
             format: "🪟 {title} — {app_name}".to_owned(),
             html_escape: Some(false),
+            urgent_thresholds: None,
+            timezones: None,
+            interface: None,
+            output: None,
+            min_query_interval: None,
             on_click: Some(HashMap::from([
                 (
                     "Left".to_owned(),
@@ -128,6 +216,8 @@ impl BarModuleFn for BarModuleWindow {
                     vec!["kill".to_owned(), "{pid}".to_owned()],
                 ),
             ])),
+            enabled: None,
+            refresh_on_sway_events: None,
         }
     }
 
@@ -140,7 +230,22 @@ impl BarModuleFn for BarModuleWindow {
 
         // In contrast to other modules, this one should only refresh its state
         // initially at startup and on sway events.
+        let output = self.config.output.as_deref();
         match reason {
+            // With an output, the window focused there is computed from the
+            // tree because the events only tell about the global focus.
+            RefreshReason::SwayWindowEvent(_)
+            | RefreshReason::SwayWorkspaceEvent(_)
+            | RefreshReason::SwayOutputEvent(_)
+                if output.is_some() =>
+            {
+                refresh_window(
+                    &mut state,
+                    &self.config.format,
+                    self.config.is_html_escape(),
+                    output,
+                )
+            }
             RefreshReason::SwayWindowEvent(ev) => match ev.change {
                 si::WindowChange::Focus | si::WindowChange::Title => {
                     refresh_state_1(
@@ -158,6 +263,14 @@ impl BarModuleFn for BarModuleWindow {
                 ),
                 _ => (),
             },
+            RefreshReason::SwayModeEvent(ev) => {
+                state.binding_mode.clone_from(&ev.change);
+                state.cached_text = subst_placeholders(
+                    &self.config.format,
+                    self.config.is_html_escape(),
+                    &state,
+                );
+            }
             RefreshReason::SwayWorkspaceEvent(ev)
                 if ev.change == si::WorkspaceChange::Init =>
             {
@@ -183,6 +296,7 @@ impl BarModuleFn for BarModuleWindow {
                     &mut state,
                     &self.config.format,
                     self.config.is_html_escape(),
+                    output,
                 )
             }
             _ => (),
@@ -209,6 +323,11 @@ impl BarModuleFn for BarModuleWindow {
         }
     }
 
+    fn get_placeholder_values(&self) -> Vec<(&'static str, FmtArg)> {
+        let state = self.state.lock().expect("Could not lock state.");
+        placeholder_values(&state)
+    }
+
     fn subst_cmd_args<'b>(&'b self, cmd: &'b [String]) -> Vec<String> {
         let state = self.state.lock().expect("Could not lock state.");
         cmd.iter()
@@ -0,0 +1,173 @@
+// Copyright (C) 2022-2023  Tassilo Horn <tsdh@gnu.org>
+//
+// This program is free software: you can redistribute it and/or modify it
+// under the terms of the GNU General Public License as published by the Free
+// Software Foundation, either version 3 of the License, or (at your option)
+// any later version.
+//
+// This program is distributed in the hope that it will be useful, but WITHOUT
+// ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or
+// FITNESS FOR A PARTICULAR PURPOSE.  See the GNU General Public License for
+// more details.
+//
+// You should have received a copy of the GNU General Public License along with
+// this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Writing the blocks of each refresh to a file or named pipe for external
+//! consumers.
+
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use swaybar_types as sbt;
+
+/// The line a FIFO writer thread should write next.  Only the most recent
+/// line is kept so that a slow reader always gets current data.
+#[derive(Default)]
+struct PendingLine {
+    line: Mutex<Option<String>>,
+    cond: Condvar,
+    /// Set by the writer thread when it quits, e.g., because the FIFO has
+    /// been removed.
+    gone: Mutex<bool>,
+}
+
+/// The `json_sink` of a bar.  The blocks of each refresh are written to
+/// `path` as a single line of JSON.  If `path` is a named pipe, a writer
+/// thread keeps it open and writes complete lines of any length.  Else, `path`
+/// is replaced atomically so that readers always see a complete JSON array.
+pub struct JsonSink {
+    path: PathBuf,
+    fifo_writer: Mutex<Option<Arc<PendingLine>>>,
+}
+
+impl JsonSink {
+    pub fn new(path: &Path) -> JsonSink {
+        JsonSink {
+            path: path.to_owned(),
+            fifo_writer: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write_blocks(&self, blocks: &[sbt::Block]) -> io::Result<()> {
+        let mut json = serde_json::to_string(blocks)?;
+        json.push('\n');
+
+        let is_fifo = fs::metadata(&self.path)
+            .map(|m| m.file_type().is_fifo())
+            .unwrap_or(false);
+        if is_fifo {
+            self.write_fifo(json);
+            Ok(())
+        } else {
+            let mut tmp = self.path.as_os_str().to_owned();
+            tmp.push(".tmp");
+            fs::write(&tmp, json)?;
+            fs::rename(&tmp, &self.path)
+        }
+    }
+
+    /// Hands `line` to the writer thread of the FIFO, starting it if needed.
+    /// A line the thread hasn't written yet is replaced.
+    fn write_fifo(&self, line: String) {
+        let mut writer = self.fifo_writer.lock().expect("Could not lock mutex");
+        if writer
+            .as_ref()
+            .is_none_or(|p| *p.gone.lock().expect("Could not lock mutex"))
+        {
+            let pending = Arc::new(PendingLine::default());
+            let path = self.path.clone();
+            let pending_for_thread = pending.clone();
+            thread::spawn(move || write_fifo_lines(&path, &pending_for_thread));
+            *writer = Some(pending);
+        }
+        let pending = writer.as_ref().expect("No FIFO writer");
+        *pending.line.lock().expect("Could not lock mutex") = Some(line);
+        pending.cond.notify_one();
+    }
+}
+
+/// Writes the pending lines to the FIFO at `path`.  Opening it blocks until
+/// there's a reader, and when the reader goes away, the FIFO is opened again.
+fn write_fifo_lines(path: &Path, pending: &PendingLine) {
+    loop {
+        let mut fifo = match fs::OpenOptions::new().write(true).open(path) {
+            Ok(fifo) => fifo,
+            Err(err) => {
+                log::error!("Could not open json_sink {path:?}: {err}");
+                *pending.gone.lock().expect("Could not lock mutex") = true;
+                return;
+            }
+        };
+        loop {
+            let line = {
+                let mut line =
+                    pending.line.lock().expect("Could not lock mutex");
+                while line.is_none() {
+                    line =
+                        pending.cond.wait(line).expect("Could not lock mutex");
+                }
+                line.take().expect("No pending line")
+            };
+            if let Err(err) = fifo.write_all(line.as_bytes()) {
+                // The reader has closed the FIFO, so wait for the next one.
+                log::debug!("Could not write to json_sink {path:?}: {err}");
+                break;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_write_blocks() {
+    let path = std::env::temp_dir()
+        .join(format!("swayrbar-test-sink-{}.json", std::process::id()));
+    let block = sbt::Block {
+        name: Some("date".to_owned()),
+        instance: Some("0".to_owned()),
+        full_text: "12:00".to_owned(),
+        ..Default::default()
+    };
+    let blocks = [block];
+    let sink = JsonSink::new(&path);
+    sink.write_blocks(&blocks).unwrap();
+    sink.write_blocks(&blocks).unwrap();
+    let content = fs::read_to_string(&path).unwrap();
+    let _ = fs::remove_file(&path);
+    assert_eq!(1, content.lines().count());
+    let blocks: Vec<sbt::Block> = serde_json::from_str(&content).unwrap();
+    assert_eq!("12:00", blocks[0].full_text);
+}
+
+#[test]
+fn test_write_blocks_to_fifo() {
+    use std::io::BufRead;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::env::temp_dir()
+        .join(format!("swayrbar-test-sink-{}.fifo", std::process::id()));
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).unwrap();
+    assert_eq!(0, unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) });
+    let sink = JsonSink::new(&path);
+    // Lines longer than an atomic pipe write arrive completely, too.
+    let text = "x".repeat(4 * libc::PIPE_BUF);
+    sink.write_blocks(&[sbt::Block {
+        full_text: text.clone(),
+        ..Default::default()
+    }])
+    .unwrap();
+
+    let mut reader = io::BufReader::new(fs::File::open(&path).unwrap());
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let _ = fs::remove_file(&path);
+    let blocks: Vec<sbt::Block> = serde_json::from_str(&line).unwrap();
+    assert_eq!(text, blocks[0].full_text);
+}